@@ -0,0 +1,361 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Commitment helpers used by RGB Core, exposed to schema-adjacent crates so
+//! they can create commitments to auxiliary data structures compatible with
+//! the ones used by the consensus layer.
+//!
+//! Commitments are produced with tagged SHA-256 hashing of the
+//! commit-encoded data; collections are committed to with [LNPBP-81]
+//! merklization. See [`crate::consensus::COMMITMENT_TAGS`] for the tags of
+//! the consensus data structures.
+//!
+//! [`CommitDump`] traces the commitment procedure of the consensus data
+//! structures field by field, which helps to pinpoint the exact field causing
+//! a commitment mismatch between implementations.
+//!
+//! [LNPBP-81]: https://github.com/LNP-BP/LNPBPs/blob/master/lnpbp-0081.md
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+
+use amplify::hex::ToHex;
+use amplify::num::u4;
+use amplify::Bytes32;
+pub use commit_verify::merkle::{MerkleLeaves, MerkleNode};
+pub use commit_verify::{strategies, CommitEncode, CommitStrategy, CommitmentId};
+use commit_verify::{DigestExt, Sha256};
+use strict_encoding::{StrictEncode, StrictWriter};
+
+/// Merkle tree tag used for merklization of the owned state assignments of a
+/// single type.
+pub const OWNED_STATE_MERKLE_TAG: u128 = u128::from_be_bytes(*b"rgb:state:owned*");
+
+/// Computes tagged SHA-256 hash of the raw data.
+pub fn tagged_hash(tag: impl AsRef<[u8]>, data: impl AsRef<[u8]>) -> Bytes32 {
+    let mut engine = Sha256::from_tag(tag);
+    engine.input_raw(data.as_ref());
+    engine.finish().into()
+}
+
+/// Commits to the value with the given tag using the same procedure as
+/// [`CommitmentId::commitment_id`], for types which don't implement
+/// [`CommitmentId`].
+pub fn commitment_hash(tag: [u8; 32], value: &impl CommitEncode) -> Bytes32 {
+    let mut engine = Sha256::from_tag(tag);
+    value.commit_encode(&mut engine);
+    engine.finish().into()
+}
+
+/// Returns data produced by commit-encoding of the value.
+pub fn commit_encoded(value: &impl CommitEncode) -> Vec<u8> {
+    let mut data = vec![];
+    value.commit_encode(&mut data);
+    data
+}
+
+pub(crate) fn strict_encoded(value: &impl StrictEncode) -> Vec<u8> {
+    let mut data = vec![];
+    value
+        .strict_encode(StrictWriter::with(u32::MAX as usize, &mut data))
+        .expect("in-memory encoding");
+    data
+}
+
+/// Single step of the commitment procedure.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CommitStep {
+    /// Name of the committed field; nested items are named with their index
+    /// or key in square brackets.
+    pub field: String,
+    /// Data contributed by the field to the commitment.
+    pub data: Vec<u8>,
+    /// Tagged hash of all the data committed up to and including this step.
+    pub running: Bytes32,
+}
+
+/// Trace of the commitment procedure, produced with
+/// [`CommitDump::commit_dump`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CommitTrace {
+    pub tag: [u8; 32],
+    pub steps: Vec<CommitStep>,
+    /// Resulting commitment, equal to [`CommitmentId::commitment_id`].
+    pub id: Bytes32,
+}
+
+impl CommitTrace {
+    /// Returns the first step at which the trace diverges from the expected
+    /// one, or `None` if both traces commit to the same data.
+    pub fn diverges_at<'a>(&'a self, expected: &CommitTrace) -> Option<&'a CommitStep> {
+        self.steps
+            .iter()
+            .zip(&expected.steps)
+            .find(|(step, expected)| step.field != expected.field || step.data != expected.data)
+            .map(|(step, _)| step)
+            .or_else(|| self.steps.get(expected.steps.len()))
+    }
+}
+
+impl Display for CommitTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tag: {}", String::from_utf8_lossy(&self.tag))?;
+        for step in &self.steps {
+            writeln!(f, "{}: {} -> {}", step.field, step.data.to_hex(), step.running)?;
+        }
+        write!(f, "id: {}", self.id)
+    }
+}
+
+/// Consensus data structures which can trace their commitment procedure.
+pub trait CommitDump: CommitmentId {
+    /// Returns commit-encoded fields in the order in which they are committed.
+    fn commit_fields(&self) -> Vec<(String, Vec<u8>)>;
+
+    /// Traces commitment procedure, reporting the data committed by each of
+    /// the fields and the running hash after each of them.
+    fn commit_dump(&self) -> CommitTrace {
+        let mut engine = Sha256::from_tag(Self::TAG);
+        let steps = self
+            .commit_fields()
+            .into_iter()
+            .map(|(field, data)| {
+                engine.input_raw(&data);
+                let running = engine.clone().finish().into();
+                CommitStep {
+                    field,
+                    data,
+                    running,
+                }
+            })
+            .collect();
+        CommitTrace {
+            tag: Self::TAG,
+            steps,
+            id: engine.finish().into(),
+        }
+    }
+}
+
+/// Builds merkle root of the leaves with the given merkle tree tag. Produces
+/// the same result as [`MerkleNode::merklize`] for a [`MerkleLeaves`]
+/// implementation returning the same leaves.
+pub fn merklize<Leaf: CommitEncode, I>(tag: u128, leaves: I) -> MerkleNode
+where
+    I: IntoIterator<Item = Leaf>,
+    I::IntoIter: ExactSizeIterator,
+{
+    MerkleNode::_merklize(tag.to_be_bytes(), leaves.into_iter(), u4::ZERO, 0)
+}
+
+/// Tag of the [`SparseTree`] nodes.
+pub const SPARSE_TREE_TAG: [u8; 32] = *b"urn:lnpbp:rgb:sparse-states#2024";
+
+/// Depth of the [`SparseTree`], which allows to address any of the
+/// assignments of a single type.
+pub const SPARSE_TREE_DEPTH: u8 = 16;
+
+fn sparse_node(level: u8, left: MerkleNode, right: MerkleNode) -> MerkleNode {
+    let mut engine = Sha256::from_tag(SPARSE_TREE_TAG);
+    engine.input_raw(&[level]);
+    engine.input_raw(left.as_slice());
+    engine.input_raw(right.as_slice());
+    MerkleNode::from(engine.finish())
+}
+
+// Roots of the empty subtrees for each of the tree levels, starting from the
+// empty leaf
+fn sparse_empty() -> Vec<MerkleNode> {
+    let mut empty = vec![MerkleNode::from([0u8; 32])];
+    for level in 1..=SPARSE_TREE_DEPTH {
+        let node = empty[level as usize - 1];
+        empty.push(sparse_node(level, node, node));
+    }
+    empty
+}
+
+/// Sparse merkle tree committing to the owned state assignments of a single
+/// type by their index, used by operations with
+/// [`crate::Ffv::SPARSE_ASSIGNMENTS`] version. Unlike [LNPBP-81]
+/// merklization, the tree allows to prove inclusion of a single assignment
+/// with a proof of a logarithmic size.
+///
+/// [LNPBP-81]: https://github.com/LNP-BP/LNPBPs/blob/master/lnpbp-0081.md
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SparseTree {
+    leaves: BTreeMap<u16, MerkleNode>,
+}
+
+impl SparseTree {
+    /// Constructs the tree with the leaves placed under their index.
+    ///
+    /// # Panics
+    ///
+    /// If there are more than 2^16 leaves.
+    pub fn with(leaves: impl IntoIterator<Item = MerkleNode>) -> Self {
+        let leaves = leaves
+            .into_iter()
+            .enumerate()
+            .map(|(index, leaf)| (u16::try_from(index).expect("too many leaves"), leaf))
+            .collect();
+        SparseTree { leaves }
+    }
+
+    /// Number of the non-empty leaves.
+    pub fn len(&self) -> usize { self.leaves.len() }
+
+    pub fn is_empty(&self) -> bool { self.leaves.is_empty() }
+
+    // Calls `f` with each of the tree levels, starting from the leaves; level
+    // nodes are keyed by their index within the level
+    fn levels(&self, mut f: impl FnMut(u8, &BTreeMap<u16, MerkleNode>)) -> MerkleNode {
+        let empty = sparse_empty();
+        let mut nodes = self.leaves.clone();
+        for level in 0..SPARSE_TREE_DEPTH {
+            f(level, &nodes);
+            let node = |index: u16| nodes.get(&index).copied().unwrap_or(empty[level as usize]);
+            nodes = nodes
+                .keys()
+                .map(|index| index >> 1)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(|parent| {
+                    (parent, sparse_node(level + 1, node(parent << 1), node((parent << 1) | 1)))
+                })
+                .collect();
+        }
+        nodes
+            .get(&0)
+            .copied()
+            .unwrap_or(empty[SPARSE_TREE_DEPTH as usize])
+    }
+
+    /// Returns the leaf with the given index, if present.
+    pub fn leaf(&self, index: u16) -> Option<MerkleNode> { self.leaves.get(&index).copied() }
+
+    /// Computes root of the tree.
+    pub fn root(&self) -> MerkleNode { self.levels(|_, _| {}) }
+
+    /// Produces proof of inclusion of the leaf with the given index, or `None`
+    /// if the leaf is absent.
+    pub fn prove(&self, index: u16) -> Option<SparseProof> {
+        self.leaves.get(&index)?;
+        let empty = sparse_empty();
+        let mut siblings = Vec::with_capacity(SPARSE_TREE_DEPTH as usize);
+        self.levels(|level, nodes| {
+            let sibling = (index >> level) ^ 1;
+            siblings.push(
+                nodes
+                    .get(&sibling)
+                    .copied()
+                    .unwrap_or(empty[level as usize]),
+            );
+        });
+        Some(SparseProof { index, siblings })
+    }
+}
+
+/// Proof of inclusion of a leaf into the [`SparseTree`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SparseProof {
+    /// Index of the leaf.
+    pub index: u16,
+    /// Siblings of the nodes on the path from the leaf to the root, starting
+    /// from the sibling of the leaf.
+    pub siblings: Vec<MerkleNode>,
+}
+
+impl SparseProof {
+    /// Computes root of the tree containing the leaf, or `None` if the proof
+    /// is malformed.
+    pub fn root(&self, leaf: MerkleNode) -> Option<MerkleNode> {
+        if self.siblings.len() != SPARSE_TREE_DEPTH as usize {
+            return None;
+        }
+        let mut node = leaf;
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            node = match (self.index >> level) & 1 {
+                0 => sparse_node(level as u8 + 1, node, *sibling),
+                _ => sparse_node(level as u8 + 1, *sibling, node),
+            };
+        }
+        Some(node)
+    }
+
+    /// Verifies that the leaf is included into the tree with the given root.
+    pub fn verify(&self, leaf: MerkleNode, root: MerkleNode) -> bool {
+        self.root(leaf) == Some(root)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::Wrapper;
+
+    use super::*;
+    use crate::{Assign, GraphSeal, TypedAssigns, VoidState};
+
+    #[test]
+    fn compatibility() {
+        let seal = |vout| GraphSeal::with_vout(bp::seals::txout::CloseMethod::TapretFirst, vout, 1);
+        let assigns = TypedAssigns::Declarative(small_vec![
+            Assign::revealed(seal(0), VoidState::default()),
+            Assign::revealed(seal(1), VoidState::default()),
+            Assign::revealed(seal(2), VoidState::default()),
+        ]);
+        assert_eq!(
+            merklize(OWNED_STATE_MERKLE_TAG, assigns.merkle_leaves()),
+            MerkleNode::merklize(OWNED_STATE_MERKLE_TAG.to_be_bytes(), &assigns)
+        );
+
+        let assign = Assign::revealed(seal(0), VoidState::default());
+        assert_eq!(
+            commitment_hash(Assign::<VoidState, GraphSeal>::TAG, &assign),
+            assign.commitment_id().into_inner()
+        );
+        assert_ne!(tagged_hash(b"a", b"data"), tagged_hash(b"b", b"data"));
+    }
+
+    #[test]
+    fn sparse_tree() {
+        let leaf = |no: u8| MerkleNode::from([no; 32]);
+        let tree = SparseTree::with((1..=5).map(leaf));
+        let root = tree.root();
+        assert_eq!(SparseTree::default().root(), sparse_empty()[SPARSE_TREE_DEPTH as usize]);
+        assert_ne!(root, SparseTree::with((1..=4).map(leaf)).root());
+        assert_ne!(root, SparseTree::with((2..=6).map(leaf)).root());
+
+        for index in 0..5u16 {
+            let proof = tree.prove(index).unwrap();
+            assert_eq!(proof.siblings.len(), SPARSE_TREE_DEPTH as usize);
+            assert!(proof.verify(leaf(index as u8 + 1), root));
+            assert!(!proof.verify(leaf(index as u8 + 2), root));
+        }
+        assert_eq!(tree.prove(5), None);
+
+        let mut proof = tree.prove(3).unwrap();
+        proof.index = 2;
+        assert!(!proof.verify(leaf(4), root));
+        proof.siblings.pop();
+        assert_eq!(proof.root(leaf(4)), None);
+    }
+}
@@ -0,0 +1,317 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Consensus freeze: all the consensus-relevant constants (commitment tags,
+//! strict type library id and ISA ids) are asserted here against their frozen
+//! values, such that any unintentional change fails the build or the tests.
+//! The [`consensus_fingerprint`] function aggregates them into a single value
+//! which downstream software can log to identify the consensus rules in use.
+//!
+//! The [`CommitmentOrder`] trait exposes the exact ordering of the items of
+//! the map-based structures in their commitment encoding, such that
+//! alternative implementations can cross-check their canonical ordering.
+
+use aluvm::isa::{Bytecode, InstructionSet};
+use amplify::{Bytes32, Wrapper};
+use commit_verify::merkle::{MerkleLeaves, MerkleNode};
+use commit_verify::{CommitmentId, DigestExt, Sha256};
+
+use crate::commit::SPARSE_TREE_TAG;
+use crate::schema::SchemaRoot;
+use crate::vm::RgbIsa;
+use crate::{
+    Assign, AssignmentType, Assignments, Checkpoint, ExposedSeal, Extension, ExtensionType, Ffv,
+    Genesis, GlobalState, GlobalStateType, GraphSeal, OpId, RevealedValue, Schema, SubSchema,
+    Transition, TransitionBundle, TransitionType, ValencyType, LIB_ID_RGB, LIB_NAME_RGB,
+};
+
+/// Tag used for computing [`ConsensusFingerprint`].
+pub const FINGERPRINT_TAG: [u8; 32] = *b"urn:lnpbp:rgb:consensus:v1#2023A";
+
+/// Expected value of the [`consensus_fingerprint`] for this release. Must be
+/// updated only together with an intentional change of the consensus rules.
+pub const CONSENSUS_FINGERPRINT: &str =
+    "06162918427af2c7132a86560d5c9ffd0d6a8c4ec08f1912345e7e50c185a084";
+
+/// Frozen commitment tags of the consensus data types.
+pub const COMMITMENT_TAGS: [(&str, [u8; 32]); 8] = [
+    ("schema", *b"urn:lnpbp:rgb:schema:v01#202302A"),
+    ("genesis", *b"urn:lnpbp:rgb:genesis:v02#202304"),
+    ("transition", *b"urn:lnpbp:rgb:transition:v02#23B"),
+    ("extension", *b"urn:lnpbp:rgb:extension:v02#2304"),
+    ("bundle", *b"urn:lnpbp:rgb:bundle:v1#20230306"),
+    ("owned-state", *b"urn:lnpbp:rgb:owned-state:v1#23A"),
+    ("checkpoint", *b"urn:lnpbp:rgb:checkpoint:v1#2023"),
+    ("sparse-states", *b"urn:lnpbp:rgb:sparse-states#2024"),
+];
+
+const fn tag_eq(tag: [u8; 32], frozen: [u8; 32]) -> bool {
+    let mut i = 0;
+    while i < 32 {
+        if tag[i] != frozen[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const _: () = {
+    assert!(tag_eq(SubSchema::TAG, COMMITMENT_TAGS[0].1), "schema commitment tag changed");
+    assert!(tag_eq(Genesis::TAG, COMMITMENT_TAGS[1].1), "genesis commitment tag changed");
+    assert!(tag_eq(Transition::TAG, COMMITMENT_TAGS[2].1), "transition commitment tag changed");
+    assert!(tag_eq(Extension::TAG, COMMITMENT_TAGS[3].1), "extension commitment tag changed");
+    assert!(tag_eq(TransitionBundle::TAG, COMMITMENT_TAGS[4].1), "bundle commitment tag changed");
+    assert!(
+        tag_eq(<Assign<RevealedValue, GraphSeal>>::TAG, COMMITMENT_TAGS[5].1),
+        "owned state commitment tag changed"
+    );
+    assert!(tag_eq(Checkpoint::TAG, COMMITMENT_TAGS[6].1), "checkpoint commitment tag changed");
+    assert!(tag_eq(SPARSE_TREE_TAG, COMMITMENT_TAGS[7].1), "sparse tree commitment tag changed");
+};
+
+/// Fingerprint of the consensus rules implemented by this library, which
+/// commits to the strict type library id, commitment tags, ISA ids of the RGB
+/// virtual machine and the latest supported fast-forward version.
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[wrapper(Deref, BorrowSlice, Display, Hex, Index, RangeOps)]
+pub struct ConsensusFingerprint(
+    #[from]
+    #[from([u8; 32])]
+    Bytes32,
+);
+
+/// Computes fingerprint of the consensus rules implemented by this library.
+pub fn consensus_fingerprint() -> ConsensusFingerprint {
+    let mut engine = Sha256::from_tag(FINGERPRINT_TAG);
+    let mut input_str = |s: &str| {
+        engine.input_raw(&[s.len() as u8]);
+        engine.input_raw(s.as_bytes());
+    };
+    input_str(LIB_NAME_RGB);
+    input_str(LIB_ID_RGB);
+    for isa_id in RgbIsa::isa_ids() {
+        input_str(isa_id);
+    }
+    for (_, tag) in COMMITMENT_TAGS {
+        engine.input_raw(&tag);
+    }
+    let range = RgbIsa::instr_range();
+    engine.input_raw(&[*range.start(), *range.end()]);
+    engine.input_raw(&Ffv::LATEST.0.to_le_bytes());
+    ConsensusFingerprint::from_inner(engine.finish().into())
+}
+
+/// Structures committed as ordered maps, exposing the order of their items in
+/// the commitment encoding.
+pub trait CommitmentOrder {
+    /// Item identifying a single leaf of the structure.
+    type Leaf;
+
+    /// Returns all leaves of the structure in the exact order in which they
+    /// are serialized by the commitment procedure.
+    fn commitment_order(&self) -> Vec<Self::Leaf>;
+}
+
+impl<Seal: ExposedSeal> CommitmentOrder for Assignments<Seal> {
+    /// Assignment type and merkle leaf of the owned state assignment.
+    type Leaf = (AssignmentType, MerkleNode);
+
+    fn commitment_order(&self) -> Vec<Self::Leaf> {
+        self.iter()
+            .flat_map(|(ty, assigns)| assigns.merkle_leaves().map(|leaf| (*ty, leaf)))
+            .collect()
+    }
+}
+
+impl CommitmentOrder for GlobalState {
+    /// Global state type and index of the value within the type.
+    type Leaf = (GlobalStateType, u16);
+
+    fn commitment_order(&self) -> Vec<Self::Leaf> {
+        self.iter()
+            .flat_map(|(ty, values)| (0..values.len() as u16).map(|no| (*ty, no)))
+            .collect()
+    }
+}
+
+impl CommitmentOrder for TransitionBundle {
+    type Leaf = OpId;
+
+    fn commitment_order(&self) -> Vec<Self::Leaf> { self.keys().copied().collect() }
+}
+
+/// Entry of one of the type tables of a schema.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum SchemaLeaf {
+    GlobalType(GlobalStateType),
+    OwnedType(AssignmentType),
+    Maturity(AssignmentType),
+    VoutDerivation(AssignmentType),
+    KeyRight(AssignmentType),
+    ValencyType(ValencyType),
+    Extension(ExtensionType),
+    Transition(TransitionType),
+    Provisional(TransitionType),
+    GlobalTypeName(GlobalStateType),
+    OwnedTypeName(AssignmentType),
+    TransitionName(TransitionType),
+}
+
+impl<Root: SchemaRoot> CommitmentOrder for Schema<Root> {
+    type Leaf = SchemaLeaf;
+
+    fn commitment_order(&self) -> Vec<Self::Leaf> {
+        let global = self
+            .global_types
+            .keys()
+            .copied()
+            .map(SchemaLeaf::GlobalType);
+        let owned = self.owned_types.keys().copied().map(SchemaLeaf::OwnedType);
+        let maturity = self.maturity.keys().copied().map(SchemaLeaf::Maturity);
+        let vout_derivation = self
+            .vout_derivation
+            .keys()
+            .copied()
+            .map(SchemaLeaf::VoutDerivation);
+        let key_rights = self.key_rights.iter().copied().map(SchemaLeaf::KeyRight);
+        let valency = self
+            .valency_types
+            .iter()
+            .copied()
+            .map(SchemaLeaf::ValencyType);
+        let extension = self.extensions.keys().copied().map(SchemaLeaf::Extension);
+        let transition = self.transitions.keys().copied().map(SchemaLeaf::Transition);
+        let provisional = self
+            .provisional
+            .keys()
+            .copied()
+            .map(SchemaLeaf::Provisional);
+        let names = &self.names;
+        let global_names = names
+            .global_types
+            .keys()
+            .copied()
+            .map(SchemaLeaf::GlobalTypeName);
+        let owned_names = names
+            .owned_types
+            .keys()
+            .copied()
+            .map(SchemaLeaf::OwnedTypeName);
+        let transition_names = names
+            .transitions
+            .keys()
+            .copied()
+            .map(SchemaLeaf::TransitionName);
+        global
+            .chain(owned)
+            .chain(maturity)
+            .chain(vout_derivation)
+            .chain(key_rights)
+            .chain(valency)
+            .chain(extension)
+            .chain(transition)
+            .chain(provisional)
+            .chain(global_names)
+            .chain(owned_names)
+            .chain(transition_names)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+    use bp::secp256k1::rand::thread_rng;
+    use strict_encoding::StrictDumb;
+    use strict_types::SemId;
+
+    use super::*;
+    use crate::commit::{commitment_hash, merklize, OWNED_STATE_MERKLE_TAG};
+    use crate::{GlobalStateSchema, RevealedData, StateSchema, TypedAssigns};
+
+    #[test]
+    fn isa_ids() {
+        assert_eq!(RgbIsa::isa_ids(), bset! {"RGB"});
+        assert_eq!(RgbIsa::instr_range(), 0b10_000_000..=0b11_111_110);
+    }
+
+    #[test]
+    fn fingerprint() {
+        assert_eq!(consensus_fingerprint().to_string(), CONSENSUS_FINGERPRINT);
+    }
+
+    #[test]
+    fn commitment_order() {
+        let mut global = GlobalState::default();
+        global.add_state(3, RevealedData::with_counter(1)).unwrap();
+        global.add_state(1, RevealedData::with_counter(2)).unwrap();
+        global.add_state(3, RevealedData::with_counter(3)).unwrap();
+        assert_eq!(global.commitment_order(), vec![(1, 0), (3, 0), (3, 1)]);
+
+        let schema = SubSchema {
+            owned_types: tiny_bmap! {
+                2 => StateSchema::Declarative,
+                1 => StateSchema::Declarative,
+            },
+            maturity: tiny_bmap! { 1 => 6 },
+            global_types: tiny_bmap! { 5 => GlobalStateSchema::once(SemId::strict_dumb()) },
+            ..default!()
+        };
+        assert_eq!(schema.commitment_order(), vec![
+            SchemaLeaf::GlobalType(5),
+            SchemaLeaf::OwnedType(1),
+            SchemaLeaf::OwnedType(2),
+            SchemaLeaf::Maturity(1),
+        ]);
+    }
+
+    #[test]
+    fn leaf_hash() {
+        let assigns = TypedAssigns::Fungible(small_vec![
+            Assign::revealed(
+                GraphSeal::with_vout(CloseMethod::OpretFirst, 0, 1),
+                RevealedValue::new(100u64, &mut thread_rng())
+            ),
+            Assign::revealed(
+                GraphSeal::with_vout(CloseMethod::TapretFirst, 1, 2),
+                RevealedValue::new(200u64, &mut thread_rng())
+            ),
+        ]);
+        let TypedAssigns::Fungible(vec) = &assigns else { unreachable!() };
+        for (no, assign) in vec.iter().enumerate() {
+            let leaf = assign.leaf_hash();
+            assert_eq!(leaf, MerkleNode::from(commitment_hash(COMMITMENT_TAGS[5].1, assign)));
+            assert_eq!(assigns.leaf_hash(no as u16), Ok(leaf));
+        }
+        assert!(assigns.leaf_hash(2).is_err());
+
+        let leaves = (0..2)
+            .map(|no| assigns.leaf_hash(no).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(assigns.merkle_leaves().collect::<Vec<_>>(), leaves);
+        assert_eq!(
+            MerkleNode::merklize(OWNED_STATE_MERKLE_TAG.to_be_bytes(), &assigns),
+            merklize(OWNED_STATE_MERKLE_TAG, leaves)
+        );
+    }
+}
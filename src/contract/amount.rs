@@ -0,0 +1,288 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Amounts of fungible assets expressed in nominal units with a fixed decimal
+//! precision. These types are never committed to and are used only at the API
+//! boundaries for the conversion between the nominal values provided by users
+//! and atomic values of [`FungibleState`].
+//!
+//! Sums of the atomic values, like the ones computed by the state conservation
+//! checks, are accumulated with [`Amount`], which never wraps around on
+//! overflow.
+
+use core::fmt::{self, Display, Formatter};
+use core::num::TryFromIntError;
+
+use amplify::Wrapper;
+
+use crate::{schema, FungibleState};
+
+/// Maximal supported precision, for which `10^precision` still fits into
+/// 128-bit integer.
+pub const MAX_PRECISION: u8 = 38;
+
+/// Errors converting amounts between nominal and atomic units.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum AmountError {
+    /// precision {0} exceeds maximal supported precision of 38 decimal digits.
+    PrecisionOverflow(u8),
+
+    /// amount exceeds maximal value representable with 128 bits.
+    Overflow,
+
+    /// amount {0} does not fit into the range of {1} fungible type.
+    ValueOverflow(PreciseAmount, schema::FungibleType),
+
+    /// amount {0} can't be represented with precision {1} without the loss of
+    /// its fractional part.
+    PrecisionLoss(PreciseAmount, u8),
+
+    /// amount with precision {0} can't be used where precision {1} is
+    /// expected.
+    PrecisionMismatch(u8, u8),
+
+    /// amount '{0}' has more fractional digits than allowed by precision {1}.
+    ExcessiveFraction(String, u8),
+
+    /// invalid amount string '{0}'.
+    InvalidFormat(String),
+}
+
+/// Accumulated number of atomic units of a fungible state. Additions either
+/// report overflow ([`Amount::checked_add`], [`Amount::sum`]) or saturate at
+/// [`Amount::MAX`], such that the sums never silently wrap around.
+#[derive(Wrapper, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default, Display)]
+#[derive(From)]
+#[display(inner)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
+pub struct Amount(#[from] u128);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const MAX: Amount = Amount(u128::MAX);
+
+    /// Adds amounts, returning `None` on overflow.
+    pub fn checked_add(self, other: impl Into<Amount>) -> Option<Amount> {
+        self.0.checked_add(other.into().0).map(Amount)
+    }
+
+    /// Adds amounts, saturating at [`Amount::MAX`] on overflow.
+    pub fn saturating_add(self, other: impl Into<Amount>) -> Amount {
+        Amount(self.0.saturating_add(other.into().0))
+    }
+
+    /// Subtracts amounts, returning `None` if `other` exceeds `self`.
+    pub fn checked_sub(self, other: impl Into<Amount>) -> Option<Amount> {
+        self.0.checked_sub(other.into().0).map(Amount)
+    }
+
+    /// Sums the amounts, returning `None` on overflow.
+    pub fn sum<A: Into<Amount>>(amounts: impl IntoIterator<Item = A>) -> Option<Amount> {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, |sum, amount| sum.checked_add(amount))
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(value: u64) -> Self { Amount(value as u128) }
+}
+
+impl From<FungibleState> for Amount {
+    fn from(state: FungibleState) -> Self { Amount(state.as_u128()) }
+}
+
+impl TryFrom<Amount> for u64 {
+    type Error = TryFromIntError;
+    fn try_from(amount: Amount) -> Result<Self, Self::Error> { u64::try_from(amount.0) }
+}
+
+/// Amount of a fungible asset represented by an integer number of atomic
+/// units and a decimal exponent (precision), such that the nominal value is
+/// `atoms * 10^-precision`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PreciseAmount {
+    atoms: u128,
+    precision: u8,
+}
+
+impl PreciseAmount {
+    /// Constructs amount from the number of atomic units and precision.
+    pub fn with_atoms(atoms: u128, precision: u8) -> Result<Self, AmountError> {
+        if precision > MAX_PRECISION {
+            return Err(AmountError::PrecisionOverflow(precision));
+        }
+        Ok(PreciseAmount { atoms, precision })
+    }
+
+    /// Constructs amount from an integer number of nominal units.
+    pub fn with_nominal(units: u128, precision: u8) -> Result<Self, AmountError> {
+        let amount = PreciseAmount::with_atoms(0, precision)?;
+        let atoms = units.checked_mul(amount.unit()).ok_or(AmountError::Overflow)?;
+        Ok(PreciseAmount { atoms, precision })
+    }
+
+    /// Constructs amount from the atomic value of a fungible state.
+    pub fn from_state(state: FungibleState, precision: u8) -> Result<Self, AmountError> {
+        PreciseAmount::with_atoms(state.as_u128(), precision)
+    }
+
+    /// Parses decimal string representation of the nominal amount (like
+    /// `12.05`) using the provided precision. Fails if the string has more
+    /// fractional digits than the precision allows.
+    pub fn parse(s: &str, precision: u8) -> Result<Self, AmountError> {
+        let invalid = || AmountError::InvalidFormat(s.to_owned());
+        PreciseAmount::with_atoms(0, precision)?;
+        let (int, fract) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if int.is_empty() || !is_digits(int) || !is_digits(fract) {
+            return Err(invalid());
+        }
+        let fract = fract.trim_end_matches('0');
+        if fract.len() > precision as usize {
+            return Err(AmountError::ExcessiveFraction(s.to_owned(), precision));
+        }
+        let int = int.parse::<u128>().map_err(|_| invalid())?;
+        let amount = PreciseAmount::with_nominal(int, precision)?;
+        let fract = if fract.is_empty() {
+            0
+        } else {
+            let scale = 10u128.pow((precision as usize - fract.len()) as u32);
+            fract.parse::<u128>().map_err(|_| invalid())? * scale
+        };
+        amount
+            .atoms
+            .checked_add(fract)
+            .map(|atoms| PreciseAmount { atoms, precision })
+            .ok_or(AmountError::Overflow)
+    }
+
+    /// Number of atomic units in the amount.
+    pub fn atoms(&self) -> u128 { self.atoms }
+
+    /// Decimal precision of the amount.
+    pub fn precision(&self) -> u8 { self.precision }
+
+    /// Number of atomic units in a single nominal unit.
+    pub fn unit(&self) -> u128 { 10u128.pow(self.precision as u32) }
+
+    /// Integer part of the nominal value.
+    pub fn int_part(&self) -> u128 { self.atoms / self.unit() }
+
+    /// Fractional part of the nominal value, in atomic units.
+    pub fn fract_part(&self) -> u128 { self.atoms % self.unit() }
+
+    /// Converts the amount to a different precision, failing if the
+    /// conversion will overflow or lose the fractional part of the value.
+    pub fn with_precision(self, precision: u8) -> Result<Self, AmountError> {
+        let target = PreciseAmount::with_atoms(0, precision)?;
+        let atoms = if precision >= self.precision {
+            let scale = 10u128.pow((precision - self.precision) as u32);
+            self.atoms.checked_mul(scale).ok_or(AmountError::Overflow)?
+        } else {
+            let scale = 10u128.pow((self.precision - precision) as u32);
+            if self.atoms % scale != 0 {
+                return Err(AmountError::PrecisionLoss(self, precision));
+            }
+            self.atoms / scale
+        };
+        Ok(PreciseAmount { atoms, ..target })
+    }
+
+    /// Converts the amount into the fungible state of the provided type,
+    /// checking that the precision matches the one expected by the contract.
+    pub fn to_state(
+        &self,
+        precision: u8,
+        ty: schema::FungibleType,
+    ) -> Result<FungibleState, AmountError> {
+        if precision != self.precision {
+            return Err(AmountError::PrecisionMismatch(self.precision, precision));
+        }
+        FungibleState::with_type(self.atoms, ty).ok_or(AmountError::ValueOverflow(*self, ty))
+    }
+}
+
+impl Display for PreciseAmount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.int_part())?;
+        if self.precision > 0 {
+            write!(f, ".{:0width$}", self.fract_part(), width = self.precision as usize)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_display() {
+        let amount = PreciseAmount::parse("12.05", 8).unwrap();
+        assert_eq!(amount.atoms(), 12_0500_0000);
+        assert_eq!(amount.to_string(), "12.05000000");
+        assert_eq!(PreciseAmount::parse("12", 0).unwrap().to_string(), "12");
+        assert!(matches!(
+            PreciseAmount::parse("0.001", 2),
+            Err(AmountError::ExcessiveFraction(_, 2))
+        ));
+        assert!(PreciseAmount::parse(".1", 2).is_err());
+        assert!(PreciseAmount::parse("1.-1", 2).is_err());
+    }
+
+    #[test]
+    fn conversions() {
+        let amount = PreciseAmount::with_nominal(5, 2).unwrap();
+        assert_eq!(amount.with_precision(4).unwrap().atoms(), 50000);
+        assert_eq!(amount.with_precision(0).unwrap().atoms(), 5);
+        let amount = PreciseAmount::with_atoms(501, 2).unwrap();
+        assert!(amount.with_precision(1).is_err());
+        assert_eq!(
+            amount.to_state(2, schema::FungibleType::Unsigned64Bit),
+            Ok(FungibleState::Bits64(501))
+        );
+        assert!(amount.to_state(3, schema::FungibleType::Unsigned64Bit).is_err());
+        let huge = PreciseAmount::with_atoms(u64::MAX as u128 + 1, 0).unwrap();
+        assert!(huge.to_state(0, schema::FungibleType::Unsigned64Bit).is_err());
+    }
+
+    #[test]
+    fn sums() {
+        let values = [FungibleState::Bits64(u64::MAX), FungibleState::Bits64(1)];
+        let sum = Amount::sum(values).unwrap();
+        assert_eq!(sum, Amount::from(u64::MAX as u128 + 1));
+        assert!(u64::try_from(sum).is_err());
+        assert_eq!(Amount::sum([10u64, 20]).and_then(|a| u64::try_from(a).ok()), Some(30));
+
+        let huge = FungibleState::Bits128(u128::MAX);
+        assert_eq!(Amount::sum([huge, FungibleState::Bits64(1)]), None);
+        assert_eq!(Amount::from(huge).checked_add(1u64), None);
+        assert_eq!(Amount::from(huge).saturating_add(1u64), Amount::MAX);
+        assert_eq!(Amount::ZERO.checked_sub(1u64), None);
+    }
+}
@@ -22,20 +22,21 @@
 
 use core::cmp::Ordering;
 use core::fmt::Debug;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::hash::{Hash, Hasher};
 use std::{io, vec};
 
-use amplify::confinement::{Confined, SmallVec, TinyOrdMap};
+use amplify::confinement::{self, Confined, SmallVec, TinyOrdMap};
 use commit_verify::merkle::{MerkleLeaves, MerkleNode};
 use commit_verify::{CommitEncode, CommitStrategy, CommitmentId, Conceal};
 use strict_encoding::{StrictDumb, StrictEncode, StrictWriter};
 
 use super::ExposedState;
+use crate::commit::{commit_encoded, strict_encoded, SparseTree, OWNED_STATE_MERKLE_TAG};
 use crate::contract::seal::GenesisSeal;
 use crate::{
-    AssignmentType, ExposedSeal, GraphSeal, RevealedAttach, RevealedData, RevealedValue,
-    SecretSeal, StateType, VoidState, LIB_NAME_RGB,
+    AssignmentType, ExposedSeal, Ffv, GraphSeal, RevealedAttach, RevealedData, RevealedValue,
+    Schema, SchemaRoot, SecretSeal, StateSchema, StateType, VoidState, LIB_NAME_RGB,
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, Error)]
@@ -202,6 +203,12 @@ impl<State: ExposedState, Seal: ExposedSeal> Assign<State, Seal> {
     }
 }
 
+impl<Seal: ExposedSeal> AssignRights<Seal> {
+    /// Constructs assignment of a right which does not carry any state to a
+    /// revealed seal.
+    pub fn right(seal: Seal) -> Self { Assign::revealed(seal, VoidState::default()) }
+}
+
 impl<State: ExposedState, Seal: ExposedSeal> Conceal for Assign<State, Seal>
 where Self: Clone
 {
@@ -226,6 +233,34 @@ where Self: Clone
     }
 }
 
+/// Trait for concealing seals of the assignments, keeping revealed only the
+/// seals which are explicitly whitelisted.
+pub trait ConcealSeals<Seal: ExposedSeal> {
+    /// Conceals all revealed seals except the ones present in the `seals`
+    /// list. Returns number of seals which were concealed.
+    fn conceal_all_except(&mut self, seals: &[Seal]) -> usize;
+}
+
+impl<State: ExposedState, Seal: ExposedSeal> ConcealSeals<Seal> for Assign<State, Seal> {
+    fn conceal_all_except(&mut self, seals: &[Seal]) -> usize {
+        let concealed = match self {
+            Assign::Revealed { seal, state } if !seals.contains(seal) => Assign::ConfidentialSeal {
+                seal: seal.conceal(),
+                state: state.clone(),
+            },
+            Assign::ConfidentialState { seal, state } if !seals.contains(seal) => {
+                Assign::Confidential {
+                    seal: seal.conceal(),
+                    state: *state,
+                }
+            }
+            _ => return 0,
+        };
+        *self = concealed;
+        1
+    }
+}
+
 // We do not derive here since we omit serialization of the tag: all data are
 // concealed, thus no tag is needed.
 impl<State: ExposedState, Seal: ExposedSeal> CommitEncode for Assign<State, Seal>
@@ -260,6 +295,21 @@ where Self: Clone
     type Id = MerkleNode;
 }
 
+impl<State: ExposedState, Seal: ExposedSeal> Assign<State, Seal>
+where Self: Clone
+{
+    /// Returns merkle leaf committing to the assignment, which is used in the
+    /// [LNPBP-81] merklization of the assignments of the same type (see
+    /// [`TypedAssigns::leaf_hash`]).
+    ///
+    /// The leaf is a SHA-256 hash tagged with
+    /// `urn:lnpbp:rgb:owned-state:v1#23A` of the commit-encoded seal followed
+    /// by the commit-encoded state.
+    ///
+    /// [LNPBP-81]: https://github.com/LNP-BP/LNPBPs/blob/master/lnpbp-0081.md
+    pub fn leaf_hash(&self) -> MerkleNode { self.commitment_id() }
+}
+
 impl<State: ExposedState> Assign<State, GenesisSeal> {
     pub fn transmutate_seals(&self) -> Assign<State, GraphSeal> {
         match self {
@@ -335,7 +385,65 @@ impl<Seal: ExposedSeal> Conceal for TypedAssigns<Seal> {
     }
 }
 
+impl<Seal: ExposedSeal> ConcealSeals<Seal> for TypedAssigns<Seal> {
+    fn conceal_all_except(&mut self, seals: &[Seal]) -> usize {
+        match self {
+            TypedAssigns::Declarative(s) => s.iter_mut().map(|a| a.conceal_all_except(seals)).sum(),
+            TypedAssigns::Fungible(s) => s.iter_mut().map(|a| a.conceal_all_except(seals)).sum(),
+            TypedAssigns::Structured(s) => s.iter_mut().map(|a| a.conceal_all_except(seals)).sum(),
+            TypedAssigns::Attachment(s) => s.iter_mut().map(|a| a.conceal_all_except(seals)).sum(),
+        }
+    }
+}
+
+/// View on declarative assignments, i.e. rights which do not carry any state
+/// (like voting or renomination rights).
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct DeclarativeAssignments<'a, Seal: ExposedSeal>(&'a [AssignRights<Seal>]);
+
+impl<'a, Seal: ExposedSeal> DeclarativeAssignments<'a, Seal> {
+    /// Returns number of rights assigned.
+    #[inline]
+    pub fn count(&self) -> u16 { self.0.len() as u16 }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Iterates over all seals to which the rights are assigned, in their
+    /// concealed form.
+    pub fn seals(&self) -> impl Iterator<Item = SecretSeal> + 'a {
+        self.0.iter().map(AssignRights::<Seal>::to_confidential_seal)
+    }
+
+    /// Iterates over seals to which the rights are assigned, skipping the
+    /// seals which are concealed.
+    pub fn revealed_seals(&self) -> impl Iterator<Item = Seal> + 'a {
+        self.0.iter().filter_map(AssignRights::<Seal>::revealed_seal)
+    }
+
+    /// Returns the underlying assignments.
+    #[inline]
+    pub fn as_slice(&self) -> &'a [AssignRights<Seal>] { self.0 }
+}
+
 impl<Seal: ExposedSeal> TypedAssigns<Seal> {
+    /// Constructs declarative assignments of rights without state to the
+    /// provided seals.
+    pub fn with_rights(seals: impl IntoIterator<Item = Seal>) -> Result<Self, confinement::Error> {
+        SmallVec::try_from_iter(seals.into_iter().map(AssignRights::right))
+            .map(TypedAssigns::Declarative)
+    }
+
+    /// Returns view on the declarative assignments, or `None` if the
+    /// assignments have other state type.
+    #[inline]
+    pub fn declarative(&self) -> Option<DeclarativeAssignments<'_, Seal>> {
+        match self {
+            TypedAssigns::Declarative(set) => Some(DeclarativeAssignments(set)),
+            _ => None,
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         match self {
             TypedAssigns::Declarative(set) => set.is_empty(),
@@ -464,6 +572,66 @@ impl<Seal: ExposedSeal> TypedAssigns<Seal> {
         })
     }
 
+    /// Returns merkle leaf of the assignment with the given index (see
+    /// [`Assign::leaf_hash`]). The leaves are merklized into the commitment
+    /// to the assignments of the type with the `rgb:state:owned*` merkle tree
+    /// tag ([`OWNED_STATE_MERKLE_TAG`]).
+    ///
+    /// If the assignment does not exist, returns [`UnknownDataError`].
+    pub fn leaf_hash(&self, index: u16) -> Result<MerkleNode, UnknownDataError> {
+        Ok(match self {
+            TypedAssigns::Declarative(vec) => vec
+                .get(index as usize)
+                .ok_or(UnknownDataError)?
+                .leaf_hash(),
+            TypedAssigns::Fungible(vec) => vec
+                .get(index as usize)
+                .ok_or(UnknownDataError)?
+                .leaf_hash(),
+            TypedAssigns::Structured(vec) => vec
+                .get(index as usize)
+                .ok_or(UnknownDataError)?
+                .leaf_hash(),
+            TypedAssigns::Attachment(vec) => vec
+                .get(index as usize)
+                .ok_or(UnknownDataError)?
+                .leaf_hash(),
+        })
+    }
+
+    /// Commits to the state of the assignments, ignoring their seals.
+    pub(crate) fn commit_states(&self, mut e: &mut impl io::Write) {
+        fn commit<State: ExposedState, Seal: ExposedSeal>(
+            assigns: &[Assign<State, Seal>],
+            mut e: &mut impl io::Write,
+        ) {
+            let w = StrictWriter::with(u32::MAX as usize, &mut e);
+            (assigns.len() as u16).strict_encode(w).ok();
+            // State is committed in the same way as by the operation id
+            for assign in assigns {
+                match assign {
+                    Assign::Confidential { state, .. } |
+                    Assign::ConfidentialState { state, .. } => state.commit_encode(e),
+                    Assign::Revealed { state, .. } | Assign::ConfidentialSeal { state, .. } => {
+                        state.commit_encode(e)
+                    }
+                }
+            }
+        }
+        let w = StrictWriter::with(u32::MAX as usize, &mut e);
+        (self.state_type() as u8).strict_encode(w).ok();
+        match self {
+            TypedAssigns::Declarative(s) => commit(s, e),
+            TypedAssigns::Fungible(s) => commit(s, e),
+            TypedAssigns::Structured(s) => commit(s, e),
+            TypedAssigns::Attachment(s) => commit(s, e),
+        }
+    }
+
+    /// Constructs [`SparseTree`] of the assignments, used by the operations of
+    /// [`Ffv::SPARSE_ASSIGNMENTS`] version.
+    pub fn sparse_tree(&self) -> SparseTree { SparseTree::with(self.merkle_leaves()) }
+
     pub fn to_confidential_seals(&self) -> Vec<SecretSeal> {
         match self {
             TypedAssigns::Declarative(s) => s
@@ -549,8 +717,7 @@ impl<Seal: ExposedSeal> TypedAssigns<Seal> {
 }
 
 impl<Seal: ExposedSeal> CommitStrategy for TypedAssigns<Seal> {
-    type Strategy =
-        commit_verify::strategies::Merklize<{ u128::from_be_bytes(*b"rgb:state:owned*") }>;
+    type Strategy = commit_verify::strategies::Merklize<OWNED_STATE_MERKLE_TAG>;
 }
 
 impl<Seal: ExposedSeal> MerkleLeaves for TypedAssigns<Seal> {
@@ -624,6 +791,30 @@ impl<Seal: ExposedSeal> Default for Assignments<Seal> {
     fn default() -> Self { Self(empty!()) }
 }
 
+impl<Seal: ExposedSeal> TryFrom<BTreeMap<AssignmentType, TypedAssigns<Seal>>>
+    for Assignments<Seal>
+{
+    type Error = confinement::Error;
+
+    fn try_from(map: BTreeMap<AssignmentType, TypedAssigns<Seal>>) -> Result<Self, Self::Error> {
+        TinyOrdMap::try_from(map).map(Assignments)
+    }
+}
+
+/// If the same assignment type is present several times, the last assignments
+/// are used.
+impl<Seal: ExposedSeal> TryFrom<Vec<(AssignmentType, TypedAssigns<Seal>)>> for Assignments<Seal> {
+    type Error = confinement::Error;
+
+    fn try_from(vec: Vec<(AssignmentType, TypedAssigns<Seal>)>) -> Result<Self, Self::Error> {
+        TinyOrdMap::try_from_iter(vec).map(Assignments)
+    }
+}
+
+impl<Seal: ExposedSeal> From<Assignments<Seal>> for BTreeMap<AssignmentType, TypedAssigns<Seal>> {
+    fn from(assignments: Assignments<Seal>) -> Self { assignments.0.into_inner() }
+}
+
 impl<Seal: ExposedSeal> CommitEncode for Assignments<Seal> {
     fn commit_encode(&self, mut e: &mut impl io::Write) {
         let w = StrictWriter::with(u32::MAX as usize, &mut e);
@@ -636,6 +827,74 @@ impl<Seal: ExposedSeal> CommitEncode for Assignments<Seal> {
     }
 }
 
+impl<Seal: ExposedSeal> Assignments<Seal> {
+    /// Data committed by each of the assignment types, for
+    /// [`crate::commit::CommitDump`].
+    ///
+    /// Operations of [`Ffv::SPARSE_ASSIGNMENTS`] version commit to the number
+    /// of the assignments of each type and the root of their [`SparseTree`].
+    pub(crate) fn commit_fields(&self, ffv: Ffv) -> Vec<(String, Vec<u8>)> {
+        let mut fields = vec![("assignments.len".into(), strict_encoded(&self.0.len_u8()))];
+        for (ty, state) in &self.0 {
+            fields.push((format!("assignments[{ty}].type"), strict_encoded(ty)));
+            let data = if ffv.has_sparse_assignments() {
+                sparse_field(state.len_u16(), state.sparse_tree().root())
+            } else {
+                commit_encoded(state)
+            };
+            fields.push((format!("assignments[{ty}]"), data));
+        }
+        fields
+    }
+}
+
+impl<Seal: ExposedSeal> Assignments<Seal> {
+    /// Commits to the assignments ignoring their seals, for
+    /// [`crate::PrevalidationId`].
+    pub(crate) fn commit_states(&self, mut e: &mut impl io::Write) {
+        let w = StrictWriter::with(u32::MAX as usize, &mut e);
+        self.0.len_u8().strict_encode(w).ok();
+        for (ty, state) in &self.0 {
+            let w = StrictWriter::with(u32::MAX as usize, &mut e);
+            ty.strict_encode(w).ok();
+            state.commit_states(e);
+        }
+    }
+}
+
+/// Data committed by the assignments of a single type represented by the root
+/// of their [`SparseTree`].
+pub(crate) fn sparse_field(count: u16, root: MerkleNode) -> Vec<u8> {
+    let mut data = strict_encoded(&count);
+    data.extend(commit_encoded(&root));
+    data
+}
+
+impl<Seal: ExposedSeal> ConcealSeals<Seal> for Assignments<Seal> {
+    fn conceal_all_except(&mut self, seals: &[Seal]) -> usize {
+        self.keyed_values_mut()
+            .map(|(_, a)| a.conceal_all_except(seals))
+            .sum()
+    }
+}
+
+impl<Seal: ExposedSeal> Assignments<Seal> {
+    /// Iterates over assignments together with the state schema defined for
+    /// their type by the provided contract schema. Assignments of types not
+    /// declared in the schema (which can't pass validation) are skipped.
+    pub fn iter_with_schema<'a, Root: SchemaRoot>(
+        &'a self,
+        schema: &'a Schema<Root>,
+    ) -> impl Iterator<Item = (AssignmentType, &'a StateSchema, &'a TypedAssigns<Seal>)> + 'a {
+        self.iter().filter_map(|(ty, assigns)| {
+            schema
+                .owned_types
+                .get(ty)
+                .map(|state_schema| (*ty, state_schema, assigns))
+        })
+    }
+}
+
 impl Assignments<GenesisSeal> {
     pub fn transmutate_seals(&self) -> Assignments<GraphSeal> {
         Assignments(
@@ -685,3 +944,73 @@ impl AssignmentsRef<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+    use bp::Txid;
+
+    use super::*;
+
+    #[test]
+    fn conceal_assign() {
+        let kept = GraphSeal::with_vout(CloseMethod::OpretFirst, 0, 1);
+        let other = GraphSeal::with_vout(CloseMethod::OpretFirst, 1, 2);
+        let data = RevealedData::from(small_vec![1u8, 2, 3]);
+
+        let mut assign = AssignData::revealed(kept, data.clone());
+        assert_eq!(assign.conceal_all_except(&[kept]), 0);
+        assert_eq!(assign.as_revealed(), Some((&kept, &data)));
+
+        let original = AssignData::revealed(other, data.clone());
+        let mut assign = original.clone();
+        assert_eq!(assign.conceal_all_except(&[kept]), 1);
+        assert!(matches!(assign, Assign::ConfidentialSeal { ref state, .. } if *state == data));
+        assert_eq!(assign, original);
+        assert_eq!(assign.conceal_all_except(&[kept]), 0);
+
+        let original = AssignData::ConfidentialState {
+            seal: other,
+            state: data.conceal(),
+        };
+        let mut assign = original.clone();
+        assert_eq!(assign.conceal_all_except(&[kept]), 1);
+        assert!(matches!(assign, Assign::Confidential { .. }));
+        assert_eq!(assign, original);
+    }
+
+    #[test]
+    fn conceal_assignments() {
+        let kept = GraphSeal::with_vout(CloseMethod::OpretFirst, 0, 1);
+        let other = GraphSeal::with_vout(CloseMethod::OpretFirst, 1, 2);
+        let txid = Txid::from([3u8; 32]);
+        let external = GraphSeal::with_blinding(CloseMethod::OpretFirst, txid, 0, 3);
+
+        let mut assigns = TypedAssigns::Declarative(small_vec![
+            Assign::right(kept),
+            Assign::right(other),
+            Assign::right(external),
+        ]);
+        assert_eq!(assigns.conceal_all_except(&[kept]), 2);
+        assert_eq!(assigns.revealed_seal_at(0), Ok(Some(kept)));
+        assert_eq!(assigns.revealed_seal_at(1), Ok(None));
+        assert_eq!(assigns.revealed_seal_at(2), Ok(None));
+        assert_eq!(assigns.conceal_all_except(&[kept]), 0);
+
+        let mut assignments = Assignments::from(tiny_bmap! {
+            1 => TypedAssigns::Declarative(small_vec![Assign::right(kept), Assign::right(other)]),
+            2 => TypedAssigns::Declarative(small_vec![Assign::right(external)]),
+        });
+        let revealed = |assignments: &Assignments<GraphSeal>| {
+            assignments
+                .values()
+                .flat_map(|assigns| (0..assigns.len_u16()).map(|i| assigns.revealed_seal_at(i)))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(assignments.conceal_all_except(&[kept, external]), 1);
+        assert_eq!(revealed(&assignments), vec![Some(kept), None, Some(external)]);
+        assert_eq!(assignments.conceal_all_except(&[]), 2);
+        assert_eq!(revealed(&assignments), vec![None, None, None]);
+    }
+}
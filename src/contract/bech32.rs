@@ -0,0 +1,203 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bech32m (BIP-350) encoding of 32-byte identifiers, provided as an
+//! alternative to Baid58 for the systems which can't handle the latter.
+
+/// Human-readable part of bech32m-encoded contract ids.
+pub const HRP_CONTRACT: &str = "rgbc";
+/// Human-readable part of bech32m-encoded operation (node) ids.
+pub const HRP_OPERATION: &str = "rgbn";
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32M_CONST: u32 = 0x2bc830a3;
+const CHECKSUM_LEN: usize = 6;
+const PAYLOAD_LEN: usize = 52;
+
+/// Errors parsing bech32m-encoded identifiers.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum Bech32Error {
+    /// bech32m string has no separator between human-readable part and data.
+    NoSeparator,
+
+    /// bech32m string mixes upper and lower case characters.
+    MixedCase,
+
+    /// invalid bech32m character '{0}'.
+    InvalidChar(char),
+
+    /// unexpected human-readable part '{found}' instead of '{expected}'.
+    HrpMismatch {
+        expected: &'static str,
+        found: String,
+    },
+
+    /// bech32m data has invalid length {0}.
+    InvalidLength(usize),
+
+    /// bech32m data has non-zero padding.
+    InvalidPadding,
+
+    /// invalid bech32m checksum.
+    InvalidChecksum,
+}
+
+fn polymod(values: impl IntoIterator<Item = u8>) -> u32 {
+    const GEN: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk = 1u32;
+    for v in values {
+        let top = chk >> 25;
+        chk = (chk & 0x1ffffff) << 5 ^ v as u32;
+        for (i, gen) in GEN.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= gen;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes()
+        .map(|b| b >> 5)
+        .chain([0])
+        .chain(hrp.bytes().map(|b| b & 0x1f))
+}
+
+/// Encodes 32-byte payload into bech32m string with the provided HRP.
+pub(crate) fn encode(hrp: &str, payload: [u8; 32]) -> String {
+    let mut data = Vec::with_capacity(PAYLOAD_LEN + CHECKSUM_LEN);
+    let (mut acc, mut bits) = (0u32, 0u8);
+    for byte in payload {
+        acc = acc << 8 | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            data.push((acc >> bits) as u8 & 0x1f);
+        }
+    }
+    if bits > 0 {
+        data.push((acc << (5 - bits)) as u8 & 0x1f);
+    }
+    let chk = polymod(
+        hrp_expand(hrp)
+            .chain(data.iter().copied())
+            .chain([0; CHECKSUM_LEN]),
+    );
+    let chk = chk ^ BECH32M_CONST;
+    data.extend((0..CHECKSUM_LEN).map(|i| (chk >> (5 * (5 - i))) as u8 & 0x1f));
+
+    let mut s = String::with_capacity(hrp.len() + 1 + data.len());
+    s.push_str(hrp);
+    s.push('1');
+    s.extend(data.into_iter().map(|d| CHARSET[d as usize] as char));
+    s
+}
+
+/// Decodes bech32m string with the provided HRP into a 32-byte payload.
+pub(crate) fn decode(hrp: &'static str, s: &str) -> Result<[u8; 32], Bech32Error> {
+    if s.chars().any(char::is_lowercase) && s.chars().any(char::is_uppercase) {
+        return Err(Bech32Error::MixedCase);
+    }
+    let s = s.to_lowercase();
+    let (found, data) = s.rsplit_once('1').ok_or(Bech32Error::NoSeparator)?;
+    if found != hrp {
+        return Err(Bech32Error::HrpMismatch {
+            expected: hrp,
+            found: found.to_owned(),
+        });
+    }
+    if data.len() != PAYLOAD_LEN + CHECKSUM_LEN {
+        return Err(Bech32Error::InvalidLength(data.len()));
+    }
+    let data = data
+        .chars()
+        .map(|c| {
+            CHARSET
+                .iter()
+                .position(|b| *b as char == c)
+                .map(|pos| pos as u8)
+                .ok_or(Bech32Error::InvalidChar(c))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if polymod(hrp_expand(hrp).chain(data.iter().copied())) != BECH32M_CONST {
+        return Err(Bech32Error::InvalidChecksum);
+    }
+
+    let mut payload = [0u8; 32];
+    let (mut acc, mut bits, mut pos) = (0u32, 0u8, 0usize);
+    for d in &data[..PAYLOAD_LEN] {
+        acc = acc << 5 | *d as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            payload[pos] = (acc >> bits) as u8;
+            pos += 1;
+        }
+    }
+    if acc & ((1 << bits) - 1) != 0 {
+        return Err(Bech32Error::InvalidPadding);
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ContractId, OpId};
+
+    #[test]
+    fn roundtrip() {
+        let payload = [0xA5; 32];
+        let s = encode(HRP_CONTRACT, payload);
+        assert!(s.starts_with("rgbc1"));
+        assert_eq!(decode(HRP_CONTRACT, &s), Ok(payload));
+        assert_eq!(decode(HRP_CONTRACT, &s.to_uppercase()), Ok(payload));
+        assert!(matches!(decode(HRP_OPERATION, &s), Err(Bech32Error::HrpMismatch { .. })));
+
+        let mut corrupted = s.clone();
+        corrupted.replace_range(10..11, if &s[10..11] == "q" { "p" } else { "q" });
+        assert_eq!(decode(HRP_CONTRACT, &corrupted), Err(Bech32Error::InvalidChecksum));
+    }
+
+    #[test]
+    fn id_formats() {
+        let id = ContractId::from([0x01; 32]);
+        assert_eq!(format!("{id:x}"), "01".repeat(32));
+        assert_eq!(ContractId::from_bech32m_str(&id.to_bech32m_string()), Ok(id));
+        let opid = OpId::from([0x01; 32]);
+        assert!(opid.to_bech32m_string().starts_with("rgbn1"));
+        assert!(ContractId::from_bech32m_str(&opid.to_bech32m_string()).is_err());
+    }
+
+    #[test]
+    fn bip350_checksum() {
+        // Valid bech32m test vector from BIP-350.
+        let s = "a1lqfn3a";
+        let data: Vec<u8> = "lqfn3a"
+            .chars()
+            .map(|c| CHARSET.iter().position(|b| *b as char == c).unwrap() as u8)
+            .collect();
+        assert_eq!(polymod(hrp_expand(&s[..1]).chain(data)), BECH32M_CONST);
+    }
+}
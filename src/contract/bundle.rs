@@ -20,11 +20,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{btree_map, BTreeMap};
+
 use amplify::confinement::{TinyOrdMap, TinyOrdSet};
 use amplify::{Bytes32, Wrapper};
 use commit_verify::{mpc, CommitStrategy, CommitmentId, Conceal};
 
-use super::{OpId, Transition};
+use super::{ContractId, OpId, Transition};
+use crate::commit::{strict_encoded, CommitDump};
 use crate::LIB_NAME_RGB;
 
 /// Unique state transition bundle identifier equivalent to the bundle
@@ -55,7 +58,11 @@ impl From<mpc::Message> for BundleId {
 #[derive(Clone, PartialEq, Eq, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
 pub struct BundleItem {
     pub inputs: TinyOrdSet<u16>,
     pub transition: Option<Transition>,
@@ -77,7 +84,11 @@ impl Conceal for BundleItem {
 #[wrapper_mut(DerefMut)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
 pub struct TransitionBundle(TinyOrdMap<OpId, BundleItem>);
 
 impl Conceal for TransitionBundle {
@@ -98,10 +109,69 @@ impl CommitmentId for TransitionBundle {
     type Id = BundleId;
 }
 
+impl CommitDump for TransitionBundle {
+    fn commit_fields(&self) -> Vec<(String, Vec<u8>)> {
+        let mut fields = vec![("items.len".into(), strict_encoded(&self.len_u8()))];
+        for (opid, item) in self.iter() {
+            fields.push((format!("items[{opid}].id"), strict_encoded(opid)));
+            fields.push((format!("items[{opid}]"), strict_encoded(&item.conceal())));
+        }
+        fields
+    }
+}
+
 impl TransitionBundle {
+    /// Computes bundle id as a tagged hash of the strict-encoded concealed
+    /// bundle, where all transitions are removed and only the operation ids
+    /// with their spent input indexes are kept.
     pub fn bundle_id(&self) -> BundleId { self.commitment_id() }
 }
 
+/// Errors ordering bundles anchored in a single witness transaction.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum AnchoringError {
+    /// contract {0} has two different bundles {1} and {2} anchored in the same
+    /// witness transaction, while a multi-protocol commitment may contain only
+    /// a single message per contract.
+    ContractReused(ContractId, BundleId, BundleId),
+
+    /// number of the anchored bundles exceeds the maximal number of messages in
+    /// a multi-protocol commitment.
+    TooManyBundles,
+}
+
+/// Returns bundle ids in the order in which they are committed by a single
+/// anchor: each contract contributes a single message equal to its bundle id
+/// under the protocol id equal to the contract id, and the messages are
+/// ordered by the protocol id. The returned map is ready to be used as a
+/// source of the LNPBP-4 multi-protocol commitment.
+///
+/// The same bundle may be provided several times; providing different bundles
+/// for the same contract is an error.
+pub fn anchoring_order<'bundle>(
+    bundles: impl IntoIterator<Item = (ContractId, &'bundle TransitionBundle)>,
+) -> Result<mpc::MessageMap, AnchoringError> {
+    let mut messages = BTreeMap::<mpc::ProtocolId, mpc::Message>::new();
+    for (contract_id, bundle) in bundles {
+        let bundle_id = bundle.bundle_id();
+        match messages.entry(contract_id.to_protocol_id()) {
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(bundle_id.into());
+            }
+            btree_map::Entry::Occupied(entry) if *entry.get() == mpc::Message::from(bundle_id) => {}
+            btree_map::Entry::Occupied(entry) => {
+                return Err(AnchoringError::ContractReused(
+                    contract_id,
+                    BundleId::from(*entry.get()),
+                    bundle_id,
+                ));
+            }
+        }
+    }
+    mpc::MessageMap::try_from(messages).map_err(|_| AnchoringError::TooManyBundles)
+}
+
 impl TransitionBundle {
     pub fn validate(&self) -> bool {
         let mut used_inputs = bset! {};
@@ -114,3 +184,62 @@ impl TransitionBundle {
         true
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bundle(items: &[(u8, &[u16])]) -> TransitionBundle {
+        let items = items.iter().map(|(no, inputs)| {
+            (OpId::from([*no; 32]), BundleItem {
+                inputs: TinyOrdSet::try_from_iter(inputs.iter().copied()).unwrap(),
+                transition: None,
+            })
+        });
+        TransitionBundle::from(TinyOrdMap::try_from_iter(items).unwrap())
+    }
+
+    #[test]
+    fn bundle_id_vectors() {
+        assert_eq!(
+            bundle(&[]).bundle_id().to_string(),
+            "0cdb701039c40c16bb9699f29290831477c256e3e391ad838db1320703f8c153"
+        );
+        assert_eq!(
+            bundle(&[(1, &[0])]).bundle_id().to_string(),
+            "cfeb91df0873bb1b6836ccfef55d2fd32a9e5ae236f1bd5dbfde25667e7467f7"
+        );
+        assert_eq!(
+            bundle(&[(2, &[1, 2]), (1, &[0])]).bundle_id().to_string(),
+            "fd5e4618fb613ade7ef6f3b44937a6f1ed35245253f0b5720d964593ca7a65c1"
+        );
+        let bundle = bundle(&[(1, &[0])]);
+        assert_eq!(bundle.commit_dump().id, bundle.bundle_id().into_inner());
+    }
+
+    #[test]
+    fn anchoring() {
+        let first = bundle(&[(1, &[0])]);
+        let second = bundle(&[(2, &[1, 2])]);
+        let contract = |no: u8| ContractId::from([no; 32]);
+
+        let messages =
+            anchoring_order([(contract(9), &first), (contract(3), &second), (contract(9), &first)])
+                .unwrap();
+        assert_eq!(
+            messages.into_iter().collect::<Vec<_>>(),
+            vec![
+                (contract(3).to_protocol_id(), mpc::Message::from(second.bundle_id())),
+                (contract(9).to_protocol_id(), mpc::Message::from(first.bundle_id())),
+            ]
+        );
+        assert_eq!(
+            anchoring_order([(contract(9), &first), (contract(9), &second)]),
+            Err(AnchoringError::ContractReused(
+                contract(9),
+                first.bundle_id(),
+                second.bundle_id()
+            ))
+        );
+    }
+}
@@ -0,0 +1,372 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checkpoints compacting a validated prefix of the contract history.
+//!
+//! A checkpoint commits to the aggregate contract state produced by the
+//! compacted operations: their global state and all owned state which remains
+//! unspent. For the schemata allowing checkpoints, consignments may include a
+//! checkpoint and start the operation graph from it instead of the genesis;
+//! the validator then checks that the checkpoint conforms to the schema and
+//! resolves spent state not present in the consignment from the checkpoint.
+
+use std::cmp::Ordering;
+use std::io;
+
+use amplify::confinement::{self, MediumOrdSet};
+use amplify::{Bytes32, Wrapper};
+use bp::seals::txout::TxPtr;
+use bp::Txid;
+use commit_verify::{CommitEncode, CommitmentId};
+use strict_encoding::{StrictDeserialize, StrictEncode, StrictSerialize, StrictWriter};
+
+use crate::commit::{commit_encoded, strict_encoded, CommitDump};
+use crate::schema::{SchemaId, SchemaRoot};
+use crate::{
+    validation, Assignments, ContractId, Extension, Ffv, Genesis, GlobalState, GraphSeal, OpId,
+    Operation, Opout, Schema, Transition, TypedAssigns, LIB_NAME_RGB,
+};
+
+/// Unique checkpoint identifier equivalent to the checkpoint commitment hash
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[wrapper(Deref, BorrowSlice, Display, Hex, Index, RangeOps)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
+pub struct CheckpointId(
+    #[from]
+    #[from([u8; 32])]
+    Bytes32,
+);
+
+/// Errors constructing checkpoint.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum CheckpointError {
+    /// operation {0} belongs to a different contract.
+    ContractMismatch(OpId),
+
+    /// operation {0} spends output {1} which is not a part of the checkpoint
+    /// state.
+    UnknownInput(OpId, Opout),
+
+    /// checkpoint state exceeds the size limits. Details: {0}
+    #[from]
+    TooLarge(confinement::Error),
+}
+
+/// Owned state which was assigned by a compacted operation and remains unspent
+/// at the checkpoint.
+#[derive(Clone, Eq, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct CheckpointOutput {
+    pub opout: Opout,
+    /// Assignment containing a single seal with its state.
+    pub assign: TypedAssigns<GraphSeal>,
+    /// Witness transaction of the operation which has assigned the state;
+    /// `None` for genesis and state extensions, which define explicit seals.
+    pub witness: Option<Txid>,
+}
+
+impl PartialEq for CheckpointOutput {
+    fn eq(&self, other: &Self) -> bool { self.opout == other.opout }
+}
+
+impl PartialOrd for CheckpointOutput {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for CheckpointOutput {
+    fn cmp(&self, other: &Self) -> Ordering { self.opout.cmp(&other.opout) }
+}
+
+/// Checkpoint compacting a validated prefix of the contract history into the
+/// aggregate contract state.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct Checkpoint {
+    pub ffv: Ffv,
+    pub contract_id: ContractId,
+    pub schema_id: SchemaId,
+    /// Global state defined by all compacted operations.
+    pub globals: GlobalState,
+    /// Owned state which remains unspent after the compacted operations.
+    pub outputs: MediumOrdSet<CheckpointOutput>,
+}
+
+impl StrictSerialize for Checkpoint {}
+impl StrictDeserialize for Checkpoint {}
+
+// Owned state is committed in the same way as by the operations, such that
+// the checkpoint id doesn't depend on whether the seals and state are revealed.
+impl CommitEncode for Checkpoint {
+    fn commit_encode(&self, mut e: &mut impl io::Write) {
+        let w = StrictWriter::with(u32::MAX as usize, &mut e);
+        self.ffv
+            .strict_encode(w)
+            .and_then(|w| self.contract_id.strict_encode(w))
+            .and_then(|w| self.schema_id.strict_encode(w))
+            .and_then(|w| self.globals.strict_encode(w))
+            .and_then(|w| (self.outputs.len() as u32).strict_encode(w))
+            .ok();
+        for output in &self.outputs {
+            let w = StrictWriter::with(u32::MAX as usize, &mut e);
+            output.opout.strict_encode(w).ok();
+            output.assign.commit_encode(e);
+            let w = StrictWriter::with(u32::MAX as usize, &mut e);
+            output.witness.strict_encode(w).ok();
+        }
+    }
+}
+
+impl CommitmentId for Checkpoint {
+    const TAG: [u8; 32] = *b"urn:lnpbp:rgb:checkpoint:v1#2023";
+    type Id = CheckpointId;
+}
+
+impl CommitDump for Checkpoint {
+    fn commit_fields(&self) -> Vec<(String, Vec<u8>)> {
+        let mut fields = vec![
+            ("ffv".into(), strict_encoded(&self.ffv)),
+            ("contract_id".into(), strict_encoded(&self.contract_id)),
+            ("schema_id".into(), strict_encoded(&self.schema_id)),
+            ("globals".into(), strict_encoded(&self.globals)),
+            ("outputs.len".into(), strict_encoded(&(self.outputs.len() as u32))),
+        ];
+        for output in &self.outputs {
+            let opout = output.opout;
+            fields.push((format!("outputs[{opout}].opout"), strict_encoded(&opout)));
+            fields.push((format!("outputs[{opout}].assign"), commit_encoded(&output.assign)));
+            fields.push((format!("outputs[{opout}].witness"), strict_encoded(&output.witness)));
+        }
+        fields
+    }
+}
+
+impl Checkpoint {
+    /// Starts a checkpoint from the contract genesis. Operations following the
+    /// genesis must be added in their consensus order after being validated.
+    pub fn with(genesis: &Genesis) -> Result<Self, CheckpointError> {
+        let mut checkpoint = Checkpoint {
+            ffv: Ffv::default(),
+            contract_id: genesis.contract_id(),
+            schema_id: genesis.schema_id,
+            globals: none!(),
+            outputs: none!(),
+        };
+        checkpoint.add_operation(genesis, genesis.assignments.transmutate_seals(), None)?;
+        Ok(checkpoint)
+    }
+
+    #[inline]
+    pub fn checkpoint_id(&self) -> CheckpointId { self.commitment_id() }
+
+    /// Adds a state transition with the given witness transaction to the
+    /// checkpoint.
+    pub fn add_transition(
+        &mut self,
+        transition: &Transition,
+        witness_txid: Txid,
+    ) -> Result<(), CheckpointError> {
+        self.add_operation(transition, transition.assignments.clone(), Some(witness_txid))
+    }
+
+    /// Adds a state extension to the checkpoint.
+    pub fn add_extension(&mut self, extension: &Extension) -> Result<(), CheckpointError> {
+        self.add_operation(extension, extension.assignments.transmutate_seals(), None)
+    }
+
+    fn add_operation(
+        &mut self,
+        op: &impl Operation,
+        assignments: Assignments<GraphSeal>,
+        witness: Option<Txid>,
+    ) -> Result<(), CheckpointError> {
+        let opid = op.id();
+        if op.contract_id() != self.contract_id {
+            return Err(CheckpointError::ContractMismatch(opid));
+        }
+        for input in &op.inputs() {
+            let Some(output) = self.output(input.prev_out).cloned() else {
+                return Err(CheckpointError::UnknownInput(opid, input.prev_out));
+            };
+            self.outputs.remove(&output)?;
+        }
+        for (ty, values) in op.globals() {
+            for value in values.iter() {
+                self.globals.add_state(*ty, value.clone())?;
+            }
+        }
+        for (ty, assigns) in assignments.iter() {
+            for no in 0..assigns.len_u16() {
+                let assign = single(assigns, no as usize);
+                let opout = Opout::new(opid, *ty, no);
+                self.outputs.push(CheckpointOutput {
+                    opout,
+                    assign,
+                    witness,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns owned state of the given output, if it is a part of the
+    /// checkpoint.
+    pub fn output(&self, opout: Opout) -> Option<&CheckpointOutput> {
+        self.outputs.iter().find(|output| output.opout == opout)
+    }
+
+    /// Verifies that the checkpoint state conforms to the schema.
+    pub fn verify<Root: SchemaRoot>(&self, schema: &Schema<Root>) -> validation::Status {
+        let mut status = validation::Status::new();
+        let id = self.checkpoint_id();
+
+        for (ty, values) in self.globals.iter() {
+            match schema.global_types.get(ty) {
+                None => {
+                    status.add_failure(validation::Failure::CheckpointGlobalType(id, *ty));
+                }
+                Some(global_schema) if values.len() > global_schema.max_items as usize => {
+                    status.add_failure(validation::Failure::CheckpointGlobalType(id, *ty));
+                }
+                Some(_) => {}
+            }
+        }
+        for output in &self.outputs {
+            let opout = &output.opout;
+            let state_type = schema.owned_types.get(&opout.ty).map(|s| s.state_type());
+            let witness_seal = matches!(
+                output.assign.revealed_seal_at(0),
+                Ok(Some(seal)) if seal.txid == TxPtr::WitnessTx
+            );
+            if output.assign.len_u16() != 1 ||
+                state_type != Some(output.assign.state_type()) ||
+                (witness_seal && output.witness.is_none())
+            {
+                status.add_failure(validation::Failure::CheckpointOutput(id, *opout));
+            }
+        }
+        status
+    }
+}
+
+pub(super) fn single(assigns: &TypedAssigns<GraphSeal>, no: usize) -> TypedAssigns<GraphSeal> {
+    match assigns {
+        TypedAssigns::Declarative(vec) => TypedAssigns::Declarative(small_vec![vec[no].clone()]),
+        TypedAssigns::Fungible(vec) => TypedAssigns::Fungible(small_vec![vec[no].clone()]),
+        TypedAssigns::Structured(vec) => TypedAssigns::Structured(small_vec![vec[no].clone()]),
+        TypedAssigns::Attachment(vec) => TypedAssigns::Attachment(small_vec![vec[no].clone()]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+    use bp::Chain;
+
+    use super::*;
+    use crate::schema::{FungibleType, StateSchema};
+    use crate::{
+        Assign, BlindingFactor, GenesisSeal, Input, Inputs, OpFeatures, RevealedValue, SubSchema,
+        Valencies,
+    };
+
+    #[test]
+    fn checkpoint() {
+        let seal =
+            GenesisSeal::with_blinding(CloseMethod::TapretFirst, Txid::from([1u8; 32]), 0, 0);
+        let value = RevealedValue::with(10, BlindingFactor::try_from([1u8; 32]).unwrap());
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: SchemaId::from([0u8; 32]),
+            chain: Chain::Regtest,
+            metadata: none!(),
+            globals: none!(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Fungible(small_vec![Assign::revealed(seal, value.clone())]),
+            }),
+            valencies: none!(),
+        };
+        let mut checkpoint = Checkpoint::with(&genesis).unwrap();
+        let genesis_out = Opout::new(genesis.id(), 1, 0);
+        assert!(checkpoint.output(genesis_out).is_some());
+
+        let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 0);
+        let transition = Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            transition_type: 1,
+            metadata: none!(),
+            globals: none!(),
+            inputs: Inputs::from(tiny_bset![Input::with(genesis_out)]),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Fungible(small_vec![
+                    Assign::revealed(seal, value.clone()),
+                    Assign::revealed(seal, value),
+                ]),
+            }),
+            valencies: Valencies::default(),
+        };
+        let witness = Txid::from([2u8; 32]);
+        let id = checkpoint.checkpoint_id();
+        checkpoint.add_transition(&transition, witness).unwrap();
+        assert_ne!(checkpoint.checkpoint_id(), id);
+        assert!(checkpoint.output(genesis_out).is_none());
+        assert_eq!(checkpoint.outputs.len(), 2);
+        assert_eq!(checkpoint.commit_dump().id, checkpoint.checkpoint_id().into_inner());
+        let output = checkpoint
+            .output(Opout::new(transition.id(), 1, 1))
+            .unwrap();
+        assert_eq!(output.witness, Some(witness));
+        assert_eq!(
+            checkpoint.add_transition(&transition, witness),
+            Err(CheckpointError::UnknownInput(transition.id(), genesis_out))
+        );
+
+        let mut schema = SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Fungible(FungibleType::Unsigned64Bit) },
+            ..default!()
+        };
+        assert!(checkpoint.verify(&schema).failures.is_empty());
+        schema.owned_types = tiny_bmap! { 1 => StateSchema::Declarative };
+        assert_eq!(checkpoint.verify(&schema).failures.len(), 2);
+    }
+}
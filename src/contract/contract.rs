@@ -23,6 +23,7 @@
 //! Extraction of contract state.
 
 use std::cmp::Ordering;
+use std::collections::BTreeSet;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::num::ParseIntError;
@@ -37,9 +38,9 @@ use strict_encoding::{StrictDecode, StrictDumb, StrictEncode};
 
 use crate::{
     Assign, AssignmentType, Assignments, AssignmentsRef, ContractId, ExposedSeal, ExposedState,
-    Extension, Genesis, GlobalStateType, OpId, Operation, RevealedAttach, RevealedData,
-    RevealedValue, SchemaId, SealWitness, SubSchema, Transition, TypedAssigns, VoidState,
-    LIB_NAME_RGB,
+    Extension, Genesis, GlobalStateSchema, GlobalStateType, GlobalStateView, OpId, Operation,
+    RevealedAttach, RevealedData, RevealedValue, SchemaId, SealWitness, SubSchema, Transition,
+    TypedAssigns, VoidState, LIB_NAME_RGB,
 };
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
@@ -220,6 +221,31 @@ impl OrderedTxid {
     pub fn new(height: u32, txid: Txid) -> Self { OrderedTxid { height, txid } }
 }
 
+/// Position of a witness transaction in the blockchain, used to determine
+/// expiry of the global state (see [`crate::schema::GlobalExpiry`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display("{height}@{timestamp}")]
+pub struct WitnessPos {
+    /// Height of the block mining the transaction.
+    pub height: u32,
+    /// UNIX timestamp of the block mining the transaction.
+    pub timestamp: i64,
+    /// Median time past (see BIP-113) of the block mining the transaction,
+    /// used by the timechain instructions of the scripts (see
+    /// [`crate::vm::TimechainOp`]). Zero if not known.
+    pub mtp: i64,
+}
+
+impl WitnessPos {
+    pub fn new(height: u32, timestamp: i64) -> Self {
+        WitnessPos {
+            height,
+            timestamp,
+            mtp: 0,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB)]
@@ -278,6 +304,40 @@ pub type FungibleOutput = OutputAssignment<RevealedValue>;
 pub type DataOutput = OutputAssignment<RevealedData>;
 pub type AttachOutput = OutputAssignment<RevealedAttach>;
 
+/// Record of an output assigned by a contract operation, which is kept by the
+/// [`ContractHistory`] after the output is spent.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct AllocationRecord {
+    pub seal: Outpoint,
+    pub witness: SealWitness,
+    /// Witness transaction of the assigning operation; `None` for genesis.
+    pub ord_txid: Option<OrderedTxid>,
+    /// Operation which has spent the output, if any.
+    pub spent_by: Option<OpId>,
+}
+
+/// Event in the history of the state allocated to a seal, returned by
+/// [`ContractState::history_of`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub enum AllocationEvent {
+    /// State is assigned to the seal by the operation output.
+    Assigned { opout: Opout, witness: SealWitness },
+    /// State assigned by the operation output is spent by operation `opid`.
+    Spent { opout: Opout, opid: OpId },
+}
+
 /// Contract history accumulates raw data from the contract history, extracted
 /// from a series of consignments over the time. It does consensus ordering of
 /// the state data, but it doesn't interpret or validates the state against the
@@ -305,6 +365,9 @@ pub struct ContractHistory {
     fungibles: LargeOrdSet<FungibleOutput>,
     data: LargeOrdSet<DataOutput>,
     attach: LargeOrdSet<AttachOutput>,
+    /// All outputs with the revealed state ever assigned by the contract
+    /// operations, including the spent ones.
+    allocations: LargeOrdMap<Opout, AllocationRecord>,
 }
 
 impl ContractHistory {
@@ -327,6 +390,7 @@ impl ContractHistory {
             fungibles: empty!(),
             data: empty!(),
             attach: empty!(),
+            allocations: empty!(),
         };
         state.update_genesis(genesis);
         state
@@ -386,6 +450,9 @@ impl ContractHistory {
 
         // Remove invalidated state
         for input in &op.inputs() {
+            if let Some(record) = self.allocations.get_mut(&input.prev_out) {
+                record.spent_by = Some(opid);
+            }
             if let Some(o) = self.rights.iter().find(|r| r.opout == input.prev_out) {
                 let o = o.clone(); // need this b/c of borrow checker
                 self.rights
@@ -414,24 +481,29 @@ impl ContractHistory {
 
         match op.assignments() {
             AssignmentsRef::Genesis(assignments) => {
-                self.add_assignments(witness, opid, assignments)
+                self.add_assignments(witness, ord_txid, opid, assignments)
+            }
+            AssignmentsRef::Graph(assignments) => {
+                self.add_assignments(witness, ord_txid, opid, assignments)
             }
-            AssignmentsRef::Graph(assignments) => self.add_assignments(witness, opid, assignments),
         }
     }
 
     fn add_assignments<Seal: ExposedSeal>(
         &mut self,
         witness: SealWitness,
+        ord_txid: Option<OrderedTxid>,
         opid: OpId,
         assignments: &Assignments<Seal>,
     ) {
         fn process<State: ExposedState, Seal: ExposedSeal>(
             contract_state: &mut LargeOrdSet<OutputAssignment<State>>,
+            allocations: &mut LargeOrdMap<Opout, AllocationRecord>,
             assignments: &[Assign<State, Seal>],
             opid: OpId,
             ty: AssignmentType,
             witness: SealWitness,
+            ord_txid: Option<OrderedTxid>,
         ) {
             for (no, seal, state) in assignments
                 .iter()
@@ -454,26 +526,60 @@ impl ContractHistory {
                         OutputAssignment::with_extension(seal, state.into(), opid, ty, no as u16)
                     }
                 };
+                let record = AllocationRecord {
+                    seal: assigned_state.seal,
+                    witness,
+                    ord_txid,
+                    spent_by: None,
+                };
+                allocations
+                    .insert(assigned_state.opout, record)
+                    .expect("contract state exceeded 2^32 items, which is unrealistic");
                 contract_state
                     .push(assigned_state)
                     .expect("contract state exceeded 2^32 items, which is unrealistic");
             }
         }
 
+        let allocations = &mut self.allocations;
         for (ty, assignments) in assignments.iter() {
             match assignments {
-                TypedAssigns::Declarative(assignments) => {
-                    process(&mut self.rights, &assignments, opid, *ty, witness)
-                }
-                TypedAssigns::Fungible(assignments) => {
-                    process(&mut self.fungibles, &assignments, opid, *ty, witness)
-                }
-                TypedAssigns::Structured(assignments) => {
-                    process(&mut self.data, &assignments, opid, *ty, witness)
-                }
-                TypedAssigns::Attachment(assignments) => {
-                    process(&mut self.attach, &assignments, opid, *ty, witness)
-                }
+                TypedAssigns::Declarative(assignments) => process(
+                    &mut self.rights,
+                    allocations,
+                    &assignments,
+                    opid,
+                    *ty,
+                    witness,
+                    ord_txid,
+                ),
+                TypedAssigns::Fungible(assignments) => process(
+                    &mut self.fungibles,
+                    allocations,
+                    &assignments,
+                    opid,
+                    *ty,
+                    witness,
+                    ord_txid,
+                ),
+                TypedAssigns::Structured(assignments) => process(
+                    &mut self.data,
+                    allocations,
+                    &assignments,
+                    opid,
+                    *ty,
+                    witness,
+                    ord_txid,
+                ),
+                TypedAssigns::Attachment(assignments) => process(
+                    &mut self.attach,
+                    allocations,
+                    &assignments,
+                    opid,
+                    *ty,
+                    witness,
+                    ord_txid,
+                ),
             }
         }
     }
@@ -519,4 +625,226 @@ impl ContractState {
         let iter = state.values().take(schema.max_items as usize);
         SmallVec::try_from_iter(iter).expect("same size as previous confined collection")
     }
+
+    /// Builds read-only view of the contract global state, containing only
+    /// the state types defined by the schema and no more values for each of
+    /// the types than the schema allows.
+    pub fn global_view(&self) -> GlobalStateView { self.global_view_filtered(|_| true) }
+
+    /// Builds read-only view of the contract global state at the given
+    /// position in the blockchain. Works like [`Self::global_view`], but also
+    /// skips state types which have expired at `pos` according to the schema.
+    pub fn global_view_at(&self, pos: WitnessPos) -> GlobalStateView {
+        self.global_view_filtered(|schema| !schema.expiry.is_expired(pos))
+    }
+
+    /// Returns history of the state allocated to the `seal`: for each output
+    /// assigned to the seal, the operation which has assigned it and the one
+    /// which has spent it, if any. Outputs are ordered by their witness
+    /// transactions, starting from genesis.
+    pub fn history_of(&self, seal: Outpoint) -> Vec<AllocationEvent> {
+        let mut records = self
+            .allocations
+            .iter()
+            .filter(|(_, record)| record.seal == seal)
+            .collect::<Vec<_>>();
+        records.sort_by_key(|(opout, record)| (record.ord_txid, **opout));
+        let mut events = vec![];
+        for (opout, record) in records {
+            events.push(AllocationEvent::Assigned {
+                opout: *opout,
+                witness: record.witness,
+            });
+            if let Some(opid) = record.spent_by {
+                events.push(AllocationEvent::Spent {
+                    opout: *opout,
+                    opid,
+                });
+            }
+        }
+        events
+    }
+
+    fn global_view_filtered(&self, filter: impl Fn(&GlobalStateSchema) -> bool) -> GlobalStateView {
+        let mut view = GlobalStateView::default();
+        for (ty, schema) in &self.schema.global_types {
+            if !filter(schema) {
+                continue;
+            }
+            let Some(state) = self.global.get(ty) else {
+                continue
+            };
+            let values = state
+                .values()
+                .take(schema.max_items as usize)
+                .cloned()
+                .collect();
+            view.insert(*ty, values);
+        }
+        view
+    }
+}
+
+/// Changes in the owned state of a single kind between two snapshots of the
+/// contract state.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct AssignmentsDiff<State: ExposedState> {
+    /// Assignments created by the operations unknown to the older snapshot.
+    pub created: BTreeSet<OutputAssignment<State>>,
+    /// Assignments present in the older snapshot, which were spent in the
+    /// newer one.
+    pub spent: BTreeSet<OutputAssignment<State>>,
+    /// Assignments of the operations known to the older snapshot, which were
+    /// concealed in it and became revealed in the newer one.
+    pub revealed: BTreeSet<OutputAssignment<State>>,
+}
+
+impl<State: ExposedState> Default for AssignmentsDiff<State> {
+    fn default() -> Self {
+        AssignmentsDiff {
+            created: empty!(),
+            spent: empty!(),
+            revealed: empty!(),
+        }
+    }
+}
+
+impl<State: ExposedState> AssignmentsDiff<State> {
+    fn with(
+        old: &LargeOrdSet<OutputAssignment<State>>,
+        new: &LargeOrdSet<OutputAssignment<State>>,
+        known_ops: &BTreeSet<OpId>,
+    ) -> Self {
+        let mut diff = AssignmentsDiff {
+            created: empty!(),
+            spent: old.iter().filter(|a| !new.contains(a)).cloned().collect(),
+            revealed: empty!(),
+        };
+        for assignment in new.iter().filter(|a| !old.contains(a)) {
+            if known_ops.contains(&assignment.opout.op) {
+                diff.revealed.insert(assignment.clone());
+            } else {
+                diff.created.insert(assignment.clone());
+            }
+        }
+        diff
+    }
+
+    /// Detects whether there are no changes in the state.
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.spent.is_empty() && self.revealed.is_empty()
+    }
+}
+
+/// Changes in the owned state between two snapshots of the contract state,
+/// computed with [`ContractHistory::diff`].
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct StateDiff {
+    pub rights: AssignmentsDiff<VoidState>,
+    pub fungibles: AssignmentsDiff<RevealedValue>,
+    pub data: AssignmentsDiff<RevealedData>,
+    pub attach: AssignmentsDiff<RevealedAttach>,
+}
+
+impl StateDiff {
+    /// Detects whether there are no changes in the state.
+    pub fn is_empty(&self) -> bool {
+        self.rights.is_empty() &&
+            self.fungibles.is_empty() &&
+            self.data.is_empty() &&
+            self.attach.is_empty()
+    }
+}
+
+impl ContractHistory {
+    /// Computes changes in the owned state between this (older) and the
+    /// provided (newer) snapshots of the contract state.
+    ///
+    /// Since the contract history does not keep information about concealed
+    /// assignments, an assignment absent from this snapshot is considered
+    /// revealed (and not created) in the newer snapshot if this snapshot
+    /// contains other assignments from the same operation.
+    pub fn diff(&self, other: &ContractHistory) -> StateDiff {
+        let known_ops = self
+            .rights
+            .iter()
+            .map(|a| a.opout.op)
+            .chain(self.fungibles.iter().map(|a| a.opout.op))
+            .chain(self.data.iter().map(|a| a.opout.op))
+            .chain(self.attach.iter().map(|a| a.opout.op))
+            .collect();
+        StateDiff {
+            rights: AssignmentsDiff::with(&self.rights, &other.rights, &known_ops),
+            fungibles: AssignmentsDiff::with(&self.fungibles, &other.fungibles, &known_ops),
+            data: AssignmentsDiff::with(&self.data, &other.data, &known_ops),
+            attach: AssignmentsDiff::with(&self.attach, &other.attach, &known_ops),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+    use bp::Chain;
+
+    use super::*;
+    use crate::{Ffv, GenesisSeal, GraphSeal, Input, Inputs, OpFeatures};
+
+    #[test]
+    fn history_of() {
+        let schema = SubSchema::default();
+        let issue = Outpoint::new(Txid::from([1; 32]), 0);
+        let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue.txid, issue.vout, 1);
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: none!(),
+            globals: none!(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Declarative(small_vec![Assign::right(seal)]),
+            }),
+            valencies: none!(),
+        };
+        let prev_out = Opout::new(genesis.id(), 1, 0);
+        let transition = Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            transition_type: 1,
+            metadata: none!(),
+            globals: none!(),
+            inputs: Inputs::from(tiny_bset![Input::with(prev_out)]),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Declarative(small_vec![Assign::right(
+                    GraphSeal::with_vout(CloseMethod::OpretFirst, 1, 2)
+                )]),
+            }),
+            valencies: none!(),
+        };
+        let history =
+            ContractHistory::with(genesis.schema_id, None, genesis.contract_id(), &genesis);
+        let mut state = ContractState { schema, history };
+        let witness = Txid::from([2; 32]);
+        state.add_transition(&transition, OrderedTxid::new(100, witness));
+
+        assert_eq!(state.history_of(issue), vec![
+            AllocationEvent::Assigned {
+                opout: prev_out,
+                witness: SealWitness::Genesis,
+            },
+            AllocationEvent::Spent {
+                opout: prev_out,
+                opid: transition.id(),
+            },
+        ]);
+        assert_eq!(state.history_of(Outpoint::new(witness, 1)), vec![
+            AllocationEvent::Assigned {
+                opout: Opout::new(transition.id(), 1, 0),
+                witness: SealWitness::Present(witness),
+            }
+        ]);
+        assert!(state.history_of(Outpoint::new(witness, 0)).is_empty());
+    }
 }
@@ -38,7 +38,11 @@ use crate::{StateCommitment, StateData, StateType, LIB_NAME_RGB};
 #[strict_type(lib = LIB_NAME_RGB)]
 #[derive(CommitEncode)]
 #[commit_encode(strategy = strict)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
 pub struct VoidState(());
 
 impl ConfidentialState for VoidState {
@@ -62,7 +66,11 @@ impl Conceal for VoidState {
 #[strict_type(lib = LIB_NAME_RGB)]
 #[derive(CommitEncode)]
 #[commit_encode(conceal)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
 pub struct RevealedData(SmallVec<u8>);
 
 impl ExposedState for RevealedData {
@@ -78,6 +86,28 @@ impl Conceal for RevealedData {
 
 impl StrictSerialize for RevealedData {}
 
+impl RevealedData {
+    /// Constructs counter state data.
+    pub fn with_counter(value: u64) -> Self {
+        Self(SmallVec::try_from(value.to_le_bytes().to_vec()).expect("8 bytes"))
+    }
+
+    /// Interprets the data as a counter value, returning `None` if the data
+    /// length is not 8 bytes.
+    pub fn as_counter(&self) -> Option<u64> {
+        let bytes = <[u8; 8]>::try_from(self.0.as_slice()).ok()?;
+        Some(u64::from_le_bytes(bytes))
+    }
+
+    /// Interprets the data as a non-fungible token, returning its id from the
+    /// first four bytes in little-endian order, or `None` if the data are
+    /// shorter (see [`crate::schema::EmbeddedProcedure::NftIssue`]).
+    pub fn token_id(&self) -> Option<u32> {
+        let bytes = <[u8; 4]>::try_from(self.0.get(..4)?).ok()?;
+        Some(u32::from_le_bytes(bytes))
+    }
+}
+
 impl Debug for RevealedData {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let val = match String::from_utf8(self.0.to_inner()) {
@@ -0,0 +1,326 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dry-run validation of state transitions against the contract state.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::slice;
+
+use amplify::confinement::Confined;
+use bp::seals::txout::CloseMethod;
+use strict_encoding::StrictDumb;
+
+use super::checkpoint::single;
+use crate::validation::{AnchoredBundle, ConsignmentApi, Failure};
+use crate::{
+    Assign, BundleId, Checkpoint, CheckpointOutput, ContractState, ExposedState, Extension, Ffv,
+    Genesis, GlobalState, GlobalValues, GraphSeal, OpId, OpRef, Operation, Opout, OutputAssignment,
+    Schema, SchemaRoot, SealWitness, SecretSeal, SubSchema, Transition, TransitionBundle,
+    TypedAssigns,
+};
+
+/// Changes to the contract state which a state transition makes once it is
+/// anchored, returned by [`ContractState::accepts`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StateDelta {
+    pub opid: OpId,
+    /// Owned state spent by the transition, one assignment per output.
+    pub spent: BTreeMap<Opout, TypedAssigns<GraphSeal>>,
+    /// Owned state assigned by the transition, one assignment per output.
+    pub assigned: BTreeMap<Opout, TypedAssigns<GraphSeal>>,
+    /// Global state defined by the transition.
+    pub global: GlobalState,
+}
+
+impl ContractState {
+    /// Validates a state transition which is not anchored yet against the
+    /// schema and the current contract state, running the schema scripts.
+    /// Wallets may use it to reject invalid transfers before broadcasting the
+    /// witness transaction.
+    ///
+    /// The transition must spend the unspent state of the contract. Checks
+    /// requiring the witness transaction (closing of the seals, seal maturity,
+    /// global state expiry and time-based sunsets) and rules spanning several
+    /// operations (like replacement of provisional transitions) are not
+    /// performed.
+    pub fn accepts<Root: SchemaRoot>(
+        &self,
+        transition: &Transition,
+        schema: &Schema<Root>,
+    ) -> Result<StateDelta, Vec<Failure>> {
+        let opid = transition.id();
+        let schema_id = schema.schema_id();
+        if schema_id != self.schema_id() {
+            return Err(vec![Failure::SchemaMismatch {
+                expected: self.schema_id(),
+                actual: schema_id,
+            }]);
+        }
+
+        let consignment = StateConsignment::with(self);
+        let mut spent = BTreeMap::new();
+        let mut failures = vec![];
+        for input in &transition.inputs {
+            match consignment.checkpoint.output(input.prev_out) {
+                Some(output) => {
+                    spent.insert(input.prev_out, output.assign.clone());
+                }
+                None => failures.push(Failure::NoPrevOut(opid, input.prev_out)),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(failures);
+        }
+
+        let op = OpRef::Transition(transition);
+        let global = Some(&consignment.global);
+        let status = schema.validate(&consignment, op, None, None, global, &schema.script);
+        if !status.failures.is_empty() {
+            return Err(status.failures);
+        }
+
+        let mut assigned = BTreeMap::new();
+        for (ty, assigns) in transition.assignments.iter() {
+            for no in 0..assigns.len_u16() {
+                assigned.insert(Opout::new(opid, *ty, no), single(assigns, no as usize));
+            }
+        }
+        Ok(StateDelta {
+            opid,
+            spent,
+            assigned,
+            global: transition.globals.clone(),
+        })
+    }
+}
+
+/// Contract state exposed to the validator as a consignment which starts from
+/// a checkpoint with the unspent state and contains no operations.
+struct StateConsignment<'state> {
+    schema: &'state SubSchema,
+    // Used by the validator only to provide the schema id to the scripts
+    genesis: Genesis,
+    checkpoint: Checkpoint,
+    global: GlobalState,
+}
+
+impl<'state> StateConsignment<'state> {
+    fn with(state: &'state ContractState) -> Self {
+        // Seals of the contract state keep only the outpoints, which are not
+        // used by the validation of the spending transitions
+        fn output<State: ExposedState>(
+            assignment: &OutputAssignment<State>,
+            typed: impl Fn(Assign<State, GraphSeal>) -> TypedAssigns<GraphSeal>,
+        ) -> CheckpointOutput {
+            let seal = assignment.seal;
+            let seal = GraphSeal::with_blinding(CloseMethod::OpretFirst, seal.txid, seal.vout, 0);
+            let witness = match assignment.witness {
+                SealWitness::Present(txid) => Some(txid),
+                SealWitness::Genesis | SealWitness::Extension => None,
+            };
+            CheckpointOutput {
+                opout: assignment.opout,
+                assign: typed(Assign::revealed(seal, assignment.state.clone())),
+                witness,
+            }
+        }
+
+        let outputs = state
+            .rights()
+            .iter()
+            .map(|a| output(a, |assign| TypedAssigns::Declarative(small_vec![assign])))
+            .chain(
+                state
+                    .fungibles()
+                    .iter()
+                    .map(|a| output(a, |assign| TypedAssigns::Fungible(small_vec![assign]))),
+            )
+            .chain(
+                state
+                    .data()
+                    .iter()
+                    .map(|a| output(a, |assign| TypedAssigns::Structured(small_vec![assign]))),
+            )
+            .chain(
+                state
+                    .attach()
+                    .iter()
+                    .map(|a| output(a, |assign| TypedAssigns::Attachment(small_vec![assign]))),
+            );
+        let checkpoint = Checkpoint {
+            ffv: Ffv::default(),
+            contract_id: state.contract_id(),
+            schema_id: state.schema_id(),
+            globals: none!(),
+            outputs: Confined::try_from_iter(outputs).expect("contract state exceeds limits"),
+        };
+
+        // Scripts access the global state starting from the most recent values
+        let mut global = GlobalState::default();
+        for (ty, values) in state.global_view().iter() {
+            let Ok(values) = Confined::try_from_iter(values.iter().rev().cloned()) else {
+                continue;
+            };
+            global
+                .insert(ty, GlobalValues::from(values))
+                .expect("view contains only the schema types");
+        }
+
+        StateConsignment {
+            schema: &state.schema,
+            genesis: Genesis {
+                schema_id: state.schema_id(),
+                ..Genesis::strict_dumb()
+            },
+            checkpoint,
+            global,
+        }
+    }
+}
+
+impl<'state> ConsignmentApi for StateConsignment<'state> {
+    type BundleIter<'container> = slice::Iter<'container, AnchoredBundle>
+    where Self: 'container;
+
+    fn schema(&self) -> &SubSchema { self.schema }
+
+    fn operation(&self, _opid: OpId) -> Option<OpRef<'_>> { None }
+
+    fn genesis(&self) -> &Genesis { &self.genesis }
+
+    fn transition(&self, _opid: OpId) -> Option<&Transition> { None }
+
+    fn extension(&self, _opid: OpId) -> Option<&Extension> { None }
+
+    fn terminals(&self) -> BTreeSet<(BundleId, SecretSeal)> { empty!() }
+
+    fn anchored_bundles(&self) -> Self::BundleIter<'_> { [].iter() }
+
+    fn checkpoint(&self) -> Option<&Checkpoint> { Some(&self.checkpoint) }
+
+    fn bundle_by_id(&self, _bundle_id: BundleId) -> Option<&TransitionBundle> { None }
+
+    fn op_ids_except(&self, _ids: &BTreeSet<OpId>) -> BTreeSet<OpId> { empty!() }
+
+    fn has_operation(&self, _opid: OpId) -> bool { false }
+
+    fn known_transitions_by_bundle_id(&self, _bundle_id: BundleId) -> Option<Vec<&Transition>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::MediumOrdMap;
+    use bp::seals::txout::CloseMethod;
+    use bp::{Chain, Txid};
+    use strict_types::{Ty, TypeSystem};
+
+    use super::*;
+    use crate::schema::{Occurrences, StateSchema, TransitionSchema};
+    use crate::{
+        Assignments, ContractHistory, GenesisSeal, Input, Inputs, OpFeatures, Valencies,
+        ValencyType,
+    };
+
+    #[test]
+    fn accepts() {
+        let mut schema = SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Declarative },
+            transitions: tiny_bmap! {
+                1 => TransitionSchema {
+                    inputs: tiny_bmap! { 1 => Occurrences::Once },
+                    assignments: tiny_bmap! { 1 => Occurrences::Once },
+                    ..default!()
+                },
+            },
+            ..default!()
+        };
+        let types = bmap! { schema.genesis.metadata => Ty::UNIT };
+        schema.type_system = TypeSystem::from(MediumOrdMap::try_from(types).unwrap());
+
+        let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, Txid::from([1; 32]), 0, 1);
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: none!(),
+            globals: none!(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Declarative(small_vec![Assign::right(seal)]),
+            }),
+            valencies: none!(),
+        };
+        let history =
+            ContractHistory::with(genesis.schema_id, None, genesis.contract_id(), &genesis);
+        let state = ContractState {
+            schema: schema.clone(),
+            history,
+        };
+
+        let prev_out = Opout::new(genesis.id(), 1, 0);
+        let transition = |prev_out: Opout, valency: Option<ValencyType>| Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            transition_type: 1,
+            metadata: none!(),
+            globals: none!(),
+            inputs: Inputs::from(tiny_bset![Input::with(prev_out)]),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Declarative(small_vec![Assign::right(
+                    GraphSeal::with_vout(CloseMethod::OpretFirst, 0, 2)
+                )]),
+            }),
+            valencies: Valencies::from(Confined::try_from_iter(valency).unwrap()),
+        };
+
+        let valid = transition(prev_out, None);
+        let delta = state.accepts(&valid, &schema).unwrap();
+        assert_eq!(delta.opid, valid.id());
+        assert_eq!(delta.spent.keys().copied().collect::<Vec<_>>(), vec![prev_out]);
+        assert_eq!(delta.assigned.keys().copied().collect::<Vec<_>>(), vec![Opout::new(
+            valid.id(),
+            1,
+            0
+        )]);
+
+        let unknown = Opout::new(genesis.id(), 1, 1);
+        let invalid = transition(unknown, None);
+        assert_eq!(state.accepts(&invalid, &schema), Err(vec![Failure::NoPrevOut(
+            invalid.id(),
+            unknown
+        )]));
+
+        let invalid = transition(prev_out, Some(5));
+        assert_eq!(state.accepts(&invalid, &schema), Err(vec![
+            Failure::SchemaUnknownValencyType(invalid.id(), 5)
+        ]));
+
+        let other = SubSchema::default();
+        assert!(matches!(
+            state.accepts(&valid, &other),
+            Err(failures) if matches!(failures[..], [Failure::SchemaMismatch { .. }])
+        ));
+    }
+}
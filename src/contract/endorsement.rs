@@ -0,0 +1,332 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Endorsements of contracts by their issuers and registries.
+//!
+//! An endorsement is not a part of the consensus: a consignment is valid
+//! regardless of whether it is endorsed. Receivers may, however, require that
+//! the contract genesis and schema were endorsed by a known issuer key (and,
+//! optionally, by a registry vouching for the issuer) in addition to passing
+//! the consensus validation.
+//!
+//! Schemata may also opt into the issuer identity proofs, which are kept in
+//! the contract genesis and are verified by the consensus validation (see
+//! [`IssuerIdentity`]).
+
+use std::collections::BTreeSet;
+
+use amplify::confinement::{self, SmallVec};
+use amplify::{Bytes32, Bytes64, Wrapper};
+use secp256k1_zkp::{schnorr, KeyPair, Message, XOnlyPublicKey, SECP256K1};
+
+use crate::commit::tagged_hash;
+use crate::schema::{SchemaId, ISSUER_IDENTITY_ID};
+use crate::validation::ConsignmentApi;
+use crate::{ContractId, Genesis, Operation, RevealedData, LIB_NAME_RGB};
+
+/// Tag of the message signed by a contract issuer.
+pub const ENDORSEMENT_ISSUER_TAG: &str = "urn:lnpbp:rgb:endorsement:issuer#2023-10";
+/// Tag of the message signed by a registry.
+pub const ENDORSEMENT_REGISTRY_TAG: &str = "urn:lnpbp:rgb:endorsement:registry#2023-10";
+/// Tag of the message signed by a contract issuer to prove its identity.
+pub const ISSUER_IDENTITY_TAG: &str = "urn:lnpbp:rgb:issuer-identity#2023-10";
+
+/// Errors verifying contract endorsement.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum EndorsementError {
+    /// endorsement is made for contract {found}, while the consignment
+    /// contains contract {expected}.
+    ContractMismatch {
+        expected: ContractId,
+        found: ContractId,
+    },
+
+    /// endorsement is made for schema {found}, while the consignment uses
+    /// schema {expected}.
+    SchemaMismatch { expected: SchemaId, found: SchemaId },
+
+    /// endorsement contains invalid public key {0}.
+    InvalidKey(Bytes32),
+
+    /// endorsement signature made with key {0} is invalid.
+    InvalidSignature(Bytes32),
+
+    /// contract issuer key {0} is not trusted.
+    UntrustedIssuer(Bytes32),
+
+    /// contract endorsement lacks a signature of a trusted registry.
+    NoRegistry,
+
+    /// registry key {0} is not trusted.
+    UntrustedRegistry(Bytes32),
+}
+
+/// BIP340 signature of an endorser.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct EndorserSig {
+    /// X-only public key of the endorser.
+    pub key: Bytes32,
+    pub sig: Bytes64,
+}
+
+impl EndorserSig {
+    /// Signs the message with the given key pair.
+    pub fn sign(keypair: &KeyPair, message: Bytes32) -> Self {
+        let msg = Message::from_slice(message.as_slice()).expect("message has 32 bytes");
+        let sig = SECP256K1.sign_schnorr(&msg, keypair);
+        let (key, _) = keypair.x_only_public_key();
+        EndorserSig {
+            key: key.serialize().into(),
+            sig: Bytes64::from_inner(*sig.as_ref()),
+        }
+    }
+
+    /// Verifies the signature of the message.
+    pub fn verify(&self, message: Bytes32) -> Result<(), EndorsementError> {
+        let key = XOnlyPublicKey::from_slice(self.key.as_slice())
+            .map_err(|_| EndorsementError::InvalidKey(self.key))?;
+        let sig = schnorr::Signature::from_slice(self.sig.as_slice())
+            .map_err(|_| EndorsementError::InvalidSignature(self.key))?;
+        let msg = Message::from_slice(message.as_slice()).expect("message has 32 bytes");
+        SECP256K1
+            .verify_schnorr(&sig, &msg, &key)
+            .map_err(|_| EndorsementError::InvalidSignature(self.key))
+    }
+}
+
+/// Keys of the endorsers trusted by a receiver of consignments.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TrustedEndorsers {
+    /// Keys of the trusted contract issuers.
+    pub issuers: BTreeSet<Bytes32>,
+    /// Keys of the trusted registries. If empty, registry endorsement is not
+    /// required.
+    pub registries: BTreeSet<Bytes32>,
+}
+
+/// Endorsement of a contract (its genesis and schema) by the contract issuer
+/// and, optionally, by a registry vouching for the issuer.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct Endorsement {
+    pub contract_id: ContractId,
+    pub schema_id: SchemaId,
+    /// Signature of the contract issuer over [`Endorsement::issuer_message`].
+    pub issuer: EndorserSig,
+    /// Signature of a registry over [`Endorsement::registry_message`].
+    pub registry: Option<EndorserSig>,
+}
+
+impl Endorsement {
+    /// Creates endorsement signed by the contract issuer.
+    pub fn with(contract_id: ContractId, schema_id: SchemaId, issuer: &KeyPair) -> Self {
+        let message = Self::issuer_message(contract_id, schema_id);
+        Endorsement {
+            contract_id,
+            schema_id,
+            issuer: EndorserSig::sign(issuer, message),
+            registry: None,
+        }
+    }
+
+    /// Adds (or replaces) registry signature to the endorsement.
+    pub fn endorse_registry(&mut self, registry: &KeyPair) {
+        let message = Self::registry_message(self.contract_id, self.schema_id, self.issuer.key);
+        self.registry = Some(EndorserSig::sign(registry, message));
+    }
+
+    /// Message signed by the contract issuer.
+    pub fn issuer_message(contract_id: ContractId, schema_id: SchemaId) -> Bytes32 {
+        let mut data = contract_id.to_vec();
+        data.extend_from_slice(schema_id.as_slice());
+        tagged_hash(ENDORSEMENT_ISSUER_TAG, data)
+    }
+
+    /// Message signed by a registry, which commits to the issuer key, such
+    /// that the registry vouches for the issuer identity.
+    pub fn registry_message(
+        contract_id: ContractId,
+        schema_id: SchemaId,
+        issuer: Bytes32,
+    ) -> Bytes32 {
+        let mut data = contract_id.to_vec();
+        data.extend_from_slice(schema_id.as_slice());
+        data.extend_from_slice(issuer.as_slice());
+        tagged_hash(ENDORSEMENT_REGISTRY_TAG, data)
+    }
+
+    /// Verifies all signatures of the endorsement.
+    pub fn verify(&self) -> Result<(), EndorsementError> {
+        self.issuer
+            .verify(Self::issuer_message(self.contract_id, self.schema_id))?;
+        if let Some(registry) = &self.registry {
+            registry.verify(Self::registry_message(
+                self.contract_id,
+                self.schema_id,
+                self.issuer.key,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Verifies that the endorsement signatures are valid, that the
+    /// endorsement is made for the consignment contract and that it is signed
+    /// by the trusted endorsers. Does not validate the consignment itself.
+    pub fn verify_consignment(
+        &self,
+        consignment: &impl ConsignmentApi,
+        trusted: &TrustedEndorsers,
+    ) -> Result<(), EndorsementError> {
+        let genesis = consignment.genesis();
+        let contract_id = genesis.contract_id();
+        if self.contract_id != contract_id {
+            return Err(EndorsementError::ContractMismatch {
+                expected: contract_id,
+                found: self.contract_id,
+            });
+        }
+        if self.schema_id != genesis.schema_id {
+            return Err(EndorsementError::SchemaMismatch {
+                expected: genesis.schema_id,
+                found: self.schema_id,
+            });
+        }
+        self.verify()?;
+        if !trusted.issuers.contains(&self.issuer.key) {
+            return Err(EndorsementError::UntrustedIssuer(self.issuer.key));
+        }
+        if !trusted.registries.is_empty() {
+            let registry = self.registry.ok_or(EndorsementError::NoRegistry)?;
+            if !trusted.registries.contains(&registry.key) {
+                return Err(EndorsementError::UntrustedRegistry(registry.key));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Proof of the contract issuer identity, kept in the genesis global state of
+/// the [`ISSUER_IDENTITY_ID`] type by the schemata enabling
+/// [`crate::schema::EmbeddedProcedure::IssuerIdentity`].
+///
+/// The state data consist of the issuer signature (the 32-byte x-only public
+/// key followed by the 64-byte signature) and the identity claim.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct IssuerIdentity {
+    /// Signature of the contract issuer over [`IssuerIdentity::message`].
+    pub sig: EndorserSig,
+    /// Identity claim of the issuer (like a domain name), which format is
+    /// defined by the schema.
+    pub claim: SmallVec<u8>,
+}
+
+impl IssuerIdentity {
+    /// Creates identity proof for the genesis signed by the contract issuer.
+    /// Any existing identity proof of the genesis is ignored.
+    pub fn sign(genesis: &Genesis, claim: SmallVec<u8>, issuer: &KeyPair) -> Self {
+        let message = Self::message(genesis, &claim);
+        IssuerIdentity {
+            sig: EndorserSig::sign(issuer, message),
+            claim,
+        }
+    }
+
+    /// Message signed by the contract issuer, which commits to the claim and
+    /// to the genesis without its identity proof.
+    pub fn message(genesis: &Genesis, claim: &[u8]) -> Bytes32 {
+        let mut genesis = genesis.clone();
+        genesis
+            .globals
+            .remove(&ISSUER_IDENTITY_ID)
+            .expect("global state has no minimal size");
+        let mut data = genesis.id().to_vec();
+        data.extend_from_slice(claim);
+        tagged_hash(ISSUER_IDENTITY_TAG, data)
+    }
+
+    /// Verifies the identity proof of the genesis.
+    pub fn verify(&self, genesis: &Genesis) -> Result<(), EndorsementError> {
+        self.sig.verify(Self::message(genesis, &self.claim))
+    }
+
+    /// Parses identity proof from the global state data, returning `None` if
+    /// the data are shorter than the signature.
+    pub fn from_state(data: &RevealedData) -> Option<Self> {
+        let data = data.as_inner();
+        let key = Bytes32::from_slice(data.get(..32)?)?;
+        let sig = Bytes64::from_slice(data.get(32..96)?)?;
+        let claim = SmallVec::try_from(data[96..].to_vec()).expect("data are a small vec");
+        Some(IssuerIdentity {
+            sig: EndorserSig { key, sig },
+            claim,
+        })
+    }
+
+    /// Serializes the identity proof into the global state data.
+    pub fn to_state(&self) -> Result<RevealedData, confinement::Error> {
+        let mut data = self.sig.key.to_vec();
+        data.extend_from_slice(self.sig.sig.as_slice());
+        data.extend_from_slice(&self.claim);
+        SmallVec::try_from(data).map(RevealedData::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use secp256k1_zkp::rand::thread_rng;
+
+    use super::*;
+
+    #[test]
+    fn endorse() {
+        let issuer = KeyPair::new(SECP256K1, &mut thread_rng());
+        let registry = KeyPair::new(SECP256K1, &mut thread_rng());
+        let contract_id = ContractId::from([1u8; 32]);
+        let schema_id = SchemaId::from([2u8; 32]);
+
+        let mut endorsement = Endorsement::with(contract_id, schema_id, &issuer);
+        assert_eq!(endorsement.verify(), Ok(()));
+        endorsement.endorse_registry(&registry);
+        assert_eq!(endorsement.verify(), Ok(()));
+
+        let mut forged = endorsement.clone();
+        forged.schema_id = SchemaId::from([3u8; 32]);
+        assert_eq!(
+            forged.verify(),
+            Err(EndorsementError::InvalidSignature(endorsement.issuer.key))
+        );
+    }
+}
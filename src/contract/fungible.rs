@@ -32,7 +32,7 @@
 
 use core::cmp::Ordering;
 use core::fmt::Debug;
-use core::num::ParseIntError;
+use core::num::{ParseIntError, TryFromIntError};
 use core::ops::Deref;
 use core::str::FromStr;
 use std::io;
@@ -57,6 +57,10 @@ use super::{ConfidentialState, ExposedState};
 use crate::{schema, StateCommitment, StateData, StateType, LIB_NAME_RGB};
 
 /// An atom of an additive state, which thus can be monomorphically encrypted.
+///
+/// Values of different bit dimensions are distinct, matching their strict
+/// encoding; use [`FungibleState::numeric_cmp`] to compare them by the
+/// numeric value.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, From)]
 #[display(inner)]
 #[derive(StrictType, StrictEncode, StrictDecode)]
@@ -71,6 +75,10 @@ pub enum FungibleState {
     #[from]
     #[strict_type(tag = 8)] // Matches strict types U64 primitive value
     Bits64(u64),
+
+    /// 128-bit value.
+    #[strict_type(tag = 16)] // Matches strict types U128 primitive value
+    Bits128(u128),
     // When/if adding more variants do not forget to re-write FromStr impl
 }
 
@@ -84,25 +92,77 @@ impl From<RevealedValue> for FungibleState {
 
 impl FromStr for FungibleState {
     type Err = ParseIntError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> { s.parse().map(FungibleState::Bits64) }
+    /// Parses decimal value, using [`FungibleState::Bits64`] when the value
+    /// fits 64 bits and [`FungibleState::Bits128`] otherwise.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(FungibleState::Bits64).or_else(|err| {
+            s.parse::<u128>()
+                .map(FungibleState::Bits128)
+                .map_err(|_| err)
+        })
+    }
 }
 
-impl From<FungibleState> for u64 {
+impl TryFrom<FungibleState> for u64 {
+    type Error = TryFromIntError;
+    fn try_from(value: FungibleState) -> Result<Self, Self::Error> {
+        match value {
+            FungibleState::Bits64(val) => Ok(val),
+            FungibleState::Bits128(val) => u64::try_from(val),
+        }
+    }
+}
+
+impl From<FungibleState> for u128 {
     fn from(value: FungibleState) -> Self {
         match value {
-            FungibleState::Bits64(val) => val,
+            FungibleState::Bits64(val) => val as u128,
+            FungibleState::Bits128(val) => val,
         }
     }
 }
 
 impl FungibleState {
+    /// Constructs value of the given fungible type, failing if the value
+    /// exceeds the type boundaries.
+    pub fn with_type(value: u128, ty: schema::FungibleType) -> Option<Self> {
+        match ty {
+            schema::FungibleType::Unsigned64Bit => {
+                u64::try_from(value).ok().map(FungibleState::Bits64)
+            }
+            schema::FungibleType::Unsigned128Bit => Some(FungibleState::Bits128(value)),
+        }
+    }
+
     pub fn fungible_type(&self) -> schema::FungibleType {
         match self {
             FungibleState::Bits64(_) => schema::FungibleType::Unsigned64Bit,
+            FungibleState::Bits128(_) => schema::FungibleType::Unsigned128Bit,
         }
     }
 
-    pub fn as_u64(&self) -> u64 { (*self).into() }
+    /// Returns value as a 64-bit integer.
+    ///
+    /// # Panics
+    ///
+    /// If the value doesn't fit into 64 bits, which may happen only for the
+    /// state of 128-bit fungible types; use [`FungibleState::try_as_u64`] for
+    /// them.
+    #[deprecated(note = "use `try_as_u64` or `as_u128`, which don't panic on 128-bit values")]
+    pub fn as_u64(&self) -> u64 {
+        self.try_as_u64()
+            .expect("128-bit fungible state value doesn't fit into 64 bits")
+    }
+
+    /// Returns value as a 64-bit integer, if it fits into 64 bits.
+    pub fn try_as_u64(&self) -> Option<u64> { (*self).try_into().ok() }
+
+    /// Returns value as a 128-bit integer.
+    pub fn as_u128(&self) -> u128 { (*self).into() }
+
+    /// Compares numeric values independently of their bit dimension, unlike
+    /// [`Ord`], which orders 64-bit values before the 128-bit ones.
+    pub fn numeric_cmp(&self, other: &Self) -> Ordering { self.as_u128().cmp(&other.as_u128()) }
 }
 
 /// Blinding factor used in creating Pedersen commitment to an [`AtomicValue`].
@@ -175,7 +235,11 @@ impl TryFrom<[u8; 32]> for BlindingFactor {
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB, rename = "RevealedFungible")]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
 pub struct RevealedValue {
     /// Original value in smallest indivisible units
     pub value: FungibleState,
@@ -286,21 +350,109 @@ impl StrictDecode for PedersenCommitment {
 
 impl CommitVerify<RevealedValue, UntaggedProtocol> for PedersenCommitment {
     fn commit(revealed: &RevealedValue) -> Self {
-        use secp256k1_zkp::{Generator, Tag, Tweak};
+        use secp256k1_zkp::Tweak;
 
         let blinding = Tweak::from_inner(revealed.blinding.0.into_inner())
             .expect("type guarantees of BlindingFactor are broken");
-        let FungibleState::Bits64(value) = revealed.value;
+        let generator = pedersen::value_generator();
+
+        // 128-bit values are committed as `lo * H + hi * 2^64 * H + r * G`,
+        // which is the same point as a commitment to the full value, and thus
+        // sums of such commitments can be verified in the same way as for
+        // 64-bit values.
+        let (lo, hi) = match revealed.value {
+            FungibleState::Bits64(value) => (value, 0u64),
+            FungibleState::Bits128(value) => (value as u64, (value >> 64) as u64),
+        };
+        let commitment =
+            secp256k1_zkp::PedersenCommitment::new(SECP256K1, lo, blinding, generator);
+        if hi == 0 {
+            return commitment.into();
+        }
+        let high = secp256k1_zkp::PedersenCommitment::new(
+            SECP256K1,
+            hi,
+            secp256k1_zkp::ZERO_TWEAK,
+            pedersen::high_limb_generator(),
+        );
+        pedersen::add(commitment, high).into()
+    }
+}
+
+/// Arithmetics over Pedersen commitments which is not provided by the
+/// secp256k1-zkp library API.
+mod pedersen {
+    use secp256k1_zkp::{
+        verify_commitments_sum_to_equal, Generator, PedersenCommitment, PublicKey, Tag, SECP256K1,
+    };
+
+    use super::*;
 
+    /// Serialization prefix of a commitment differs from the serialization
+    /// prefix of a generator by this value, while both encode the same
+    /// information about the point `y` coordinate.
+    const GENERATOR_PREFIX_SHIFT: u8 = 2;
+
+    /// Serialization prefixes of a commitment for both possible `y`
+    /// coordinates of the point with a given `x` coordinate.
+    const COMMITMENT_PREFIXES: [u8; 2] = [0x08, 0x09];
+
+    /// Generator `H` used for the value part of the commitments.
+    pub fn value_generator() -> Generator {
         // TODO: Check that we create correct generator value.
         let one_key = secp256k1_zkp::SecretKey::from_slice(&secp256k1_zkp::constants::ONE)
             .expect("secret key from a constant");
-        let g = secp256k1_zkp::PublicKey::from_secret_key(SECP256K1, &one_key);
+        let g = PublicKey::from_secret_key(SECP256K1, &one_key);
         let h: [u8; 32] = Sha256::digest(&g.serialize_uncompressed()).into();
         let tag = Tag::from(h);
-        let generator = Generator::new_unblinded(SECP256K1, tag);
+        Generator::new_unblinded(SECP256K1, tag)
+    }
 
-        secp256k1_zkp::PedersenCommitment::new(SECP256K1, value, blinding, generator).into()
+    /// Generator `2^64 * H` used for the high 64 bits of 128-bit values.
+    pub fn high_limb_generator() -> Generator {
+        let generator = scale(value_generator(), 1 << 32);
+        scale(generator, 1 << 32)
+    }
+
+    /// Multiplies generator by a non-zero 64-bit scalar.
+    fn scale(generator: Generator, factor: u64) -> Generator {
+        let point =
+            PedersenCommitment::new(SECP256K1, factor, secp256k1_zkp::ZERO_TWEAK, generator);
+        let mut data = point.serialize();
+        data[0] += GENERATOR_PREFIX_SHIFT;
+        Generator::from_slice(&data).expect("commitment and generator encodings are equivalent")
+    }
+
+    /// Adds two commitments together.
+    ///
+    /// Commitments are converted into public keys with the same `x`
+    /// coordinate, which are then added. Since commitments encode the `y`
+    /// coordinate as being a quadratic residue and public keys as its parity,
+    /// the conversion is ambiguous; thus the sum is selected from the
+    /// candidate points as the one which balances the summands.
+    pub fn add(a: PedersenCommitment, b: PedersenCommitment) -> PedersenCommitment {
+        let a_key = to_public_key(a);
+        let b_key = to_public_key(b);
+        let sums = [a_key.combine(&b_key), a_key.combine(&b_key.negate(SECP256K1))];
+        sums.into_iter()
+            .filter_map(Result::ok)
+            .flat_map(|sum| {
+                let data = sum.serialize();
+                COMMITMENT_PREFIXES.map(|prefix| {
+                    let mut data = data;
+                    data[0] = prefix;
+                    PedersenCommitment::from_slice(&data).ok()
+                })
+            })
+            .flatten()
+            .find(|sum| verify_commitments_sum_to_equal(SECP256K1, &[*sum], &[a, b]))
+            .expect("sum of commitments with a non-zero blinding can't be at infinity")
+    }
+
+    fn to_public_key(commitment: PedersenCommitment) -> PublicKey {
+        let mut data = commitment.serialize();
+        data[0] = 0x02;
+        PublicKey::from_slice(&data).expect("commitment is a valid curve point")
     }
 }
 
@@ -442,4 +594,56 @@ mod test {
             .collect::<HashSet<_>>();
         assert_eq!(generators.len(), 1);
     }
+
+    #[test]
+    fn commitments_128bit() {
+        let blinding = BlindingFactor::from(secp256k1_zkp::SecretKey::new(&mut thread_rng()));
+        let small = RevealedValue::with(FungibleState::Bits64(15), blinding);
+        let wide = RevealedValue::with(FungibleState::Bits128(15), blinding);
+        assert_eq!(PedersenCommitment::commit(&small), PedersenCommitment::commit(&wide));
+
+        let max = u64::MAX as u128;
+        let input = RevealedValue::with(FungibleState::Bits128(max * 3), blinding);
+        let blinding2 = BlindingFactor::from(secp256k1_zkp::SecretKey::new(&mut thread_rng()));
+        let out1 = RevealedValue::with(FungibleState::Bits128(max * 2 + 1), blinding2);
+        let mut blinding3 = secp256k1_zkp::SecretKey::from(blinding);
+        blinding3 = blinding3
+            .add_tweak(&secp256k1_zkp::SecretKey::from(blinding2).negate().into())
+            .unwrap();
+        let out2 = RevealedValue::with(FungibleState::Bits128(max - 1), blinding3);
+
+        let inputs = [PedersenCommitment::commit(&input).into_inner()];
+        let outputs = [out1, out2].map(|v| PedersenCommitment::commit(&v).into_inner());
+        assert!(secp256k1_zkp::verify_commitments_sum_to_equal(SECP256K1, &inputs, &outputs));
+
+        let out2 = RevealedValue::with(FungibleState::Bits128(max), blinding3);
+        let outputs = [out1, out2].map(|v| PedersenCommitment::commit(&v).into_inner());
+        assert!(!secp256k1_zkp::verify_commitments_sum_to_equal(SECP256K1, &inputs, &outputs));
+    }
+
+    #[test]
+    fn comparison() {
+        let small = FungibleState::Bits64(16);
+        let wide = FungibleState::Bits128(16);
+        assert_ne!(small, wide);
+        assert!(small < FungibleState::Bits128(15));
+        assert!(wide > FungibleState::Bits64(17));
+        assert_eq!(HashSet::from([small, wide]).len(), 2);
+
+        assert_eq!(small.numeric_cmp(&wide), Ordering::Equal);
+        assert_eq!(FungibleState::Bits128(15).numeric_cmp(&small), Ordering::Less);
+        assert_eq!(FungibleState::Bits64(17).numeric_cmp(&wide), Ordering::Greater);
+        let max = FungibleState::Bits64(u64::MAX);
+        let overflow = FungibleState::Bits128(u64::MAX as u128 + 1);
+        assert_eq!(max.numeric_cmp(&overflow), Ordering::Less);
+    }
+
+    #[test]
+    fn integer_conversions() {
+        let wide = FungibleState::Bits128(u64::MAX as u128 + 1);
+        assert_eq!(wide.try_as_u64(), None);
+        assert_eq!(wide.as_u128(), u64::MAX as u128 + 1);
+        assert_eq!(FungibleState::Bits128(5).try_as_u64(), Some(5));
+        assert_eq!(FungibleState::Bits64(5).as_u128(), 5);
+    }
 }
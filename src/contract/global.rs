@@ -20,12 +20,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::btree_map;
+use std::collections::{btree_map, BTreeMap};
 use std::vec;
 
 use amplify::confinement::{Confined, TinyOrdMap, U16};
 use amplify::{confinement, Wrapper};
-use strict_encoding::StrictDumb;
+use strict_encoding::{StrictDecode, StrictDumb, StrictReader};
 
 use crate::{schema, RevealedData, LIB_NAME_RGB};
 
@@ -102,3 +102,104 @@ impl<'a> IntoIterator for &'a GlobalState {
 
     fn into_iter(self) -> Self::IntoIter { self.0.iter() }
 }
+
+/// Errors accessing values of [`GlobalStateView`].
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum GlobalStateError {
+    /// contract has no global state of type {0}.
+    Absent(schema::GlobalStateType),
+
+    /// global state of type {0} is not a valid UTF-8 string.
+    NotUtf8(schema::GlobalStateType),
+
+    /// global state of type {0} can't be decoded. Details: {1}
+    Decode(schema::GlobalStateType, String),
+}
+
+/// Read-only snapshot of the contract global state, built from the validated
+/// contract history with [`crate::ContractState::global_view`].
+///
+/// The view owns its data and is not affected by further updates of the
+/// contract history, so it can be shared between threads.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct GlobalStateView(BTreeMap<schema::GlobalStateType, Vec<RevealedData>>);
+
+impl GlobalStateView {
+    pub(crate) fn insert(&mut self, ty: schema::GlobalStateType, values: Vec<RevealedData>) {
+        self.0.insert(ty, values);
+    }
+
+    /// Iterates over all global state types and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (schema::GlobalStateType, &[RevealedData])> {
+        self.0.iter().map(|(ty, values)| (*ty, values.as_slice()))
+    }
+
+    /// Returns all values of the given global state type in the consensus
+    /// order, or an empty slice if the contract has no state of that type.
+    pub fn values(&self, ty: schema::GlobalStateType) -> &[RevealedData] {
+        self.0.get(&ty).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Returns the first value of the given global state type in the
+    /// consensus order.
+    pub fn get(&self, ty: schema::GlobalStateType) -> Result<&RevealedData, GlobalStateError> {
+        self.values(ty).first().ok_or(GlobalStateError::Absent(ty))
+    }
+
+    /// Returns raw bytes of the first value of the given global state type.
+    pub fn as_bytes(&self, ty: schema::GlobalStateType) -> Result<&[u8], GlobalStateError> {
+        self.get(ty).map(|data| data.as_inner().as_slice())
+    }
+
+    /// Interprets the first value of the given global state type as a UTF-8
+    /// string.
+    pub fn as_str(&self, ty: schema::GlobalStateType) -> Result<&str, GlobalStateError> {
+        let bytes = self.as_bytes(ty)?;
+        std::str::from_utf8(bytes).map_err(|_| GlobalStateError::NotUtf8(ty))
+    }
+
+    /// Decodes the first value of the given global state type as a strict
+    /// encoded 64-bit unsigned integer.
+    pub fn as_u64(&self, ty: schema::GlobalStateType) -> Result<u64, GlobalStateError> {
+        self.decode(ty)
+    }
+
+    /// Decodes the first value of the given global state type into a strict
+    /// type, requiring all the data to be consumed by the decoder.
+    pub fn decode<T: StrictDecode>(
+        &self,
+        ty: schema::GlobalStateType,
+    ) -> Result<T, GlobalStateError> {
+        let bytes = self.as_bytes(ty)?;
+        let mut reader = StrictReader::in_memory(bytes.to_vec(), u16::MAX as usize);
+        let value = T::strict_decode(&mut reader)
+            .map_err(|err| GlobalStateError::Decode(ty, err.to_string()))?;
+        if reader.unbox().position() as usize != bytes.len() {
+            return Err(GlobalStateError::Decode(ty, s!("data are not entirely consumed")));
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::SmallVec;
+
+    use super::*;
+
+    #[test]
+    fn view_getters() {
+        let data = |bytes: &[u8]| RevealedData::from(SmallVec::try_from(bytes.to_vec()).unwrap());
+        let mut view = GlobalStateView::default();
+        view.insert(1, vec![data(b"TICKER"), data(b"OTHER")]);
+        view.insert(2, vec![data(&21_000_000u64.to_le_bytes())]);
+
+        assert_eq!(view.as_str(1), Ok("TICKER"));
+        assert_eq!(view.values(1).len(), 2);
+        assert_eq!(view.as_u64(2), Ok(21_000_000));
+        assert!(matches!(view.as_u64(1), Err(GlobalStateError::Decode(1, _))));
+        assert_eq!(view.as_bytes(3), Err(GlobalStateError::Absent(3)));
+        assert!(view.values(3).is_empty());
+    }
+}
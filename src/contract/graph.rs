@@ -0,0 +1,170 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Queries over the contract operation graph (DAG), used by explorers and
+//! wallets to trace what happened with the state of a contract.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::validation::{ConsignmentApi, Status, Validity};
+use crate::{OpId, Operation};
+
+/// Index of the parent-child relations between the operations of a validated
+/// consignment.
+///
+/// An operation is a parent of another operation if the latter spends the
+/// state assigned by it or redeems its valency. Relations with operations
+/// absent from the consignment (like the ones preceding a checkpoint) are not
+/// indexed.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct HistoryIndex {
+    parents: BTreeMap<OpId, BTreeSet<OpId>>,
+    children: BTreeMap<OpId, BTreeSet<OpId>>,
+}
+
+impl HistoryIndex {
+    /// Indexes the consignment, which must have been validated with the
+    /// provided status. Returns `None` if the consignment is not valid.
+    pub fn with(consignment: &impl ConsignmentApi, status: &Status) -> Option<Self> {
+        if status.validity() != Validity::Valid {
+            return None;
+        }
+        let mut index = HistoryIndex::default();
+        for opid in consignment.op_ids_except(&BTreeSet::new()) {
+            let Some(op) = consignment.operation(opid) else {
+                continue;
+            };
+            let redeemed = op
+                .as_extension()
+                .map(|extension| extension.redeemed.values().copied().collect())
+                .unwrap_or_else(Vec::new);
+            let parents = op
+                .inputs()
+                .iter()
+                .map(|input| input.prev_out.op)
+                .chain(redeemed)
+                .filter(|id| consignment.operation(*id).is_some())
+                .collect::<Vec<_>>();
+            index.insert(opid, parents);
+        }
+        Some(index)
+    }
+
+    fn insert(&mut self, opid: OpId, parents: impl IntoIterator<Item = OpId>) {
+        let parents = parents.into_iter().collect::<BTreeSet<_>>();
+        for parent in &parents {
+            self.children.entry(*parent).or_default().insert(opid);
+        }
+        self.children.entry(opid).or_default();
+        self.parents.entry(opid).or_default().extend(parents);
+    }
+
+    /// Number of the indexed operations.
+    pub fn len(&self) -> usize { self.parents.len() }
+
+    pub fn is_empty(&self) -> bool { self.parents.is_empty() }
+
+    /// Checks whether the operation is indexed.
+    pub fn contains(&self, opid: OpId) -> bool { self.parents.contains_key(&opid) }
+
+    /// Operations which state or valencies are used by the operation.
+    pub fn parents_of(&self, opid: OpId) -> BTreeSet<OpId> {
+        self.parents.get(&opid).cloned().unwrap_or_default()
+    }
+
+    /// Operations using the state or valencies of the operation.
+    pub fn children_of(&self, opid: OpId) -> BTreeSet<OpId> {
+        self.children.get(&opid).cloned().unwrap_or_default()
+    }
+
+    /// All operations the operation depends on, not including the operation
+    /// itself.
+    pub fn ancestors_of(&self, opid: OpId) -> BTreeSet<OpId> { reach(&self.parents, opid) }
+
+    /// All operations depending on the operation, not including the operation
+    /// itself. For an issuing operation these are all the operations which
+    /// have moved the issued state.
+    pub fn descendants_of(&self, opid: OpId) -> BTreeSet<OpId> { reach(&self.children, opid) }
+
+    /// Operations lying on the paths from operation `a` to its descendant `b`,
+    /// including both `a` and `b`. Returns an empty set if `b` doesn't depend
+    /// on `a`.
+    pub fn cone_between(&self, a: OpId, b: OpId) -> BTreeSet<OpId> {
+        if !self.contains(a) || !self.contains(b) {
+            return empty!();
+        }
+        let mut descendants = self.descendants_of(a);
+        descendants.insert(a);
+        let mut ancestors = self.ancestors_of(b);
+        ancestors.insert(b);
+        if !descendants.contains(&b) {
+            return empty!();
+        }
+        descendants.intersection(&ancestors).copied().collect()
+    }
+}
+
+// Traversal uses explicit stack, since recursion may overflow on the long
+// histories
+fn reach(edges: &BTreeMap<OpId, BTreeSet<OpId>>, opid: OpId) -> BTreeSet<OpId> {
+    let mut reached = BTreeSet::new();
+    let mut stack = vec![opid];
+    while let Some(opid) = stack.pop() {
+        for next in edges.get(&opid).into_iter().flatten() {
+            if reached.insert(*next) {
+                stack.push(*next);
+            }
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn queries() {
+        let id = |no: u8| OpId::from([no; 32]);
+        // 0 -> 1 -> 2 -> 4, 0 -> 3 -> 4, 1 -> 5
+        let mut index = HistoryIndex::default();
+        index.insert(id(0), []);
+        index.insert(id(1), [id(0)]);
+        index.insert(id(2), [id(1)]);
+        index.insert(id(3), [id(0)]);
+        index.insert(id(4), [id(2), id(3)]);
+        index.insert(id(5), [id(1)]);
+        assert_eq!(index.len(), 6);
+
+        assert_eq!(index.children_of(id(1)), bset![id(2), id(5)]);
+        assert_eq!(index.ancestors_of(id(4)), bset![id(0), id(1), id(2), id(3)]);
+        assert_eq!(index.descendants_of(id(1)), bset![id(2), id(4), id(5)]);
+        assert_eq!(index.descendants_of(id(4)), bset![]);
+
+        assert_eq!(index.cone_between(id(1), id(4)), bset![id(1), id(2), id(4)]);
+        assert_eq!(index.cone_between(id(0), id(4)), bset![id(0), id(1), id(2), id(3), id(4)]);
+        assert_eq!(index.cone_between(id(3), id(4)), bset![id(3), id(4)]);
+        assert_eq!(index.cone_between(id(2), id(2)), bset![id(2)]);
+        assert_eq!(index.cone_between(id(5), id(4)), bset![]);
+        assert_eq!(index.cone_between(id(4), id(0)), bset![]);
+    }
+}
@@ -0,0 +1,184 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export of the contract operation graph contained in a consignment into
+//! Graphviz DOT format, which is useful for debugging invalid consignments and
+//! for documentation tooling.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use bp::Txid;
+
+use crate::validation::{AnchoredBundle, ConsignmentApi};
+use crate::{OpId, OpRef, OpType, Operation};
+
+/// Produces Graphviz DOT description of the contract operation graph (DAG)
+/// contained in the consignment.
+///
+/// Each operation is represented by a node labeled with its kind, type and id;
+/// state transitions are additionally annotated with the id of their witness
+/// transaction. Solid edges connect owned state spent by state transitions to
+/// the operations which had defined it (labeled with the assignment type and
+/// output number); dashed edges connect redeemed valencies to the operations
+/// declaring them.
+pub fn to_dot<C: ConsignmentApi>(consignment: &C) -> String {
+    let mut witnesses = BTreeMap::<OpId, Txid>::new();
+    for AnchoredBundle { anchor, bundle, .. } in consignment.anchored_bundles() {
+        for opid in bundle.keys() {
+            witnesses.insert(*opid, anchor.txid);
+        }
+    }
+
+    let mut dot = s!("digraph contract {\n");
+    dot.push_str("    rankdir=BT;\n");
+    dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+    for opid in consignment.op_ids_except(&BTreeSet::new()) {
+        let Some(op) = consignment.operation(opid) else {
+            continue
+        };
+        let shape = match op.op_type() {
+            OpType::Genesis => "doubleoctagon",
+            OpType::StateExtension => "ellipse",
+            OpType::StateTransition => "box",
+        };
+        let label = op.full_type();
+        let witness = match witnesses.get(&opid) {
+            Some(txid) => format!("\\nwitness {txid}"),
+            None if op.op_type() == OpType::StateTransition => s!("\\nwitness unknown"),
+            None => none!(),
+        };
+        writeln!(dot, "    \"{opid}\" [shape={shape}, label=\"{label}\\n{opid}{witness}\"];")
+            .expect("writing to string");
+
+        match op {
+            OpRef::Genesis(_) => {}
+            OpRef::Transition(transition) => {
+                for input in &transition.inputs {
+                    let prev = input.prev_out;
+                    writeln!(
+                        dot,
+                        "    \"{}\" -> \"{opid}\" [label=\"{}/{}\"];",
+                        prev.op, prev.ty, prev.no
+                    )
+                    .expect("writing to string");
+                }
+            }
+            OpRef::Extension(extension) => {
+                for (valency, prev_id) in &extension.redeemed {
+                    writeln!(
+                        dot,
+                        "    \"{prev_id}\" -> \"{opid}\" [style=dashed, label=\"valency {valency}\"];"
+                    )
+                    .expect("writing to string");
+                }
+            }
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+#[cfg(feature = "testing")]
+mod test {
+    use amplify::confinement::SmallBlob;
+    use bp::seals::txout::CloseMethod;
+    use bp::Chain;
+
+    use super::*;
+    use crate::testing::{bundle_of, FakeChain, FakeConsignment};
+    use crate::{
+        Assign, Assignments, Extension, Ffv, Genesis, GenesisSeal, GlobalState, GraphSeal, Input,
+        Inputs, OpFeatures, Opout, Redeemed, SubSchema, Transition, TypedAssigns, Valencies,
+        VoidState,
+    };
+
+    fn rights<Seal: crate::ExposedSeal>(seal: Seal) -> Assignments<Seal> {
+        let assign = Assign::revealed(seal, VoidState::default());
+        Assignments::from(tiny_bmap! { 1 => TypedAssigns::Declarative(small_vec![assign]) })
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut chain = FakeChain::new();
+        let issue = chain.next_txid();
+        let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue, 0, 1);
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: SubSchema::default().schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: rights(seal),
+            valencies: Valencies::from(tiny_bset![1]),
+        };
+        let contract_id = genesis.contract_id();
+        let extension = Extension {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id,
+            extension_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: rights(GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue, 1, 2)),
+            redeemed: Redeemed::from(tiny_bmap! { 1 => genesis.id() }),
+            valencies: Valencies::default(),
+        };
+        let transition = Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id,
+            transition_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            inputs: Inputs::from(tiny_bset![Input::with(Opout::new(genesis.id(), 1, 0))]),
+            assignments: rights(GraphSeal::with_vout(CloseMethod::OpretFirst, 1, 3)),
+            valencies: Valencies::default(),
+        };
+        let (g, e, t) = (genesis.id(), extension.id(), transition.id());
+        let mut consignment = FakeConsignment::new(SubSchema::default(), genesis);
+        consignment.extensions.push(extension);
+        let anchored_bundle = chain.anchor(contract_id, bundle_of([transition]), [], 1);
+        let txid = anchored_bundle.anchor.txid;
+        consignment.add_terminal(anchored_bundle);
+
+        assert_eq!(
+            to_dot(&consignment),
+            format!(
+                r#"digraph contract {{
+    rankdir=BT;
+    node [shape=box, fontname="monospace"];
+    "{t}" [shape=box, label="state transition #1\n{t}\nwitness {txid}"];
+    "{g}" -> "{t}" [label="1/0"];
+    "{g}" [shape=doubleoctagon, label="genesis\n{g}"];
+    "{e}" [shape=ellipse, label="state extension #1\n{e}"];
+    "{g}" -> "{e}" [style=dashed, label="valency 1"];
+}}
+"#
+            )
+        );
+    }
+}
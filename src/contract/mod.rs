@@ -20,36 +20,82 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Contract operations, their state and the data structures committing to
+//! them.
+//!
+//! With `serde` feature enabled all the contract data types, including
+//! genesis, state transitions and extensions, assignments, seals and anchors,
+//! can be serialized. Field names are stable and use `camelCase`; newtype
+//! wrappers (identifiers, state collections and bundles) are represented
+//! transparently by their inner data.
+
 mod global;
 mod data;
 mod fungible;
+mod amount;
+mod bech32;
 mod attachment;
 mod state;
 pub mod seal;
 pub mod assignments;
 mod operations;
 mod bundle;
+mod checkpoint;
+mod endorsement;
+mod plan;
+mod graph;
+mod proof;
 mod contract;
+mod dry_run;
+mod provider;
+mod transfer;
+pub mod timestamp;
+#[cfg(feature = "dot")]
+pub mod history;
 
 pub use assignments::{
     Assign, AssignAttach, AssignData, AssignFungible, AssignRights, Assignments, AssignmentsRef,
-    TypedAssigns,
+    ConcealSeals, DeclarativeAssignments, TypedAssigns,
 };
+pub use amount::{Amount, AmountError, PreciseAmount, MAX_PRECISION};
+pub use bech32::{Bech32Error, HRP_CONTRACT, HRP_OPERATION};
 pub use attachment::{AttachId, ConcealedAttach, RevealedAttach};
-pub use bundle::{BundleId, BundleItem, TransitionBundle};
+pub use bundle::{anchoring_order, AnchoringError, BundleId, BundleItem, TransitionBundle};
+pub use checkpoint::{Checkpoint, CheckpointError, CheckpointId, CheckpointOutput};
 pub use contract::{
-    AttachOutput, ContractHistory, ContractState, DataOutput, FungibleOutput, GlobalOrd, Opout,
-    OpoutParseError, OrderedTxid, OutputAssignment, RightsOutput,
+    AllocationEvent, AllocationRecord, AssignmentsDiff, AttachOutput, ContractHistory,
+    ContractState, DataOutput, FungibleOutput, GlobalOrd, Opout, OpoutParseError, OrderedTxid,
+    OutputAssignment, RightsOutput, StateDiff, WitnessPos,
+};
+pub use dry_run::StateDelta;
+pub use endorsement::{
+    Endorsement, EndorsementError, EndorserSig, IssuerIdentity, TrustedEndorsers,
+    ENDORSEMENT_ISSUER_TAG, ENDORSEMENT_REGISTRY_TAG, ISSUER_IDENTITY_TAG,
 };
 pub use data::{ConcealedData, RevealedData, VoidState};
 pub use fungible::{
     BlindingFactor, ConcealedValue, FieldOrderOverflow, FungibleState, NoiseDumb,
     PedersenCommitment, RangeProof, RangeProofError, RevealedValue,
 };
-pub use global::{GlobalState, GlobalValues};
+pub use global::{GlobalState, GlobalStateError, GlobalStateView, GlobalValues};
 pub use operations::{
-    ContractId, Extension, Genesis, Input, Inputs, OpId, OpRef, Operation, Redeemed, Transition,
-    Valencies,
+    ContractId, ContractIdMismatch, Extension, Genesis, Input, InputError, Inputs, OpFeatures, OpId,
+    OpRef, Operation, PrevalidationId, PrevalidationMismatch, Redeemed, Transition, Valencies,
+};
+pub use graph::HistoryIndex;
+pub use proof::{
+    prove_assignment, verify_assignment_proof, AssignmentProof, AssignmentProofError,
+    SparseAssignments,
+};
+pub use provider::{MemState, StateProviderError, StateReadProvider, StateWriteProvider};
+pub use plan::{AssignmentIndex, AssignmentsPlan, PlanError, RecipientIndexes};
+pub use seal::{
+    ExposedSeal, GenesisSeal, GraphSeal, SealWitness, SecretSeal, TxoSeal, VoutDerivation,
+    VOUT_DERIVED,
+};
+pub use timestamp::{OtsError, OtsProof};
+pub use transfer::{
+    blank_transitions, BeneficiarySeal, BlankBatch, DeterministicRng, Transfer, TransferError,
+    TransferMismatch, TRANSFER_RNG_TAG,
 };
-pub use seal::{ExposedSeal, GenesisSeal, GraphSeal, SealWitness, SecretSeal, TxoSeal};
 pub use state::{ConfidentialState, ExposedState, StateCommitment, StateData, StateType};
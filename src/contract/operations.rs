@@ -21,22 +21,24 @@
 // limitations under the License.
 
 use std::cmp::Ordering;
-use std::collections::{btree_map, btree_set};
-use std::iter;
+use std::collections::{btree_map, btree_set, BTreeMap, BTreeSet};
+use std::{io, iter};
 use std::str::FromStr;
 
-use amplify::confinement::{SmallBlob, TinyOrdMap, TinyOrdSet};
+use amplify::confinement::{self, SmallBlob, TinyOrdMap, TinyOrdSet};
 use amplify::hex::{FromHex, ToHex};
 use amplify::{hex, Bytes32, RawArray, Wrapper};
 use baid58::{Baid58ParseError, FromBaid58, ToBaid58};
 use bp::Chain;
-use commit_verify::{mpc, CommitmentId, Conceal};
+use commit_verify::{mpc, CommitEncode, CommitmentId, Conceal, DigestExt, Sha256};
 use strict_encoding::{StrictDeserialize, StrictEncode, StrictSerialize};
 
+use super::bech32::{self, Bech32Error, HRP_CONTRACT, HRP_OPERATION};
+use crate::commit::{commit_encoded, CommitDump};
 use crate::schema::{self, ExtensionType, OpFullType, OpType, SchemaId, TransitionType};
 use crate::{
-    AssignmentType, Assignments, AssignmentsRef, Ffv, GenesisSeal, GlobalState, GraphSeal, Opout,
-    ReservedByte, TypedAssigns, LIB_NAME_RGB,
+    AssignmentType, Assignments, AssignmentsRef, ConcealSeals, Ffv, GenesisSeal, GlobalState,
+    GraphSeal, Opout, OtsError, OtsProof, ReservedByte, TypedAssigns, LIB_NAME_RGB,
 };
 
 #[derive(Wrapper, WrapperMut, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default, From)]
@@ -60,6 +62,26 @@ impl<'a> IntoIterator for &'a Valencies {
     fn into_iter(self) -> Self::IntoIter { self.0.iter().copied() }
 }
 
+impl TryFrom<BTreeSet<schema::ValencyType>> for Valencies {
+    type Error = confinement::Error;
+
+    fn try_from(set: BTreeSet<schema::ValencyType>) -> Result<Self, Self::Error> {
+        TinyOrdSet::try_from(set).map(Valencies)
+    }
+}
+
+impl TryFrom<Vec<schema::ValencyType>> for Valencies {
+    type Error = confinement::Error;
+
+    fn try_from(vec: Vec<schema::ValencyType>) -> Result<Self, Self::Error> {
+        TinyOrdSet::try_from_iter(vec).map(Valencies)
+    }
+}
+
+impl From<Valencies> for BTreeSet<schema::ValencyType> {
+    fn from(valencies: Valencies) -> Self { valencies.0.into_inner() }
+}
+
 #[derive(Wrapper, WrapperMut, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default, From)]
 #[wrapper(Deref)]
 #[wrapper_mut(DerefMut)]
@@ -81,6 +103,28 @@ impl<'a> IntoIterator for &'a Redeemed {
     fn into_iter(self) -> Self::IntoIter { self.0.iter() }
 }
 
+impl TryFrom<BTreeMap<schema::ValencyType, OpId>> for Redeemed {
+    type Error = confinement::Error;
+
+    fn try_from(map: BTreeMap<schema::ValencyType, OpId>) -> Result<Self, Self::Error> {
+        TinyOrdMap::try_from(map).map(Redeemed)
+    }
+}
+
+/// If the same valency is present several times, the last operation id is
+/// used.
+impl TryFrom<Vec<(schema::ValencyType, OpId)>> for Redeemed {
+    type Error = confinement::Error;
+
+    fn try_from(vec: Vec<(schema::ValencyType, OpId)>) -> Result<Self, Self::Error> {
+        TinyOrdMap::try_from_iter(vec).map(Redeemed)
+    }
+}
+
+impl From<Redeemed> for BTreeMap<schema::ValencyType, OpId> {
+    fn from(redeemed: Redeemed) -> Self { redeemed.0.into_inner() }
+}
+
 #[derive(Wrapper, WrapperMut, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default, From)]
 #[wrapper(Deref)]
 #[wrapper_mut(DerefMut)]
@@ -102,6 +146,82 @@ impl<'a> IntoIterator for &'a Inputs {
     fn into_iter(self) -> Self::IntoIter { self.0.iter().copied() }
 }
 
+impl TryFrom<BTreeSet<Input>> for Inputs {
+    type Error = confinement::Error;
+
+    fn try_from(set: BTreeSet<Input>) -> Result<Self, Self::Error> {
+        TinyOrdSet::try_from(set).map(Inputs)
+    }
+}
+
+impl TryFrom<Vec<Input>> for Inputs {
+    type Error = confinement::Error;
+
+    fn try_from(vec: Vec<Input>) -> Result<Self, Self::Error> {
+        TinyOrdSet::try_from_iter(vec).map(Inputs)
+    }
+}
+
+impl From<Inputs> for BTreeSet<Input> {
+    fn from(inputs: Inputs) -> Self { inputs.0.into_inner() }
+}
+
+/// Errors detected by [`Inputs::validate_against`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum InputError {
+    /// input {0} references operation which is not among the parent operations.
+    UnknownParent(Opout),
+
+    /// input {0} references assignment type which is absent in the parent
+    /// operation.
+    NoAssignmentType(Opout),
+
+    /// input {0} references assignment beyond the {1} assignments of the same
+    /// type defined by the parent operation.
+    OutOfBounds(Opout, u16),
+
+    /// output {0} is spent more than once.
+    Duplicate(Opout),
+}
+
+impl Inputs {
+    /// Checks that each of the inputs references existing assignment of one of
+    /// the `parents` operations: the parent must be present, it must define
+    /// assignments of the referenced type, and the assignment number must be
+    /// within the number of these assignments. Each previous output must be
+    /// referenced only once.
+    ///
+    /// The check is purely structural: it doesn't verify seals, state or
+    /// schema rules.
+    pub fn validate_against<'op>(
+        &self,
+        parents: impl IntoIterator<Item = OpRef<'op>>,
+    ) -> Result<(), InputError> {
+        let parents = parents
+            .into_iter()
+            .map(|op| (op.id(), op))
+            .collect::<BTreeMap<_, _>>();
+        let mut spent = BTreeSet::new();
+        for input in self {
+            let prev_out = input.prev_out;
+            if !spent.insert(prev_out) {
+                return Err(InputError::Duplicate(prev_out));
+            }
+            let parent = parents
+                .get(&prev_out.op)
+                .ok_or(InputError::UnknownParent(prev_out))?;
+            let assigns = parent
+                .assignments_by_type(prev_out.ty)
+                .ok_or(InputError::NoAssignmentType(prev_out))?;
+            if prev_out.no >= assigns.len_u16() {
+                return Err(InputError::OutOfBounds(prev_out, assigns.len_u16()));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB)]
@@ -152,9 +272,64 @@ impl OpId {
     pub fn from_slice(slice: impl AsRef<[u8]>) -> Option<Self> {
         Bytes32::from_slice(slice).map(Self)
     }
+
+    /// Returns bech32m representation of the operation id with
+    /// [`HRP_OPERATION`] human-readable part.
+    pub fn to_bech32m_string(&self) -> String {
+        bech32::encode(HRP_OPERATION, self.to_raw_array())
+    }
+
+    /// Parses bech32m representation of the operation id with
+    /// [`HRP_OPERATION`] human-readable part.
+    pub fn from_bech32m_str(s: &str) -> Result<Self, Bech32Error> {
+        bech32::decode(HRP_OPERATION, s).map(Self::from_raw_array)
+    }
+}
+
+/// Identifier of a state transition which doesn't commit to the seals of its
+/// assignments, allowing parties coordinating a transfer to agree on the
+/// transition content before the witness transaction is constructed.
+///
+/// The id commits to the same data as [`OpId`], except that the assignments
+/// are represented only by their state.
+#[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, From)]
+#[wrapper(Deref, BorrowSlice, Hex, Index, RangeOps)]
+#[display(Self::to_hex)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
+pub struct PrevalidationId(
+    #[from]
+    #[from([u8; 32])]
+    Bytes32,
+);
+
+impl PrevalidationId {
+    pub const TAG: [u8; 32] = *b"urn:lnpbp:rgb:prevalidation#2024";
+}
+
+impl FromStr for PrevalidationId {
+    type Err = hex::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::from_hex(s) }
+}
+
+/// Error checking anchored transition against the previously agreed
+/// [`PrevalidationId`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display("transition has pre-validation id {actual}, which doesn't match the agreed id {agreed}")]
+pub struct PrevalidationMismatch {
+    pub agreed: PrevalidationId,
+    pub actual: PrevalidationId,
 }
 
-/// Unique contract identifier equivalent to the contract genesis commitment
+/// Unique contract identifier equivalent to the contract genesis commitment.
+///
+/// Displayed in Baid58 by default; hex representation is available with
+/// `{:x}` formatting and bech32m with [`ContractId::to_bech32m_string`].
 #[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, From)]
 #[wrapper(Deref, BorrowSlice, Hex, Index, RangeOps)]
 #[display(Self::to_baid58_string)]
@@ -178,10 +353,58 @@ impl PartialEq<ContractId> for OpId {
     fn eq(&self, other: &ContractId) -> bool { self.to_raw_array() == other.to_raw_array() }
 }
 
+/// Error checking contract id against the contract genesis.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display("genesis defines contract {actual}, which doesn't match the claimed contract {claimed}")]
+pub struct ContractIdMismatch {
+    pub claimed: ContractId,
+    pub actual: ContractId,
+}
+
 impl ContractId {
     pub fn from_slice(slice: impl AsRef<[u8]>) -> Option<Self> {
         Bytes32::from_slice(slice).map(Self)
     }
+
+    /// Derives contract id from the contract genesis. The id is the tagged
+    /// hash of the genesis commitment, which includes the chain the contract
+    /// is issued on, so the same genesis produces different ids on different
+    /// chains.
+    ///
+    /// Test vectors: genesis with strict encoding given by
+    /// [`crate::test_vectors::GENESIS_HEX`] has id
+    /// [`crate::test_vectors::CONTRACT_ID`].
+    pub fn from_genesis(genesis: &Genesis) -> Self { genesis.contract_id() }
+
+    /// Checks that the genesis defines the contract with this id, allowing
+    /// light clients to verify contract ids received from untrusted sources.
+    pub fn check_genesis(&self, genesis: &Genesis) -> Result<(), ContractIdMismatch> {
+        let actual = ContractId::from_genesis(genesis);
+        if actual != *self {
+            return Err(ContractIdMismatch {
+                claimed: *self,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns LNPBP-4 multi-protocol commitment protocol id under which all
+    /// the contract state transition bundles are anchored.
+    #[inline]
+    pub fn to_protocol_id(&self) -> mpc::ProtocolId { mpc::ProtocolId::from(*self) }
+
+    /// Returns bech32m representation of the contract id with
+    /// [`HRP_CONTRACT`] human-readable part.
+    pub fn to_bech32m_string(&self) -> String {
+        bech32::encode(HRP_CONTRACT, self.to_raw_array())
+    }
+
+    /// Parses bech32m representation of the contract id with
+    /// [`HRP_CONTRACT`] human-readable part.
+    pub fn from_bech32m_str(s: &str) -> Result<Self, Bech32Error> {
+        bech32::decode(HRP_CONTRACT, s).map(Self::from_raw_array)
+    }
 }
 
 impl ToBaid58<32> for ContractId {
@@ -207,6 +430,43 @@ impl From<ContractId> for mpc::ProtocolId {
     fn from(id: ContractId) -> Self { mpc::ProtocolId::from_inner(id.into_inner()) }
 }
 
+/// Feature bits of a contract operation, reserved for the future upgrades of
+/// the protocol. Operations created by this library version have no features
+/// set; validators report operations with unknown feature bits according to
+/// their validation profile (see [`crate::validation::ValidationProfile`]).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default, Display)]
+#[display("{0:#06x}")]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[derive(CommitEncode)]
+#[commit_encode(strategy = strict)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", transparent)
+)]
+pub struct OpFeatures(u16);
+
+impl OpFeatures {
+    /// Operation without any features.
+    pub const NONE: OpFeatures = OpFeatures(0);
+
+    /// Feature bits known to this library version.
+    pub const KNOWN: OpFeatures = OpFeatures::NONE;
+
+    /// Constructs features from raw bits.
+    pub const fn from_bits(bits: u16) -> Self { OpFeatures(bits) }
+
+    /// Returns raw feature bits.
+    pub const fn bits(self) -> u16 { self.0 }
+
+    /// Checks whether no feature bits are set.
+    pub const fn is_empty(self) -> bool { self.0 == 0 }
+
+    /// Returns feature bits which are unknown to this library version.
+    pub const fn unknown(self) -> OpFeatures { OpFeatures(self.0 & !OpFeatures::KNOWN.0) }
+}
+
 /// RGB contract operation API, defined as trait
 ///
 /// Implemented by all contract operation types (see [`OpType`]):
@@ -227,6 +487,11 @@ pub trait Operation {
     /// serialization
     fn id(&self) -> OpId;
 
+    /// Returns id of the operation with empty metadata, which is used as a
+    /// message for signatures placed into the operation metadata (for
+    /// instance, to spend key rights, see [`crate::Schema::key_rights`]).
+    fn signing_id(&self) -> OpId;
+
     /// Returns [`ContractId`] this operation belongs to.
     fn contract_id(&self) -> ContractId;
 
@@ -238,6 +503,9 @@ pub trait Operation {
     /// [`Option::None`] for genesis and state transitions
     fn extension_type(&self) -> Option<ExtensionType>;
 
+    /// Returns feature bits of the operation (see [`OpFeatures`]).
+    fn features(&self) -> OpFeatures;
+
     /// Returns metadata associated with the operation, if any.
     fn metadata(&self) -> &SmallBlob;
 
@@ -254,12 +522,23 @@ pub trait Operation {
     /// While public state extension do have parent nodes, they do not contain
     /// indexed rights.
     fn inputs(&self) -> Inputs;
+
+    /// Downcasts the operation to [`Genesis`], returning `None` for other
+    /// operation types.
+    fn as_genesis(&self) -> Option<&Genesis> { None }
+
+    /// Downcasts the operation to [`Transition`], returning `None` for other
+    /// operation types.
+    fn as_transition(&self) -> Option<&Transition> { None }
+
+    /// Downcasts the operation to [`Extension`], returning `None` for other
+    /// operation types.
+    fn as_extension(&self) -> Option<&Extension> { None }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB)]
-#[derive(CommitEncode)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -267,6 +546,7 @@ pub trait Operation {
 )]
 pub struct Genesis {
     pub ffv: Ffv,
+    pub features: OpFeatures,
     pub schema_id: SchemaId,
     pub chain: Chain,
     pub metadata: SmallBlob,
@@ -281,7 +561,6 @@ impl StrictDeserialize for Genesis {}
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB)]
-#[derive(CommitEncode)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -289,6 +568,7 @@ impl StrictDeserialize for Genesis {}
 )]
 pub struct Extension {
     pub ffv: Ffv,
+    pub features: OpFeatures,
     pub contract_id: ContractId,
     pub extension_type: ExtensionType,
     pub metadata: SmallBlob,
@@ -304,7 +584,6 @@ impl StrictDeserialize for Extension {}
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB)]
-#[derive(CommitEncode)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -312,6 +591,7 @@ impl StrictDeserialize for Extension {}
 )]
 pub struct Transition {
     pub ffv: Ffv,
+    pub features: OpFeatures,
     pub contract_id: ContractId,
     pub transition_type: TransitionType,
     pub metadata: SmallBlob,
@@ -345,6 +625,12 @@ impl Conceal for Genesis {
     }
 }
 
+impl ConcealSeals<GenesisSeal> for Genesis {
+    fn conceal_all_except(&mut self, seals: &[GenesisSeal]) -> usize {
+        self.assignments.conceal_all_except(seals)
+    }
+}
+
 impl CommitmentId for Genesis {
     const TAG: [u8; 32] = *b"urn:lnpbp:rgb:genesis:v02#202304";
     type Id = ContractId;
@@ -362,6 +648,12 @@ impl Conceal for Transition {
     }
 }
 
+impl ConcealSeals<GraphSeal> for Transition {
+    fn conceal_all_except(&mut self, seals: &[GraphSeal]) -> usize {
+        self.assignments.conceal_all_except(seals)
+    }
+}
+
 impl CommitmentId for Transition {
     const TAG: [u8; 32] = *b"urn:lnpbp:rgb:transition:v02#23B";
     type Id = OpId;
@@ -379,19 +671,144 @@ impl Conceal for Extension {
     }
 }
 
+impl ConcealSeals<GenesisSeal> for Extension {
+    fn conceal_all_except(&mut self, seals: &[GenesisSeal]) -> usize {
+        self.assignments.conceal_all_except(seals)
+    }
+}
+
 impl CommitmentId for Extension {
     const TAG: [u8; 32] = *b"urn:lnpbp:rgb:extension:v02#2304";
     type Id = OpId;
 }
 
+// Operations commit to the fields in the order reported by `CommitDump`, which
+// depends on the fast-forward version
+impl CommitEncode for Genesis {
+    fn commit_encode(&self, e: &mut impl io::Write) {
+        for (_, data) in self.commit_fields() {
+            e.write_all(&data).ok();
+        }
+    }
+}
+
+impl CommitDump for Genesis {
+    fn commit_fields(&self) -> Vec<(String, Vec<u8>)> {
+        let mut fields = vec![
+            ("ffv".into(), commit_encoded(&self.ffv)),
+            ("features".into(), commit_encoded(&self.features)),
+            ("schema_id".into(), commit_encoded(&*self.schema_id)),
+            ("chain".into(), commit_encoded(&self.chain)),
+            ("metadata".into(), commit_encoded(&self.metadata)),
+            ("globals".into(), commit_encoded(&self.globals)),
+        ];
+        fields.extend(self.assignments.commit_fields(self.ffv));
+        fields.push(("valencies".into(), commit_encoded(&self.valencies)));
+        fields
+    }
+}
+
+impl CommitEncode for Transition {
+    fn commit_encode(&self, e: &mut impl io::Write) {
+        for (_, data) in self.commit_fields() {
+            e.write_all(&data).ok();
+        }
+    }
+}
+
+impl CommitDump for Transition {
+    fn commit_fields(&self) -> Vec<(String, Vec<u8>)> {
+        let mut fields = vec![
+            ("ffv".into(), commit_encoded(&self.ffv)),
+            ("features".into(), commit_encoded(&self.features)),
+            ("contract_id".into(), commit_encoded(&*self.contract_id)),
+            ("transition_type".into(), commit_encoded(&self.transition_type)),
+            ("metadata".into(), commit_encoded(&self.metadata)),
+            ("globals".into(), commit_encoded(&self.globals)),
+            ("inputs".into(), commit_encoded(&self.inputs)),
+        ];
+        fields.extend(self.assignments.commit_fields(self.ffv));
+        fields.push(("valencies".into(), commit_encoded(&self.valencies)));
+        fields
+    }
+}
+
+impl CommitEncode for Extension {
+    fn commit_encode(&self, e: &mut impl io::Write) {
+        for (_, data) in self.commit_fields() {
+            e.write_all(&data).ok();
+        }
+    }
+}
+
+impl CommitDump for Extension {
+    fn commit_fields(&self) -> Vec<(String, Vec<u8>)> {
+        let mut fields = vec![
+            ("ffv".into(), commit_encoded(&self.ffv)),
+            ("features".into(), commit_encoded(&self.features)),
+            ("contract_id".into(), commit_encoded(&*self.contract_id)),
+            ("extension_type".into(), commit_encoded(&self.extension_type)),
+            ("metadata".into(), commit_encoded(&self.metadata)),
+            ("globals".into(), commit_encoded(&self.globals)),
+        ];
+        fields.extend(self.assignments.commit_fields(self.ffv));
+        fields.push(("redeemed".into(), commit_encoded(&self.redeemed)));
+        fields.push(("valencies".into(), commit_encoded(&self.valencies)));
+        fields
+    }
+}
+
 impl Transition {
     /// Returns reference to information about the owned rights in form of
     /// [`Inputs`] wrapper structure which this operation updates with
     /// state transition ("parent owned rights").
     pub fn prev_state(&self) -> &Inputs { &self.inputs }
+
+    /// Computes [`PrevalidationId`] of the transition, which doesn't depend on
+    /// the seals of the assignments.
+    pub fn prevalidation_id(&self) -> PrevalidationId {
+        let mut engine = Sha256::from_tag(PrevalidationId::TAG);
+        self.ffv.commit_encode(&mut engine);
+        self.features.commit_encode(&mut engine);
+        self.contract_id.commit_encode(&mut engine);
+        self.transition_type.commit_encode(&mut engine);
+        self.metadata.commit_encode(&mut engine);
+        self.globals.commit_encode(&mut engine);
+        self.inputs.commit_encode(&mut engine);
+        self.assignments.commit_states(&mut engine);
+        self.valencies.commit_encode(&mut engine);
+        engine.finish().into()
+    }
+
+    /// Checks that the transition, after its seals were defined and it was
+    /// anchored, matches the content agreed with the [`PrevalidationId`].
+    pub fn check_prevalidation(
+        &self,
+        agreed: PrevalidationId,
+    ) -> Result<(), PrevalidationMismatch> {
+        let actual = self.prevalidation_id();
+        if actual != agreed {
+            return Err(PrevalidationMismatch { agreed, actual });
+        }
+        Ok(())
+    }
+}
+
+impl Genesis {
+    /// Extracts timestamp proof from the genesis metadata. Must be used only
+    /// if the genesis schema is timestamped.
+    pub fn timestamp_proof(&self) -> Result<OtsProof, OtsError> {
+        OtsProof::extract(&self.metadata).map(|(proof, _)| proof)
+    }
 }
 
 impl Extension {
+    /// Extracts timestamp proof from the extension metadata. Must be used
+    /// only if the extension schema is timestamped.
+    pub fn timestamp_proof(&self) -> Result<OtsProof, OtsError> {
+        OtsProof::extract(&self.metadata).map(|(proof, _)| proof)
+    }
+
     /// Returns reference to information about the public rights (in form of
     /// [`Redeemed`] wrapper structure), defined with "parent" state
     /// extensions (i.e. those finalized with the current state transition) or
@@ -410,6 +827,12 @@ impl Operation for Genesis {
     #[inline]
     fn id(&self) -> OpId { OpId(self.commitment_id().into_inner()) }
 
+    fn signing_id(&self) -> OpId {
+        let mut op = self.clone();
+        op.metadata = none!();
+        op.id()
+    }
+
     #[inline]
     fn contract_id(&self) -> ContractId { ContractId::from_inner(self.id().into_inner()) }
 
@@ -419,6 +842,9 @@ impl Operation for Genesis {
     #[inline]
     fn extension_type(&self) -> Option<ExtensionType> { None }
 
+    #[inline]
+    fn features(&self) -> OpFeatures { self.features }
+
     #[inline]
     fn metadata(&self) -> &SmallBlob { &self.metadata }
 
@@ -440,6 +866,9 @@ impl Operation for Genesis {
 
     #[inline]
     fn inputs(&self) -> Inputs { empty!() }
+
+    #[inline]
+    fn as_genesis(&self) -> Option<&Genesis> { Some(self) }
 }
 
 impl Operation for Extension {
@@ -452,6 +881,12 @@ impl Operation for Extension {
     #[inline]
     fn id(&self) -> OpId { self.commitment_id() }
 
+    fn signing_id(&self) -> OpId {
+        let mut op = self.clone();
+        op.metadata = none!();
+        op.id()
+    }
+
     #[inline]
     fn contract_id(&self) -> ContractId { self.contract_id }
 
@@ -461,6 +896,9 @@ impl Operation for Extension {
     #[inline]
     fn extension_type(&self) -> Option<ExtensionType> { Some(self.extension_type) }
 
+    #[inline]
+    fn features(&self) -> OpFeatures { self.features }
+
     #[inline]
     fn metadata(&self) -> &SmallBlob { &self.metadata }
 
@@ -482,6 +920,9 @@ impl Operation for Extension {
 
     #[inline]
     fn inputs(&self) -> Inputs { empty!() }
+
+    #[inline]
+    fn as_extension(&self) -> Option<&Extension> { Some(self) }
 }
 
 impl Operation for Transition {
@@ -494,6 +935,12 @@ impl Operation for Transition {
     #[inline]
     fn id(&self) -> OpId { self.commitment_id() }
 
+    fn signing_id(&self) -> OpId {
+        let mut op = self.clone();
+        op.metadata = none!();
+        op.id()
+    }
+
     #[inline]
     fn contract_id(&self) -> ContractId { self.contract_id }
 
@@ -503,6 +950,9 @@ impl Operation for Transition {
     #[inline]
     fn extension_type(&self) -> Option<ExtensionType> { None }
 
+    #[inline]
+    fn features(&self) -> OpFeatures { self.features }
+
     #[inline]
     fn metadata(&self) -> &SmallBlob { &self.metadata }
 
@@ -521,6 +971,9 @@ impl Operation for Transition {
     }
 
     fn inputs(&self) -> Inputs { self.inputs.clone() }
+
+    #[inline]
+    fn as_transition(&self) -> Option<&Transition> { Some(self) }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, From)]
@@ -558,6 +1011,14 @@ impl<'op> Operation for OpRef<'op> {
         }
     }
 
+    fn signing_id(&self) -> OpId {
+        match self {
+            OpRef::Genesis(op) => op.signing_id(),
+            OpRef::Transition(op) => op.signing_id(),
+            OpRef::Extension(op) => op.signing_id(),
+        }
+    }
+
     fn contract_id(&self) -> ContractId {
         match self {
             OpRef::Genesis(op) => op.contract_id(),
@@ -582,6 +1043,14 @@ impl<'op> Operation for OpRef<'op> {
         }
     }
 
+    fn features(&self) -> OpFeatures {
+        match self {
+            OpRef::Genesis(op) => op.features(),
+            OpRef::Transition(op) => op.features(),
+            OpRef::Extension(op) => op.features(),
+        }
+    }
+
     fn metadata(&self) -> &SmallBlob {
         match self {
             OpRef::Genesis(op) => op.metadata(),
@@ -629,4 +1098,130 @@ impl<'op> Operation for OpRef<'op> {
             OpRef::Extension(op) => op.inputs(),
         }
     }
+
+    fn as_genesis(&self) -> Option<&Genesis> {
+        match self {
+            OpRef::Genesis(op) => Some(op),
+            OpRef::Transition(_) | OpRef::Extension(_) => None,
+        }
+    }
+
+    fn as_transition(&self) -> Option<&Transition> {
+        match self {
+            OpRef::Transition(op) => Some(op),
+            OpRef::Genesis(_) | OpRef::Extension(_) => None,
+        }
+    }
+
+    fn as_extension(&self) -> Option<&Extension> {
+        match self {
+            OpRef::Extension(op) => Some(op),
+            OpRef::Genesis(_) | OpRef::Transition(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::Assign;
+
+    #[test]
+    fn plain_conversions() {
+        let valencies = Valencies::try_from(vec![2u16, 1, 2]).unwrap();
+        assert_eq!(valencies.len(), 2);
+        assert_eq!(BTreeSet::from(valencies.clone()), bset! { 1, 2 });
+        assert_eq!(Valencies::try_from(bset! { 1u16, 2 }), Ok(valencies));
+        assert!(Valencies::try_from((0..=u8::MAX as u16).collect::<Vec<_>>()).is_err());
+
+        let opid = OpId::from([1u8; 32]);
+        let redeemed = Redeemed::try_from(vec![(1u16, opid)]).unwrap();
+        assert_eq!(BTreeMap::from(redeemed.clone()), bmap! { 1 => opid });
+        assert_eq!(Redeemed::try_from(bmap! { 1u16 => opid }), Ok(redeemed));
+
+        let input = Input::with(Opout::new(opid, 1, 0));
+        let inputs = Inputs::try_from(vec![input]).unwrap();
+        assert_eq!(BTreeSet::from(inputs.clone()), bset! { input });
+        assert_eq!(Inputs::try_from(bset! { input }), Ok(inputs));
+
+        let assignments = Assignments::<GraphSeal>::try_from(vec![(
+            1u16,
+            TypedAssigns::Declarative(none!()),
+        )])
+        .unwrap();
+        assert_eq!(
+            Assignments::try_from(BTreeMap::from(assignments.clone())),
+            Ok(assignments)
+        );
+    }
+
+    #[test]
+    fn validate_inputs() {
+        let seal = GraphSeal::with_vout(CloseMethod::OpretFirst, 0, 1);
+        let mut parent = Transition::strict_dumb();
+        parent.assignments = Assignments::from(tiny_bmap! {
+            1 => TypedAssigns::Declarative(small_vec![Assign::right(seal), Assign::right(seal)]),
+        });
+        let opid = parent.id();
+        let inputs = |prev_outs: Vec<Opout>| {
+            Inputs::try_from(prev_outs.into_iter().map(Input::with).collect::<Vec<_>>()).unwrap()
+        };
+
+        let valid = inputs(vec![Opout::new(opid, 1, 0), Opout::new(opid, 1, 1)]);
+        assert_eq!(valid.validate_against([OpRef::from(&parent)]), Ok(()));
+        assert_eq!(Inputs::default().validate_against([]), Ok(()));
+
+        let prev_out = Opout::new(opid, 1, 0);
+        assert_eq!(
+            inputs(vec![prev_out]).validate_against([]),
+            Err(InputError::UnknownParent(prev_out))
+        );
+
+        let prev_out = Opout::new(opid, 2, 0);
+        assert_eq!(
+            inputs(vec![prev_out]).validate_against([OpRef::from(&parent)]),
+            Err(InputError::NoAssignmentType(prev_out))
+        );
+
+        let prev_out = Opout::new(opid, 1, 2);
+        assert_eq!(
+            inputs(vec![prev_out]).validate_against([OpRef::from(&parent)]),
+            Err(InputError::OutOfBounds(prev_out, 2))
+        );
+    }
+
+    #[test]
+    fn conceal_transition() {
+        let kept = GraphSeal::with_vout(CloseMethod::OpretFirst, 0, 1);
+        let other = GraphSeal::with_vout(CloseMethod::OpretFirst, 1, 2);
+        let mut transition = Transition::strict_dumb();
+        transition.assignments = Assignments::from(tiny_bmap! {
+            1 => TypedAssigns::Declarative(small_vec![Assign::right(kept), Assign::right(other)]),
+            2 => TypedAssigns::Declarative(small_vec![Assign::right(other)]),
+        });
+        let opid = transition.id();
+
+        assert_eq!(transition.conceal_all_except(&[kept]), 2);
+        assert_eq!(transition.id(), opid);
+        let assigns = transition.assignments.get(&1).unwrap();
+        assert_eq!(assigns.revealed_seal_at(0), Ok(Some(kept)));
+        assert_eq!(assigns.revealed_seal_at(1), Ok(None));
+        assert_eq!(transition.assignments.get(&2).unwrap().revealed_seal_at(0), Ok(None));
+
+        assert_eq!(transition.conceal_all_except(&[]), 1);
+        assert_eq!(transition.conceal_all_except(&[]), 0);
+        assert_eq!(transition.id(), opid);
+    }
+
+    #[test]
+    fn protocol_id() {
+        let contract_id = ContractId::from([0xA5u8; 32]);
+        let protocol_id = contract_id.to_protocol_id();
+        assert_eq!(protocol_id.to_raw_array(), contract_id.to_raw_array());
+        assert_eq!(protocol_id, mpc::ProtocolId::from(contract_id));
+        assert_ne!(protocol_id, ContractId::from([0x5Au8; 32]).to_protocol_id());
+    }
 }
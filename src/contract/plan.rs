@@ -0,0 +1,224 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Planning of the operation assignments with many recipients.
+//!
+//! Recipients learn about the state assigned to them by the index of the
+//! assignment within the operation. [`AssignmentsPlan`] keeps track of which
+//! recipient receives each of the assignments and puts the assignments in the
+//! canonical order (by their concealed seals, see [`crate::Assign`]), such
+//! that the resulting indexes don't depend on the order in which the
+//! recipients were added.
+
+use std::collections::BTreeMap;
+
+use amplify::confinement::{self, Confined};
+
+use crate::{
+    AssignAttach, AssignData, AssignFungible, AssignRights, AssignmentType, Assignments,
+    ExposedSeal, GraphSeal, StateType, TypedAssigns,
+};
+
+/// Errors planning operation assignments.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PlanError {
+    /// assignment type {ty} is used both for {expected} and {found} state.
+    StateTypeMismatch {
+        ty: AssignmentType,
+        expected: StateType,
+        found: StateType,
+    },
+
+    /// number of assignments exceeds the limits of a single operation.
+    #[from(confinement::Error)]
+    TooLarge,
+}
+
+/// Index of an assignment within the operation.
+pub type AssignmentIndex = (AssignmentType, u16);
+
+/// Indexes of the assignments received by each of the recipients.
+pub type RecipientIndexes<Recipient> = BTreeMap<Recipient, Vec<AssignmentIndex>>;
+
+/// Builder of the operation assignments, tracking the recipient of each of the
+/// assignments.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AssignmentsPlan<Recipient: Ord, Seal: ExposedSeal = GraphSeal> {
+    // Each of the typed assignments contains a single assignment
+    entries: BTreeMap<AssignmentType, Vec<(Recipient, TypedAssigns<Seal>)>>,
+}
+
+impl<Recipient: Ord, Seal: ExposedSeal> Default for AssignmentsPlan<Recipient, Seal> {
+    fn default() -> Self { AssignmentsPlan { entries: empty!() } }
+}
+
+impl<Recipient: Ord + Clone, Seal: ExposedSeal> AssignmentsPlan<Recipient, Seal> {
+    pub fn new() -> Self { Self::default() }
+
+    /// Number of the planned assignments.
+    pub fn len(&self) -> usize { self.entries.values().map(Vec::len).sum() }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn assign_right(
+        &mut self,
+        recipient: Recipient,
+        ty: AssignmentType,
+        assign: AssignRights<Seal>,
+    ) -> Result<(), PlanError> {
+        self.push(recipient, ty, TypedAssigns::Declarative(small_vec![assign]))
+    }
+
+    pub fn assign_fungible(
+        &mut self,
+        recipient: Recipient,
+        ty: AssignmentType,
+        assign: AssignFungible<Seal>,
+    ) -> Result<(), PlanError> {
+        self.push(recipient, ty, TypedAssigns::Fungible(small_vec![assign]))
+    }
+
+    pub fn assign_data(
+        &mut self,
+        recipient: Recipient,
+        ty: AssignmentType,
+        assign: AssignData<Seal>,
+    ) -> Result<(), PlanError> {
+        self.push(recipient, ty, TypedAssigns::Structured(small_vec![assign]))
+    }
+
+    pub fn assign_attach(
+        &mut self,
+        recipient: Recipient,
+        ty: AssignmentType,
+        assign: AssignAttach<Seal>,
+    ) -> Result<(), PlanError> {
+        self.push(recipient, ty, TypedAssigns::Attachment(small_vec![assign]))
+    }
+
+    fn push(
+        &mut self,
+        recipient: Recipient,
+        ty: AssignmentType,
+        assign: TypedAssigns<Seal>,
+    ) -> Result<(), PlanError> {
+        let entries = self.entries.entry(ty).or_default();
+        if let Some((_, first)) = entries.first() {
+            if first.state_type() != assign.state_type() {
+                return Err(PlanError::StateTypeMismatch {
+                    ty,
+                    expected: first.state_type(),
+                    found: assign.state_type(),
+                });
+            }
+        }
+        entries.push((recipient, assign));
+        Ok(())
+    }
+
+    /// Sorts assignments in the canonical order and returns them together with
+    /// the indexes of the assignments received by each of the recipients.
+    pub fn commit(self) -> Result<(Assignments<Seal>, RecipientIndexes<Recipient>), PlanError> {
+        let mut assignments = BTreeMap::new();
+        let mut indexes = RecipientIndexes::<Recipient>::new();
+        for (ty, mut entries) in self.entries {
+            // Sort is stable, so assignments to the same seal keep their order
+            entries.sort_by_key(|(_, assign)| assign.to_confidential_seals());
+            let mut iter = entries.into_iter().enumerate();
+            let Some((_, (recipient, mut typed))) = iter.next() else {
+                continue;
+            };
+            indexes.entry(recipient).or_default().push((ty, 0));
+            for (no, (recipient, assign)) in iter {
+                let no = u16::try_from(no).map_err(|_| PlanError::TooLarge)?;
+                match (&mut typed, assign) {
+                    (TypedAssigns::Declarative(vec), TypedAssigns::Declarative(other)) => {
+                        vec.extend(other)?
+                    }
+                    (TypedAssigns::Fungible(vec), TypedAssigns::Fungible(other)) => {
+                        vec.extend(other)?
+                    }
+                    (TypedAssigns::Structured(vec), TypedAssigns::Structured(other)) => {
+                        vec.extend(other)?
+                    }
+                    (TypedAssigns::Attachment(vec), TypedAssigns::Attachment(other)) => {
+                        vec.extend(other)?
+                    }
+                    _ => unreachable!("state types are checked when assignments are added"),
+                }
+                indexes.entry(recipient).or_default().push((ty, no));
+            }
+            assignments.insert(ty, typed);
+        }
+        let assignments = Assignments::from(Confined::try_from(assignments)?);
+        Ok((assignments, indexes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+
+    use super::*;
+    use crate::Assign;
+
+    #[test]
+    fn canonical_order() {
+        let seal = |vout| GraphSeal::with_vout(CloseMethod::TapretFirst, vout, vout as u64);
+        let assign = |vout| Assign::right(seal(vout));
+
+        let mut plan = AssignmentsPlan::new();
+        plan.assign_right("alice", 1, assign(0)).unwrap();
+        plan.assign_right("bob", 1, assign(1)).unwrap();
+        plan.assign_right("carol", 1, assign(2)).unwrap();
+        plan.assign_right("alice", 2, assign(3)).unwrap();
+        assert_eq!(plan.len(), 4);
+
+        let mut reversed = AssignmentsPlan::new();
+        reversed.assign_right("alice", 2, assign(3)).unwrap();
+        reversed.assign_right("carol", 1, assign(2)).unwrap();
+        reversed.assign_right("bob", 1, assign(1)).unwrap();
+        reversed.assign_right("alice", 1, assign(0)).unwrap();
+
+        let (assignments, indexes) = plan.commit().unwrap();
+        assert_eq!(reversed.commit().unwrap(), (assignments.clone(), indexes.clone()));
+
+        for (recipient, vout) in [("alice", 0), ("bob", 1), ("carol", 2)] {
+            let (ty, no) = indexes[recipient][0];
+            assert_eq!(ty, 1);
+            assert_eq!(assignments[&1].revealed_seal_at(no), Ok(Some(seal(vout))));
+        }
+        assert_eq!(indexes["alice"][1], (2, 0));
+
+        let mut plan = AssignmentsPlan::<_, GraphSeal>::new();
+        plan.assign_right("alice", 1, assign(0)).unwrap();
+        assert_eq!(
+            plan.assign_data("bob", 1, Assign::revealed(seal(1), small_vec![1u8].into())),
+            Err(PlanError::StateTypeMismatch {
+                ty: 1,
+                expected: StateType::Void,
+                found: StateType::Structured
+            })
+        );
+    }
+}
@@ -0,0 +1,222 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proofs of inclusion of a single assignment into an operation, allowing
+//! receivers to be convinced that their allocation is a part of the operation
+//! without being given all other assignments.
+//!
+//! Proofs are supported only for the operations committing to the owned state
+//! with sparse merkle trees (see [`Ffv::SPARSE_ASSIGNMENTS`]).
+
+use std::collections::BTreeMap;
+
+use commit_verify::merkle::{MerkleLeaves, MerkleNode};
+use commit_verify::{DigestExt, Sha256};
+
+use super::assignments::sparse_field;
+use crate::commit::{strict_encoded, CommitDump, SparseProof, SparseTree};
+use crate::{AssignmentType, Extension, Ffv, Genesis, Transition};
+
+/// Errors producing [`AssignmentProof`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum AssignmentProofError {
+    /// operation of version {0} doesn't commit to the owned state with sparse
+    /// merkle trees.
+    NotSparse(Ffv),
+
+    /// operation doesn't contain assignment #{1} of type {0}.
+    NoAssignment(AssignmentType, u16),
+}
+
+/// Operations committing to their assignments, which can be proven with
+/// [`AssignmentProof`].
+pub trait SparseAssignments: CommitDump + Clone {
+    /// Fast-forward version of the operation.
+    fn ffv(&self) -> Ffv;
+
+    /// Sparse merkle trees of the assignments of each type.
+    fn sparse_trees(&self) -> BTreeMap<AssignmentType, SparseTree>;
+
+    /// Removes all assignments from the operation.
+    fn strip_assignments(&mut self);
+}
+
+macro_rules! impl_sparse_assignments {
+    ($ty:ty) => {
+        impl SparseAssignments for $ty {
+            fn ffv(&self) -> Ffv { self.ffv }
+
+            fn sparse_trees(&self) -> BTreeMap<AssignmentType, SparseTree> {
+                self.assignments
+                    .iter()
+                    .map(|(ty, assigns)| (*ty, SparseTree::with(assigns.merkle_leaves())))
+                    .collect()
+            }
+
+            fn strip_assignments(&mut self) { self.assignments = none!(); }
+        }
+    };
+}
+
+impl_sparse_assignments!(Genesis);
+impl_sparse_assignments!(Transition);
+impl_sparse_assignments!(Extension);
+
+/// Proof of inclusion of a single assignment into an operation.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AssignmentProof<Op: SparseAssignments> {
+    /// Operation with all assignments removed.
+    pub op: Op,
+    /// Number of the assignments and the root of their sparse merkle tree for
+    /// each of the assignment types.
+    pub roots: BTreeMap<AssignmentType, (u16, MerkleNode)>,
+    /// Type of the proven assignment.
+    pub ty: AssignmentType,
+    /// Commitment to the proven assignment (see
+    /// [`commit_verify::CommitmentId`] implementation for [`crate::Assign`]).
+    pub leaf: MerkleNode,
+    /// Path from the assignment to the root of the sparse merkle tree.
+    pub path: SparseProof,
+}
+
+impl<Op: SparseAssignments> AssignmentProof<Op> {
+    /// Index of the proven assignment.
+    pub fn index(&self) -> u16 { self.path.index }
+}
+
+/// Proves inclusion of the assignment with the given type and index into the
+/// operation.
+pub fn prove_assignment<Op: SparseAssignments>(
+    op: &Op,
+    ty: AssignmentType,
+    index: u16,
+) -> Result<AssignmentProof<Op>, AssignmentProofError> {
+    if !op.ffv().has_sparse_assignments() {
+        return Err(AssignmentProofError::NotSparse(op.ffv()));
+    }
+    let trees = op.sparse_trees();
+    let no_assignment = AssignmentProofError::NoAssignment(ty, index);
+    let tree = trees.get(&ty).ok_or(no_assignment)?;
+    let path = tree.prove(index).ok_or(no_assignment)?;
+    let leaf = tree.leaf(index).ok_or(no_assignment)?;
+    let roots = trees
+        .iter()
+        .map(|(ty, tree)| (*ty, (tree.len() as u16, tree.root())))
+        .collect();
+    let mut op = op.clone();
+    op.strip_assignments();
+    Ok(AssignmentProof {
+        op,
+        roots,
+        ty,
+        leaf,
+        path,
+    })
+}
+
+/// Verifies that the proof commits the assignment to the operation with the
+/// given id.
+pub fn verify_assignment_proof<Op: SparseAssignments>(
+    id: Op::Id,
+    proof: &AssignmentProof<Op>,
+) -> bool
+where
+    Op::Id: Eq,
+{
+    if !proof.op.ffv().has_sparse_assignments() || !proof.op.sparse_trees().is_empty() {
+        return false;
+    }
+    let Some((count, root)) = proof.roots.get(&proof.ty) else {
+        return false;
+    };
+    if proof.index() >= *count || proof.path.root(proof.leaf) != Some(*root) {
+        return false;
+    }
+
+    // Assignments of the stripped operation are committed by a single field,
+    // which is replaced with the commitments to the sparse merkle tree roots
+    let mut engine = Sha256::from_tag(Op::TAG);
+    for (field, data) in proof.op.commit_fields() {
+        if field != "assignments.len" {
+            engine.input_raw(&data);
+            continue;
+        }
+        let Ok(len) = u8::try_from(proof.roots.len()) else {
+            return false;
+        };
+        engine.input_raw(&strict_encoded(&len));
+        for (ty, (count, root)) in &proof.roots {
+            engine.input_raw(&strict_encoded(ty));
+            engine.input_raw(&sparse_field(*count, *root));
+        }
+    }
+    Op::Id::from(engine.finish()) == id
+}
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+    use commit_verify::CommitmentId;
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::{Assign, Assignments, GraphSeal, Operation, TypedAssigns};
+
+    #[test]
+    fn assignment_proof() {
+        let seal = |vout| GraphSeal::with_vout(CloseMethod::TapretFirst, vout, 1);
+        let rights = |vouts: &[u32]| {
+            TypedAssigns::with_rights(vouts.iter().map(|vout| seal(*vout))).unwrap()
+        };
+        let mut transition = Transition::strict_dumb();
+        transition.assignments =
+            Assignments::from(tiny_bmap! { 1 => rights(&[0, 1, 2]), 2 => rights(&[3]) });
+
+        assert_eq!(
+            prove_assignment(&transition, 1, 0),
+            Err(AssignmentProofError::NotSparse(Ffv::default()))
+        );
+        transition.ffv = Ffv::SPARSE_ASSIGNMENTS;
+        let id = transition.id();
+
+        let proof = prove_assignment(&transition, 1, 2).unwrap();
+        assert!(proof.op.assignments.is_empty());
+        assert_eq!(proof.leaf, Assign::right(seal(2)).commitment_id());
+        assert!(verify_assignment_proof(id, &proof));
+        assert!(verify_assignment_proof(id, &prove_assignment(&transition, 2, 0).unwrap()));
+        assert_eq!(
+            prove_assignment(&transition, 2, 1),
+            Err(AssignmentProofError::NoAssignment(2, 1))
+        );
+
+        let mut forged = proof.clone();
+        forged.leaf = Assign::right(seal(5)).commitment_id();
+        assert!(!verify_assignment_proof(id, &forged));
+        let mut forged = proof.clone();
+        forged.ty = 2;
+        assert!(!verify_assignment_proof(id, &forged));
+        let mut forged = proof;
+        forged.op.metadata = small_vec![1u8];
+        assert!(!verify_assignment_proof(id, &forged));
+    }
+}
@@ -0,0 +1,376 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Persistence of the contract state and operations.
+//!
+//! Validation glue and state folding code access the persisted data only via
+//! [`StateReadProvider`] and [`StateWriteProvider`] traits, such that storage
+//! backends (key-value stores, SQL databases or the in-memory [`MemState`])
+//! can be swapped without changes to the code using them.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::error::Error;
+
+use bp::Outpoint;
+
+use crate::validation::{ConsignmentApi, ResolveTx, Status, Validity};
+use crate::{
+    ContractHistory, ContractId, ContractState, Extension, Genesis, OpId, Operation, Opout,
+    OrderedTxid, Schema, SubSchema, Transition,
+};
+
+/// Errors of the contract state folding with [`StateWriteProvider`].
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum StateProviderError<E: Error> {
+    /// state of the contract {0} is unknown to the provider.
+    UnknownContract(ContractId),
+
+    /// consignment must be valid before its data are added to the contract
+    /// state.
+    InvalidConsignment,
+
+    /// storage backend failure. Details: {0}
+    Backend(E),
+}
+
+/// Read access to the persisted contract data.
+pub trait StateReadProvider {
+    type Error: Error;
+
+    /// Reads the state of the contract, if it is known to the provider.
+    fn contract_state(&self, contract_id: ContractId) -> Result<Option<ContractState>, Self::Error>;
+
+    /// Reads genesis of the contract, if it is known to the provider.
+    fn genesis(&self, contract_id: ContractId) -> Result<Option<Genesis>, Self::Error>;
+
+    /// Reads state transition with the given id.
+    fn transition(&self, opid: OpId) -> Result<Option<Transition>, Self::Error>;
+
+    /// Reads state extension with the given id.
+    fn extension(&self, opid: OpId) -> Result<Option<Extension>, Self::Error>;
+
+    /// Returns outputs of all operations which have ever assigned state to the
+    /// given seal. Whether the state is still unspent is defined by the
+    /// contract state.
+    fn seal_opouts(&self, seal: Outpoint) -> Result<BTreeSet<Opout>, Self::Error>;
+
+    /// Detects whether a state transition or extension with the given id is
+    /// known to the provider.
+    fn has_operation(&self, opid: OpId) -> Result<bool, Self::Error> {
+        Ok(self.transition(opid)?.is_some() || self.extension(opid)?.is_some())
+    }
+}
+
+/// Write access to the persisted contract data.
+///
+/// Backends implement only the `put_*` and [`Self::index_seal`] methods;
+/// folding of the operations into the contract state is provided by the
+/// trait.
+pub trait StateWriteProvider: StateReadProvider {
+    /// Saves the state of the contract, replacing the previous one.
+    fn put_contract_state(&mut self, state: ContractState) -> Result<(), Self::Error>;
+
+    /// Saves genesis of the contract.
+    fn put_genesis(&mut self, genesis: Genesis) -> Result<(), Self::Error>;
+
+    /// Saves state transition.
+    fn put_transition(&mut self, transition: Transition) -> Result<(), Self::Error>;
+
+    /// Saves state extension.
+    fn put_extension(&mut self, extension: Extension) -> Result<(), Self::Error>;
+
+    /// Adds operation output to the list of the outputs assigned to the seal.
+    fn index_seal(&mut self, seal: Outpoint, opout: Opout) -> Result<(), Self::Error>;
+
+    /// Starts the contract state from the genesis, replacing the existing
+    /// state of the contract, if any. Genesis must be validated against the
+    /// `schema` beforehand.
+    fn add_genesis(
+        &mut self,
+        schema: SubSchema,
+        genesis: &Genesis,
+    ) -> Result<(), StateProviderError<Self::Error>> {
+        let root_schema_id = schema.subset_of.as_ref().map(Schema::schema_id);
+        let contract_id = genesis.contract_id();
+        let history =
+            ContractHistory::with(genesis.schema_id, root_schema_id, contract_id, genesis);
+        let mut state = ContractState { schema, history };
+        fold(self, &mut state, genesis.id(), None)?;
+        self.put_genesis(genesis.clone())
+            .map_err(StateProviderError::Backend)?;
+        self.put_contract_state(state)
+            .map_err(StateProviderError::Backend)
+    }
+
+    /// Adds validated state transition mined at the `ord_txid` position to the
+    /// state of its contract.
+    fn add_transition(
+        &mut self,
+        transition: &Transition,
+        ord_txid: OrderedTxid,
+    ) -> Result<(), StateProviderError<Self::Error>> {
+        let mut state = load(self, transition.contract_id)?;
+        state.add_transition(transition, ord_txid);
+        fold(self, &mut state, transition.id(), Some(transition))?;
+        self.put_contract_state(state)
+            .map_err(StateProviderError::Backend)
+    }
+
+    /// Adds validated state extension, ordered at the `ord_txid` position, to
+    /// the state of its contract.
+    fn add_extension(
+        &mut self,
+        extension: &Extension,
+        ord_txid: OrderedTxid,
+    ) -> Result<(), StateProviderError<Self::Error>> {
+        let mut state = load(self, extension.contract_id)?;
+        state.add_extension(extension, ord_txid);
+        self.put_extension(extension.clone())
+            .map_err(StateProviderError::Backend)?;
+        fold(self, &mut state, extension.id(), None)?;
+        self.put_contract_state(state)
+            .map_err(StateProviderError::Backend)
+    }
+
+    /// Adds state transitions of a consignment, which was validated with the
+    /// provided `status`, to the contract state. The contract is started from
+    /// the consignment genesis if its state is unknown to the provider.
+    ///
+    /// Transitions are folded in the order of the heights of their witness
+    /// transactions, retrieved from the `resolver`; transitions with witnesses
+    /// which are not mined yet are skipped and their witness ids are returned.
+    /// State extensions are saved, but not folded into the state, since their
+    /// position in the contract history is defined by the caller (see
+    /// [`Self::add_extension`]).
+    fn import_consignment(
+        &mut self,
+        consignment: &impl ConsignmentApi,
+        status: &Status,
+        resolver: &impl ResolveTx,
+    ) -> Result<BTreeSet<bp::Txid>, StateProviderError<Self::Error>> {
+        if status.validity() != Validity::Valid {
+            return Err(StateProviderError::InvalidConsignment);
+        }
+        let genesis = consignment.genesis();
+        let contract_id = genesis.contract_id();
+        if self
+            .contract_state(contract_id)
+            .map_err(StateProviderError::Backend)?
+            .is_none()
+        {
+            self.add_genesis(consignment.schema().clone(), genesis)?;
+        }
+        let mut state = load(self, contract_id)?;
+
+        let mut unmined = BTreeSet::new();
+        let mut transitions = Vec::new();
+        for anchored_bundle in consignment.anchored_bundles() {
+            let txid = anchored_bundle.anchor.txid;
+            let Ok(height) = resolver.resolve_height(txid) else {
+                unmined.insert(txid);
+                continue;
+            };
+            let ord_txid = OrderedTxid::new(height, txid);
+            transitions.extend(
+                anchored_bundle
+                    .bundle
+                    .values()
+                    .filter_map(|item| item.transition.as_ref())
+                    .map(|transition| (ord_txid, transition)),
+            );
+        }
+        transitions.sort_by_key(|(ord_txid, _)| *ord_txid);
+        for (ord_txid, transition) in transitions {
+            state.add_transition(transition, ord_txid);
+            fold(self, &mut state, transition.id(), Some(transition))?;
+        }
+
+        for opid in consignment.op_ids_except(&BTreeSet::new()) {
+            if let Some(extension) = consignment.extension(opid) {
+                self.put_extension(extension.clone())
+                    .map_err(StateProviderError::Backend)?;
+            }
+        }
+
+        self.put_contract_state(state)
+            .map_err(StateProviderError::Backend)?;
+        Ok(unmined)
+    }
+}
+
+fn load<P: StateReadProvider + ?Sized>(
+    provider: &P,
+    contract_id: ContractId,
+) -> Result<ContractState, StateProviderError<P::Error>> {
+    provider
+        .contract_state(contract_id)
+        .map_err(StateProviderError::Backend)?
+        .ok_or(StateProviderError::UnknownContract(contract_id))
+}
+
+/// Saves the transition (if any) and indexes seals of the revealed assignments
+/// of the operation, which must be already added to the `state`.
+fn fold<P: StateWriteProvider + ?Sized>(
+    provider: &mut P,
+    state: &mut ContractState,
+    opid: OpId,
+    transition: Option<&Transition>,
+) -> Result<(), StateProviderError<P::Error>> {
+    if let Some(transition) = transition {
+        provider
+            .put_transition(transition.clone())
+            .map_err(StateProviderError::Backend)?;
+    }
+    let seals = state
+        .rights()
+        .iter()
+        .map(|a| (a.seal, a.opout))
+        .chain(state.fungibles().iter().map(|a| (a.seal, a.opout)))
+        .chain(state.data().iter().map(|a| (a.seal, a.opout)))
+        .chain(state.attach().iter().map(|a| (a.seal, a.opout)))
+        .filter(|(_, opout)| opout.op == opid);
+    for (seal, opout) in seals {
+        provider
+            .index_seal(seal, opout)
+            .map_err(StateProviderError::Backend)?;
+    }
+    Ok(())
+}
+
+/// Contract data kept in memory, used by tests and short-living applications
+/// which don't need persistence.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct MemState {
+    contracts: BTreeMap<ContractId, ContractState>,
+    genesis: BTreeMap<ContractId, Genesis>,
+    transitions: BTreeMap<OpId, Transition>,
+    extensions: BTreeMap<OpId, Extension>,
+    seals: BTreeMap<Outpoint, BTreeSet<Opout>>,
+}
+
+impl MemState {
+    pub fn new() -> Self { MemState::default() }
+}
+
+impl StateReadProvider for MemState {
+    type Error = Infallible;
+
+    fn contract_state(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<Option<ContractState>, Self::Error> {
+        Ok(self.contracts.get(&contract_id).cloned())
+    }
+
+    fn genesis(&self, contract_id: ContractId) -> Result<Option<Genesis>, Self::Error> {
+        Ok(self.genesis.get(&contract_id).cloned())
+    }
+
+    fn transition(&self, opid: OpId) -> Result<Option<Transition>, Self::Error> {
+        Ok(self.transitions.get(&opid).cloned())
+    }
+
+    fn extension(&self, opid: OpId) -> Result<Option<Extension>, Self::Error> {
+        Ok(self.extensions.get(&opid).cloned())
+    }
+
+    fn seal_opouts(&self, seal: Outpoint) -> Result<BTreeSet<Opout>, Self::Error> {
+        Ok(self.seals.get(&seal).cloned().unwrap_or_default())
+    }
+}
+
+impl StateWriteProvider for MemState {
+    fn put_contract_state(&mut self, state: ContractState) -> Result<(), Self::Error> {
+        self.contracts.insert(state.contract_id(), state);
+        Ok(())
+    }
+
+    fn put_genesis(&mut self, genesis: Genesis) -> Result<(), Self::Error> {
+        self.genesis.insert(genesis.contract_id(), genesis);
+        Ok(())
+    }
+
+    fn put_transition(&mut self, transition: Transition) -> Result<(), Self::Error> {
+        self.transitions.insert(transition.id(), transition);
+        Ok(())
+    }
+
+    fn put_extension(&mut self, extension: Extension) -> Result<(), Self::Error> {
+        self.extensions.insert(extension.id(), extension);
+        Ok(())
+    }
+
+    fn index_seal(&mut self, seal: Outpoint, opout: Opout) -> Result<(), Self::Error> {
+        self.seals.entry(seal).or_default().insert(opout);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "test_vectors")]
+mod test {
+    use bp::Txid;
+
+    use super::*;
+    use crate::test_vectors;
+
+    #[test]
+    fn fold_operations() {
+        let genesis = test_vectors::genesis();
+        let transition = test_vectors::transition();
+        let contract_id = genesis.contract_id();
+        let txid = Txid::from([0x22; 32]);
+        let mut provider = MemState::new();
+
+        assert_eq!(
+            provider.add_transition(&transition, OrderedTxid::new(1, txid)),
+            Err(StateProviderError::UnknownContract(contract_id))
+        );
+
+        provider
+            .add_genesis(test_vectors::schema(), &genesis)
+            .unwrap();
+        assert_eq!(provider.genesis(contract_id).unwrap().map(|g| g.id()), Some(genesis.id()));
+        let genesis_opout = Opout::new(genesis.id(), test_vectors::ASSIGNMENT_TYPE, 0);
+        let genesis_seal = Outpoint::new(Txid::from([0x11; 32]), 0);
+        assert_eq!(provider.seal_opouts(genesis_seal).unwrap(), bset![genesis_opout]);
+
+        provider
+            .add_transition(&transition, OrderedTxid::new(1, txid))
+            .unwrap();
+        assert!(provider.has_operation(transition.id()).unwrap());
+        let opout = Opout::new(transition.id(), test_vectors::ASSIGNMENT_TYPE, 0);
+        assert_eq!(provider.seal_opouts(Outpoint::new(txid, 1)).unwrap(), bset![opout]);
+
+        let state = provider.contract_state(contract_id).unwrap().unwrap();
+        let unspent = state
+            .fungibles()
+            .iter()
+            .map(|a| a.opout)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(unspent, bset![opout]);
+        // Spent outputs are still indexed
+        assert_eq!(provider.seal_opouts(genesis_seal).unwrap(), bset![genesis_opout]);
+    }
+}
@@ -27,7 +27,8 @@ pub use bp::seals::txout::blind::{
     ChainBlindSeal as GraphSeal, ParseError, SecretSeal, SingleBlindSeal as GenesisSeal,
 };
 pub use bp::seals::txout::TxoSeal;
-use bp::Txid;
+use bp::seals::txout::CloseMethod;
+use bp::{Tx, Txid, Vout};
 use commit_verify::{CommitEncode, Conceal};
 use strict_encoding::{StrictDecode, StrictDumb, StrictEncode};
 
@@ -73,3 +74,94 @@ pub enum SealWitness {
     #[display("~")]
     Extension,
 }
+
+/// Output number used by seals which reference a whole transaction instead of
+/// a specific transaction output. The output actually used by such seal is
+/// derived from the transaction with the [`VoutDerivation`] declared by the
+/// schema for the assignment type, allowing receivers to provide seals which
+/// don't depend on the structure of the transaction.
+pub const VOUT_DERIVED: u32 = u32::MAX;
+
+/// Derivation of the output number for the seals referencing a whole
+/// transaction (see [`VOUT_DERIVED`]).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB, tags = repr, into_u8, try_from_u8)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[repr(u8)]
+pub enum VoutDerivation {
+    /// Output containing the deterministic bitcoin commitment of the
+    /// transaction. Can be used only with tapret commitments, since opret
+    /// outputs are unspendable.
+    #[display("commitment")]
+    #[strict_type(dumb)]
+    CommitmentOutput = 0,
+
+    /// First output of the transaction not containing the deterministic
+    /// bitcoin commitment.
+    #[display("first-other")]
+    FirstOtherOutput = 1,
+}
+
+impl VoutDerivation {
+    /// Derives output number of a seal closed with the `method` and
+    /// referencing the transaction `tx`. Returns `None` if the transaction has
+    /// no matching output.
+    pub fn derive_vout(self, tx: &Tx, method: CloseMethod) -> Option<Vout> {
+        let outputs = &tx.outputs;
+        let commitment = match method {
+            CloseMethod::TapretFirst => outputs.iter().position(|out| out.script_pubkey.is_p2tr()),
+            CloseMethod::OpretFirst => outputs
+                .iter()
+                .position(|out| !out.script_pubkey.is_empty() && out.script_pubkey.is_op_return()),
+            _ => None,
+        }?;
+        let vout = match self {
+            VoutDerivation::CommitmentOutput if method == CloseMethod::TapretFirst => commitment,
+            VoutDerivation::CommitmentOutput => return None,
+            VoutDerivation::FirstOtherOutput => (0..outputs.len()).find(|no| *no != commitment)?,
+        };
+        Some(Vout::from(vout as u32))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bp::secp256k1::XOnlyPublicKey;
+    use bp::{LockTime, ScriptPubkey, TxOut};
+
+    use super::*;
+
+    #[test]
+    fn vout_derivation() {
+        let key = XOnlyPublicKey::from_str(
+            "c5f93479093e2b8f724a79844cc10928dd44e9a390b539843fb83fbf842723f3",
+        )
+        .unwrap();
+        let p2tr = ScriptPubkey::p2tr_tweaked(key);
+        let opret = ScriptPubkey::op_return(&[]);
+        let output = |script_pubkey| TxOut {
+            value: 1000.into(),
+            script_pubkey,
+        };
+        let tx = Tx {
+            version: default!(),
+            inputs: none!(),
+            outputs: confined_vec![output(opret), output(p2tr)],
+            lock_time: LockTime::from_consensus_u32(0),
+        };
+
+        let tapret = CloseMethod::TapretFirst;
+        let opret = CloseMethod::OpretFirst;
+        assert_eq!(VoutDerivation::CommitmentOutput.derive_vout(&tx, tapret), Some(Vout::from(1)));
+        assert_eq!(VoutDerivation::CommitmentOutput.derive_vout(&tx, opret), None);
+        assert_eq!(VoutDerivation::FirstOtherOutput.derive_vout(&tx, tapret), Some(Vout::from(0)));
+        assert_eq!(VoutDerivation::FirstOtherOutput.derive_vout(&tx, opret), Some(Vout::from(1)));
+    }
+}
@@ -0,0 +1,374 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OpenTimestamps proofs attached to the metadata of genesis and state
+//! extensions.
+//!
+//! If the operation schema is declared as timestamped, operation metadata must
+//! start with a detached OpenTimestamps proof, serialized as a blob with a
+//! 16-bit little-endian length prefix, followed by the metadata data matching
+//! the schema semantic type. Consensus verifies only the structure of the
+//! proof; verification of the timestamp itself against the bitcoin blockchain
+//! is left to the application.
+
+use amplify::confinement::{Confined, SmallBlob};
+
+/// Magic bytes starting each detached OpenTimestamps proof.
+pub const OTS_MAGIC: [u8; 31] =
+    *b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+/// Supported major version of the OpenTimestamps proof format.
+pub const OTS_VERSION: u64 = 1;
+
+const OTS_MAX_DEPTH: usize = 256;
+const OTS_MAX_OP_ARG: usize = 4096;
+const OTS_MAX_PAYLOAD: usize = 8192;
+
+const TAG_ATTESTATION: u8 = 0x00;
+const TAG_FORK: u8 = 0xff;
+const TAG_SHA1: u8 = 0x02;
+const TAG_RIPEMD160: u8 = 0x03;
+const TAG_SHA256: u8 = 0x08;
+const TAG_KECCAK256: u8 = 0x67;
+const TAG_APPEND: u8 = 0xf0;
+const TAG_PREPEND: u8 = 0xf1;
+const TAG_REVERSE: u8 = 0xf2;
+const TAG_HEXLIFY: u8 = 0xf3;
+
+const ATTESTATION_BITCOIN: [u8; 8] = [0x05, 0x88, 0x96, 0x0d, 0x73, 0xd7, 0x19, 0x01];
+const ATTESTATION_PENDING: [u8; 8] = [0x83, 0xdf, 0xe3, 0x0d, 0x2e, 0xf9, 0x0c, 0x8e];
+
+/// Errors in the structure of an OpenTimestamps proof.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[display(doc_comments)]
+pub enum OtsError {
+    /// operation metadata does not contain timestamp proof.
+    NoProof,
+
+    /// timestamp proof does not start with OpenTimestamps magic bytes.
+    InvalidMagic,
+
+    /// unsupported OpenTimestamps proof version {0}.
+    UnsupportedVersion(u64),
+
+    /// unknown operation tag {0:#04x} in the timestamp proof.
+    UnknownTag(u8),
+
+    /// timestamp proof is truncated.
+    UnexpectedEnd,
+
+    /// timestamp proof contains {0} excessive bytes after its end.
+    ExcessiveData(usize),
+
+    /// timestamp proof exceeds allowed depth of the commitment tree.
+    TooDeep,
+
+    /// timestamp proof contains argument or payload exceeding maximum size.
+    TooLarge,
+
+    /// timestamp proof does not contain any attestations.
+    NoAttestations,
+
+    /// timestamp proof is too large to be embedded into operation metadata.
+    Confinement,
+}
+
+/// Hash function used to produce the timestamped digest.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum OtsHash {
+    Sha1,
+    Ripemd160,
+    Sha256,
+    Keccak256,
+}
+
+impl OtsHash {
+    fn with_tag(tag: u8) -> Option<Self> {
+        Some(match tag {
+            TAG_SHA1 => OtsHash::Sha1,
+            TAG_RIPEMD160 => OtsHash::Ripemd160,
+            TAG_SHA256 => OtsHash::Sha256,
+            TAG_KECCAK256 => OtsHash::Keccak256,
+            _ => return None,
+        })
+    }
+
+    /// Length of the digest produced by the hash function.
+    pub fn digest_len(self) -> usize {
+        match self {
+            OtsHash::Sha1 | OtsHash::Ripemd160 => 20,
+            OtsHash::Sha256 | OtsHash::Keccak256 => 32,
+        }
+    }
+}
+
+/// Attestation contained in a timestamp proof.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum OtsAttestation {
+    /// Attestation by a bitcoin block header merkle root at a given height.
+    Bitcoin(u64),
+    /// Attestation which is not yet completed by a calendar server.
+    Pending(String),
+    /// Other attestation type.
+    Unknown([u8; 8]),
+}
+
+/// Detached OpenTimestamps proof with a verified structure.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct OtsProof {
+    data: Vec<u8>,
+    hash: OtsHash,
+    attestations: Vec<OtsAttestation>,
+}
+
+impl OtsProof {
+    /// Parses the proof and verifies its structure.
+    pub fn from_bytes(data: impl Into<Vec<u8>>) -> Result<Self, OtsError> {
+        let data = data.into();
+        let mut reader = Reader {
+            data: &data,
+            pos: 0,
+        };
+        if reader.bytes(OTS_MAGIC.len())? != OTS_MAGIC {
+            return Err(OtsError::InvalidMagic);
+        }
+        let version = reader.varuint()?;
+        if version != OTS_VERSION {
+            return Err(OtsError::UnsupportedVersion(version));
+        }
+        let tag = reader.byte()?;
+        let hash = OtsHash::with_tag(tag).ok_or(OtsError::UnknownTag(tag))?;
+        reader.bytes(hash.digest_len())?;
+        let mut attestations = vec![];
+        reader.timestamp(0, &mut attestations)?;
+        if reader.pos < data.len() {
+            return Err(OtsError::ExcessiveData(data.len() - reader.pos));
+        }
+        if attestations.is_empty() {
+            return Err(OtsError::NoAttestations);
+        }
+        Ok(OtsProof {
+            data,
+            hash,
+            attestations,
+        })
+    }
+
+    /// Extracts the proof from the beginning of the operation metadata,
+    /// returning the proof and the rest of the metadata.
+    pub fn extract(metadata: &[u8]) -> Result<(Self, &[u8]), OtsError> {
+        if metadata.len() < 2 {
+            return Err(OtsError::NoProof);
+        }
+        let len = u16::from_le_bytes([metadata[0], metadata[1]]) as usize;
+        let rest = &metadata[2..];
+        if rest.len() < len {
+            return Err(OtsError::UnexpectedEnd);
+        }
+        let proof = OtsProof::from_bytes(&rest[..len])?;
+        Ok((proof, &rest[len..]))
+    }
+
+    /// Constructs operation metadata consisting of the proof followed by the
+    /// provided typed metadata.
+    pub fn embed(&self, metadata: &[u8]) -> Result<SmallBlob, OtsError> {
+        let len = u16::try_from(self.data.len()).map_err(|_| OtsError::Confinement)?;
+        let mut data = Vec::with_capacity(2 + self.data.len() + metadata.len());
+        data.extend(len.to_le_bytes());
+        data.extend(&self.data);
+        data.extend(metadata);
+        Confined::try_from(data).map_err(|_| OtsError::Confinement)
+    }
+
+    /// Hash function used to produce timestamped digest.
+    pub fn hash(&self) -> OtsHash { self.hash }
+
+    /// Digest which is timestamped by the proof.
+    pub fn digest(&self) -> &[u8] {
+        let start = OTS_MAGIC.len() + 2;
+        &self.data[start..start + self.hash.digest_len()]
+    }
+
+    /// Attestations contained in the proof.
+    pub fn attestations(&self) -> &[OtsAttestation] { &self.attestations }
+
+    /// Returns the lowest bitcoin block height attesting the timestamp, if
+    /// any.
+    pub fn bitcoin_height(&self) -> Option<u64> {
+        self.attestations
+            .iter()
+            .filter_map(|a| match a {
+                OtsAttestation::Bitcoin(height) => Some(*height),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Returns serialized proof data.
+    pub fn as_bytes(&self) -> &[u8] { &self.data }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], OtsError> {
+        let end = self.pos.checked_add(len).ok_or(OtsError::UnexpectedEnd)?;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or(OtsError::UnexpectedEnd)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, OtsError> { Ok(self.bytes(1)?[0]) }
+
+    fn varuint(&mut self) -> Result<u64, OtsError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(OtsError::TooLarge)
+    }
+
+    fn varbytes(&mut self, max: usize) -> Result<&'a [u8], OtsError> {
+        let len = self.varuint()? as usize;
+        if len > max {
+            return Err(OtsError::TooLarge);
+        }
+        self.bytes(len)
+    }
+
+    fn timestamp(
+        &mut self,
+        depth: usize,
+        attestations: &mut Vec<OtsAttestation>,
+    ) -> Result<(), OtsError> {
+        if depth > OTS_MAX_DEPTH {
+            return Err(OtsError::TooDeep);
+        }
+        let mut tag = self.byte()?;
+        while tag == TAG_FORK {
+            let branch = self.byte()?;
+            self.branch(branch, depth, attestations)?;
+            tag = self.byte()?;
+        }
+        self.branch(tag, depth, attestations)
+    }
+
+    fn branch(
+        &mut self,
+        tag: u8,
+        depth: usize,
+        attestations: &mut Vec<OtsAttestation>,
+    ) -> Result<(), OtsError> {
+        match tag {
+            TAG_ATTESTATION => {
+                let mut kind = [0u8; 8];
+                kind.copy_from_slice(self.bytes(8)?);
+                let payload = self.varbytes(OTS_MAX_PAYLOAD)?;
+                let mut payload = Reader {
+                    data: payload,
+                    pos: 0,
+                };
+                let attestation = match kind {
+                    ATTESTATION_BITCOIN => OtsAttestation::Bitcoin(payload.varuint()?),
+                    ATTESTATION_PENDING => {
+                        let uri = payload.varbytes(OTS_MAX_PAYLOAD)?;
+                        OtsAttestation::Pending(String::from_utf8_lossy(uri).into_owned())
+                    }
+                    _ => OtsAttestation::Unknown(kind),
+                };
+                attestations.push(attestation);
+                return Ok(());
+            }
+            TAG_APPEND | TAG_PREPEND => {
+                self.varbytes(OTS_MAX_OP_ARG)?;
+            }
+            TAG_SHA1 | TAG_RIPEMD160 | TAG_SHA256 | TAG_KECCAK256 | TAG_REVERSE | TAG_HEXLIFY => {}
+            other => return Err(OtsError::UnknownTag(other)),
+        }
+        self.timestamp(depth + 1, attestations)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn proof() -> Vec<u8> {
+        let mut data = OTS_MAGIC.to_vec();
+        data.push(0x01);
+        data.push(TAG_SHA256);
+        data.extend([0xAB; 32]);
+        // Fork into pending and bitcoin attestations
+        data.push(TAG_FORK);
+        data.push(TAG_ATTESTATION);
+        data.extend(ATTESTATION_PENDING);
+        data.extend([0x05, 0x04, b'c', b'a', b'l', b'1']);
+        data.extend([TAG_APPEND, 0x02, 0x01, 0x02, TAG_SHA256]);
+        data.push(TAG_ATTESTATION);
+        data.extend(ATTESTATION_BITCOIN);
+        data.extend([0x03, 0xc0, 0xc4, 0x07]);
+        data
+    }
+
+    #[test]
+    fn structure() {
+        let proof = OtsProof::from_bytes(proof()).unwrap();
+        assert_eq!(proof.hash(), OtsHash::Sha256);
+        assert_eq!(proof.digest(), &[0xAB; 32]);
+        assert_eq!(proof.attestations(), &[
+            OtsAttestation::Pending(s!("cal1")),
+            OtsAttestation::Bitcoin(123456)
+        ]);
+        assert_eq!(proof.bitcoin_height(), Some(123456));
+
+        let mut data = proof.as_bytes().to_vec();
+        data.push(0);
+        assert_eq!(OtsProof::from_bytes(data), Err(OtsError::ExcessiveData(1)));
+        let data = proof.as_bytes()[..60].to_vec();
+        assert_eq!(OtsProof::from_bytes(data), Err(OtsError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn embedding() {
+        let proof = OtsProof::from_bytes(proof()).unwrap();
+        let metadata = proof.embed(b"meta").unwrap();
+        let (extracted, rest) = OtsProof::extract(&metadata).unwrap();
+        assert_eq!(extracted, proof);
+        assert_eq!(rest, b"meta");
+        assert_eq!(OtsProof::extract(b"m"), Err(OtsError::NoProof));
+    }
+}
@@ -0,0 +1,841 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Construction of state transitions transferring fungible state to a set of
+//! beneficiaries.
+//!
+//! Closing a seal spends all the state assigned to it, including the state of
+//! other contracts co-located at the same bitcoin transaction output. Thus,
+//! the transfer moves the remaining state of the spent outputs to a change
+//! seal: the state of the transferred contract is reassigned by the main
+//! transition, while the state of each co-located contract is reassigned by a
+//! blank transition.
+//!
+//! Transfers are constructed deterministically: given the same contract
+//! history, beneficiaries and blinding source, the resulting transitions are
+//! byte-identical regardless of the order in which the beneficiaries were
+//! provided. This allows several devices of a multi-signature wallet to
+//! construct the same transfer independently, using [`DeterministicRng`]
+//! with a shared seed, and to check the result with
+//! [`Transfer::check_identical`].
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use amplify::confinement::{self, Confined, SmallVec};
+use amplify::{Bytes32, Wrapper};
+use bp::Outpoint;
+use commit_verify::Conceal;
+use secp256k1_zkp::rand::{self, CryptoRng, Rng, RngCore};
+use secp256k1_zkp::{Scalar, SecretKey};
+use strict_encoding::StrictSerialize;
+
+use crate::commit::tagged_hash;
+use crate::schema::{AssignmentType, TransitionType, BLANK_TRANSITION_ID};
+use crate::{
+    Assign, Assignments, BlindingFactor, BundleItem, ContractHistory, ContractId, ContractState,
+    ExposedState, Ffv, GraphSeal, Input, Inputs, OpFeatures, Operation, Opout, OutputAssignment,
+    RevealedValue, SecretSeal, Transition, TransitionBundle, TypedAssigns,
+};
+
+/// Errors constructing transfer.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum TransferError {
+    /// no beneficiaries are provided for the transfer.
+    NoBeneficiaries,
+
+    /// contract has insufficient state of type {ty}: {available} is available
+    /// while {required} is required.
+    InsufficientState {
+        ty: AssignmentType,
+        available: u64,
+        required: u64,
+    },
+
+    /// total transferred amount overflows 64-bit integer.
+    AmountOverflow,
+
+    /// fungible state at {0} doesn't fit into 64-bit integer.
+    NonU64State(Opout),
+
+    /// transfer exceeds limit of inputs or assignments of a single state
+    /// transition.
+    #[from(confinement::Error)]
+    TooLarge,
+
+    /// blinding factors of the transferred state can't be balanced.
+    BlindingBalance,
+
+    /// schema of contract {0} doesn't allow blank transitions to reassign
+    /// state of type {1}.
+    BlankUnsupported(ContractId, AssignmentType),
+}
+
+/// Tag used by [`DeterministicRng`] for deriving the random data.
+pub const TRANSFER_RNG_TAG: &str = "urn:lnpbp:rgb:transfer:rng#2023-10";
+
+/// Errors checking that two transfers are identical.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum TransferMismatch {
+    /// transfers spend different outputs.
+    PrevOuts,
+
+    /// transfers contain different transitions for contract {0}.
+    Transition(ContractId),
+
+    /// transfers contain different bundles for contract {0}.
+    Bundle(ContractId),
+}
+
+/// Random number generator deriving its output from a seed, which makes
+/// blinding factors of a transfer reproducible.
+///
+/// The same seed must never be used for more than a single transfer, since
+/// this reveals the relation between the blinding factors of the transfers.
+/// A seed can be derived, for instance, from a secret shared by the wallet
+/// devices and the outputs spent by the transfer.
+#[derive(Clone, Debug)]
+pub struct DeterministicRng {
+    seed: Bytes32,
+    counter: u64,
+    buf: [u8; 32],
+    pos: usize,
+}
+
+impl DeterministicRng {
+    pub fn with(seed: Bytes32) -> Self {
+        DeterministicRng {
+            seed,
+            counter: 0,
+            buf: [0u8; 32],
+            pos: 32,
+        }
+    }
+
+    fn refill(&mut self) {
+        let mut data = self.seed.to_vec();
+        data.extend_from_slice(&self.counter.to_le_bytes());
+        self.buf = tagged_hash(TRANSFER_RNG_TAG, data).into_inner();
+        self.counter += 1;
+        self.pos = 0;
+    }
+}
+
+impl RngCore for DeterministicRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0u8; 4];
+        self.fill_bytes(&mut buf);
+        u32::from_le_bytes(buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.fill_bytes(&mut buf);
+        u64::from_le_bytes(buf)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            if self.pos == self.buf.len() {
+                self.refill();
+            }
+            *byte = self.buf[self.pos];
+            self.pos += 1;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for DeterministicRng {}
+
+/// Seal receiving transferred state.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, From)]
+pub enum BeneficiarySeal {
+    /// Seal known to the sender, assigned in a revealed form.
+    #[from]
+    Revealed(GraphSeal),
+
+    /// Seal provided by the beneficiary in the concealed form.
+    #[from]
+    Concealed(SecretSeal),
+}
+
+impl BeneficiarySeal {
+    pub fn to_secret_seal(self) -> SecretSeal {
+        match self {
+            BeneficiarySeal::Revealed(seal) => seal.conceal(),
+            BeneficiarySeal::Concealed(seal) => seal,
+        }
+    }
+
+    fn assign(self, state: RevealedValue) -> Assign<RevealedValue, GraphSeal> {
+        match self {
+            BeneficiarySeal::Revealed(seal) => Assign::revealed(seal, state),
+            BeneficiarySeal::Concealed(seal) => Assign::ConfidentialSeal { seal, state },
+        }
+    }
+}
+
+/// Data required for anchoring a transfer constructed with
+/// [`ContractHistory::transfer`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Transfer {
+    /// Outputs which must be spent by the witness transaction. The position
+    /// of an output in this list is the input number used by the bundles.
+    pub prev_outs: Vec<Outpoint>,
+
+    /// State transition of the transferred contract.
+    pub transition: Transition,
+
+    /// Blank transitions of the contracts co-located at the spent outputs.
+    pub blanks: BTreeMap<ContractId, Transition>,
+
+    /// Transition bundles for each of the contracts, ready for anchoring.
+    pub bundles: BTreeMap<ContractId, TransitionBundle>,
+}
+
+impl Transfer {
+    /// Checks that the transfer is byte-identical to the other one, which was
+    /// independently constructed for the same contract and beneficiaries.
+    pub fn check_identical(&self, other: &Transfer) -> Result<(), TransferMismatch> {
+        if self.prev_outs != other.prev_outs {
+            return Err(TransferMismatch::PrevOuts);
+        }
+        if !identical_transitions(&self.transition, &other.transition) {
+            return Err(TransferMismatch::Transition(self.transition.contract_id));
+        }
+        for contract_id in self.blanks.keys().chain(other.blanks.keys()) {
+            match (self.blanks.get(contract_id), other.blanks.get(contract_id)) {
+                (Some(a), Some(b)) if identical_transitions(a, b) => {}
+                _ => return Err(TransferMismatch::Transition(*contract_id)),
+            }
+        }
+        for contract_id in self.bundles.keys().chain(other.bundles.keys()) {
+            match (self.bundles.get(contract_id), other.bundles.get(contract_id)) {
+                (Some(a), Some(b)) if identical_bundles(a, b) => {}
+                _ => return Err(TransferMismatch::Bundle(*contract_id)),
+            }
+        }
+        Ok(())
+    }
+}
+
+// Transitions are compared by their serialization, since the equality of
+// assignments ignores whether their seals and state are revealed
+fn identical_transitions(a: &Transition, b: &Transition) -> bool {
+    let a = a.to_strict_serialized::<{ usize::MAX }>();
+    let b = b.to_strict_serialized::<{ usize::MAX }>();
+    a.expect("unlimited serialization") == b.expect("unlimited serialization")
+}
+
+fn identical_bundles(a: &TransitionBundle, b: &TransitionBundle) -> bool {
+    a.len() == b.len() &&
+        a.iter().zip(b.iter()).all(|((id_a, a), (id_b, b))| {
+            id_a == id_b &&
+                a.inputs == b.inputs &&
+                match (&a.transition, &b.transition) {
+                    (Some(a), Some(b)) => identical_transitions(a, b),
+                    (None, None) => true,
+                    _ => false,
+                }
+        })
+}
+
+/// Blank transitions of several contracts spending the same set of outputs,
+/// constructed with [`blank_transitions`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlankBatch {
+    /// Outputs which must be spent by the witness transaction. The position
+    /// of an output in this list is the input number used by the bundles.
+    pub prev_outs: Vec<Outpoint>,
+
+    /// Blank transitions for each of the contracts having state assigned to
+    /// the spent outputs.
+    pub blanks: BTreeMap<ContractId, Transition>,
+
+    /// Transition bundles for each of the contracts, ready for anchoring.
+    pub bundles: BTreeMap<ContractId, TransitionBundle>,
+}
+
+/// Accumulator of transition inputs and assignments.
+#[derive(Default)]
+struct Builder {
+    inputs: BTreeSet<Input>,
+    outpoints: BTreeSet<Outpoint>,
+    assignments: BTreeMap<AssignmentType, TypedAssigns<GraphSeal>>,
+}
+
+impl Builder {
+    fn spend<State: ExposedState>(&mut self, output: &OutputAssignment<State>) {
+        self.inputs.insert(Input::with(output.opout));
+        self.outpoints.insert(output.seal);
+    }
+
+    fn push(
+        &mut self,
+        ty: AssignmentType,
+        assign: TypedAssigns<GraphSeal>,
+    ) -> Result<(), TransferError> {
+        match (self.assignments.get_mut(&ty), assign) {
+            (None, assign) => {
+                self.assignments.insert(ty, assign);
+            }
+            (Some(TypedAssigns::Declarative(vec)), TypedAssigns::Declarative(other)) => {
+                vec.extend(other)?
+            }
+            (Some(TypedAssigns::Fungible(vec)), TypedAssigns::Fungible(other)) => {
+                vec.extend(other)?
+            }
+            (Some(TypedAssigns::Structured(vec)), TypedAssigns::Structured(other)) => {
+                vec.extend(other)?
+            }
+            (Some(TypedAssigns::Attachment(vec)), TypedAssigns::Attachment(other)) => {
+                vec.extend(other)?
+            }
+            _ => unreachable!("contract history has inconsistent state types"),
+        }
+        Ok(())
+    }
+
+    /// Spends all the state of the contract assigned to the provided outputs,
+    /// except fungible state of the `skip` type, and reassigns it to the
+    /// change seal.
+    fn reassign(
+        &mut self,
+        history: &ContractHistory,
+        outpoints: &BTreeSet<Outpoint>,
+        skip: Option<AssignmentType>,
+        change: GraphSeal,
+    ) -> Result<(), TransferError> {
+        for output in history
+            .rights()
+            .iter()
+            .filter(|o| outpoints.contains(&o.seal))
+        {
+            self.spend(output);
+            let assign = Assign::revealed(change, output.state);
+            self.push(output.opout.ty, TypedAssigns::Declarative(small_vec![assign]))?;
+        }
+        for output in history
+            .data()
+            .iter()
+            .filter(|o| outpoints.contains(&o.seal))
+        {
+            self.spend(output);
+            let assign = Assign::revealed(change, output.state.clone());
+            self.push(output.opout.ty, TypedAssigns::Structured(small_vec![assign]))?;
+        }
+        for output in history
+            .attach()
+            .iter()
+            .filter(|o| outpoints.contains(&o.seal))
+        {
+            self.spend(output);
+            let assign = Assign::revealed(change, output.state.clone());
+            self.push(output.opout.ty, TypedAssigns::Attachment(small_vec![assign]))?;
+        }
+
+        // Fungible state of the same type is merged into a single assignment
+        let mut fungibles = BTreeMap::<AssignmentType, Vec<&OutputAssignment<_>>>::new();
+        for output in history
+            .fungibles()
+            .iter()
+            .filter(|o| outpoints.contains(&o.seal))
+        {
+            if Some(output.opout.ty) == skip {
+                continue;
+            }
+            self.spend(output);
+            fungibles.entry(output.opout.ty).or_default().push(output);
+        }
+        for (ty, outputs) in fungibles {
+            let value = sum_values(outputs.iter().copied())?;
+            let blinding = balance_blinding(outputs.iter().map(|o| o.state.blinding), [])?;
+            let assign = Assign::revealed(change, RevealedValue::with(value, blinding));
+            self.push(ty, TypedAssigns::Fungible(small_vec![assign]))?;
+        }
+        Ok(())
+    }
+
+    fn transition(
+        self,
+        contract_id: ContractId,
+        transition_type: TransitionType,
+    ) -> Result<(Transition, BTreeSet<Outpoint>), TransferError> {
+        let transition = Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id,
+            transition_type,
+            metadata: none!(),
+            globals: none!(),
+            inputs: Inputs::from(Confined::try_from(self.inputs)?),
+            assignments: Assignments::from(Confined::try_from_iter(
+                self.assignments
+                    .into_iter()
+                    .map(|(ty, assigns)| (ty, canonical(assigns))),
+            )?),
+            valencies: none!(),
+        };
+        Ok((transition, self.outpoints))
+    }
+}
+
+/// Puts the assignments in the canonical order (see [`Assign`]), such that
+/// the transition doesn't depend on the order in which they were added.
+fn canonical(assigns: TypedAssigns<GraphSeal>) -> TypedAssigns<GraphSeal> {
+    fn sorted<T: Ord>(vec: SmallVec<T>) -> SmallVec<T> {
+        let mut vec = vec.into_inner();
+        vec.sort();
+        Confined::try_from(vec).expect("same number of items")
+    }
+    match assigns {
+        TypedAssigns::Declarative(vec) => TypedAssigns::Declarative(sorted(vec)),
+        TypedAssigns::Fungible(vec) => TypedAssigns::Fungible(sorted(vec)),
+        TypedAssigns::Structured(vec) => TypedAssigns::Structured(sorted(vec)),
+        TypedAssigns::Attachment(vec) => TypedAssigns::Attachment(sorted(vec)),
+    }
+}
+
+/// Constructs blank transitions for each of the contracts which have state
+/// assigned to the provided outputs.
+fn blanks<'a>(
+    histories: impl IntoIterator<Item = &'a ContractHistory>,
+    outpoints: &BTreeSet<Outpoint>,
+    change: GraphSeal,
+) -> Result<Vec<(Transition, BTreeSet<Outpoint>)>, TransferError> {
+    let mut blanks = vec![];
+    for history in histories {
+        let mut builder = Builder::default();
+        builder.reassign(history, outpoints, None, change)?;
+        if !builder.inputs.is_empty() {
+            blanks.push(builder.transition(history.contract_id(), BLANK_TRANSITION_ID)?);
+        }
+    }
+    Ok(blanks)
+}
+
+/// Packs each of the transitions into a separate bundle, referencing
+/// witness transaction inputs spending `prev_outs`.
+fn bundles<'a>(
+    prev_outs: &[Outpoint],
+    transitions: impl IntoIterator<Item = &'a (Transition, BTreeSet<Outpoint>)>,
+) -> Result<BTreeMap<ContractId, TransitionBundle>, TransferError> {
+    let mut bundles = BTreeMap::new();
+    for (transition, spent) in transitions {
+        let vins = prev_outs
+            .iter()
+            .enumerate()
+            .filter(|(_, outpoint)| spent.contains(outpoint))
+            .map(|(vin, _)| vin as u16);
+        let item = BundleItem {
+            inputs: Confined::try_from_iter(vins)?,
+            transition: Some(transition.clone()),
+        };
+        let items = Confined::try_from_iter([(transition.id(), item)])?;
+        bundles.insert(transition.contract_id, TransitionBundle::from(items));
+    }
+    Ok(bundles)
+}
+
+/// Constructs blank transitions moving all the state of the `contracts`
+/// assigned to `outpoints` to the `change` seal, such that the outputs can
+/// be spent without losing the state. Contracts having no state at the
+/// outputs are skipped.
+///
+/// Each blank transition is checked against the blank transition schema of
+/// its own contract.
+pub fn blank_transitions<'a>(
+    contracts: impl IntoIterator<Item = &'a ContractState>,
+    outpoints: &BTreeSet<Outpoint>,
+    change: GraphSeal,
+) -> Result<BlankBatch, TransferError> {
+    let contracts = contracts
+        .into_iter()
+        .map(|state| (state.contract_id(), state))
+        .collect::<BTreeMap<_, _>>();
+    let blanks = blanks(contracts.values().map(|state| &state.history), outpoints, change)?;
+    for (blank, _) in &blanks {
+        let schema = contracts[&blank.contract_id].schema.blank_transition();
+        if let Some(ty) = blank
+            .assignments
+            .keys()
+            .find(|ty| !schema.assignments.contains_key(ty))
+        {
+            return Err(TransferError::BlankUnsupported(blank.contract_id, *ty));
+        }
+    }
+
+    let prev_outs = blanks
+        .iter()
+        .flat_map(|(_, spent)| spent)
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let bundles = bundles(&prev_outs, &blanks)?;
+    let blanks = blanks
+        .into_iter()
+        .map(|(blank, _)| (blank.contract_id, blank))
+        .collect();
+    Ok(BlankBatch {
+        prev_outs,
+        blanks,
+        bundles,
+    })
+}
+
+fn sum_values<'a>(
+    outputs: impl IntoIterator<Item = &'a OutputAssignment<RevealedValue>>,
+) -> Result<u64, TransferError> {
+    outputs.into_iter().try_fold(0u64, |sum, output| {
+        let value = output
+            .state
+            .value
+            .try_as_u64()
+            .ok_or(TransferError::NonU64State(output.opout))?;
+        sum.checked_add(value).ok_or(TransferError::AmountOverflow)
+    })
+}
+
+/// Computes blinding factor which makes sum of the output blinding factors
+/// equal to the sum of the input blinding factors.
+fn balance_blinding(
+    inputs: impl IntoIterator<Item = BlindingFactor>,
+    outputs: impl IntoIterator<Item = BlindingFactor>,
+) -> Result<BlindingFactor, TransferError> {
+    let mut acc = None::<SecretKey>;
+    let keys = inputs
+        .into_iter()
+        .map(SecretKey::from)
+        .chain(outputs.into_iter().map(|bf| SecretKey::from(bf).negate()));
+    for key in keys {
+        acc = Some(match acc {
+            None => key,
+            Some(acc) => {
+                let tweak = Scalar::from_be_bytes(key.secret_bytes())
+                    .expect("secret key is always a valid scalar");
+                acc.add_tweak(&tweak)
+                    .map_err(|_| TransferError::BlindingBalance)?
+            }
+        });
+    }
+    acc.map(BlindingFactor::from)
+        .ok_or(TransferError::BlindingBalance)
+}
+
+impl ContractHistory {
+    /// Constructs transfer of the fungible state of type `assignment_type`
+    /// to the list of beneficiaries, spending the minimal number of outputs
+    /// holding the state.
+    ///
+    /// All other state assigned to the spent outputs, including the state of
+    /// the `co_located` contracts, is reassigned to the `change` seal, which
+    /// also receives the change. The transition schema for the
+    /// `transition_type` must allow all these state types as inputs and
+    /// assignments.
+    ///
+    /// Blinding factors of the transferred state are generated with `rng`;
+    /// use [`DeterministicRng`] to make the transfer reproducible.
+    pub fn transfer<'a, R: Rng + RngCore>(
+        &self,
+        transition_type: TransitionType,
+        assignment_type: AssignmentType,
+        beneficiaries: &[(u64, BeneficiarySeal)],
+        change: GraphSeal,
+        co_located: impl IntoIterator<Item = &'a ContractHistory>,
+        rng: &mut R,
+    ) -> Result<Transfer, TransferError> {
+        if beneficiaries.is_empty() {
+            return Err(TransferError::NoBeneficiaries);
+        }
+        // Canonical order of the beneficiaries makes the transition independent
+        // from the order in which they were provided
+        let mut beneficiaries = beneficiaries.to_vec();
+        beneficiaries.sort_by_key(|(amount, seal)| (seal.to_secret_seal(), *amount));
+        let required = beneficiaries
+            .iter()
+            .try_fold(0u64, |sum, (amount, _)| sum.checked_add(*amount))
+            .ok_or(TransferError::AmountOverflow)?;
+
+        // Selecting outputs with the largest amounts first, which minimizes
+        // the number of the spent outputs
+        let mut allocations = BTreeMap::<Outpoint, Vec<&OutputAssignment<RevealedValue>>>::new();
+        for output in self
+            .fungibles()
+            .iter()
+            .filter(|o| o.opout.ty == assignment_type)
+        {
+            allocations.entry(output.seal).or_default().push(output);
+        }
+        let mut allocations = allocations
+            .into_iter()
+            .map(|(outpoint, outputs)| {
+                Ok((sum_values(outputs.iter().copied())?, outpoint, outputs))
+            })
+            .collect::<Result<Vec<_>, TransferError>>()?;
+        allocations.sort_by(|(a, ..), (b, ..)| b.cmp(a));
+
+        let mut builder = Builder::default();
+        let mut available = 0u64;
+        let mut spent = vec![];
+        for (amount, _, outputs) in allocations {
+            if available >= required {
+                break;
+            }
+            available = available
+                .checked_add(amount)
+                .ok_or(TransferError::AmountOverflow)?;
+            outputs.iter().for_each(|output| builder.spend(*output));
+            spent.extend(outputs);
+        }
+        if available < required {
+            return Err(TransferError::InsufficientState {
+                ty: assignment_type,
+                available,
+                required,
+            });
+        }
+
+        let mut assigns = Vec::with_capacity(beneficiaries.len() + 1);
+        let mut blindings = Vec::with_capacity(beneficiaries.len());
+        let change_amount = available - required;
+        let (last, rest) = if change_amount > 0 {
+            (&(change_amount, BeneficiarySeal::Revealed(change)), &beneficiaries[..])
+        } else {
+            beneficiaries.split_last().expect("non-empty beneficiaries")
+        };
+        for (amount, seal) in rest {
+            let state = RevealedValue::new(*amount, rng);
+            blindings.push(state.blinding);
+            assigns.push(seal.assign(state));
+        }
+        let blinding = balance_blinding(spent.iter().map(|o| o.state.blinding), blindings)?;
+        assigns.push(last.1.assign(RevealedValue::with(last.0, blinding)));
+        builder.push(assignment_type, TypedAssigns::Fungible(SmallVec::try_from(assigns)?))?;
+
+        let outpoints = builder.outpoints.clone();
+        builder.reassign(self, &outpoints, Some(assignment_type), change)?;
+        let mut transitions = vec![builder.transition(self.contract_id(), transition_type)?];
+        transitions.extend(blanks(co_located, &outpoints, change)?);
+
+        let prev_outs = outpoints.into_iter().collect::<Vec<_>>();
+        let bundles = bundles(&prev_outs, &transitions)?;
+        let mut transitions = transitions.into_iter().map(|(transition, _)| transition);
+        let transition = transitions
+            .next()
+            .expect("main transition is always present");
+        let blanks = transitions
+            .map(|blank| (blank.contract_id, blank))
+            .collect();
+        Ok(Transfer {
+            prev_outs,
+            transition,
+            blanks,
+            bundles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+    use bp::secp256k1::rand::thread_rng;
+    use bp::{Chain, Txid};
+
+    use super::*;
+    use crate::schema::{FungibleType, StateSchema};
+    use crate::{Genesis, GenesisSeal, SchemaId, SubSchema};
+
+    const FUNGIBLE: AssignmentType = 1;
+    const RIGHTS: AssignmentType = 2;
+
+    fn seal(txid: u8) -> GenesisSeal {
+        GenesisSeal::with_blinding(CloseMethod::TapretFirst, Txid::from([txid; 32]), 0, 0)
+    }
+
+    fn history(chain: Chain, allocations: &[(u8, u64)], rights: &[u8]) -> ContractHistory {
+        let mut assignments = BTreeMap::new();
+        let fungibles = allocations
+            .iter()
+            .map(|(txid, value)| {
+                Assign::revealed(seal(*txid), RevealedValue::new(*value, &mut thread_rng()))
+            })
+            .collect::<Vec<_>>();
+        assignments
+            .insert(FUNGIBLE, TypedAssigns::Fungible(Confined::try_from(fungibles).unwrap()));
+        if !rights.is_empty() {
+            let rights = rights
+                .iter()
+                .map(|txid| Assign::right(seal(*txid)))
+                .collect::<Vec<_>>();
+            assignments
+                .insert(RIGHTS, TypedAssigns::Declarative(Confined::try_from(rights).unwrap()));
+        }
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: SchemaId::from([0u8; 32]),
+            chain,
+            metadata: none!(),
+            globals: none!(),
+            assignments: Assignments::from(Confined::try_from(assignments).unwrap()),
+            valencies: none!(),
+        };
+        ContractHistory::with(genesis.schema_id, None, genesis.contract_id(), &genesis)
+    }
+
+    #[test]
+    fn transfer() {
+        let contract = history(Chain::Regtest, &[(1, 60), (2, 50), (3, 30)], &[2]);
+        let other = history(Chain::Testnet3, &[(1, 10)], &[]);
+        let beneficiary =
+            BeneficiarySeal::Revealed(GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 1));
+        let change = GraphSeal::with_vout(CloseMethod::TapretFirst, 1, 2);
+
+        let transfer = contract
+            .transfer(1, FUNGIBLE, &[(80, beneficiary)], change, [&other], &mut thread_rng())
+            .unwrap();
+        let prev_outs = [1, 2].map(|txid| Outpoint::new(Txid::from([txid; 32]), 0));
+        assert_eq!(transfer.prev_outs, prev_outs);
+
+        let transition = &transfer.transition;
+        assert_eq!(transition.inputs.len(), 3);
+        let fungibles = transition.assignments.get(&FUNGIBLE).unwrap().as_fungible();
+        let values = fungibles
+            .iter()
+            .map(|a| a.as_revealed_state().unwrap().value.as_u128())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(values, bset! {80, 30});
+        assert_eq!(transition.assignments.get(&RIGHTS).unwrap().len_u16(), 1);
+
+        let inputs = contract
+            .fungibles()
+            .iter()
+            .filter(|o| transfer.prev_outs.contains(&o.seal))
+            .map(|o| o.state.blinding);
+        let outputs = fungibles
+            .iter()
+            .map(|a| a.as_revealed_state().unwrap().blinding);
+        // Sum of the blinding factors is zero and can't be represented as a key
+        assert_eq!(balance_blinding(inputs, outputs), Err(TransferError::BlindingBalance));
+
+        let blank = &transfer.blanks[&other.contract_id()];
+        assert_eq!(blank.transition_type, BLANK_TRANSITION_ID);
+        assert_eq!(blank.inputs.len(), 1);
+
+        let bundle = &transfer.bundles[&contract.contract_id()];
+        assert_eq!(bundle.values().next().unwrap().inputs, tiny_bset![0, 1]);
+        let bundle = &transfer.bundles[&other.contract_id()];
+        assert_eq!(bundle.values().next().unwrap().inputs, tiny_bset![0]);
+    }
+
+    #[test]
+    fn deterministic() {
+        let contract = history(Chain::Regtest, &[(1, 60), (2, 50)], &[]);
+        let beneficiaries = [1, 2].map(|vout| {
+            (10 * vout as u64, GraphSeal::with_vout(CloseMethod::TapretFirst, vout, 1).into())
+        });
+        let reversed = [beneficiaries[1], beneficiaries[0]];
+        let change = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 2);
+        let seed = Bytes32::from([7u8; 32]);
+
+        let transfer = |beneficiaries: &[(u64, BeneficiarySeal)], seed| {
+            let mut rng = DeterministicRng::with(seed);
+            contract
+                .transfer(1, FUNGIBLE, beneficiaries, change, [], &mut rng)
+                .unwrap()
+        };
+        let first = transfer(&beneficiaries, seed);
+        let second = transfer(&reversed, seed);
+        assert_eq!(first.check_identical(&second), Ok(()));
+
+        let other = transfer(&beneficiaries, Bytes32::from([8u8; 32]));
+        assert_eq!(
+            first.check_identical(&other),
+            Err(TransferMismatch::Transition(contract.contract_id()))
+        );
+    }
+
+    fn state(history: ContractHistory, rights: bool) -> ContractState {
+        let mut schema = SubSchema::default();
+        let fungible = StateSchema::Fungible(FungibleType::Unsigned64Bit);
+        schema.owned_types.insert(FUNGIBLE, fungible).unwrap();
+        if rights {
+            schema
+                .owned_types
+                .insert(RIGHTS, StateSchema::Declarative)
+                .unwrap();
+        }
+        ContractState { schema, history }
+    }
+
+    #[test]
+    fn blanks() {
+        let first = state(history(Chain::Regtest, &[(1, 10), (2, 20)], &[2]), true);
+        let second = state(history(Chain::Testnet3, &[(1, 30)], &[]), true);
+        let third = state(history(Chain::Signet, &[(3, 40)], &[]), true);
+        let outpoints = [1, 2].map(|txid| Outpoint::new(Txid::from([txid; 32]), 0));
+        let change = GraphSeal::with_vout(CloseMethod::TapretFirst, 1, 2);
+
+        let batch = blank_transitions(
+            [&first, &second, &third],
+            &bset! {outpoints[0], outpoints[1]},
+            change,
+        )
+        .unwrap();
+        assert_eq!(batch.prev_outs, outpoints);
+        assert_eq!(batch.blanks.len(), 2);
+        assert_eq!(batch.blanks[&first.contract_id()].inputs.len(), 3);
+        assert_eq!(batch.blanks[&second.contract_id()].inputs.len(), 1);
+        let bundle = &batch.bundles[&first.contract_id()];
+        assert_eq!(bundle.values().next().unwrap().inputs, tiny_bset![0, 1]);
+        let bundle = &batch.bundles[&second.contract_id()];
+        assert_eq!(bundle.values().next().unwrap().inputs, tiny_bset![0]);
+
+        let first = state(first.history, false);
+        assert_eq!(
+            blank_transitions([&first], &bset! {outpoints[1]}, change),
+            Err(TransferError::BlankUnsupported(first.contract_id(), RIGHTS))
+        );
+    }
+
+    #[test]
+    fn insufficient() {
+        let contract = history(Chain::Regtest, &[(1, 60)], &[]);
+        let change = GraphSeal::with_vout(CloseMethod::TapretFirst, 1, 2);
+        let beneficiary = BeneficiarySeal::Revealed(change);
+        let err = contract
+            .transfer(1, FUNGIBLE, &[(80, beneficiary)], change, [], &mut thread_rng())
+            .unwrap_err();
+        assert_eq!(err, TransferError::InsufficientState {
+            ty: FUNGIBLE,
+            available: 60,
+            required: 80
+        });
+    }
+}
@@ -0,0 +1,57 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for fuzzing strict decoding of consensus data types. Fuzzing
+//! targets are located in the `fuzz` directory and are run with `cargo fuzz`.
+
+use std::fmt::Debug;
+
+use strict_encoding::{StrictDecode, StrictEncode, StrictReader, StrictWriter};
+
+/// Maximal size of the data accepted by the fuzzing targets.
+pub const FUZZ_MAX_LEN: usize = 1 << 24;
+
+/// Decodes a value of type `T` from the fuzzer-provided data and, if the data
+/// are decodable, checks that encoding the value back produces exactly the
+/// same bytes as were consumed by the decoder.
+///
+/// # Panics
+///
+/// Panics if the decode→encode identity does not hold.
+pub fn roundtrip<T>(data: &[u8]) -> Option<T>
+where T: StrictDecode + StrictEncode + Debug {
+    let mut reader = StrictReader::in_memory(data.to_vec(), FUZZ_MAX_LEN);
+    let value = T::strict_decode(&mut reader).ok()?;
+    let consumed = reader.unbox().position() as usize;
+
+    let writer = StrictWriter::in_memory(FUZZ_MAX_LEN);
+    let encoded = value
+        .strict_encode(writer)
+        .expect("in-memory encoding of a decoded value must not fail")
+        .unbox();
+    assert_eq!(
+        encoded,
+        &data[..consumed],
+        "decode-encode roundtrip is not identity for {value:?}"
+    );
+    Some(value)
+}
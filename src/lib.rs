@@ -44,12 +44,20 @@ extern crate commit_verify;
 extern crate serde_crate as serde;
 extern crate core;
 
+pub mod commit;
+pub mod consensus;
 pub mod contract;
 pub mod schema;
 pub mod validation;
 pub mod vm;
 #[cfg(feature = "stl")]
 pub mod stl;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(feature = "test_vectors")]
+pub mod test_vectors;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub mod prelude {
     pub use bp::dbc::{Anchor, AnchorId};
@@ -64,6 +72,8 @@ pub mod prelude {
 pub use prelude::*;
 
 pub const LIB_NAME_RGB: &str = "RGB";
+/// Strict types id for the library providing data types for RGB consensus.
+pub const LIB_ID_RGB: &str = "orbit_pizza_vocal_HKbJsVDauCLS6ZMHHFr6SUxv5oNhohZa5KyZ9odchJra";
 
 /// Reserved byte.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug, Display)]
@@ -112,6 +122,21 @@ mod _reserved {
 )]
 pub struct Ffv(u16);
 
+impl Ffv {
+    /// Version of the operations committing to the owned state assignments
+    /// with [`commit::SparseTree`] instead of [LNPBP-81] merklization.
+    ///
+    /// [LNPBP-81]: https://github.com/LNP-BP/LNPBPs/blob/master/lnpbp-0081.md
+    pub const SPARSE_ASSIGNMENTS: Ffv = Ffv(1);
+
+    /// Latest version supported by this library.
+    pub const LATEST: Ffv = Ffv::SPARSE_ASSIGNMENTS;
+
+    /// Checks whether the owned state assignments are committed to with
+    /// [`commit::SparseTree`].
+    pub fn has_sparse_assignments(self) -> bool { self >= Ffv::SPARSE_ASSIGNMENTS }
+}
+
 mod _ffv {
     use strict_encoding::{DecodeError, ReadTuple, StrictDecode, TypedRead};
 
@@ -120,7 +145,7 @@ mod _ffv {
     impl StrictDecode for Ffv {
         fn strict_decode(reader: &mut impl TypedRead) -> Result<Self, DecodeError> {
             let ffv = reader.read_tuple(|r| r.read_field().map(Self))?;
-            if ffv != Ffv::default() {
+            if ffv > Ffv::LATEST {
                 Err(DecodeError::DataIntegrityError(format!(
                     "unsupported fast-forward version code belonging to a future RGB version. \
                      Please update your software, or, if the problem persists, contact your \
@@ -0,0 +1,220 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Schema bindle: a self-contained artifact with the compiled schema, which
+//! issuers publish and registries store.
+//!
+//! The bindle contains the schema together with its type system, script
+//! libraries and type names (from which the [`super::SchemaInterface`] is
+//! derived), and is protected by a checksum over its strict encoding. Since
+//! the strict encoding of a schema is deterministic, compiling the same schema
+//! always produces a byte-identical bindle.
+
+use amplify::confinement::{MediumBlob, U24};
+use amplify::Bytes32;
+use strict_encoding::{DeserializeError, StrictDeserialize, StrictSerialize};
+
+use super::{EntryPointError, SchemaId, SchemaInterface, SubSchema};
+use crate::commit::tagged_hash;
+use crate::validation::{Status, Validity};
+use crate::LIB_NAME_RGB;
+
+/// Tag of the schema bindle checksum.
+pub const SCHEMA_BINDLE_TAG: &str = "urn:lnpbp:rgb:schema-bindle#2023-10";
+
+/// Maximal size of the serialized schema bindle.
+pub const SCHEMA_BINDLE_MAX_LEN: usize = U24;
+
+/// Errors loading schema bindle.
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum BindleError {
+    /// invalid schema bindle encoding. Details: {0}
+    #[from]
+    Decode(DeserializeError),
+
+    /// schema bindle checksum {found} doesn't match the bindle data, which
+    /// has checksum {expected}.
+    ChecksumMismatch { expected: Bytes32, found: Bytes32 },
+
+    /// schema bindle is made for schema {found}, while it contains schema
+    /// {expected}.
+    SchemaMismatch { expected: SchemaId, found: SchemaId },
+
+    /// schema contained in the bindle is inconsistent.
+    /// {0}
+    Inconsistent(Box<Status>),
+
+    /// schema contained in the bindle has invalid script. Details: {0}
+    #[from]
+    Script(EntryPointError),
+}
+
+/// Self-contained compiled schema with its checksum.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct SchemaBindle {
+    pub schema_id: SchemaId,
+    pub schema: SubSchema,
+    /// Checksum over the strict encoding of the schema (see
+    /// [`SchemaBindle::checksum_of`]).
+    pub checksum: Bytes32,
+}
+
+impl StrictSerialize for SchemaBindle {}
+impl StrictDeserialize for SchemaBindle {}
+
+impl SubSchema {
+    /// Produces bindle with the schema.
+    pub fn bindle(&self) -> SchemaBindle {
+        SchemaBindle {
+            schema_id: self.schema_id(),
+            schema: self.clone(),
+            checksum: SchemaBindle::checksum_of(self),
+        }
+    }
+}
+
+impl SchemaBindle {
+    /// Computes checksum over the strict encoding of the schema.
+    pub fn checksum_of(schema: &SubSchema) -> Bytes32 {
+        let data = schema
+            .to_strict_serialized::<SCHEMA_BINDLE_MAX_LEN>()
+            .expect("schema size exceeds bindle limits");
+        tagged_hash(SCHEMA_BINDLE_TAG, data)
+    }
+
+    /// Decodes bindle from its strict encoding and verifies it with
+    /// [`SchemaBindle::verify`].
+    pub fn load(data: MediumBlob) -> Result<Self, BindleError> {
+        let bindle = Self::from_strict_serialized::<SCHEMA_BINDLE_MAX_LEN>(data)?;
+        bindle.verify()?;
+        Ok(bindle)
+    }
+
+    /// Serializes bindle with strict encoding.
+    pub fn to_blob(&self) -> MediumBlob {
+        self.to_strict_serialized::<SCHEMA_BINDLE_MAX_LEN>()
+            .expect("schema size exceeds bindle limits")
+    }
+
+    /// Verifies the internal consistency of the bindle: its checksum and
+    /// schema id must match the schema, which must pass the schema
+    /// verification and have script consistent with the schema.
+    pub fn verify(&self) -> Result<(), BindleError> {
+        let checksum = Self::checksum_of(&self.schema);
+        if checksum != self.checksum {
+            return Err(BindleError::ChecksumMismatch {
+                expected: checksum,
+                found: self.checksum,
+            });
+        }
+        let schema_id = self.schema.schema_id();
+        if schema_id != self.schema_id {
+            return Err(BindleError::SchemaMismatch {
+                expected: schema_id,
+                found: self.schema_id,
+            });
+        }
+        let status = self.schema.verify();
+        if status.validity() != Validity::Valid {
+            return Err(BindleError::Inconsistent(Box::new(status)));
+        }
+        self.schema.verify_script(&self.schema.script)?;
+        Ok(())
+    }
+
+    /// Extracts interface of the bundled schema.
+    #[inline]
+    pub fn interface(&self) -> SchemaInterface { self.schema.interface() }
+
+    /// Releases the schema from the bindle.
+    #[inline]
+    pub fn into_schema(self) -> SubSchema { self.schema }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::MediumOrdMap;
+    use strict_types::{Ty, TypeSystem};
+
+    use super::*;
+    use crate::{AssignmentCategory, StateSchema};
+
+    fn schema() -> SubSchema {
+        let mut schema = SubSchema::default();
+        let types = bmap! { schema.genesis.metadata => Ty::UNIT };
+        schema.type_system = TypeSystem::from(MediumOrdMap::try_from(types).unwrap());
+        schema
+    }
+
+    #[test]
+    fn load() {
+        let schema = schema();
+        let bindle = schema.bindle();
+        assert_eq!(bindle.schema_id, schema.schema_id());
+        let blob = bindle.to_blob();
+        assert_eq!(schema.bindle().to_blob(), blob);
+        assert_eq!(SchemaBindle::load(blob).unwrap(), bindle);
+
+        let mut tampered = bindle.clone();
+        tampered.checksum = Bytes32::from([0xAA; 32]);
+        assert!(matches!(
+            SchemaBindle::load(tampered.to_blob()),
+            Err(BindleError::ChecksumMismatch { .. })
+        ));
+
+        let mut tampered = bindle.clone();
+        tampered.schema_id = SchemaId::from([0xAA; 32]);
+        assert!(matches!(
+            SchemaBindle::load(tampered.to_blob()),
+            Err(BindleError::SchemaMismatch { .. })
+        ));
+
+        let mut blob = bindle.to_blob().into_inner();
+        blob.push(0);
+        assert!(matches!(
+            SchemaBindle::load(MediumBlob::try_from(blob).unwrap()),
+            Err(BindleError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn inconsistent() {
+        let mut schema = schema();
+        schema
+            .owned_types
+            .insert(1, StateSchema::Declarative)
+            .unwrap();
+        schema
+            .categories
+            .insert(1, AssignmentCategory::Value)
+            .unwrap();
+        assert!(matches!(schema.bindle().verify(), Err(BindleError::Inconsistent(_))));
+    }
+}
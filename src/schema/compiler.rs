@@ -0,0 +1,301 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hooks for external compilers producing schema validation scripts from
+//! sources written in a high-level language.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use aluvm::data::encoding::Encode;
+use aluvm::isa::Instr;
+use aluvm::library::{LibId, LibSite};
+
+use crate::vm::{supported_isa_ids, EntryPoint, RgbIsa};
+use crate::{Schema, SchemaRoot, Script, VmType, BLANK_TRANSITION_ID};
+
+/// Maximal size of a serialized library which can be stored in a schema.
+pub const LIB_MAX_SIZE: usize = u16::MAX as usize;
+
+/// Maximal number of libraries which can be stored in a schema.
+pub const LIBS_MAX_STORED: usize = u8::MAX as usize;
+
+/// Errors in the consistency of the script libraries and entry points.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum EntryPointError {
+    /// entry point {0:?} references state or operation type which is not
+    /// defined by the schema.
+    UnknownType(EntryPoint),
+
+    /// entry point {0:?} references library {1} absent from the script.
+    LibAbsent(EntryPoint, LibId),
+
+    /// entry point {0:?} references position {1} outside of the library
+    /// code.
+    OutOfBounds(EntryPoint, LibSite),
+
+    /// library {0} is stored in the script under the id {1}.
+    LibIdMismatch(LibId, LibId),
+
+    /// library {0} depends on library {1}, which is absent from the script.
+    DependencyAbsent(LibId, LibId),
+
+    /// script contains {0} libraries, while a schema can store at most 255
+    /// libraries.
+    TooManyLibs(usize),
+
+    /// library {0} is serialized into {1} bytes, exceeding the limit of 65535
+    /// bytes.
+    LibTooLarge(LibId, usize),
+
+    /// library {0} requires ISA extension {1} which is not supported by the
+    /// runtime.
+    UnsupportedIsa(LibId, String),
+
+    /// library {0} has truncated or undecodable code.
+    Undecodable(LibId),
+
+    /// script has no entry point {0:?}, for which the source code was
+    /// provided.
+    Missed(EntryPoint),
+}
+
+/// Errors compiling schema validation scripts.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum CompileError<E: Error> {
+    /// compiler error. Details: {0}
+    Compiler(E),
+
+    /// compiler has produced script for {found} while {expected} was expected.
+    VmMismatch { expected: VmType, found: VmType },
+
+    /// invalid script produced by the compiler. Details: {0}
+    #[from]
+    EntryPoint(EntryPointError),
+}
+
+/// Compiler of schema validation scripts.
+///
+/// Compiler takes source code for each of the validation actions (entry
+/// points), written in a compiler-specific language, and produces a complete
+/// script with all required libraries and entry point table.
+pub trait ScriptCompiler {
+    /// Source code of a validation procedure.
+    type Source;
+
+    /// Compiler-specific errors.
+    type Error: Error;
+
+    /// Virtual machine targeted by the compiler.
+    const VM_TYPE: VmType;
+
+    /// Compiles sources into the script.
+    fn compile(
+        &mut self,
+        sources: &BTreeMap<EntryPoint, Self::Source>,
+    ) -> Result<Script, Self::Error>;
+
+    /// Compiles sources into the script, verifying that the produced script
+    /// targets the proper virtual machine, has entry points for all of the
+    /// sources and that all of its entry points are consistent with the
+    /// schema.
+    fn compile_for<Root: SchemaRoot>(
+        &mut self,
+        schema: &Schema<Root>,
+        sources: &BTreeMap<EntryPoint, Self::Source>,
+    ) -> Result<Script, CompileError<Self::Error>> {
+        let script = self.compile(sources).map_err(CompileError::Compiler)?;
+        if script.vm_type() != Self::VM_TYPE {
+            return Err(CompileError::VmMismatch {
+                expected: Self::VM_TYPE,
+                found: script.vm_type(),
+            });
+        }
+        schema.verify_script(&script)?;
+        // The embedded virtual machine has no entry points at all
+        let missed = match &script {
+            Script::AluVM(alu) => sources
+                .keys()
+                .find(|entry_point| !alu.entry_points.contains_key(entry_point)),
+            Script::Embedded => sources.keys().next(),
+        };
+        if let Some(entry_point) = missed {
+            return Err(EntryPointError::Missed(*entry_point).into());
+        }
+        Ok(script)
+    }
+}
+
+impl<Root: SchemaRoot> Schema<Root> {
+    /// Verifies that the script libraries can be stored in the schema, are
+    /// decodable, require only the ISA extensions supported by the runtime and
+    /// reference only the libraries present in the script; and that the script
+    /// entry points are consistent with the schema and point to the code of
+    /// the script libraries.
+    ///
+    /// The verification doesn't depend on the contract data and thus needs to
+    /// be done only once, when the schema is imported.
+    pub fn verify_script(&self, script: &Script) -> Result<(), EntryPointError> {
+        let Script::AluVM(alu) = script else {
+            // Embedded virtual machine has no script code to verify
+            return Ok(());
+        };
+
+        if alu.libs.len() > LIBS_MAX_STORED {
+            return Err(EntryPointError::TooManyLibs(alu.libs.len()));
+        }
+        let supported = supported_isa_ids();
+        for (id, lib) in &alu.libs {
+            if lib.id() != *id {
+                return Err(EntryPointError::LibIdMismatch(lib.id(), *id));
+            }
+            if let Some(dep) = lib.libs.iter().find(|dep| !alu.libs.contains_key(*dep)) {
+                return Err(EntryPointError::DependencyAbsent(*id, *dep));
+            }
+            let size = lib.serialize().len();
+            if size > LIB_MAX_SIZE {
+                return Err(EntryPointError::LibTooLarge(*id, size));
+            }
+            if let Some(isa) = lib.isae.iter().find(|isa| !supported.contains(isa.as_str())) {
+                return Err(EntryPointError::UnsupportedIsa(*id, isa.clone()));
+            }
+            if lib.disassemble::<Instr<RgbIsa>>().is_err() {
+                return Err(EntryPointError::Undecodable(*id));
+            }
+        }
+
+        for (entry_point, site) in &alu.entry_points {
+            let known = match entry_point {
+                EntryPoint::ValidateGenesis => true,
+                EntryPoint::ValidateTransition(ty) => {
+                    *ty == BLANK_TRANSITION_ID || self.transitions.contains_key(ty)
+                }
+                EntryPoint::ValidateExtension(ty) => self.extensions.contains_key(ty),
+                EntryPoint::ValidateGlobalState(ty) => self.global_types.contains_key(ty),
+                EntryPoint::ValidateOwnedState(ty) | EntryPoint::MigrateOwnedState(ty) => {
+                    self.owned_types.contains_key(ty)
+                }
+            };
+            if !known {
+                return Err(EntryPointError::UnknownType(*entry_point));
+            }
+            let Some(lib) = alu.libs.get(&site.lib) else {
+                return Err(EntryPointError::LibAbsent(*entry_point, site.lib));
+            };
+            if site.pos as usize >= lib.code.len() as usize {
+                return Err(EntryPointError::OutOfBounds(*entry_point, *site));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aluvm::library::{Lib, LibSeg};
+    use amplify::confinement;
+
+    use super::*;
+    use crate::vm::stdlib::StdRoutine;
+    use crate::vm::AluScript;
+    use crate::{RootSchema, StateSchema};
+
+    struct StdCompiler;
+
+    impl ScriptCompiler for StdCompiler {
+        type Source = StdRoutine;
+        type Error = confinement::Error;
+        const VM_TYPE: VmType = VmType::AluVM;
+
+        fn compile(
+            &mut self,
+            sources: &BTreeMap<EntryPoint, StdRoutine>,
+        ) -> Result<Script, Self::Error> {
+            let mut script = AluScript::default();
+            for (entry_point, routine) in sources {
+                script.link_std(*entry_point, *routine)?;
+            }
+            Ok(Script::AluVM(script))
+        }
+    }
+
+    #[test]
+    fn compile_verify() {
+        let mut schema = RootSchema::default();
+        schema
+            .owned_types
+            .insert(1, StateSchema::Fungible(default!()))
+            .unwrap();
+
+        let mut sources = BTreeMap::new();
+        sources.insert(EntryPoint::ValidateOwnedState(1), StdRoutine::FungibleConservation(1));
+        let script = StdCompiler.compile_for(&schema, &sources).unwrap();
+        assert_eq!(schema.verify_script(&script), Ok(()));
+
+        sources.insert(EntryPoint::ValidateOwnedState(2), StdRoutine::FungibleConservation(2));
+        assert_eq!(
+            StdCompiler.compile_for(&schema, &sources),
+            Err(CompileError::EntryPoint(EntryPointError::UnknownType(
+                EntryPoint::ValidateOwnedState(2)
+            )))
+        );
+    }
+
+    #[test]
+    fn embedded_script() {
+        let schema = RootSchema::default();
+        assert_eq!(Script::Embedded.vm_type(), VmType::Embedded);
+        assert_eq!(schema.verify_script(&Script::Embedded), Ok(()));
+    }
+
+    #[test]
+    fn verify_libs() {
+        let schema = RootSchema::default();
+        let script = |lib: Lib| {
+            Script::AluVM(AluScript {
+                libs: confined_bmap! { lib.id() => lib },
+                entry_points: none!(),
+            })
+        };
+
+        let lib = Lib::with("ALU RGB", vec![0b11_000_000], vec![], LibSeg::default()).unwrap();
+        let id = lib.id();
+        assert_eq!(schema.verify_script(&script(lib)), Err(EntryPointError::Undecodable(id)));
+
+        let lib = Lib::with("ALU FLOAT", vec![], vec![], LibSeg::default()).unwrap();
+        let id = lib.id();
+        assert_eq!(
+            schema.verify_script(&script(lib)),
+            Err(EntryPointError::UnsupportedIsa(id, s!("FLOAT")))
+        );
+
+        let lib = Lib::with("ALU", vec![], vec![0u8; LIB_MAX_SIZE], LibSeg::default()).unwrap();
+        let (id, size) = (lib.id(), lib.serialize().len());
+        assert_eq!(
+            schema.verify_script(&script(lib)),
+            Err(EntryPointError::LibTooLarge(id, size))
+        );
+    }
+}
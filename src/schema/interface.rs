@@ -0,0 +1,520 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Interface of a schema: a structured view of the operations which can be
+//! performed under the schema, their arguments and the state they produce.
+//! Wallets may use it to construct operations for arbitrary schemata without
+//! relying on the assumptions about a specific contract standard; the optional
+//! type names defined by the schema can be used as field labels.
+//!
+//! Contract standards (like fungible or non-fungible assets) can be described
+//! with an [`InterfaceSpec`], listing the named state and transitions the
+//! schemata implementing the standard must provide; [`Schema::conforms_to`]
+//! checks a schema against such a specification.
+
+use std::collections::BTreeMap;
+
+use strict_encoding::TypeName;
+use strict_types::SemId;
+
+use super::{
+    AssignmentCategory, AssignmentType, GlobalStateType, Occurrences, OpFullType, OpSchema, Schema,
+    SchemaId, SchemaRoot, StateSchema, Sunset, ValencyType,
+};
+use crate::StateType;
+
+/// Global state produced by an operation.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct GlobalIface {
+    pub ty: GlobalStateType,
+    pub name: Option<TypeName>,
+    pub sem_id: SemId,
+    pub max_items: u16,
+    pub occurrences: Occurrences,
+}
+
+/// Owned state consumed or produced by an operation.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct OwnedIface {
+    pub ty: AssignmentType,
+    pub name: Option<TypeName>,
+    pub state: StateSchema,
+    /// Category declared by the schema, which user interfaces may use to
+    /// render the state.
+    pub category: Option<AssignmentCategory>,
+    pub occurrences: Occurrences,
+}
+
+/// Interface of a single operation type.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct OpIface {
+    pub op_type: OpFullType,
+    /// Name of the transition type; always `None` for genesis and extensions.
+    pub name: Option<TypeName>,
+    /// Semantic type of the operation metadata.
+    pub metadata: SemId,
+    pub timestamped: bool,
+    pub globals: Vec<GlobalIface>,
+    /// Owned state which must be spent by the operation; always empty for
+    /// genesis and extensions.
+    pub inputs: Vec<OwnedIface>,
+    /// Owned state assigned by the operation.
+    pub outputs: Vec<OwnedIface>,
+    /// Valencies redeemed by the operation; always empty for genesis and
+    /// transitions.
+    pub redeems: Vec<ValencyType>,
+    pub valencies: Vec<ValencyType>,
+    /// Sunset of the operation type, if it is deprecated by the schema.
+    /// Wallets should avoid creating operations of the deprecated types.
+    pub sunset: Option<Sunset>,
+}
+
+/// Interface of a schema, listing all operation types it defines.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct SchemaInterface {
+    pub schema_id: SchemaId,
+    pub genesis: OpIface,
+    pub transitions: Vec<OpIface>,
+    pub extensions: Vec<OpIface>,
+}
+
+impl SchemaInterface {
+    /// Returns deprecated operation types with their sunsets.
+    pub fn deprecations(&self) -> BTreeMap<OpFullType, Sunset> {
+        self.transitions
+            .iter()
+            .chain(&self.extensions)
+            .filter_map(|op| Some((op.op_type, op.sunset?)))
+            .collect()
+    }
+}
+
+/// Global state type required by an [`InterfaceSpec`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct GlobalSpec {
+    pub name: TypeName,
+    /// Semantic type of the state, if it is fixed by the specification.
+    pub sem_id: Option<SemId>,
+}
+
+/// Owned state type required by an [`InterfaceSpec`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct OwnedSpec {
+    pub name: TypeName,
+    pub state: StateType,
+    /// Category which the schema must declare for the state type, if it is
+    /// required by the specification.
+    pub category: Option<AssignmentCategory>,
+}
+
+/// State transition required by an [`InterfaceSpec`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct TransitionSpec {
+    pub name: TypeName,
+    /// Names of the owned state types the transition must be able to spend.
+    pub inputs: Vec<TypeName>,
+    /// Names of the owned state types the transition must be able to assign.
+    pub outputs: Vec<TypeName>,
+}
+
+/// Specification of a contract interface (like a fungible asset), which
+/// schemata of different issuers may implement. Schema types are matched by
+/// the names the schema gives them (see [`super::TypeNames`]).
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct InterfaceSpec {
+    pub name: TypeName,
+    pub globals: Vec<GlobalSpec>,
+    pub owned: Vec<OwnedSpec>,
+    pub transitions: Vec<TransitionSpec>,
+}
+
+/// Mismatches between a schema and an [`InterfaceSpec`].
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum IfaceMismatch {
+    /// schema doesn't declare global state type named {0}.
+    GlobalAbsent(TypeName),
+
+    /// global state type {name} has semantic type {found}, while the interface
+    /// requires {expected}.
+    GlobalSemId {
+        name: TypeName,
+        expected: SemId,
+        found: SemId,
+    },
+
+    /// schema doesn't declare owned state type named {0}.
+    OwnedAbsent(TypeName),
+
+    /// owned state type {name} holds {found} state, while the interface
+    /// requires {expected} state.
+    OwnedState {
+        name: TypeName,
+        expected: StateType,
+        found: StateType,
+    },
+
+    /// owned state type {0} doesn't have category {1} required by the
+    /// interface.
+    OwnedCategory(TypeName, AssignmentCategory),
+
+    /// schema doesn't declare state transition named {0}.
+    TransitionAbsent(TypeName),
+
+    /// state transition {0} can't spend owned state {1}.
+    TransitionInput(TypeName, TypeName),
+
+    /// state transition {0} can't assign owned state {1}.
+    TransitionOutput(TypeName, TypeName),
+}
+
+impl<Root: SchemaRoot> Schema<Root> {
+    /// Checks whether the schema implements the interface `spec`, returning
+    /// all the detected mismatches otherwise. The schema may define more
+    /// types and transitions than required by the interface.
+    pub fn conforms_to(&self, spec: &InterfaceSpec) -> Result<(), Vec<IfaceMismatch>> {
+        fn named<Ty: Copy>(names: &BTreeMap<Ty, TypeName>, name: &TypeName) -> Option<Ty> {
+            names
+                .iter()
+                .find(|(_, n)| *n == name)
+                .map(|(ty, _)| *ty)
+        }
+        let owned = |name: &TypeName| {
+            named(self.names.owned_types.as_inner(), name)
+                .filter(|ty| self.owned_types.contains_key(ty))
+        };
+
+        let mut mismatches = vec![];
+        for global in &spec.globals {
+            let Some(schema) = named(self.names.global_types.as_inner(), &global.name)
+                .and_then(|ty| self.global_types.get(&ty))
+            else {
+                mismatches.push(IfaceMismatch::GlobalAbsent(global.name.clone()));
+                continue;
+            };
+            match global.sem_id {
+                Some(expected) if expected != schema.sem_id => {
+                    mismatches.push(IfaceMismatch::GlobalSemId {
+                        name: global.name.clone(),
+                        expected,
+                        found: schema.sem_id,
+                    })
+                }
+                _ => {}
+            }
+        }
+        for spec in &spec.owned {
+            let Some(ty) = owned(&spec.name) else {
+                mismatches.push(IfaceMismatch::OwnedAbsent(spec.name.clone()));
+                continue;
+            };
+            let found = self.owned_types[&ty].state_type();
+            if found != spec.state {
+                mismatches.push(IfaceMismatch::OwnedState {
+                    name: spec.name.clone(),
+                    expected: spec.state,
+                    found,
+                });
+            }
+            match spec.category {
+                Some(category) if self.categories.get(&ty) != Some(&category) => {
+                    mismatches.push(IfaceMismatch::OwnedCategory(spec.name.clone(), category))
+                }
+                _ => {}
+            }
+        }
+        for spec in &spec.transitions {
+            let Some(transition) = named(self.names.transitions.as_inner(), &spec.name)
+                .and_then(|ty| self.transitions.get(&ty))
+            else {
+                mismatches.push(IfaceMismatch::TransitionAbsent(spec.name.clone()));
+                continue;
+            };
+            for name in &spec.inputs {
+                if !owned(name).map_or(false, |ty| transition.inputs.contains_key(&ty)) {
+                    mismatches
+                        .push(IfaceMismatch::TransitionInput(spec.name.clone(), name.clone()));
+                }
+            }
+            for name in &spec.outputs {
+                if !owned(name).map_or(false, |ty| transition.assignments.contains_key(&ty)) {
+                    mismatches
+                        .push(IfaceMismatch::TransitionOutput(spec.name.clone(), name.clone()));
+                }
+            }
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    /// Extracts interface of the schema. State types which are referenced by
+    /// the operations but not declared by the schema are omitted (such
+    /// schemata fail the consistency verification).
+    pub fn interface(&self) -> SchemaInterface {
+        SchemaInterface {
+            schema_id: self.schema_id(),
+            genesis: self.op_iface(OpFullType::Genesis, &self.genesis),
+            transitions: self
+                .transitions
+                .iter()
+                .map(|(ty, op)| self.op_iface(OpFullType::StateTransition(*ty), op))
+                .collect(),
+            extensions: self
+                .extensions
+                .iter()
+                .map(|(ty, op)| self.op_iface(OpFullType::StateExtension(*ty), op))
+                .collect(),
+        }
+    }
+
+    fn op_iface(&self, op_type: OpFullType, op: &impl OpSchema) -> OpIface {
+        let owned = |(ty, occurrences): (&AssignmentType, &Occurrences)| {
+            Some(OwnedIface {
+                ty: *ty,
+                name: self.names.owned_types.get(ty).cloned(),
+                state: *self.owned_types.get(ty)?,
+                category: self.categories.get(ty).copied(),
+                occurrences: occurrences.clone(),
+            })
+        };
+        let name = match op_type {
+            OpFullType::StateTransition(ty) => self.names.transitions.get(&ty).cloned(),
+            OpFullType::Genesis | OpFullType::StateExtension(_) => None,
+        };
+        OpIface {
+            op_type,
+            name,
+            metadata: op.metadata(),
+            timestamped: op.timestamped(),
+            globals: op
+                .globals()
+                .iter()
+                .filter_map(|(ty, occurrences)| {
+                    let schema = self.global_types.get(ty)?;
+                    Some(GlobalIface {
+                        ty: *ty,
+                        name: self.names.global_types.get(ty).cloned(),
+                        sem_id: schema.sem_id,
+                        max_items: schema.max_items,
+                        occurrences: occurrences.clone(),
+                    })
+                })
+                .collect(),
+            inputs: op
+                .inputs()
+                .into_iter()
+                .flatten()
+                .filter_map(owned)
+                .collect(),
+            outputs: op.assignments().iter().filter_map(owned).collect(),
+            redeems: op.redeems().into_iter().flatten().copied().collect(),
+            valencies: op.valencies().iter().copied().collect(),
+            sunset: self.deprecations.sunset(op_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::{
+        FungibleType, GlobalStateSchema, SchemaDeprecations, SubSchema, TransitionSchema, TypeNames,
+    };
+
+    #[test]
+    fn interface() {
+        let sem_id = SemId::strict_dumb();
+        let schema = SubSchema {
+            global_types: tiny_bmap! { 1 => GlobalStateSchema::once(sem_id) },
+            owned_types: tiny_bmap! {
+                2 => StateSchema::Fungible(FungibleType::Unsigned64Bit),
+                3 => StateSchema::Declarative,
+            },
+            categories: tiny_bmap! { 2 => AssignmentCategory::Value },
+            transitions: tiny_bmap! {
+                10 => TransitionSchema {
+                    globals: tiny_bmap! { 1 => Occurrences::Once },
+                    inputs: tiny_bmap! { 2 => Occurrences::OnceOrMore },
+                    assignments: tiny_bmap! {
+                        2 => Occurrences::NoneOrMore,
+                        4 => Occurrences::Once,
+                    },
+                    ..default!()
+                },
+            },
+            names: TypeNames {
+                owned_types: tiny_bmap! { 2 => tn!("Amount") },
+                transitions: tiny_bmap! { 10 => tn!("Transfer") },
+                ..default!()
+            },
+            deprecations: SchemaDeprecations {
+                transitions: tiny_bmap! { 10 => Sunset::Time(1_700_000_000) },
+                ..default!()
+            },
+            ..default!()
+        };
+        let iface = schema.interface();
+        assert_eq!(iface.schema_id, schema.schema_id());
+        assert!(iface.genesis.inputs.is_empty());
+        assert!(iface.extensions.is_empty());
+        assert_eq!(iface.transitions.len(), 1);
+
+        let transfer = &iface.transitions[0];
+        assert_eq!(transfer.op_type, OpFullType::StateTransition(10));
+        assert_eq!(transfer.name, Some(tn!("Transfer")));
+        assert_eq!(transfer.sunset, Some(Sunset::Time(1_700_000_000)));
+        assert_eq!(
+            iface.deprecations(),
+            bmap! { OpFullType::StateTransition(10) => Sunset::Time(1_700_000_000) }
+        );
+        assert_eq!(transfer.globals, vec![GlobalIface {
+            ty: 1,
+            name: None,
+            sem_id,
+            max_items: 1,
+            occurrences: Occurrences::Once,
+        }]);
+        assert_eq!(transfer.inputs, vec![OwnedIface {
+            ty: 2,
+            name: Some(tn!("Amount")),
+            state: StateSchema::Fungible(FungibleType::Unsigned64Bit),
+            category: Some(AssignmentCategory::Value),
+            occurrences: Occurrences::OnceOrMore,
+        }]);
+        // Undeclared assignment type 4 is omitted
+        assert_eq!(transfer.outputs.len(), 1);
+    }
+
+    #[test]
+    fn conformance() {
+        let sem_id = SemId::strict_dumb();
+        let schema = SubSchema {
+            global_types: tiny_bmap! { 1 => GlobalStateSchema::once(sem_id) },
+            owned_types: tiny_bmap! {
+                2 => StateSchema::Fungible(FungibleType::Unsigned64Bit),
+                3 => StateSchema::Declarative,
+            },
+            categories: tiny_bmap! { 2 => AssignmentCategory::Value },
+            transitions: tiny_bmap! {
+                10 => TransitionSchema {
+                    inputs: tiny_bmap! { 2 => Occurrences::OnceOrMore },
+                    assignments: tiny_bmap! { 2 => Occurrences::NoneOrMore },
+                    ..default!()
+                },
+            },
+            names: TypeNames {
+                global_types: tiny_bmap! { 1 => tn!("Ticker") },
+                owned_types: tiny_bmap! { 2 => tn!("Assets"), 3 => tn!("Inflation") },
+                transitions: tiny_bmap! { 10 => tn!("Transfer") },
+                ..default!()
+            },
+            ..default!()
+        };
+        let mut spec = InterfaceSpec {
+            name: tn!("Fungible"),
+            globals: vec![GlobalSpec {
+                name: tn!("Ticker"),
+                sem_id: Some(sem_id),
+            }],
+            owned: vec![OwnedSpec {
+                name: tn!("Assets"),
+                state: StateType::Fungible,
+                category: Some(AssignmentCategory::Value),
+            }],
+            transitions: vec![TransitionSpec {
+                name: tn!("Transfer"),
+                inputs: vec![tn!("Assets")],
+                outputs: vec![tn!("Assets")],
+            }],
+        };
+        assert_eq!(schema.conforms_to(&spec), Ok(()));
+
+        spec.globals.push(GlobalSpec {
+            name: tn!("Name"),
+            sem_id: None,
+        });
+        spec.owned.push(OwnedSpec {
+            name: tn!("Inflation"),
+            state: StateType::Fungible,
+            category: None,
+        });
+        spec.transitions[0].outputs.push(tn!("Inflation"));
+        assert_eq!(
+            schema.conforms_to(&spec),
+            Err(vec![
+                IfaceMismatch::GlobalAbsent(tn!("Name")),
+                IfaceMismatch::OwnedState {
+                    name: tn!("Inflation"),
+                    expected: StateType::Fungible,
+                    found: StateType::Void,
+                },
+                IfaceMismatch::TransitionOutput(tn!("Transfer"), tn!("Inflation")),
+            ])
+        );
+    }
+}
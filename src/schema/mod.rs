@@ -26,15 +26,30 @@ mod schema;
 pub mod script;
 mod state;
 mod occurrences;
+mod compiler;
+mod interface;
+mod bindle;
+mod procedure;
 
+pub use bindle::{BindleError, SchemaBindle, SCHEMA_BINDLE_MAX_LEN, SCHEMA_BINDLE_TAG};
+pub use compiler::{CompileError, EntryPointError, ScriptCompiler, LIBS_MAX_STORED, LIB_MAX_SIZE};
+pub use interface::{
+    GlobalIface, GlobalSpec, IfaceMismatch, InterfaceSpec, OpIface, OwnedIface, OwnedSpec,
+    SchemaInterface, TransitionSpec,
+};
+pub use procedure::EmbeddedProcedure;
 pub use occurrences::{Occurrences, OccurrencesMismatch};
 pub use operations::{
     AssignmentType, AssignmentsSchema, ExtensionSchema, GenesisSchema, GlobalSchema, OpFullType,
     OpSchema, OpType, TransitionSchema, ValencySchema, ValencyType,
 };
 pub use schema::{
-    ExtensionType, GlobalStateType, RootSchema, Schema, SchemaId, SchemaRoot, SchemaTypeIndex,
-    SubSchema, TransitionType, BLANK_TRANSITION_ID,
+    ConstantType, ExtensionType, GlobalStateType, HistoryLimits, RootSchema, Schema, SchemaConstant,
+    SchemaDeprecations, SchemaId, SchemaIdVersion, SchemaMigration, SchemaRoot, SchemaTypeIndex,
+    SubSchema, Sunset, TransitionType, TypeNames, BLANK_TRANSITION_ID, ISSUER_IDENTITY_ID,
 };
 pub use script::{Script, VmType};
-pub use state::{FungibleType, GlobalStateSchema, MediaType, StateSchema};
+pub use state::{
+    AssignmentCategory, CounterSchema, FungibleType, GlobalExpiry, GlobalStateSchema, MediaType,
+    StateCovenant, StateSchema,
+};
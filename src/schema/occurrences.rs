@@ -153,7 +153,11 @@ impl StrictDecode for Occurrences {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Display)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(crate = "serde_crate"))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
 #[display("expected from {min} to {max} elements, while {found} were provided")]
 pub struct OccurrencesMismatch {
     pub min: u16,
@@ -107,6 +107,8 @@ pub trait OpSchema {
     fn redeems(&self) -> Option<&ValencySchema>;
     fn assignments(&self) -> &AssignmentsSchema;
     fn valencies(&self) -> &ValencySchema;
+    /// Whether the operation metadata must start with a timestamp proof.
+    fn timestamped(&self) -> bool;
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
@@ -122,6 +124,8 @@ pub struct GenesisSchema {
     pub globals: GlobalSchema,
     pub assignments: AssignmentsSchema,
     pub valencies: ValencySchema,
+    /// Requires operation metadata to start with an OpenTimestamps proof.
+    pub timestamped: bool,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
@@ -138,6 +142,8 @@ pub struct ExtensionSchema {
     pub redeems: ValencySchema,
     pub assignments: AssignmentsSchema,
     pub valencies: ValencySchema,
+    /// Requires operation metadata to start with an OpenTimestamps proof.
+    pub timestamped: bool,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
@@ -171,6 +177,8 @@ impl OpSchema for GenesisSchema {
     fn assignments(&self) -> &AssignmentsSchema { &self.assignments }
     #[inline]
     fn valencies(&self) -> &ValencySchema { &self.valencies }
+    #[inline]
+    fn timestamped(&self) -> bool { self.timestamped }
 }
 
 impl OpSchema for ExtensionSchema {
@@ -188,6 +196,8 @@ impl OpSchema for ExtensionSchema {
     fn assignments(&self) -> &AssignmentsSchema { &self.assignments }
     #[inline]
     fn valencies(&self) -> &ValencySchema { &self.valencies }
+    #[inline]
+    fn timestamped(&self) -> bool { self.timestamped }
 }
 
 impl OpSchema for TransitionSchema {
@@ -205,4 +215,6 @@ impl OpSchema for TransitionSchema {
     fn assignments(&self) -> &AssignmentsSchema { &self.assignments }
     #[inline]
     fn valencies(&self) -> &ValencySchema { &self.valencies }
+    #[inline]
+    fn timestamped(&self) -> bool { false }
 }
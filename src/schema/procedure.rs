@@ -0,0 +1,77 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{AssignmentType, TransitionType};
+use crate::LIB_NAME_RGB;
+
+/// Validation procedures embedded into the validator, which schemata may
+/// enable instead of implementing the same rules with scripts (see
+/// [`crate::Schema::procedures`]).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(
+    lib = LIB_NAME_RGB,
+    tags = order,
+    dumb = { Self::NftIssue { token_type: strict_dumb!(), engraving: None } }
+)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub enum EmbeddedProcedure {
+    /// Non-fungible token issuance.
+    ///
+    /// Each token is a structured state of `token_type`, which data start
+    /// with a 32-bit little-endian token id (see
+    /// [`crate::RevealedData::token_id`]). Tokens are issued by genesis and
+    /// state extensions, and each token id must be issued only once across
+    /// the whole contract. State transitions must re-assign exactly the
+    /// tokens they spend, without changing their data, except the `engraving`
+    /// transitions, which may append (engrave) immutable data of the owner
+    /// to the data of the spent tokens.
+    #[display("nft_issue({token_type})")]
+    NftIssue {
+        token_type: AssignmentType,
+        engraving: Option<TransitionType>,
+    },
+
+    /// Verifiable identity of the contract issuer.
+    ///
+    /// Genesis must define a single value of the global state type
+    /// [`super::ISSUER_IDENTITY_ID`] with the identity proof of the issuer
+    /// (see [`crate::IssuerIdentity`]), which signature commits to the rest of
+    /// the genesis. Other operations can't define the identity.
+    #[display("issuer_identity")]
+    IssuerIdentity,
+}
+
+impl EmbeddedProcedure {
+    /// Returns the owned state type holding the tokens, if the procedure
+    /// issues tokens.
+    pub fn token_type(&self) -> Option<AssignmentType> {
+        match self {
+            EmbeddedProcedure::NftIssue { token_type, .. } => Some(*token_type),
+            EmbeddedProcedure::IssuerIdentity => None,
+        }
+    }
+}
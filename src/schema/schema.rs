@@ -23,18 +23,22 @@
 use std::cmp::Ordering;
 use std::str::FromStr;
 
-use amplify::confinement::{TinyOrdMap, TinyOrdSet};
+use amplify::confinement::{SmallBlob, TinyOrdMap, TinyOrdSet};
 use amplify::{Bytes32, RawArray};
 use baid58::{Baid58ParseError, FromBaid58, ToBaid58};
-use commit_verify::{CommitStrategy, CommitmentId};
-use strict_encoding::{StrictDecode, StrictDeserialize, StrictEncode, StrictSerialize, StrictType};
-use strict_types::TypeSystem;
+use commit_verify::{CommitEncode, CommitStrategy, CommitmentId, DigestExt, Sha256};
+use strict_encoding::{
+    StrictDecode, StrictDeserialize, StrictEncode, StrictSerialize, StrictType, TypeName,
+};
+use strict_types::{SemId, TypeSystem};
 
 use super::{
-    AssignmentType, ExtensionSchema, GenesisSchema, Script, StateSchema, TransitionSchema,
-    ValencyType,
+    AssignmentCategory, AssignmentType, EmbeddedProcedure, ExtensionSchema, GenesisSchema,
+    OpFullType, Script, StateCovenant, StateSchema, TransitionSchema, ValencyType,
+};
+use crate::{
+    Ffv, GlobalStateSchema, Occurrences, Transition, VoutDerivation, WitnessPos, LIB_NAME_RGB,
 };
-use crate::{Ffv, GlobalStateSchema, Occurrences, LIB_NAME_RGB};
 
 pub trait SchemaTypeIndex:
     Copy + Eq + Ord + Default + StrictType + StrictEncode + StrictDecode
@@ -45,7 +49,11 @@ impl SchemaTypeIndex for u16 {}
 pub type GlobalStateType = u16;
 pub type ExtensionType = u16;
 pub type TransitionType = u16;
+pub type ConstantType = u16;
 pub const BLANK_TRANSITION_ID: u16 = TransitionType::MAX;
+/// Global state type reserved for the issuer identity proof (see
+/// [`EmbeddedProcedure::IssuerIdentity`]).
+pub const ISSUER_IDENTITY_ID: u16 = GlobalStateType::MAX;
 
 /// Schema identifier.
 ///
@@ -78,6 +86,49 @@ impl SchemaId {
         self.to_baid58()
             .mnemonic_with_case(baid58::MnemonicCase::Kebab)
     }
+
+    /// Detects version of the commitment tag which was used to produce this
+    /// id from the provided schema. Returns `None` if the id does not match
+    /// the schema under any of the known versions.
+    #[inline]
+    pub fn version<Root: SchemaRoot>(&self, schema: &Schema<Root>) -> Option<SchemaIdVersion> {
+        schema.schema_id_version(*self)
+    }
+}
+
+/// Versions of schema identifiers, each of which uses a distinct commitment
+/// tag. Older versions are kept in the registry during transition periods, such
+/// that contracts referencing schemata by their previous ids remain valid.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[display(lowercase)]
+pub enum SchemaIdVersion {
+    /// Schema ids produced with `urn:lnpbp:rgb:schema:v01#202302A` tag.
+    #[default]
+    V1,
+}
+
+impl SchemaIdVersion {
+    /// Version used for producing new schema ids.
+    pub const CURRENT: SchemaIdVersion = SchemaIdVersion::V1;
+
+    /// Registry of all versions which are accepted by the validation.
+    pub const ACCEPTED: &'static [SchemaIdVersion] = &[SchemaIdVersion::V1];
+
+    /// Commitment tag used by the version.
+    pub const fn tag(self) -> [u8; 32] {
+        match self {
+            SchemaIdVersion::V1 => *b"urn:lnpbp:rgb:schema:v01#202302A",
+        }
+    }
+
+    /// Detects whether this version is superseded by a newer one.
+    #[inline]
+    pub fn is_legacy(self) -> bool { self != Self::CURRENT }
 }
 
 impl FromStr for SchemaId {
@@ -91,6 +142,184 @@ impl SchemaRoot for RootSchema {}
 pub type RootSchema = Schema<()>;
 pub type SubSchema = Schema<RootSchema>;
 
+/// Optional human-readable names of the schema types. Names are committed to
+/// by the schema id, but are not used in the validation.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct TypeNames {
+    pub global_types: TinyOrdMap<GlobalStateType, TypeName>,
+    pub owned_types: TinyOrdMap<AssignmentType, TypeName>,
+    pub transitions: TinyOrdMap<TransitionType, TypeName>,
+    pub constants: TinyOrdMap<ConstantType, TypeName>,
+}
+
+/// Constant value declared by the schema, like a maximal supply, precision or
+/// royalty rate. Constants are committed to by the schema id and are
+/// accessible to the validation scripts (see [`crate::vm::ContractOp::LdK`]).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct SchemaConstant {
+    /// Semantic type of the value, which must be present in the schema type
+    /// system.
+    pub sem_id: SemId,
+    /// Strict-encoded value.
+    pub value: SmallBlob,
+}
+
+/// Limits of the contract history size, which allow issuers of contracts with
+/// many operations to bound the cost of the consignment validation.
+///
+/// Depth of an operation is the number of operations in the longest chain
+/// leading from it to the genesis, which has a zero depth. If the consignment
+/// starts from a [`crate::Checkpoint`], the operations spending the
+/// checkpoint state are counted from the checkpoint.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct HistoryLimits {
+    /// Maximal depth of an operation in the contract history.
+    pub max_depth: Option<u32>,
+    /// Maximal number of operations having the same depth.
+    pub max_width: Option<u32>,
+}
+
+impl HistoryLimits {
+    /// Checks that the limits are not weaker than the `root` limits.
+    pub fn is_within(&self, root: &HistoryLimits) -> bool {
+        fn within(limit: Option<u32>, root: Option<u32>) -> bool {
+            match (limit, root) {
+                (_, None) => true,
+                (Some(limit), Some(root)) => limit <= root,
+                (None, Some(_)) => false,
+            }
+        }
+        within(self.max_depth, root.max_depth) && within(self.max_width, root.max_width)
+    }
+}
+
+/// Migration of the owned state from the previous version of the schema,
+/// allowing contracts issued with that version to evolve without re-issuance.
+///
+/// Operations created under the previous schema version are validated against
+/// the upgraded schema with their owned state types replaced according to the
+/// migration map. Transitions spending the migrated state additionally run the
+/// [`crate::vm::EntryPoint::MigrateOwnedState`] script entry point, which may
+/// check the transformation of the state values between the versions.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct SchemaMigration {
+    /// Id of the previous schema version.
+    pub from: SchemaId,
+    /// Owned state types of the previous schema version mapped to the types
+    /// of the upgraded schema.
+    pub owned_types: TinyOrdMap<AssignmentType, AssignmentType>,
+}
+
+impl SchemaMigration {
+    /// Returns the type replacing the owned state type `ty` of the previous
+    /// schema version.
+    pub fn migrate(&self, ty: AssignmentType) -> AssignmentType {
+        self.owned_types.get(&ty).copied().unwrap_or(ty)
+    }
+}
+
+/// Point starting from which operations of a deprecated type are invalid.
+/// Before the sunset the operations remain valid, but the validation warns
+/// about their deprecation.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB, tags = order, dumb = { Sunset::Ffv(Ffv::LATEST) })]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub enum Sunset {
+    /// Operations having the given or a newer fast-forward version are
+    /// invalid.
+    Ffv(Ffv),
+
+    /// Operations with witness transactions mined in a block with the given
+    /// or a later UNIX timestamp are invalid. Since state extensions and
+    /// operations with unmined witnesses have no position in the blockchain,
+    /// they are never sunset by time.
+    Time(i64),
+}
+
+impl Sunset {
+    /// Checks whether an operation with the given version and the position of
+    /// its witness transaction is past the sunset.
+    pub fn is_reached(self, ffv: Ffv, pos: Option<WitnessPos>) -> bool {
+        match (self, pos) {
+            (Sunset::Ffv(sunset), _) => ffv >= sunset,
+            (Sunset::Time(time), Some(pos)) => pos.timestamp >= time,
+            (Sunset::Time(_), None) => false,
+        }
+    }
+}
+
+/// Transition and extension types deprecated by the root schema, which are
+/// going to be removed from the future versions of the schema.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct SchemaDeprecations {
+    pub transitions: TinyOrdMap<TransitionType, Sunset>,
+    pub extensions: TinyOrdMap<ExtensionType, Sunset>,
+}
+
+impl SchemaDeprecations {
+    /// Returns sunset of the operation type, if the type is deprecated.
+    pub fn sunset(&self, op_type: OpFullType) -> Option<Sunset> {
+        match op_type {
+            OpFullType::Genesis => None,
+            OpFullType::StateTransition(ty) => self.transitions.get(&ty).copied(),
+            OpFullType::StateExtension(ty) => self.extensions.get(&ty).copied(),
+        }
+    }
+
+    /// Iterates over the deprecated operation types and their sunsets.
+    pub fn iter(&self) -> impl Iterator<Item = (OpFullType, Sunset)> + '_ {
+        let transitions = self
+            .transitions
+            .iter()
+            .map(|(ty, sunset)| (OpFullType::StateTransition(*ty), *sunset));
+        let extensions = self
+            .extensions
+            .iter()
+            .map(|(ty, sunset)| (OpFullType::StateExtension(*ty), *sunset));
+        transitions.chain(extensions)
+    }
+}
+
 #[derive(Clone, Eq, Default, Debug)]
 #[derive(StrictType, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_RGB)]
@@ -105,10 +334,77 @@ pub struct Schema<Root: SchemaRoot> {
 
     pub global_types: TinyOrdMap<GlobalStateType, GlobalStateSchema>,
     pub owned_types: TinyOrdMap<AssignmentType, StateSchema>,
+    /// Minimal depth (in blocks) which the witness transaction creating a seal
+    /// for the given assignment type must have before the seal can be spent,
+    /// similar to the maturity of bitcoin coinbase outputs.
+    pub maturity: TinyOrdMap<AssignmentType, u16>,
+    /// Derivation of the output number for seals of the given assignment type
+    /// which reference a whole transaction (see [`crate::VOUT_DERIVED`]).
+    /// Such seals are invalid for assignment types lacking the derivation.
+    pub vout_derivation: TinyOrdMap<AssignmentType, VoutDerivation>,
+    /// Assignment types which are bound to a public key instead of a seal
+    /// (key rights). The state of such assignments must be structured data
+    /// containing a 32-byte x-only public key; the seals of the assignments
+    /// are ignored and the witness transaction is not required to close them.
+    /// Spending a key right requires a BIP340 signature of the transition
+    /// signing id with the key, which is checked by the validator: the
+    /// metadata of the spending transition (following the timestamp proof, if
+    /// any) must start with the signatures for all spent key rights, ordered
+    /// by the key right type and then by the input.
+    ///
+    /// Unlike seals, keys don't provide single-use guarantees: the same key
+    /// right may be spent by several transitions. Thus, key rights are
+    /// suitable for attestations and identity-style contracts, but not for
+    /// the scarce state.
+    pub key_rights: TinyOrdSet<AssignmentType>,
+    /// Declarative owned rights requiring a threshold of the rights to be
+    /// spent together. A transition spending a threshold right must spend at
+    /// least the given number of the rights of that type, which may be
+    /// assigned by any of the parent operations. This allows N-of-M control
+    /// of the rights, like a multi-party issuance.
+    pub thresholds: TinyOrdMap<AssignmentType, u8>,
+    /// Categories of the owned state types. Types lacking the category are
+    /// not subject to the category-specific validation rules.
+    pub categories: TinyOrdMap<AssignmentType, AssignmentCategory>,
+    /// Conditional transfer rights (covenants) of the owned state types.
+    pub covenants: TinyOrdMap<AssignmentType, StateCovenant>,
+    /// Validation procedures embedded into the validator, which are enabled by
+    /// the schema.
+    pub procedures: TinyOrdSet<EmbeddedProcedure>,
     pub valency_types: TinyOrdSet<ValencyType>,
     pub genesis: GenesisSchema,
     pub extensions: TinyOrdMap<ExtensionType, ExtensionSchema>,
     pub transitions: TinyOrdMap<TransitionType, TransitionSchema>,
+    /// Provisional (state channel) transition types, mapped to the global
+    /// state type holding the revocation number of the transition. Versions
+    /// of a provisional transition spending the same state replace each
+    /// other, such that only the version with the highest revocation number
+    /// is valid.
+    pub provisional: TinyOrdMap<TransitionType, GlobalStateType>,
+    /// Human-readable names of the schema types.
+    pub names: TypeNames,
+    /// Allows consignments to start from a [`crate::Checkpoint`] instead of
+    /// the full contract history.
+    ///
+    /// The compacted history can't be verified, so the validator accepts only
+    /// the checkpoints trusted by the caller (see
+    /// [`crate::validation::ValidatorConfig::trusted_checkpoints`]).
+    pub checkpoints: bool,
+    /// Limits of the contract history size, enforced by the validator.
+    pub history_limits: HistoryLimits,
+    /// Migration from the previous schema version, if the schema upgrades it.
+    pub migration: Option<SchemaMigration>,
+    /// Operation types deprecated by the root schema. Subschemata must repeat
+    /// the deprecations of their root schema.
+    pub deprecations: SchemaDeprecations,
+    /// Typed constants accessible to the validation scripts.
+    pub constants: TinyOrdMap<ConstantType, SchemaConstant>,
+    /// Plug-in points: valencies which may be redeemed only by state
+    /// extensions governed by a foreign schema with the given id. Such
+    /// extensions are validated against the rules of the foreign schema,
+    /// which must be provided by the consignment (see
+    /// [`crate::validation::ConsignmentApi::plugin_schema`]).
+    pub plugins: TinyOrdMap<ValencyType, SchemaId>,
 
     /// Type system
     pub type_system: TypeSystem,
@@ -133,7 +429,7 @@ impl<Root: SchemaRoot> CommitStrategy for Schema<Root> {
 }
 
 impl<Root: SchemaRoot> CommitmentId for Schema<Root> {
-    const TAG: [u8; 32] = *b"urn:lnpbp:rgb:schema:v01#202302A";
+    const TAG: [u8; 32] = SchemaIdVersion::CURRENT.tag();
     type Id = SchemaId;
 }
 
@@ -144,6 +440,50 @@ impl<Root: SchemaRoot> Schema<Root> {
     #[inline]
     pub fn schema_id(&self) -> SchemaId { self.commitment_id() }
 
+    /// Computes schema id using commitment tag of a specific version.
+    pub fn schema_id_versioned(&self, version: SchemaIdVersion) -> SchemaId {
+        let mut engine = Sha256::from_tag(version.tag());
+        self.commit_encode(&mut engine);
+        engine.finish().into()
+    }
+
+    /// Detects which of the accepted id versions produces the provided id for
+    /// this schema, if any.
+    pub fn schema_id_version(&self, id: SchemaId) -> Option<SchemaIdVersion> {
+        SchemaIdVersion::ACCEPTED
+            .iter()
+            .copied()
+            .find(|version| self.schema_id_versioned(*version) == id)
+    }
+
+    /// Returns the owned state type of this schema replacing the type `ty`,
+    /// which may be defined by the previous schema version (see
+    /// [`SchemaMigration`]).
+    pub fn migrated_type(&self, ty: AssignmentType) -> AssignmentType {
+        self.migration
+            .as_ref()
+            .map(|migration| migration.migrate(ty))
+            .unwrap_or(ty)
+    }
+
+    /// Returns revocation number of a provisional transition, or `None` if
+    /// the transition type is not provisional or the revocation number is not
+    /// a little-endian 64-bit integer.
+    pub fn revocation_number(&self, transition: &Transition) -> Option<u64> {
+        let ty = self.provisional.get(&transition.transition_type)?;
+        let values = transition.globals.get(ty)?;
+        values.first()?.as_counter()
+    }
+
+    /// Checks whether the schema defines the operation type.
+    pub fn defines(&self, op_type: OpFullType) -> bool {
+        match op_type {
+            OpFullType::Genesis => true,
+            OpFullType::StateTransition(ty) => self.transitions.contains_key(&ty),
+            OpFullType::StateExtension(ty) => self.extensions.contains_key(&ty),
+        }
+    }
+
     pub fn blank_transition(&self) -> TransitionSchema {
         let mut schema = TransitionSchema::default();
         for id in self.owned_types.keys() {
@@ -159,6 +499,8 @@ mod test {
     use strict_encoding::StrictDumb;
 
     use super::*;
+    use crate::validation::Failure;
+    use crate::RevealedData;
 
     #[test]
     fn display() {
@@ -172,4 +514,101 @@ mod test {
         assert_eq!(&format!("{less_dumb::^#}"), "5ffNUkMTVSnWquPLT6xKb7VmAxUbw8CUNqCkUWsZfkwz");
         assert_eq!(less_dumb.mnemonic_checksum(), "salami-comedy-cello");
     }
+
+    #[test]
+    fn versions() {
+        let schema = RootSchema::default();
+        let id = schema.schema_id();
+        assert_eq!(schema.schema_id_versioned(SchemaIdVersion::CURRENT), id);
+        assert_eq!(id.version(&schema), Some(SchemaIdVersion::CURRENT));
+        assert_eq!(SchemaId::strict_dumb().version(&schema), None);
+    }
+
+    #[test]
+    fn provisional() {
+        let mut schema = SubSchema {
+            transitions: tiny_bmap! {
+                1 => TransitionSchema {
+                    globals: tiny_bmap! { 2 => Occurrences::Once },
+                    ..default!()
+                },
+            },
+            provisional: tiny_bmap! { 1 => 2 },
+            ..default!()
+        };
+        let mut transition = Transition::strict_dumb();
+        transition.transition_type = 1;
+        assert_eq!(schema.revocation_number(&transition), None);
+        transition
+            .globals
+            .add_state(2, RevealedData::with_counter(5))
+            .unwrap();
+        assert_eq!(schema.revocation_number(&transition), Some(5));
+
+        schema.provisional.insert(3, 2).unwrap();
+        let failures = schema.verify().failures;
+        assert!(failures.contains(&Failure::SchemaProvisionalInvalid(3)));
+        assert!(!failures.contains(&Failure::SchemaProvisionalInvalid(1)));
+    }
+
+    #[test]
+    fn migration() {
+        let schema = SubSchema {
+            owned_types: tiny_bmap! { 2 => StateSchema::Declarative },
+            migration: Some(SchemaMigration {
+                from: SchemaId::strict_dumb(),
+                owned_types: tiny_bmap! { 1 => 2, 3 => 4 },
+            }),
+            ..default!()
+        };
+        assert_eq!(schema.migrated_type(1), 2);
+        assert_eq!(schema.migrated_type(2), 2);
+        assert_eq!(schema.migrated_type(5), 5);
+
+        let failures = schema.verify().failures;
+        assert!(failures.contains(&Failure::SchemaMigrationInvalid(3, 4)));
+        assert!(!failures.contains(&Failure::SchemaMigrationInvalid(1, 2)));
+    }
+
+    #[test]
+    fn plugins() {
+        let schema = SubSchema {
+            valency_types: tiny_bset! { 1 },
+            plugins: tiny_bmap! { 1 => SchemaId::strict_dumb(), 2 => SchemaId::strict_dumb() },
+            ..default!()
+        };
+        let failures = schema.verify().failures;
+        assert!(failures.contains(&Failure::SchemaPluginInvalid(2)));
+        assert!(!failures.contains(&Failure::SchemaPluginInvalid(1)));
+    }
+
+    #[test]
+    fn constants() {
+        let sem_id = SemId::strict_dumb();
+        let schema = SubSchema {
+            constants: tiny_bmap! {
+                1 => SchemaConstant { sem_id, value: small_vec![1u8, 2] },
+            },
+            ..default!()
+        };
+        let failures = schema.verify().failures;
+        assert!(failures.contains(&Failure::SchemaConstantInvalid(1, sem_id)));
+    }
+
+    #[test]
+    fn history_limits() {
+        let root = HistoryLimits {
+            max_depth: Some(100),
+            max_width: None,
+        };
+        assert!(HistoryLimits::default().is_within(&HistoryLimits::default()));
+        assert!(!HistoryLimits::default().is_within(&root));
+        assert!(root.is_within(&HistoryLimits::default()));
+        let stricter = HistoryLimits {
+            max_depth: Some(10),
+            max_width: Some(10),
+        };
+        assert!(stricter.is_within(&root));
+        assert!(!root.is_within(&stricter));
+    }
 }
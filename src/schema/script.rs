@@ -31,10 +31,19 @@ use crate::LIB_NAME_RGB;
 /// Virtual machine types.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
 #[display(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
 pub enum VmType {
     /// AluVM: pure functional register-based virtual machine designed for RGB
     /// and multiparty computing.
     AluVM,
+
+    /// Legacy embedded virtual machine, which validates contract operations
+    /// only with the rules built into the validator.
+    Embedded,
 }
 
 /// Virtual machine and machine-specific script data.
@@ -57,6 +66,14 @@ pub enum Script {
     /// Its routines can be accessed only through well-typed ABI entrance
     /// pointers, defined as a part of the schema.
     AluVM(AluScript),
+
+    /// Legacy embedded virtual machine: contract operations are validated
+    /// against the schema rules built into the validator, without running any
+    /// script code.
+    ///
+    /// Supported only for validating historical contracts (see
+    /// [`crate::validation::ScriptPolicy`]); new schemata must use AluVM.
+    Embedded,
 }
 
 impl Default for Script {
@@ -67,6 +84,7 @@ impl Script {
     pub fn vm_type(&self) -> VmType {
         match self {
             Script::AluVM(_) => VmType::AluVM,
+            Script::Embedded => VmType::Embedded,
         }
     }
 }
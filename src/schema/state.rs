@@ -20,10 +20,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use strict_encoding::constants::U64;
+use strict_encoding::constants::{U128, U64};
 use strict_types::SemId;
 
-use crate::{StateType, LIB_NAME_RGB};
+use crate::{AssignmentType, StateType, WitnessPos, LIB_NAME_RGB};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
@@ -64,6 +64,9 @@ pub enum StateSchema {
     Fungible(FungibleType),
     Structured(SemId),
     Attachment(MediaType),
+    /// Monotonic counter, represented by structured state data holding a
+    /// little-endian 64-bit unsigned integer.
+    Counter(CounterSchema),
 }
 
 impl StateSchema {
@@ -71,17 +74,109 @@ impl StateSchema {
         match self {
             StateSchema::Declarative => StateType::Void,
             StateSchema::Fungible(_) => StateType::Fungible,
-            StateSchema::Structured(_) => StateType::Structured,
+            StateSchema::Structured(_) | StateSchema::Counter(_) => StateType::Structured,
             StateSchema::Attachment(_) => StateType::Attachment,
         }
     }
 }
 
-/// Today we support only a single format of confidential data, because of the
-/// limitations of the underlying secp256k1-zkp library: it works only with
-/// u64 numbers. Nevertheless, homomorphic commitments can be created to
-/// everything that has up to 256 bits and commutative arithmetics, so in the
-/// future we plan to support more types. We reserve this possibility by
+/// Category of an owned state type, declared by the schema (see
+/// [`crate::Schema::categories`]). The validator applies category-specific
+/// sanity rules to the state, and user interfaces may use the category to
+/// render the state correctly.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB, tags = repr, into_u8, try_from_u8)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[display(lowercase)]
+#[repr(u8)]
+pub enum AssignmentCategory {
+    /// Value-bearing (money-like) fungible state. Blank state transitions
+    /// must move such state without changing its total amount.
+    #[strict_type(dumb)]
+    Value = 0,
+
+    /// Control right, like a right to issue more tokens or to update the
+    /// contract data; must be a declarative state or a key right.
+    Right = 1,
+
+    /// Data, which must be structured state, counter or attachment.
+    Data = 2,
+}
+
+/// Conditional transfer rights of an owned state type, declared by the schema
+/// (see [`crate::Schema::covenants`]) and enforced when the state is spent by
+/// a state transition.
+///
+/// Blank transitions are not subject to the transfer restrictions, since they
+/// only keep the state of the contract when the seal is closed by a witness
+/// transaction of another contract.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct StateCovenant {
+    /// The state can't be re-assigned by a transition spending it, so it can
+    /// only be destroyed (like soulbound tokens).
+    pub non_transferable: bool,
+    /// The state can be re-assigned by a transition only once: the state
+    /// assigned by a transition spending the state issued by genesis or an
+    /// extension becomes non-transferable.
+    pub transferable_once: bool,
+    /// Owned right type which must be spent by a transition committed into
+    /// the same bundle as each transition spending the state, such that the
+    /// transfer is co-signed by the holder of the right.
+    pub cosign: Option<AssignmentType>,
+}
+
+/// Bounds of a monotonic counter (accumulator) state.
+///
+/// Counter values must always stay within `min..=max` range. A state
+/// transition may assign a counter only with a value which is not less than
+/// the largest value of the same counter type spent by the transition, and
+/// which exceeds it by no more than `step`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub struct CounterSchema {
+    /// Minimal allowed counter value.
+    pub min: u64,
+    /// Maximal allowed counter value.
+    pub max: u64,
+    /// Maximal increment of the counter by a single state transition.
+    pub step: u64,
+}
+
+impl CounterSchema {
+    /// Checks whether the counter value is within the bounds.
+    pub fn contains(&self, value: u64) -> bool { (self.min..=self.max).contains(&value) }
+
+    /// Checks whether the counter may be changed from `prev` to `value` by a
+    /// single state transition.
+    pub fn allows_step(&self, prev: u64, value: u64) -> bool {
+        value >= prev && value - prev <= self.step
+    }
+}
+
+/// Today we support 64- and 128-bit unsigned formats of confidential data. The
+/// underlying secp256k1-zkp library works only with u64 numbers, so 128-bit
+/// values are committed to by combining commitments to their 64-bit halves.
+/// Nevertheless, homomorphic commitments can be created to everything that has
+/// up to 256 bits and commutative arithmetics, so in the future we plan to
+/// support more types. We reserve this possibility by
 /// internally encoding [`ConfidentialFormat`] with the same type specification
 /// details as used for [`DateFormat`]
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Display)]
@@ -97,6 +192,9 @@ pub enum FungibleType {
     #[default]
     #[display("64bit")]
     Unsigned64Bit = U64.into_code(),
+
+    #[display("128bit")]
+    Unsigned128Bit = U128.into_code(),
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
@@ -110,6 +208,7 @@ pub enum FungibleType {
 pub struct GlobalStateSchema {
     pub sem_id: SemId,
     pub max_items: u16,
+    pub expiry: GlobalExpiry,
 }
 
 impl GlobalStateSchema {
@@ -117,6 +216,7 @@ impl GlobalStateSchema {
         GlobalStateSchema {
             sem_id,
             max_items: 1,
+            expiry: GlobalExpiry::Never,
         }
     }
 
@@ -124,6 +224,68 @@ impl GlobalStateSchema {
         GlobalStateSchema {
             sem_id,
             max_items: u16::MAX,
+            expiry: GlobalExpiry::Never,
+        }
+    }
+
+    pub fn expiring(mut self, expiry: GlobalExpiry) -> Self {
+        self.expiry = expiry;
+        self
+    }
+}
+
+/// Expiry of the global state entries of a specific type.
+///
+/// Expired entries are not a part of the contract state: they are excluded
+/// from [`crate::ContractState::global_view_at`], are not visible to the
+/// scripts validating operations with witness transactions mined after the
+/// expiry, and operations with such witnesses can't define new entries of the
+/// expired type.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_RGB, tags = order)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+pub enum GlobalExpiry {
+    /// State entries never expire.
+    #[default]
+    Never,
+
+    /// State entries are valid until the block with the given height, i.e.
+    /// they expire starting from that height.
+    Height(u32),
+
+    /// State entries are valid until the block with the given UNIX
+    /// timestamp, i.e. they expire starting from that timestamp.
+    Time(i64),
+}
+
+impl GlobalExpiry {
+    /// Checks whether the state has expired at the given position in the
+    /// blockchain.
+    pub fn is_expired(self, pos: WitnessPos) -> bool {
+        match self {
+            GlobalExpiry::Never => false,
+            GlobalExpiry::Height(height) => pos.height >= height,
+            GlobalExpiry::Time(time) => pos.timestamp >= time,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expiry() {
+        let pos = WitnessPos::new(800_000, 1_690_000_000);
+        assert!(!GlobalExpiry::Never.is_expired(pos));
+        assert!(!GlobalExpiry::Height(800_001).is_expired(pos));
+        assert!(GlobalExpiry::Height(800_000).is_expired(pos));
+        assert!(!GlobalExpiry::Time(1_690_000_001).is_expired(pos));
+        assert!(GlobalExpiry::Time(1_600_000_000).is_expired(pos));
+    }
+}
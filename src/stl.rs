@@ -23,17 +23,20 @@
 use aluvm::stl::aluvm_stl;
 use bp::bc::stl::bitcoin_stl;
 use bp::stl::bp_core_stl;
-use strict_types::stl::strict_types_stl;
+use strict_types::stl::{std_stl, strict_types_stl};
 use strict_types::typelib::LibBuilder;
 use strict_types::{CompileError, TypeLib};
 
-use crate::{Extension, Genesis, SubSchema, TransitionBundle, LIB_NAME_RGB};
+pub use crate::LIB_ID_RGB;
+use crate::{
+    Checkpoint, Endorsement, Extension, Genesis, SchemaBindle, SubSchema, TransitionBundle,
+    LIB_NAME_RGB,
+};
 
-/// Strict types id for the library providing data types for RGB consensus.
-pub const LIB_ID_RGB: &str = "fiber_deal_falcon_3NhgEBNcHTwSGZ1zeoGvebAAZrkhxio9HEkc7dYUjaAF";
 
 fn _rgb_core_stl() -> Result<TypeLib, CompileError> {
     LibBuilder::new(libname!(LIB_NAME_RGB), tiny_bset! {
+        std_stl().to_dependency(),
         strict_types_stl().to_dependency(),
         bitcoin_stl().to_dependency(),
         bp_core_stl().to_dependency(),
@@ -43,6 +46,9 @@ fn _rgb_core_stl() -> Result<TypeLib, CompileError> {
     .transpile::<Genesis>()
     .transpile::<TransitionBundle>()
     .transpile::<Extension>()
+    .transpile::<Checkpoint>()
+    .transpile::<Endorsement>()
+    .transpile::<SchemaBindle>()
     .compile()
 }
 
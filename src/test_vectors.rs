@@ -0,0 +1,323 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Golden test vectors: canonical strict encodings and identifiers of a
+//! reference schema, genesis, state transition and transition bundle. All the
+//! data are fully deterministic (no random blinding), so alternative
+//! implementations and downstream crates can check their binary compatibility
+//! with a specific release of RGB Core.
+//!
+//! Consignments are defined outside of the consensus layer; the transition
+//! bundle is the largest consensus data structure covered by these vectors.
+
+use amplify::confinement::SmallBlob;
+use amplify::hex::ToHex;
+use bp::seals::txout::CloseMethod;
+use bp::{Chain, Txid};
+use strict_encoding::{StrictEncode, StrictWriter};
+
+use crate::schema::{
+    AssignmentType, FungibleType, GenesisSchema, Occurrences, StateSchema, TransitionSchema,
+    TransitionType,
+};
+use crate::{
+    Assign, Assignments, BlindingFactor, BundleItem, ExposedSeal, Ffv, Genesis, GenesisSeal,
+    GlobalState, GraphSeal, Input, Inputs, OpFeatures, Operation, Opout, RevealedValue, SubSchema,
+    Transition, TransitionBundle, TypedAssigns, Valencies,
+};
+
+/// Assignment type used by the reference schema for the fungible state.
+pub const ASSIGNMENT_TYPE: AssignmentType = 1;
+/// Transition type used by the reference schema.
+pub const TRANSITION_TYPE: TransitionType = 1;
+/// Amount issued in the reference genesis and moved by the reference
+/// transition.
+pub const AMOUNT: u64 = 100;
+
+/// Identifier of the [`schema`].
+pub const SCHEMA_ID: &str = "8C9He3Bo7Z8ZqpanT6SRwD1Zu7SkvqPQQvsvw31oZQjp";
+/// Identifier of the contract created by the [`genesis`].
+pub const CONTRACT_ID: &str = "FabricAnalogTower04pZUDftYhPDMACwwTCErg1Cu5tNDpowKzirVvSNeKxCV";
+/// Identifier of the [`transition`].
+pub const TRANSITION_ID: &str = "0b78cae5beb8698b3c3f942f4e9962033d2596515ff7012d371a6af4c865ba8a";
+/// Identifier of the [`bundle`].
+pub const BUNDLE_ID: &str = "460adce35f6e09e4c702e6690907d7db9037323d15ba0a09444f8cf5cdb6e9f7";
+
+/// Hex strict encoding of the [`schema`].
+pub const SCHEMA_HEX: &str = concat!(
+    "0000000001010001080000000000000000d83fbee02f0de5b46cf80fe11ef7fdf061c78d975d31ad",
+    "e9eea2bc4099339e6c0001010001000100000000010100d83fbee02f0de5b46cf80fe11ef7fdf061",
+    "c78d975d31ade9eea2bc4099339e6c000101000100ffff0101000100ffff00000000000000000000",
+    "0000000000000000000000",
+);
+/// Hex strict encoding of the [`genesis`].
+pub const GENESIS_HEX: &str = concat!(
+    "000000006adc913fd806c7749ed44cc31f1cc55e9f130cb557acf26212ae55c60d643e4f80000000",
+    "01010001010003011111111111111111111111111111111111111111111111111111111111111111",
+    "00000000080706050403020108640000000000000001010101010101010101010101010101010101",
+    "0101010101010101010101010100",
+);
+/// Hex strict encoding of the [`transition`].
+pub const TRANSITION_HEX: &str = concat!(
+    "0000000038c2126ac64892cf56cff44163fbb0305d8f45e45d9313de0fd4610c3aa1e05601000000",
+    "000138c2126ac64892cf56cff44163fbb0305d8f45e45d9313de0fd4610c3aa1e056010000000001",
+    "01000101000301000100000001020304050607080864000000000000000202020202020202020202",
+    "02020202020202020202020202020202020202020200",
+);
+/// Hex strict encoding of the [`bundle`].
+pub const BUNDLE_HEX: &str = concat!(
+    "010b78cae5beb8698b3c3f942f4e9962033d2596515ff7012d371a6af4c865ba8a01000001000000",
+    "0038c2126ac64892cf56cff44163fbb0305d8f45e45d9313de0fd4610c3aa1e05601000000000138",
+    "c2126ac64892cf56cff44163fbb0305d8f45e45d9313de0fd4610c3aa1e056010000000001010001",
+    "01000301000100000001020304050607080864000000000000000202020202020202020202020202",
+    "02020202020202020202020202020202020200",
+);
+
+fn blinding(byte: u8) -> BlindingFactor {
+    BlindingFactor::try_from([byte; 32]).expect("hardcoded blinding factor")
+}
+
+fn value(byte: u8) -> RevealedValue { RevealedValue::with(AMOUNT, blinding(byte)) }
+
+fn typed_assigns<Seal: ExposedSeal>(seal: Seal, state: RevealedValue) -> Assignments<Seal> {
+    let assign = TypedAssigns::Fungible(small_vec![Assign::revealed(seal, state)]);
+    Assignments::from(tiny_bmap! { ASSIGNMENT_TYPE => assign })
+}
+
+/// Reference schema with a single fungible assignment type, issued in genesis
+/// and moved by a single transition type.
+pub fn schema() -> SubSchema {
+    SubSchema {
+        ffv: Ffv::default(),
+        subset_of: None,
+        owned_types: tiny_bmap! {
+            ASSIGNMENT_TYPE => StateSchema::Fungible(FungibleType::Unsigned64Bit),
+        },
+        genesis: GenesisSchema {
+            assignments: tiny_bmap! { ASSIGNMENT_TYPE => Occurrences::Once },
+            ..default!()
+        },
+        transitions: tiny_bmap! {
+            TRANSITION_TYPE => TransitionSchema {
+                inputs: tiny_bmap! { ASSIGNMENT_TYPE => Occurrences::OnceOrMore },
+                assignments: tiny_bmap! { ASSIGNMENT_TYPE => Occurrences::OnceOrMore },
+                ..default!()
+            },
+        },
+        ..default!()
+    }
+}
+
+/// Reference genesis issuing [`AMOUNT`] to a seal with a fixed blinding.
+pub fn genesis() -> Genesis {
+    let seal = GenesisSeal::with_blinding(
+        CloseMethod::TapretFirst,
+        Txid::from([0x11; 32]),
+        0,
+        0x0102030405060708,
+    );
+    Genesis {
+        ffv: Ffv::default(),
+        features: OpFeatures::NONE,
+        schema_id: schema().schema_id(),
+        chain: Chain::Regtest,
+        metadata: SmallBlob::default(),
+        globals: GlobalState::default(),
+        assignments: typed_assigns(seal, value(0x01)),
+        valencies: Valencies::default(),
+    }
+}
+
+/// Reference transition spending the output of the [`genesis`] to a seal
+/// defined relatively to the witness transaction.
+pub fn transition() -> Transition {
+    let genesis = genesis();
+    let input = Input::with(Opout::new(genesis.id(), ASSIGNMENT_TYPE, 0));
+    let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 1, 0x0807060504030201);
+    Transition {
+        ffv: Ffv::default(),
+        features: OpFeatures::NONE,
+        contract_id: genesis.contract_id(),
+        transition_type: TRANSITION_TYPE,
+        metadata: SmallBlob::default(),
+        globals: GlobalState::default(),
+        inputs: Inputs::from(tiny_bset![input]),
+        assignments: typed_assigns(seal, value(0x02)),
+        valencies: Valencies::default(),
+    }
+}
+
+/// Reference bundle containing the [`transition`] under input number 0.
+pub fn bundle() -> TransitionBundle {
+    let transition = transition();
+    TransitionBundle::from(tiny_bmap! {
+        transition.id() => BundleItem {
+            inputs: tiny_bset![0],
+            transition: Some(transition),
+        },
+    })
+}
+
+/// Produces hex of the canonical strict encoding of the value, as used by the
+/// `*_HEX` constants of this module.
+pub fn to_hex(value: &impl StrictEncode) -> String {
+    value
+        .strict_encode(StrictWriter::in_memory(usize::MAX))
+        .expect("in-memory encoding must not fail")
+        .unbox()
+        .to_hex()
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+    use crate::{ContractId, ContractIdMismatch, PrevalidationMismatch};
+
+    #[test]
+    fn golden_ids() {
+        assert_eq!(schema().schema_id().to_string(), SCHEMA_ID);
+        assert_eq!(genesis().contract_id().to_string(), CONTRACT_ID);
+        assert_eq!(transition().id().to_string(), TRANSITION_ID);
+        assert_eq!(bundle().bundle_id().to_string(), BUNDLE_ID);
+    }
+
+    #[test]
+    fn contract_id_derivation() {
+        let genesis = genesis();
+        let contract_id = ContractId::from_str(CONTRACT_ID).unwrap();
+        assert_eq!(ContractId::from_genesis(&genesis), contract_id);
+        assert_eq!(contract_id.check_genesis(&genesis), Ok(()));
+
+        // Contract id commits to the chain
+        let mut testnet = genesis;
+        testnet.chain = Chain::Testnet3;
+        let actual = ContractId::from_genesis(&testnet);
+        assert_ne!(actual, contract_id);
+        assert_eq!(
+            contract_id.check_genesis(&testnet),
+            Err(ContractIdMismatch {
+                claimed: contract_id,
+                actual
+            })
+        );
+    }
+
+    #[test]
+    fn prevalidation_id() {
+        let transition = transition();
+        let agreed = transition.prevalidation_id();
+        assert_eq!(transition.check_prevalidation(agreed), Ok(()));
+
+        // Seals don't affect the pre-validation id
+        let mut anchored = transition.clone();
+        let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 3, 0x0102030405060708);
+        anchored.assignments = typed_assigns(seal, value(0x02));
+        assert_ne!(anchored.id(), transition.id());
+        assert_eq!(anchored.check_prevalidation(agreed), Ok(()));
+
+        let mut changed = anchored;
+        changed.assignments = typed_assigns(seal, value(0x03));
+        let actual = changed.prevalidation_id();
+        assert_ne!(actual, agreed);
+        assert_eq!(
+            changed.check_prevalidation(agreed),
+            Err(PrevalidationMismatch { agreed, actual })
+        );
+    }
+
+    #[test]
+    fn commit_dump() {
+        use amplify::Wrapper;
+
+        use crate::commit::CommitDump;
+
+        let genesis = genesis();
+        let trace = genesis.commit_dump();
+        assert_eq!(trace.id, genesis.contract_id().into_inner());
+        assert_eq!(transition().commit_dump().id, transition().id().into_inner());
+        assert_eq!(bundle().commit_dump().id, bundle().bundle_id().into_inner());
+
+        let mut sparse = transition();
+        sparse.ffv = Ffv::SPARSE_ASSIGNMENTS;
+        assert_ne!(sparse.id(), transition().id());
+        assert_eq!(sparse.commit_dump().id, sparse.id().into_inner());
+        let data = sparse
+            .to_strict_serialized::<{ u16::MAX as usize }>()
+            .unwrap();
+        let decoded = Transition::from_strict_serialized::<{ u16::MAX as usize }>(data).unwrap();
+        assert_eq!(decoded.id(), sparse.id());
+
+        let mut changed = genesis;
+        changed.metadata = small_vec![1u8];
+        let other = changed.commit_dump();
+        assert_eq!(other.diverges_at(&trace).unwrap().field, "metadata");
+        assert_eq!(trace.diverges_at(&trace), None);
+    }
+
+    #[test]
+    fn golden_encodings() {
+        assert_eq!(to_hex(&schema()), SCHEMA_HEX);
+        assert_eq!(to_hex(&genesis()), GENESIS_HEX);
+        assert_eq!(to_hex(&transition()), TRANSITION_HEX);
+        assert_eq!(to_hex(&bundle()), BUNDLE_HEX);
+    }
+
+    #[test]
+    fn assignments_with_schema() {
+        let schema = schema();
+        let genesis = genesis();
+        let mut iter = genesis.assignments.iter_with_schema(&schema);
+        let (ty, state_schema, assigns) = iter.next().unwrap();
+        assert_eq!(ty, ASSIGNMENT_TYPE);
+        assert_eq!(state_schema, &StateSchema::Fungible(FungibleType::Unsigned64Bit));
+        assert_eq!(assigns.as_fungible().len(), 1);
+        assert!(iter.next().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip<T>(value: T) -> serde_json::Value
+    where T: serde::Serialize + serde::de::DeserializeOwned + StrictEncode {
+        let json = serde_json::to_value(&value).unwrap();
+        let decoded: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(to_hex(&decoded), to_hex(&value));
+        json
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde() {
+        let json = serde_roundtrip(schema());
+        assert!(json.get("ownedTypes").is_some());
+        let json = serde_roundtrip(genesis());
+        assert!(json.get("schemaId").is_some());
+        serde_roundtrip(genesis().assignments);
+        let json = serde_roundtrip(transition());
+        assert!(json.get("transitionType").is_some());
+        assert!(json.get("contractId").is_some());
+        serde_roundtrip(transition().assignments);
+        serde_roundtrip(bundle());
+    }
+}
@@ -0,0 +1,620 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic test harness, allowing to run the full contract validation
+//! without a bitcoin node.
+//!
+//! [`FakeChain`] is a synthetic blockchain acting as a transaction resolver,
+//! which assigns deterministic ids, heights and block times to the
+//! transactions added to it. It also forges witness transactions with opret
+//! commitments and the anchors proving them, such that the consignments
+//! produced with [`FakeConsignment`] pass the validation of the witnesses.
+//!
+//! Witness transactions forged by the chain close seals using
+//! [`CloseMethod::OpretFirst`]: the first output of each witness transaction
+//! is the commitment output, followed by the outputs which can be referenced
+//! by the seals of the anchored transitions.
+//!
+//! [`CloseMethod::OpretFirst`]: bp::seals::txout::CloseMethod::OpretFirst
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use amplify::confinement::Confined;
+use bp::dbc::{self, Anchor};
+use bp::{LockTime, Outpoint, ScriptPubkey, SeqNo, Tx, TxIn, TxOut, Txid, Witness};
+use commit_verify::{mpc, DigestExt, Sha256};
+
+use crate::validation::{AnchoredBundle, ConsignmentApi, ResolveTx, TxResolverError};
+use crate::{
+    BundleId, BundleItem, Checkpoint, ContractId, Extension, Genesis, OpId, OpRef, Operation,
+    SecretSeal, SubSchema, Transition, TransitionBundle,
+};
+
+/// Timestamp of the first block of the [`FakeChain`].
+pub const GENESIS_TIME: i64 = 1_231_006_505;
+/// Time interval between blocks of the [`FakeChain`], in seconds.
+pub const BLOCK_INTERVAL: i64 = 600;
+/// Value of each non-commitment output of the witness transactions forged by
+/// the [`FakeChain`].
+pub const OUTPUT_VALUE: u64 = 1000;
+
+/// Produces transaction id deterministically derived from the seed.
+pub fn fake_txid(seed: u64) -> Txid {
+    let mut engine = Sha256::from_tag(b"urn:lnpbp:rgb:testing:txid#2023");
+    engine.input_raw(&seed.to_le_bytes());
+    Txid::from(engine.finish())
+}
+
+/// Constructs transaction spending the `spent` outpoints, with the first
+/// output committing to the `commitment` with opret and `outputs` number of
+/// other outputs.
+pub fn witness_tx(
+    spent: impl IntoIterator<Item = Outpoint>,
+    commitment: mpc::Commitment,
+    outputs: u16,
+) -> Tx {
+    let inputs = spent.into_iter().map(|prev_output| TxIn {
+        prev_output,
+        sig_script: none!(),
+        sequence: SeqNo::from_consensus_u32(u32::MAX),
+        witness: Witness::from_consensus_stack(Vec::<Vec<u8>>::new()),
+    });
+    let opret = TxOut {
+        value: 0.into(),
+        script_pubkey: ScriptPubkey::op_return(commitment.as_slice()),
+    };
+    // Outputs with `OP_TRUE` script
+    let other = (0..outputs).map(|_| TxOut {
+        value: OUTPUT_VALUE.into(),
+        script_pubkey: ScriptPubkey::from(vec![0x51]),
+    });
+    Tx {
+        version: default!(),
+        inputs: Confined::try_from_iter(inputs).expect("too many inputs"),
+        outputs: Confined::try_from_iter([opret].into_iter().chain(other))
+            .expect("too many outputs"),
+        lock_time: LockTime::from_consensus_u32(0),
+    }
+}
+
+/// Constructs bundle of the transitions, assigning them consecutive numbers
+/// of the witness transaction inputs in the order of the transition inputs.
+pub fn bundle_of(transitions: impl IntoIterator<Item = Transition>) -> TransitionBundle {
+    let mut no = 0u16;
+    let items = transitions.into_iter().map(|transition| {
+        let len = transition.inputs.len() as u16;
+        let inputs = Confined::try_from_iter(no..no + len).expect("too many inputs");
+        no += len;
+        let item = BundleItem {
+            inputs,
+            transition: Some(transition),
+        };
+        (item.transition.as_ref().expect("just set").id(), item)
+    });
+    TransitionBundle::from(Confined::try_from_iter(items).expect("too many transitions"))
+}
+
+/// Forges anchor committing to the bundle under the protocol id of the
+/// contract with opret commitment in the transaction `txid`. The
+/// multi-protocol commitment contains the bundle only, so the anchor and the
+/// commitment are fully deterministic.
+pub fn forge_anchor(
+    contract_id: ContractId,
+    bundle: &TransitionBundle,
+    txid: Txid,
+) -> (Anchor<mpc::MerkleProof>, mpc::Commitment) {
+    let mpc_proof = mpc::MerkleProof::default();
+    let commitment = mpc_proof
+        .convolve(contract_id.to_protocol_id(), bundle.bundle_id().into())
+        .expect("single-leaf proof is related to any protocol");
+    let anchor = Anchor {
+        txid,
+        mpc_proof,
+        dbc_proof: dbc::Proof::OpretFirst,
+    };
+    (anchor, commitment)
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct FakeTx {
+    tx: Tx,
+    height: Option<u32>,
+}
+
+/// Synthetic blockchain resolving the transactions added to it.
+///
+/// Each mined transaction is included into a separate block, such that the
+/// chain height grows by one with each of them. Transaction ids are derived
+/// from a counter with [`fake_txid`] and do not depend on the transaction
+/// data.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct FakeChain {
+    height: u32,
+    nonce: u64,
+    txs: BTreeMap<Txid, FakeTx>,
+}
+
+impl FakeChain {
+    pub fn new() -> Self { FakeChain::default() }
+
+    /// Height of the last block.
+    pub fn height(&self) -> u32 { self.height }
+
+    /// Timestamp of the block at the given height.
+    pub fn block_time(height: u32) -> i64 { GENESIS_TIME + height as i64 * BLOCK_INTERVAL }
+
+    /// Produces next unique transaction id, not assigned to any transaction.
+    pub fn next_txid(&mut self) -> Txid {
+        self.nonce += 1;
+        fake_txid(self.nonce)
+    }
+
+    /// Mines transaction in a new block, returning its id.
+    pub fn mine(&mut self, tx: Tx) -> Txid {
+        let txid = self.next_txid();
+        self.mine_with_id(txid, tx);
+        txid
+    }
+
+    /// Mines transaction under the provided id in a new block.
+    pub fn mine_with_id(&mut self, txid: Txid, tx: Tx) {
+        self.height += 1;
+        let height = Some(self.height);
+        self.txs.insert(txid, FakeTx { tx, height });
+    }
+
+    /// Adds transaction known off-chain (see [`ResolveTx::resolve_offchain_tx`])
+    /// under the provided id. The transaction may be mined later with
+    /// [`FakeChain::confirm`].
+    pub fn add_offchain(&mut self, txid: Txid, tx: Tx) {
+        self.txs.insert(txid, FakeTx { tx, height: None });
+    }
+
+    /// Mines previously added off-chain transaction in a new block. Returns
+    /// `false` if the transaction is unknown or already mined.
+    pub fn confirm(&mut self, txid: Txid) -> bool {
+        match self.txs.get_mut(&txid) {
+            Some(fake) if fake.height.is_none() => {
+                self.height += 1;
+                fake.height = Some(self.height);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forges anchored bundle and mines its witness transaction, which spends
+    /// the `spent` outpoints and has `outputs` number of outputs in addition
+    /// to the commitment output.
+    pub fn anchor(
+        &mut self,
+        contract_id: ContractId,
+        bundle: TransitionBundle,
+        spent: impl IntoIterator<Item = Outpoint>,
+        outputs: u16,
+    ) -> AnchoredBundle {
+        let txid = self.next_txid();
+        let (anchor, commitment) = forge_anchor(contract_id, &bundle, txid);
+        self.mine_with_id(txid, witness_tx(spent, commitment, outputs));
+        AnchoredBundle {
+            anchor,
+            bundle,
+            alt_dbc_proofs: none!(),
+        }
+    }
+
+    /// Forges anchored bundle like [`FakeChain::anchor`], keeping the witness
+    /// transaction off-chain.
+    pub fn anchor_offchain(
+        &mut self,
+        contract_id: ContractId,
+        bundle: TransitionBundle,
+        spent: impl IntoIterator<Item = Outpoint>,
+        outputs: u16,
+    ) -> AnchoredBundle {
+        let txid = self.next_txid();
+        let (anchor, commitment) = forge_anchor(contract_id, &bundle, txid);
+        self.add_offchain(txid, witness_tx(spent, commitment, outputs));
+        AnchoredBundle {
+            anchor,
+            bundle,
+            alt_dbc_proofs: none!(),
+        }
+    }
+}
+
+impl ResolveTx for FakeChain {
+    fn resolve_tx(&self, txid: Txid) -> Result<Tx, TxResolverError> {
+        match self.txs.get(&txid) {
+            Some(FakeTx {
+                tx,
+                height: Some(_),
+            }) => Ok(tx.clone()),
+            _ => Err(TxResolverError::Unknown(txid)),
+        }
+    }
+
+    fn resolve_height(&self, txid: Txid) -> Result<u32, TxResolverError> {
+        self.txs
+            .get(&txid)
+            .and_then(|fake| fake.height)
+            .ok_or(TxResolverError::Unknown(txid))
+    }
+
+    fn resolve_time(&self, txid: Txid) -> Result<i64, TxResolverError> {
+        self.resolve_height(txid).map(FakeChain::block_time)
+    }
+
+    fn resolve_offchain_tx(&self, txid: Txid) -> Result<Tx, TxResolverError> {
+        match self.txs.get(&txid) {
+            Some(FakeTx { tx, height: None }) => Ok(tx.clone()),
+            _ => Err(TxResolverError::Unknown(txid)),
+        }
+    }
+}
+
+/// In-memory consignment containing all of the provided operations.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FakeConsignment {
+    pub schema: SubSchema,
+    pub genesis: Genesis,
+    pub extensions: Vec<Extension>,
+    pub bundles: Vec<AnchoredBundle>,
+    pub terminals: BTreeSet<(BundleId, SecretSeal)>,
+    pub checkpoint: Option<Checkpoint>,
+}
+
+impl FakeConsignment {
+    pub fn new(schema: SubSchema, genesis: Genesis) -> Self {
+        FakeConsignment {
+            schema,
+            genesis,
+            extensions: empty!(),
+            bundles: empty!(),
+            terminals: empty!(),
+            checkpoint: None,
+        }
+    }
+
+    /// Adds anchored bundle, registering its transitions as terminals under
+    /// each of the seals they define.
+    pub fn add_terminal(&mut self, anchored_bundle: AnchoredBundle) {
+        let bundle_id = anchored_bundle.bundle_id();
+        for transition in anchored_bundle
+            .bundle
+            .values()
+            .filter_map(|item| item.transition.as_ref())
+        {
+            for seal in transition
+                .assignments
+                .values()
+                .flat_map(|assigns| assigns.to_confidential_seals())
+            {
+                self.terminals.insert((bundle_id, seal));
+            }
+        }
+        self.bundles.push(anchored_bundle);
+    }
+
+    fn transitions(&self) -> impl Iterator<Item = &Transition> {
+        self.bundles
+            .iter()
+            .flat_map(|anchored_bundle| anchored_bundle.bundle.values())
+            .filter_map(|item| item.transition.as_ref())
+    }
+}
+
+impl ConsignmentApi for FakeConsignment {
+    type BundleIter<'container> = std::slice::Iter<'container, AnchoredBundle>;
+
+    fn schema(&self) -> &SubSchema { &self.schema }
+
+    fn operation(&self, opid: OpId) -> Option<OpRef<'_>> {
+        if opid == self.genesis.id() {
+            return Some(OpRef::Genesis(&self.genesis));
+        }
+        self.transition(opid)
+            .map(OpRef::Transition)
+            .or_else(|| self.extension(opid).map(OpRef::Extension))
+    }
+
+    fn genesis(&self) -> &Genesis { &self.genesis }
+
+    fn transition(&self, opid: OpId) -> Option<&Transition> {
+        self.transitions()
+            .find(|transition| transition.id() == opid)
+    }
+
+    fn extension(&self, opid: OpId) -> Option<&Extension> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.id() == opid)
+    }
+
+    fn terminals(&self) -> BTreeSet<(BundleId, SecretSeal)> { self.terminals.clone() }
+
+    fn anchored_bundles(&self) -> Self::BundleIter<'_> { self.bundles.iter() }
+
+    fn checkpoint(&self) -> Option<&Checkpoint> { self.checkpoint.as_ref() }
+
+    fn bundle_by_id(&self, bundle_id: BundleId) -> Option<&TransitionBundle> {
+        self.bundles
+            .iter()
+            .find(|anchored_bundle| anchored_bundle.bundle_id() == bundle_id)
+            .map(|anchored_bundle| &anchored_bundle.bundle)
+    }
+
+    fn op_ids_except(&self, ids: &BTreeSet<OpId>) -> BTreeSet<OpId> {
+        let genesis = [self.genesis.id()];
+        let extensions = self.extensions.iter().map(Extension::id);
+        let transitions = self.transitions().map(Transition::id);
+        genesis
+            .into_iter()
+            .chain(extensions)
+            .chain(transitions)
+            .filter(|opid| !ids.contains(opid))
+            .collect()
+    }
+
+    fn has_operation(&self, opid: OpId) -> bool { self.operation(opid).is_some() }
+
+    fn known_transitions_by_bundle_id(&self, bundle_id: BundleId) -> Option<Vec<&Transition>> {
+        let bundle = self.bundle_by_id(bundle_id)?;
+        Some(
+            bundle
+                .values()
+                .filter_map(|item| item.transition.as_ref())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::SmallBlob;
+    use bp::seals::txout::CloseMethod;
+    use bp::Chain;
+
+    use super::*;
+    use crate::schema::{GenesisSchema, Occurrences, StateSchema, TransitionSchema};
+    use crate::validation::{Validator, Validity};
+    use crate::{
+        Assign, Assignments, Ffv, GenesisSeal, GlobalState, GraphSeal, Input, Inputs, OpFeatures,
+        Opout, TypedAssigns, Valencies, VoidState,
+    };
+
+    fn schema() -> SubSchema {
+        SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Declarative },
+            genesis: GenesisSchema {
+                assignments: tiny_bmap! { 1 => Occurrences::Once },
+                ..default!()
+            },
+            transitions: tiny_bmap! {
+                1 => TransitionSchema {
+                    inputs: tiny_bmap! { 1 => Occurrences::Once },
+                    assignments: tiny_bmap! { 1 => Occurrences::Once },
+                    ..default!()
+                },
+            },
+            ..default!()
+        }
+    }
+
+    fn rights<Seal: crate::ExposedSeal>(seal: Seal) -> Assignments<Seal> {
+        let assign = Assign::revealed(seal, VoidState::default());
+        Assignments::from(tiny_bmap! { 1 => TypedAssigns::Declarative(small_vec![assign]) })
+    }
+
+    #[test]
+    fn fake_chain() {
+        let mut chain = FakeChain::new();
+        let issue = chain.next_txid();
+        assert_ne!(issue, chain.next_txid());
+        assert_eq!(fake_txid(1), issue);
+
+        let schema = schema();
+        let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue, 0, 1);
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: rights(seal),
+            valencies: Valencies::default(),
+        };
+        let transition = Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            transition_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            inputs: Inputs::from(tiny_bset![Input::with(Opout::new(genesis.id(), 1, 0))]),
+            assignments: rights(GraphSeal::with_vout(CloseMethod::OpretFirst, 1, 2)),
+            valencies: Valencies::default(),
+        };
+        let contract_id = genesis.contract_id();
+        let mut consignment = FakeConsignment::new(schema, genesis);
+        let spent = [Outpoint::new(issue, 0)];
+        let anchored_bundle = chain.anchor_offchain(contract_id, bundle_of([transition]), spent, 1);
+        let txid = anchored_bundle.anchor.txid;
+        consignment.add_terminal(anchored_bundle);
+
+        let status = Validator::validate_witnesses(&consignment, &chain, Chain::Regtest);
+        assert_eq!(status.validity(), Validity::OffchainWitnesses);
+        assert_eq!(status.offchain_witnesses, vec![txid]);
+
+        assert!(chain.confirm(txid));
+        assert_eq!(chain.resolve_time(txid).unwrap(), FakeChain::block_time(1));
+        let status = Validator::validate_witnesses(&consignment, &chain, Chain::Regtest);
+        assert_eq!(status.validity(), Validity::Valid);
+        assert!(status.offchain_witnesses.is_empty());
+
+        let status =
+            Validator::validate_witnesses(&consignment, &FakeChain::new(), Chain::Regtest);
+        assert_eq!(status.unmined_terminals, vec![txid]);
+    }
+
+    #[test]
+    fn spent_seals() {
+        let mut chain = FakeChain::new();
+        let issue = chain.next_txid();
+        let schema = schema();
+        let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue, 0, 1);
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: rights(seal),
+            valencies: Valencies::default(),
+        };
+        let transition = |prev_out: Opout, blinding: u64| Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            transition_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            inputs: Inputs::from(tiny_bset![Input::with(prev_out)]),
+            assignments: rights(GraphSeal::with_vout(CloseMethod::OpretFirst, 1, blinding)),
+            valencies: Valencies::default(),
+        };
+        let contract_id = genesis.contract_id();
+        let first_out = Opout::new(genesis.id(), 1, 0);
+        let first = transition(first_out, 2);
+        let second_out = Opout::new(first.id(), 1, 0);
+        let second = transition(second_out, 3);
+
+        let mut consignment = FakeConsignment::new(schema, genesis);
+        let anchored_bundle =
+            chain.anchor(contract_id, bundle_of([first]), [Outpoint::new(issue, 0)], 1);
+        let txid = anchored_bundle.anchor.txid;
+        consignment.bundles.push(anchored_bundle);
+        let anchored_bundle =
+            chain.anchor(contract_id, bundle_of([second]), [Outpoint::new(txid, 1)], 1);
+        consignment.add_terminal(anchored_bundle);
+
+        let witness_seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, txid, 1, 2);
+        assert_eq!(
+            consignment.spent_seals(),
+            bmap! { first_out => Some(seal), second_out => Some(witness_seal) }
+        );
+    }
+
+    #[test]
+    fn trusted_checkpoint() {
+        use amplify::confinement::MediumOrdMap;
+        use strict_types::{Ty, TypeSystem};
+
+        use crate::schema::FungibleType;
+        use crate::validation::{Failure, ValidatorConfig};
+        use crate::{BlindingFactor, RevealedValue};
+
+        let mut chain = FakeChain::new();
+        let issue = chain.next_txid();
+        let mut schema = schema();
+        schema.owned_types = tiny_bmap! { 1 => StateSchema::Fungible(FungibleType::Unsigned64Bit) };
+        schema.checkpoints = true;
+        let types = bmap! { schema.genesis.metadata => Ty::UNIT };
+        schema.type_system = TypeSystem::from(MediumOrdMap::try_from(types).unwrap());
+        fn coins<Seal: crate::ExposedSeal>(seal: Seal, amount: u64) -> Assignments<Seal> {
+            let blinding = BlindingFactor::try_from([1u8; 32]).unwrap();
+            let assign = Assign::revealed(seal, RevealedValue::with(amount, blinding));
+            Assignments::from(tiny_bmap! { 1 => TypedAssigns::Fungible(small_vec![assign]) })
+        }
+        let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue, 0, 1);
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: coins(seal, 10),
+            valencies: Valencies::default(),
+        };
+        let contract_id = genesis.contract_id();
+        let transition = |prev_out: Opout, blinding: u64, amount: u64| Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id,
+            transition_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            inputs: Inputs::from(tiny_bset![Input::with(prev_out)]),
+            assignments: coins(GraphSeal::with_vout(CloseMethod::OpretFirst, 1, blinding), amount),
+            valencies: Valencies::default(),
+        };
+
+        // History compacted by the checkpoint: the genesis and the first transfer
+        let genesis_out = Opout::new(genesis.id(), 1, 0);
+        let first = transition(genesis_out, 2, 10);
+        let spent = [Outpoint::new(issue, 0)];
+        let witness = chain
+            .anchor(contract_id, bundle_of([first.clone()]), spent, 1)
+            .anchor
+            .txid;
+        let checkpoint = |first: &Transition| {
+            let mut checkpoint = Checkpoint::with(&genesis).unwrap();
+            checkpoint.add_transition(first, witness).unwrap();
+            checkpoint
+        };
+        let consignment = |chain: &mut FakeChain, first: &Transition, amount: u64| {
+            let mut consignment = FakeConsignment::new(schema.clone(), genesis.clone());
+            consignment.checkpoint = Some(checkpoint(first));
+            let second = transition(Opout::new(first.id(), 1, 0), 3, amount);
+            let spent = [Outpoint::new(witness, 1)];
+            consignment.add_terminal(chain.anchor(contract_id, bundle_of([second]), spent, 1));
+            consignment
+        };
+        let trusted_id = checkpoint(&first).checkpoint_id();
+        let config = ValidatorConfig {
+            trusted_checkpoints: bset! { trusted_id },
+            ..default!()
+        };
+
+        let trusted = consignment(&mut chain, &first, 10);
+        let status = Validator::validate_with_config(
+            &trusted,
+            &chain,
+            Chain::Regtest,
+            ValidatorConfig::default(),
+        );
+        assert_eq!(status.failures, vec![Failure::CheckpointUntrusted(trusted_id)]);
+        let status =
+            Validator::validate_with_config(&trusted, &chain, Chain::Regtest, config.clone());
+        assert_eq!(status.validity(), Validity::Valid);
+
+        // Checkpoint with the inflated output of the first transfer
+        let forged = transition(genesis_out, 2, 1_000_000);
+        let forged_id = checkpoint(&forged).checkpoint_id();
+        let forged = consignment(&mut chain, &forged, 1_000_000);
+        let status = Validator::validate_with_config(&forged, &chain, Chain::Regtest, config);
+        assert_eq!(status.failures, vec![Failure::CheckpointUntrusted(forged_id)]);
+    }
+}
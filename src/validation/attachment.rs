@@ -0,0 +1,143 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Availability of the media attached to the contract state.
+
+use std::collections::BTreeSet;
+
+use super::{
+    ConsignmentApi, Failure, OfflineResolver, Status, ValidationProfile, Validator, Warning,
+};
+use crate::{AttachId, Operation, TypedAssigns};
+
+/// Resolver of the media attached to the contract state (see
+/// [`crate::RevealedAttach`]).
+pub trait AttachmentResolver {
+    /// Checks whether the attachment with the provided id is retrievable or
+    /// at least known to the resolver.
+    fn is_available(&self, id: AttachId) -> bool;
+}
+
+impl AttachmentResolver for BTreeSet<AttachId> {
+    fn is_available(&self, id: AttachId) -> bool { self.contains(&id) }
+}
+
+impl<'consignment, C: ConsignmentApi> Validator<'consignment, 'static, C, OfflineResolver> {
+    /// Checks that the media attached to the revealed state of the consignment
+    /// operations are available from the `resolver`, reporting the missing
+    /// ones according to the validation `profile`.
+    ///
+    /// The check doesn't validate the consignment itself and is independent
+    /// of other validation phases; the returned status can be combined with
+    /// their statuses with `+=`.
+    pub fn validate_attachments(
+        consignment: &'consignment C,
+        resolver: &impl AttachmentResolver,
+        profile: ValidationProfile,
+    ) -> Status {
+        let mut status = Status::default();
+        for opid in consignment.op_ids_except(&BTreeSet::new()) {
+            let Some(op) = consignment.operation(opid) else {
+                continue;
+            };
+            let assignments = op.assignments();
+            for ty in assignments.types() {
+                let Some(TypedAssigns::Attachment(assigns)) = assignments.get(ty) else {
+                    continue;
+                };
+                for attach in assigns.iter().filter_map(|a| a.as_revealed_state()) {
+                    if resolver.is_available(attach.id) {
+                        continue;
+                    }
+                    match profile {
+                        ValidationProfile::Lenient => {
+                            status.add_warning(Warning::AttachmentMissing(opid, attach.id));
+                        }
+                        ValidationProfile::Strict => {
+                            status.add_failure(Failure::AttachmentMissing(opid, attach.id));
+                        }
+                    }
+                }
+            }
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "testing")]
+mod test {
+    use amplify::confinement::{Confined, SmallBlob};
+    use bp::seals::txout::CloseMethod;
+    use bp::{Chain, Txid};
+
+    use super::*;
+    use crate::schema::StateSchema;
+    use crate::testing::FakeConsignment;
+    use crate::validation::Validity;
+    use crate::{
+        Assign, Assignments, Ffv, Genesis, GenesisSeal, GlobalState, MediaType, OpFeatures,
+        RevealedAttach, SubSchema, Valencies,
+    };
+
+    #[test]
+    fn availability() {
+        let schema = SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Attachment(MediaType::Any) },
+            ..default!()
+        };
+        let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, Txid::from([1; 32]), 0, 1);
+        let ids = [AttachId::from([0xAA; 32]), AttachId::from([0xBB; 32])];
+        let assigns = ids
+            .map(|id| Assign::revealed(seal, RevealedAttach::new(id, MediaType::Any)))
+            .to_vec();
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Attachment(Confined::try_from(assigns).unwrap()),
+            }),
+            valencies: Valencies::default(),
+        };
+        let opid = genesis.id();
+        let consignment = FakeConsignment::new(schema, genesis);
+
+        let known = bset![ids[0]];
+        let status =
+            Validator::validate_attachments(&consignment, &known, ValidationProfile::Lenient);
+        assert_eq!(status.validity(), Validity::Valid);
+        assert_eq!(status.warnings, vec![Warning::AttachmentMissing(opid, ids[1])]);
+
+        let status =
+            Validator::validate_attachments(&consignment, &known, ValidationProfile::Strict);
+        assert_eq!(status.failures, vec![Failure::AttachmentMissing(opid, ids[1])]);
+
+        let known = bset![ids[0], ids[1]];
+        let status =
+            Validator::validate_attachments(&consignment, &known, ValidationProfile::Strict);
+        assert_eq!(status, Status::default());
+    }
+}
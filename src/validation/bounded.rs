@@ -0,0 +1,399 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation of consignments streamed from a [`ConsignmentReader`] with an
+//! explicit memory budget, suitable for devices with limited memory, like
+//! hardware signers.
+//!
+//! Instead of loading the whole consignment, the validator keeps only the
+//! frontier of the contract history: schema, genesis, checkpoint and the
+//! operations which still have unspent outputs or valencies. Operations are
+//! validated in the order they are streamed, so the reader must provide them
+//! in topological order, starting from the operations closest to the genesis.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::mem;
+
+use amplify::Wrapper;
+use bp::Chain;
+use strict_encoding::{StrictEncode, StrictWriter};
+
+use super::validator::Validator;
+use super::{AnchoredBundle, ConsignmentApi, ResolveTx, Status, Validity};
+use crate::{
+    BundleId, Checkpoint, Extension, Genesis, OpId, OpRef, Operation, Opout, SecretSeal, SubSchema,
+    Transition, TransitionBundle,
+};
+
+/// Item of the consignment history streamed by [`ConsignmentReader`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ConsignmentItem {
+    /// State extension.
+    Extension(Extension),
+
+    /// Anchored bundle of state transitions.
+    Bundle(AnchoredBundle),
+}
+
+/// Source of the consignment data for [`BoundedValidator`], which provides
+/// them one by one.
+pub trait ConsignmentReader {
+    type Error: Error;
+
+    /// Reads schema of the contract. Called once, before any other method.
+    fn read_schema(&mut self) -> Result<SubSchema, Self::Error>;
+
+    /// Reads contract genesis. Called once, right after the schema.
+    fn read_genesis(&mut self) -> Result<Genesis, Self::Error>;
+
+    /// Reads checkpoint from which the consignment history starts, if any (see
+    /// [`ConsignmentApi::checkpoint`]). Called once, right after the genesis.
+    fn read_checkpoint(&mut self) -> Result<Option<Checkpoint>, Self::Error> { Ok(None) }
+
+    /// Reads next item of the contract history, returning `None` once all the
+    /// items were read. Items must be provided in topological order, such
+    /// that each operation is read after all operations it depends on.
+    fn read_item(&mut self) -> Result<Option<ConsignmentItem>, Self::Error>;
+}
+
+/// Errors of the bounded-memory validation.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum BoundedError<E: Error> {
+    /// validation requires {required} bytes of memory, exceeding the budget of
+    /// {budget} bytes.
+    BudgetExceeded { required: usize, budget: usize },
+
+    /// unable to read consignment. Details: {0}
+    Reader(E),
+}
+
+/// Validator keeping only the frontier of the contract history in memory and
+/// rejecting consignments which require more memory than allowed by the
+/// budget.
+///
+/// Unlike [`Validator`], validates all the operations provided by the reader,
+/// not only the history of the consignment terminals. Non-mined witness
+/// transactions are reported as failures.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BoundedValidator {
+    budget: usize,
+}
+
+impl BoundedValidator {
+    /// Constructs validator with the memory budget, in bytes. The budget
+    /// accounts for the size of the consensus data kept by the validator.
+    pub fn with_budget(budget: usize) -> Self { Self { budget } }
+
+    pub fn budget(&self) -> usize { self.budget }
+
+    /// Validates consignment streamed by the `reader`, returning the same
+    /// status as [`Validator::validate`], or an error if the consignment can't
+    /// be read or exceeds the memory budget.
+    pub fn validate<Reader: ConsignmentReader, R: ResolveTx>(
+        &self,
+        reader: &mut Reader,
+        resolver: &R,
+        chain: Chain,
+    ) -> Result<Status, BoundedError<Reader::Error>> {
+        let schema = reader.read_schema().map_err(BoundedError::Reader)?;
+        let genesis = reader.read_genesis().map_err(BoundedError::Reader)?;
+        let checkpoint = reader.read_checkpoint().map_err(BoundedError::Reader)?;
+        let mut frontier = Frontier::new(schema, genesis, checkpoint);
+        self.check_budget(&frontier)?;
+
+        let mut status = Validator::validate_head(&frontier, resolver, chain);
+        // Like the non-bounded validator, we do not validate operations against an
+        // invalid schema
+        if status.validity() == Validity::Invalid {
+            return Ok(status);
+        }
+
+        while let Some(item) = reader.read_item().map_err(BoundedError::Reader)? {
+            match item {
+                ConsignmentItem::Extension(extension) => {
+                    status += Validator::validate_streamed(&frontier, resolver, Some(&extension));
+                    frontier.add_extension(extension);
+                }
+                ConsignmentItem::Bundle(anchored_bundle) => {
+                    frontier.set_current(anchored_bundle);
+                    self.check_budget(&frontier)?;
+                    status += Validator::validate_streamed(&frontier, resolver, None);
+                    frontier.apply_current();
+                }
+            }
+            self.check_budget(&frontier)?;
+        }
+
+        Ok(status)
+    }
+
+    fn check_budget<E: Error>(&self, frontier: &Frontier) -> Result<(), BoundedError<E>> {
+        let required = frontier.size();
+        if required > self.budget {
+            return Err(BoundedError::BudgetExceeded {
+                required,
+                budget: self.budget,
+            });
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn encoded_len(value: &impl StrictEncode) -> usize {
+    value
+        .strict_encode(StrictWriter::counter())
+        .expect("counting writer must not fail")
+        .unbox()
+        .count
+}
+
+/// Frontier of the contract history, exposed to the validator as a
+/// consignment.
+struct Frontier {
+    schema: SubSchema,
+    genesis: Genesis,
+    checkpoint: Option<Checkpoint>,
+    transitions: BTreeMap<OpId, Transition>,
+    extensions: BTreeMap<OpId, Extension>,
+    unspent: BTreeSet<Opout>,
+    current: Option<AnchoredBundle>,
+
+    // Encoded size of the data kept by the frontier
+    base_size: usize,
+    op_sizes: BTreeMap<OpId, usize>,
+    current_size: usize,
+}
+
+impl Frontier {
+    fn new(schema: SubSchema, genesis: Genesis, checkpoint: Option<Checkpoint>) -> Self {
+        let base_size = encoded_len(&schema) +
+            encoded_len(&genesis) +
+            checkpoint.as_ref().map(encoded_len).unwrap_or_default();
+        Frontier {
+            schema,
+            genesis,
+            checkpoint,
+            transitions: empty!(),
+            extensions: empty!(),
+            unspent: empty!(),
+            current: None,
+            base_size,
+            op_sizes: empty!(),
+            current_size: 0,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.base_size +
+            self.op_sizes.values().sum::<usize>() +
+            self.unspent.len() * mem::size_of::<Opout>() +
+            self.current_size
+    }
+
+    fn set_current(&mut self, anchored_bundle: AnchoredBundle) {
+        self.current_size = encoded_len(&anchored_bundle);
+        self.current = Some(anchored_bundle);
+    }
+
+    /// Spends inputs of the transitions from the current bundle and adds the
+    /// transitions to the frontier.
+    fn apply_current(&mut self) {
+        let Some(anchored_bundle) = self.current.take() else {
+            return;
+        };
+        self.current_size = 0;
+        let transitions = anchored_bundle
+            .bundle
+            .into_inner()
+            .into_values()
+            .filter_map(|item| item.transition);
+        for transition in transitions {
+            for input in &transition.inputs {
+                self.spend(input.prev_out);
+            }
+            let opid = transition.id();
+            if self.add_outputs(&transition) {
+                self.op_sizes.insert(opid, encoded_len(&transition));
+                self.transitions.insert(opid, transition);
+            }
+        }
+    }
+
+    fn add_extension(&mut self, extension: Extension) {
+        let opid = extension.id();
+        if self.add_outputs(&extension) {
+            self.op_sizes.insert(opid, encoded_len(&extension));
+            self.extensions.insert(opid, extension);
+        }
+    }
+
+    /// Registers unspent outputs of the operation, returning whether the
+    /// operation must be kept in the frontier.
+    fn add_outputs(&mut self, op: &impl Operation) -> bool {
+        let opid = op.id();
+        let mut keep = !op.valencies().is_empty();
+        for ty in op.assignments().types() {
+            let len = op
+                .assignments_by_type(ty)
+                .map(|a| a.len_u16())
+                .unwrap_or_default();
+            for no in 0..len {
+                self.unspent.insert(Opout::new(opid, ty, no));
+                keep = true;
+            }
+        }
+        keep
+    }
+
+    /// Removes the output from the frontier, dropping operation which has no
+    /// more unspent outputs or valencies.
+    fn spend(&mut self, opout: Opout) {
+        if !self.unspent.remove(&opout) {
+            return;
+        }
+        let op = opout.op;
+        let first = Opout::new(op, 0, 0);
+        let last = Opout::new(op, u16::MAX, u16::MAX);
+        if self.unspent.range(first..=last).next().is_some() {
+            return;
+        }
+        let valencies = match self.operation(op) {
+            Some(OpRef::Transition(transition)) => !transition.valencies.is_empty(),
+            Some(OpRef::Extension(extension)) => !extension.valencies.is_empty(),
+            _ => true,
+        };
+        if !valencies {
+            self.transitions.remove(&op);
+            self.extensions.remove(&op);
+            self.op_sizes.remove(&op);
+        }
+    }
+
+    fn current_transitions(&self) -> impl Iterator<Item = &Transition> {
+        self.current
+            .iter()
+            .flat_map(|anchored_bundle| anchored_bundle.bundle.values())
+            .filter_map(|item| item.transition.as_ref())
+    }
+}
+
+impl ConsignmentApi for Frontier {
+    type BundleIter<'container> = std::option::Iter<'container, AnchoredBundle>;
+
+    fn schema(&self) -> &SubSchema { &self.schema }
+
+    fn operation(&self, opid: OpId) -> Option<OpRef<'_>> {
+        if opid == self.genesis.id() {
+            return Some(OpRef::Genesis(&self.genesis));
+        }
+        self.transition(opid)
+            .map(OpRef::Transition)
+            .or_else(|| self.extension(opid).map(OpRef::Extension))
+    }
+
+    fn genesis(&self) -> &Genesis { &self.genesis }
+
+    fn transition(&self, opid: OpId) -> Option<&Transition> {
+        self.transitions
+            .get(&opid)
+            .or_else(|| self.current_transitions().find(|t| t.id() == opid))
+    }
+
+    fn extension(&self, opid: OpId) -> Option<&Extension> { self.extensions.get(&opid) }
+
+    fn terminals(&self) -> BTreeSet<(BundleId, SecretSeal)> { empty!() }
+
+    fn anchored_bundles(&self) -> Self::BundleIter<'_> { self.current.iter() }
+
+    fn checkpoint(&self) -> Option<&Checkpoint> { self.checkpoint.as_ref() }
+
+    fn bundle_by_id(&self, bundle_id: BundleId) -> Option<&TransitionBundle> {
+        self.current
+            .as_ref()
+            .filter(|anchored_bundle| anchored_bundle.bundle_id() == bundle_id)
+            .map(|anchored_bundle| &anchored_bundle.bundle)
+    }
+
+    // Frontier doesn't keep the history, so it can't report excessive operations
+    fn op_ids_except(&self, _ids: &BTreeSet<OpId>) -> BTreeSet<OpId> { empty!() }
+
+    fn has_operation(&self, opid: OpId) -> bool { self.operation(opid).is_some() }
+
+    fn known_transitions_by_bundle_id(&self, bundle_id: BundleId) -> Option<Vec<&Transition>> {
+        self.bundle_by_id(bundle_id)?;
+        Some(self.current_transitions().collect())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "test_vectors")]
+mod test {
+    use std::convert::Infallible;
+
+    use super::*;
+    use crate::test_vectors;
+    use crate::validation::{Failure, OfflineResolver};
+
+    struct GenesisReader;
+
+    impl ConsignmentReader for GenesisReader {
+        type Error = Infallible;
+
+        fn read_schema(&mut self) -> Result<SubSchema, Self::Error> { Ok(test_vectors::schema()) }
+
+        fn read_genesis(&mut self) -> Result<Genesis, Self::Error> { Ok(test_vectors::genesis()) }
+
+        fn read_item(&mut self) -> Result<Option<ConsignmentItem>, Self::Error> { Ok(None) }
+    }
+
+    #[test]
+    fn budget() {
+        assert!(
+            BoundedValidator::with_budget(10_000)
+                .validate(&mut GenesisReader, &OfflineResolver, Chain::Regtest)
+                .is_ok()
+        );
+
+        let status = BoundedValidator::with_budget(10_000)
+            .validate(&mut GenesisReader, &OfflineResolver, Chain::Bitcoin)
+            .unwrap();
+        assert!(status.failures.contains(&Failure::WrongChain {
+            expected: Chain::Bitcoin,
+            actual: Chain::Regtest
+        }));
+
+        let required = encoded_len(&test_vectors::schema()) + encoded_len(&test_vectors::genesis());
+        assert_eq!(
+            BoundedValidator::with_budget(100).validate(
+                &mut GenesisReader,
+                &OfflineResolver,
+                Chain::Regtest
+            ),
+            Err(BoundedError::BudgetExceeded {
+                required,
+                budget: 100
+            })
+        );
+    }
+}
@@ -0,0 +1,228 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+use bp::{Tx, Txid};
+
+use super::{ResolveTx, TxResolverError};
+
+/// Persistent storage of the witness transactions used by [`CachedResolver`],
+/// allowing to keep resolved transactions across application restarts.
+pub trait PersistTx {
+    /// Loads transaction from the storage, if it is known.
+    fn load_tx(&self, txid: Txid) -> Option<Tx>;
+
+    /// Saves transaction retrieved from the underlying resolver.
+    fn save_tx(&self, txid: Txid, tx: &Tx);
+}
+
+/// No persistence: transactions are cached in memory only.
+impl PersistTx for () {
+    fn load_tx(&self, _txid: Txid) -> Option<Tx> { None }
+
+    fn save_tx(&self, _txid: Txid, _tx: &Tx) {}
+}
+
+/// Least-recently-used cache of transactions.
+#[derive(Debug)]
+struct TxLru {
+    capacity: usize,
+    tick: u64,
+    txs: BTreeMap<Txid, (u64, Tx)>,
+    order: BTreeMap<u64, Txid>,
+}
+
+impl TxLru {
+    fn new(capacity: usize) -> Self {
+        TxLru {
+            capacity,
+            tick: 0,
+            txs: empty!(),
+            order: empty!(),
+        }
+    }
+
+    fn get(&mut self, txid: Txid) -> Option<Tx> {
+        let (tick, tx) = self.txs.get_mut(&txid)?;
+        self.order.remove(tick);
+        self.tick += 1;
+        *tick = self.tick;
+        self.order.insert(self.tick, txid);
+        Some(tx.clone())
+    }
+
+    fn insert(&mut self, txid: Txid, tx: Tx) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some((tick, _)) = self.txs.remove(&txid) {
+            self.order.remove(&tick);
+        }
+        while self.txs.len() >= self.capacity {
+            let Some((_, evicted)) = self.order.pop_first() else {
+                break;
+            };
+            self.txs.remove(&evicted);
+        }
+        self.tick += 1;
+        self.txs.insert(txid, (self.tick, tx));
+        self.order.insert(self.tick, txid);
+    }
+}
+
+/// Resolver wrapper caching witness transactions, such that repeated
+/// validations of consignments with overlapping histories don't retrieve the
+/// same transactions again.
+///
+/// Transactions are kept in a least-recently-used cache of a limited capacity
+/// and, optionally, in a persistent storage (see [`PersistTx`]). Only
+/// transactions which were mined are cached; block heights and times, as well
+/// as off-chain transactions, are always requested from the underlying
+/// resolver, since they may change with chain re-organizations or channel
+/// updates.
+#[derive(Debug)]
+pub struct CachedResolver<R: ResolveTx, P: PersistTx = ()> {
+    resolver: R,
+    persistence: P,
+    cache: Mutex<TxLru>,
+}
+
+impl<R: ResolveTx> CachedResolver<R> {
+    /// Constructs in-memory cache keeping up to `capacity` transactions.
+    pub fn new(resolver: R, capacity: usize) -> Self {
+        CachedResolver::with_persistence(resolver, capacity, ())
+    }
+}
+
+impl<R: ResolveTx, P: PersistTx> CachedResolver<R, P> {
+    /// Constructs cache keeping up to `capacity` transactions in memory and
+    /// all resolved transactions in the `persistence` storage.
+    pub fn with_persistence(resolver: R, capacity: usize, persistence: P) -> Self {
+        CachedResolver {
+            resolver,
+            persistence,
+            cache: Mutex::new(TxLru::new(capacity)),
+        }
+    }
+
+    /// Number of transactions cached in memory.
+    pub fn len(&self) -> usize { self.lock().txs.len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn capacity(&self) -> usize { self.lock().capacity }
+
+    /// Removes all transactions from the in-memory cache. Persistent storage is
+    /// not affected.
+    pub fn clear(&self) {
+        let capacity = self.capacity();
+        *self.lock() = TxLru::new(capacity);
+    }
+
+    pub fn resolver(&self) -> &R { &self.resolver }
+
+    pub fn into_inner(self) -> R { self.resolver }
+
+    fn lock(&self) -> MutexGuard<'_, TxLru> {
+        // Cache is always left in a consistent state, so we can recover from poisoning
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<R: ResolveTx, P: PersistTx> ResolveTx for CachedResolver<R, P> {
+    fn resolve_tx(&self, txid: Txid) -> Result<Tx, TxResolverError> {
+        if let Some(tx) = self.lock().get(txid) {
+            return Ok(tx);
+        }
+        let tx = match self.persistence.load_tx(txid) {
+            Some(tx) => tx,
+            None => {
+                let tx = self.resolver.resolve_tx(txid)?;
+                self.persistence.save_tx(txid, &tx);
+                tx
+            }
+        };
+        self.lock().insert(txid, tx.clone());
+        Ok(tx)
+    }
+
+    fn resolve_height(&self, txid: Txid) -> Result<u32, TxResolverError> {
+        self.resolver.resolve_height(txid)
+    }
+
+    fn resolve_time(&self, txid: Txid) -> Result<i64, TxResolverError> {
+        self.resolver.resolve_time(txid)
+    }
+
+    fn resolve_offchain_tx(&self, txid: Txid) -> Result<Tx, TxResolverError> {
+        self.resolver.resolve_offchain_tx(txid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use bp::LockTime;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingResolver(Cell<usize>);
+
+    impl ResolveTx for CountingResolver {
+        fn resolve_tx(&self, _txid: Txid) -> Result<Tx, TxResolverError> {
+            self.0.set(self.0.get() + 1);
+            Ok(Tx {
+                version: default!(),
+                inputs: none!(),
+                outputs: none!(),
+                lock_time: LockTime::from_consensus_u32(0),
+            })
+        }
+    }
+
+    #[test]
+    fn lru() {
+        let resolver = CachedResolver::new(CountingResolver::default(), 2);
+        let (a, b, c) = (Txid::from([1u8; 32]), Txid::from([2u8; 32]), Txid::from([3u8; 32]));
+        resolver.resolve_tx(a).unwrap();
+        resolver.resolve_tx(b).unwrap();
+        resolver.resolve_tx(a).unwrap();
+        assert_eq!(resolver.resolver().0.get(), 2);
+
+        // `b` is the least recently used one and must be evicted
+        resolver.resolve_tx(c).unwrap();
+        assert_eq!(resolver.len(), 2);
+        resolver.resolve_tx(a).unwrap();
+        assert_eq!(resolver.resolver().0.get(), 3);
+        resolver.resolve_tx(b).unwrap();
+        assert_eq!(resolver.resolver().0.get(), 4);
+
+        resolver.clear();
+        assert!(resolver.is_empty());
+    }
+}
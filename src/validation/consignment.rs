@@ -24,13 +24,18 @@
 //! state transitions, extensions, genesis, outputs, assignments &
 //! single-use-seal data.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
+use amplify::confinement::TinyVec;
+use bp::dbc::{self, anchor};
+use bp::seals::txout::TxPtr;
+use bp::Tx;
 use commit_verify::mpc;
 
+use super::SpvProofs;
 use crate::{
-    Anchor, BundleId, Extension, Genesis, OpId, OpRef, SecretSeal, SubSchema, Transition,
-    TransitionBundle, LIB_NAME_RGB,
+    Anchor, BundleId, Checkpoint, ContractId, Extension, Genesis, GenesisSeal, OpId, OpRef,
+    Operation, Opout, SchemaId, SecretSeal, SubSchema, Transition, TransitionBundle, LIB_NAME_RGB,
 };
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -44,6 +49,51 @@ use crate::{
 pub struct AnchoredBundle {
     pub anchor: Anchor<mpc::MerkleProof>,
     pub bundle: TransitionBundle,
+    /// Additional deterministic bitcoin commitment proofs for the same witness
+    /// transaction and multi-protocol commitment as the main anchor, used when
+    /// the witness transaction carries several commitments (for instance both
+    /// tapret and opret during a wallet migration).
+    pub alt_dbc_proofs: TinyVec<dbc::Proof>,
+}
+
+impl AnchoredBundle {
+    /// Returns id of the bundle, which is used as a message for the LNPBP-4
+    /// multi-protocol commitment.
+    #[inline]
+    pub fn bundle_id(&self) -> BundleId { self.bundle.bundle_id() }
+
+    /// Verifies that the multi-protocol commitment merkle proof from the anchor
+    /// commits to the bundle under the protocol id derived from the provided
+    /// contract id, returning the resulting multi-protocol commitment.
+    pub fn convolve(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<mpc::Commitment, mpc::UnrelatedProof> {
+        self.anchor
+            .convolve(contract_id.to_protocol_id(), self.bundle_id().into())
+    }
+
+    /// Iterates over all anchor variants: first the main anchor, and then the
+    /// anchors constructed with each of the alternative DBC proofs.
+    pub fn anchors(&self) -> impl Iterator<Item = Anchor<mpc::MerkleProof>> + '_ {
+        let alt = self.alt_dbc_proofs.iter().map(|proof| Anchor {
+            dbc_proof: proof.clone(),
+            ..self.anchor.clone()
+        });
+        [self.anchor.clone()].into_iter().chain(alt)
+    }
+
+    /// Verifies that the witness transaction contains tapret or opret
+    /// commitment to the anchor, and that the anchor commits to the bundle
+    /// under the protocol id derived from the provided contract id.
+    pub fn verify(
+        &self,
+        contract_id: ContractId,
+        witness_tx: &Tx,
+    ) -> Result<bool, anchor::VerifyError> {
+        self.anchor
+            .verify(contract_id.to_protocol_id(), self.bundle_id().into(), witness_tx)
+    }
 }
 
 /// Trait defining common data access API for all storage-related RGB structures
@@ -107,6 +157,33 @@ pub trait ConsignmentApi {
     /// Data on all anchored state transitions contained in the consignment
     fn anchored_bundles(&self) -> Self::BundleIter<'_>;
 
+    /// Checkpoint from which the consignment history starts, if the schema
+    /// allows checkpoints. State spent by the consignment operations which is
+    /// not assigned by any of them is resolved from the checkpoint.
+    ///
+    /// The validator verifies only the conformance of the checkpoint to the
+    /// schema; the history compacted by the checkpoint can't be validated, so
+    /// the checkpoint id must be trusted by the receiver (see
+    /// [`crate::validation::ValidatorConfig::trusted_checkpoints`]).
+    ///
+    /// Defaults to `None`, meaning that the history starts from the genesis.
+    fn checkpoint(&self) -> Option<&Checkpoint> { None }
+
+    /// Foreign schema with the given id, governing state extensions which
+    /// redeem plug-in valencies of the contract schema (see
+    /// [`crate::Schema::plugins`]).
+    ///
+    /// Defaults to `None`, meaning that the consignment doesn't contain
+    /// extensions of foreign schemata.
+    fn plugin_schema(&self, _schema_id: SchemaId) -> Option<&SubSchema> { None }
+
+    /// SPV proofs of the witness transactions, used by light clients instead
+    /// of a trusted resolver (see [`crate::validation::Validator::validate_spv`]).
+    ///
+    /// Defaults to `None`, meaning that the consignment doesn't provide SPV
+    /// proofs.
+    fn spv_proofs(&self) -> Option<&SpvProofs> { None }
+
     fn bundle_by_id(&self, bundle_id: BundleId) -> Option<&TransitionBundle>;
 
     fn op_ids_except(&self, ids: &BTreeSet<OpId>) -> BTreeSet<OpId>;
@@ -114,4 +191,144 @@ pub trait ConsignmentApi {
     fn has_operation(&self, opid: OpId) -> bool;
 
     fn known_transitions_by_bundle_id(&self, bundle_id: BundleId) -> Option<Vec<&Transition>>;
+
+    /// Seals closed by the state transitions of the consignment, indexed by
+    /// the spent outputs. Wallets can use them to mark their allocations as
+    /// spent after accepting the consignment.
+    ///
+    /// Seals pointing to the witness transaction are resolved using the
+    /// witness of the operation which has defined them; if the operation has
+    /// several anchors, the first one is used. The seal is `None` if it is
+    /// concealed or its witness transaction is unknown. Key rights, which are
+    /// not bound to seals, and outputs absent both from the consignment and
+    /// its checkpoint are not reported.
+    fn spent_seals(&self) -> BTreeMap<Opout, Option<GenesisSeal>> {
+        let transitions = || {
+            self.anchored_bundles().flat_map(|anchored_bundle| {
+                anchored_bundle
+                    .bundle
+                    .values()
+                    .filter_map(|item| item.transition.as_ref())
+                    .map(move |transition| (transition, anchored_bundle.anchor.txid))
+            })
+        };
+        let mut witnesses = BTreeMap::new();
+        for (transition, txid) in transitions() {
+            witnesses.entry(transition.id()).or_insert(txid);
+        }
+
+        let schema = self.schema();
+        let checkpoint = self.checkpoint();
+        let mut spent = BTreeMap::new();
+        for (transition, _) in transitions() {
+            for input in &transition.inputs {
+                let prev_out = input.prev_out;
+                if schema.key_rights.contains(&schema.migrated_type(prev_out.ty)) {
+                    continue;
+                }
+                let (assigns, no, witness) = match self.operation(prev_out.op) {
+                    Some(op) => {
+                        let Some(assigns) = op.assignments_by_type(prev_out.ty) else {
+                            continue;
+                        };
+                        (assigns, prev_out.no, witnesses.get(&prev_out.op).copied())
+                    }
+                    // State assigned before the checkpoint is kept as a single assignment
+                    None => match checkpoint.and_then(|checkpoint| checkpoint.output(prev_out)) {
+                        Some(output) => (output.assign.clone(), 0, output.witness),
+                        None => continue,
+                    },
+                };
+                let Ok(seal) = assigns.revealed_seal_at(no) else {
+                    continue;
+                };
+                let seal = seal.and_then(|seal| {
+                    match seal.txid {
+                        TxPtr::Txid(txid) => Some(txid),
+                        TxPtr::WitnessTx => witness,
+                    }
+                    .map(|txid| seal.resolve(txid))
+                });
+                spent.insert(prev_out, seal);
+            }
+        }
+        spent
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "testing")]
+mod test {
+    use amplify::confinement::Confined;
+    use commit_verify::mpc::{MerkleBlock, MerkleTree, MultiSource};
+    use commit_verify::{CommitmentId, TryCommitVerify};
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::testing::{bundle_of, fake_txid, witness_tx};
+
+    fn contract() -> ContractId { ContractId::from([0xA5u8; 32]) }
+    fn other() -> ContractId { ContractId::from([0x5Au8; 32]) }
+
+    fn bundle(transition_type: u16) -> TransitionBundle {
+        let mut transition = Transition::strict_dumb();
+        transition.transition_type = transition_type;
+        bundle_of([transition])
+    }
+
+    /// Anchors the bundle into a multi-protocol commitment, which also
+    /// contains a message from the other contract.
+    fn anchored(bundle: TransitionBundle) -> (AnchoredBundle, mpc::Commitment) {
+        let source = MultiSource {
+            min_depth: 3.try_into().unwrap(),
+            messages: Confined::try_from(bmap! {
+                contract().to_protocol_id() => mpc::Message::from(bundle.bundle_id()),
+                other().to_protocol_id() => mpc::Message::from([0xFFu8; 32]),
+            })
+            .unwrap(),
+        };
+        let tree = MerkleTree::try_commit(&source).unwrap();
+        let block = MerkleBlock::from(&tree);
+        let mpc_proof = block.to_merkle_proof(contract().to_protocol_id()).unwrap();
+        let anchor = Anchor {
+            txid: fake_txid(1),
+            mpc_proof,
+            dbc_proof: dbc::Proof::OpretFirst,
+        };
+        let anchored = AnchoredBundle {
+            anchor,
+            bundle,
+            alt_dbc_proofs: none!(),
+        };
+        (anchored, block.commitment_id())
+    }
+
+    #[test]
+    fn convolve() {
+        let (anchored_bundle, commitment) = anchored(bundle(1));
+        assert_eq!(anchored_bundle.convolve(contract()), Ok(commitment));
+        assert_eq!(anchored_bundle.convolve(other()), Err(mpc::UnrelatedProof));
+
+        let forged = AnchoredBundle {
+            bundle: bundle(2),
+            ..anchored_bundle
+        };
+        assert_ne!(forged.convolve(contract()), Ok(commitment));
+    }
+
+    #[test]
+    fn verify() {
+        let (anchored_bundle, commitment) = anchored(bundle(1));
+        let tx = witness_tx([], commitment, 1);
+        assert_eq!(anchored_bundle.verify(contract(), &tx), Ok(true));
+        assert_eq!(
+            anchored_bundle.verify(other(), &tx),
+            Err(anchor::VerifyError::Lnpbp4UnrelatedProtocol)
+        );
+
+        let (other, other_commitment) = anchored(bundle(2));
+        assert_eq!(other.verify(contract(), &tx), Ok(false));
+        let other_tx = witness_tx([], other_commitment, 1);
+        assert_eq!(anchored_bundle.verify(contract(), &other_tx), Ok(false));
+    }
 }
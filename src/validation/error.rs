@@ -0,0 +1,391 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Layered hierarchy of consensus errors, classifying validation
+//! [`Failure`]s and errors decoding untrusted data by the layer of the
+//! protocol which has detected them.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use strict_encoding::DecodeError;
+
+use super::{Failure, UntrustedError};
+
+/// Cause of a layer error.
+#[derive(Clone, PartialEq, Eq, Debug, Display, From)]
+#[display(inner)]
+enum Cause {
+    #[from]
+    Failure(Failure),
+
+    #[from]
+    Decode(UntrustedError),
+}
+
+macro_rules! layer_error {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Clone, PartialEq, Eq, Debug, Display)]
+        #[display(inner)]
+        pub struct $name(Cause);
+
+        impl From<Failure> for $name {
+            fn from(failure: Failure) -> Self { Self(Cause::Failure(failure)) }
+        }
+
+        impl $name {
+            /// Returns reference to the underlying validation failure, if the
+            /// error was detected by the validation.
+            pub fn as_failure(&self) -> Option<&Failure> {
+                match &self.0 {
+                    Cause::Failure(failure) => Some(failure),
+                    Cause::Decode(_) => None,
+                }
+            }
+
+            /// Returns the underlying validation failure, if the error was
+            /// detected by the validation.
+            pub fn into_failure(self) -> Option<Failure> {
+                match self.0 {
+                    Cause::Failure(failure) => Some(failure),
+                    Cause::Decode(_) => None,
+                }
+            }
+
+            /// Returns reference to the underlying decoding error, if the
+            /// error was detected while decoding untrusted data.
+            pub fn as_decode_error(&self) -> Option<&UntrustedError> {
+                match &self.0 {
+                    Cause::Failure(_) => None,
+                    Cause::Decode(err) => Some(err),
+                }
+            }
+        }
+
+        impl Error for $name {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                match &self.0 {
+                    Cause::Failure(failure) => failure.source(),
+                    Cause::Decode(err) => Some(err),
+                }
+            }
+        }
+    };
+}
+
+layer_error!(
+    /// Errors in the schema, or in the conformance of the contract operations
+    /// to the schema.
+    SchemaError
+);
+
+layer_error!(
+    /// Errors reported by the contract validation scripts.
+    ScriptError
+);
+
+layer_error!(
+    /// Errors in client-side-validated data and commitments to it, including
+    /// the consignment structure and multi-protocol commitments.
+    CommitmentError
+);
+
+layer_error!(
+    /// Errors in the layer 1 (bitcoin) data: witness transactions, seal
+    /// closings and deterministic bitcoin commitments.
+    Layer1Error
+);
+
+/// Top-level consensus error.
+#[derive(Clone, PartialEq, Eq, Debug, From)]
+pub enum ConsensusError {
+    /// Schema-level error.
+    #[from]
+    Schema(SchemaError),
+
+    /// Script-level error.
+    #[from]
+    Script(ScriptError),
+
+    /// Client-side-validated data commitment error.
+    #[from]
+    Commitment(CommitmentError),
+
+    /// Layer 1 error.
+    #[from]
+    Layer1(Layer1Error),
+
+    /// Custom error by external services on top of RGB Core.
+    Custom(String),
+}
+
+impl Display for ConsensusError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsensusError::Schema(_) => f.write_str("schema validation error"),
+            ConsensusError::Script(_) => f.write_str("script validation error"),
+            ConsensusError::Commitment(_) => f.write_str("commitment validation error"),
+            ConsensusError::Layer1(_) => f.write_str("layer 1 validation error"),
+            ConsensusError::Custom(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl Error for ConsensusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConsensusError::Schema(err) => Some(err),
+            ConsensusError::Script(err) => Some(err),
+            ConsensusError::Commitment(err) => Some(err),
+            ConsensusError::Layer1(err) => Some(err),
+            ConsensusError::Custom(_) => None,
+        }
+    }
+}
+
+impl ConsensusError {
+    /// Returns reference to the underlying validation failure.
+    pub fn as_failure(&self) -> Option<&Failure> {
+        match self {
+            ConsensusError::Schema(err) => err.as_failure(),
+            ConsensusError::Script(err) => err.as_failure(),
+            ConsensusError::Commitment(err) => err.as_failure(),
+            ConsensusError::Layer1(err) => err.as_failure(),
+            ConsensusError::Custom(_) => None,
+        }
+    }
+}
+
+impl From<Failure> for ConsensusError {
+    fn from(failure: Failure) -> Self {
+        match failure {
+            Failure::SchemaMismatch { .. } |
+            Failure::SchemaBlankTransitionRedefined |
+            Failure::SchemaGlobalSemIdUnknown(..) |
+            Failure::SchemaConstantInvalid(..) |
+            Failure::SchemaOwnedSemIdUnknown(..) |
+            Failure::SchemaOpMetaSemIdUnknown(..) |
+            Failure::SchemaOpEmptyInputs(..) |
+            Failure::SchemaOpGlobalTypeUnknown(..) |
+            Failure::SchemaOpAssignmentTypeUnknown(..) |
+            Failure::SchemaOpValencyTypeUnknown(..) |
+            Failure::SchemaMaturityTypeUnknown(..) |
+            Failure::SchemaVoutDerivationTypeUnknown(..) |
+            Failure::SchemaKeyRightInvalid(..) |
+            Failure::SchemaThresholdInvalid(..) |
+            Failure::SchemaCategoryInvalid(..) |
+            Failure::SchemaCovenantInvalid(..) |
+            Failure::SchemaProcedureInvalid(..) |
+            Failure::SchemaDeprecationTypeUnknown(..) |
+            Failure::SchemaMigrationInvalid(..) |
+            Failure::SchemaScriptNondeterministic(..) |
+            Failure::SchemaCounterInvalid(..) |
+            Failure::SchemaProvisionalInvalid(..) |
+            Failure::SchemaPluginInvalid(..) |
+            Failure::SubschemaGlobalStateMismatch(..) |
+            Failure::SubschemaAssignmentTypeMismatch(..) |
+            Failure::SubschemaValencyTypeMismatch(..) |
+            Failure::SubschemaMaturityMismatch(..) |
+            Failure::SubschemaVoutDerivationMismatch(..) |
+            Failure::SubschemaKeyRightsMismatch(..) |
+            Failure::SubschemaThresholdMismatch(..) |
+            Failure::SubschemaCategoryMismatch(..) |
+            Failure::SubschemaCovenantMismatch(..) |
+            Failure::SubschemaProcedureMismatch(..) |
+            Failure::SubschemaDeprecationMismatch(..) |
+            Failure::SubschemaHistoryLimitsMismatch |
+            Failure::SubschemaPluginMismatch(..) |
+            Failure::SubschemaTransitionTypeMismatch(..) |
+            Failure::SubschemaExtensionTypeMismatch(..) |
+            Failure::SubschemaOpMetaMismatch { .. } |
+            Failure::SubschemaOpTimestampMismatch(..) |
+            Failure::SubschemaOpGlobalStateMismatch(..) |
+            Failure::SubschemaOpInputMismatch(..) |
+            Failure::SubschemaOpRedeemMismatch(..) |
+            Failure::SubschemaOpAssignmentsMismatch(..) |
+            Failure::SubschemaOpValencyMismatch(..) |
+            Failure::SchemaUnknownExtensionType(..) |
+            Failure::SchemaUnknownTransitionType(..) |
+            Failure::SchemaUnknownGlobalStateType(..) |
+            Failure::SchemaUnknownAssignmentType(..) |
+            Failure::SchemaUnknownValencyType(..) |
+            Failure::SchemaGlobalStateOccurrences(..) |
+            Failure::SchemaGlobalStateLimit(..) |
+            Failure::GlobalStateExpired(..) |
+            Failure::SchemaInvalidMetadata(..) |
+            Failure::InvalidTimestampProof(..) |
+            Failure::SchemaInvalidGlobalValue(..) |
+            Failure::SchemaInvalidOwnedValue(..) |
+            Failure::SchemaInputOccurrences(..) |
+            Failure::SchemaAssignmentOccurrences(..) |
+            Failure::CounterMalformed(..) |
+            Failure::CounterOutOfBounds(..) |
+            Failure::CounterStep { .. } |
+            Failure::BlankValueMismatch { .. } |
+            Failure::SupplyOverflow(..) |
+            Failure::CovenantNonTransferable { .. } |
+            Failure::CovenantCosignMissing { .. } |
+            Failure::NftTokenMalformed(..) |
+            Failure::NftTokenDuplicated { .. } |
+            Failure::NftTokensMismatch { .. } |
+            Failure::NftTokenChanged { .. } |
+            Failure::IssuerIdentityInvalid(..) |
+            Failure::OperationSunset(..) |
+            Failure::ThresholdNotMet { .. } |
+            Failure::KeyRightSignatureInvalid { .. } |
+            Failure::PluginValenciesMixed(..) |
+            Failure::CheckpointNotAllowed(..) |
+            Failure::CheckpointGlobalType(..) |
+            Failure::CheckpointOutput(..) |
+            Failure::ProvisionalRevocationMalformed(..) |
+            Failure::ProvisionalConflict(..) |
+            Failure::HistoryTooDeep { .. } |
+            Failure::HistoryTooWide { .. } |
+            Failure::StateTypeMismatch { .. } |
+            Failure::MediaTypeMismatch { .. } |
+            Failure::FungibleTypeMismatch { .. } => SchemaError::from(failure).into(),
+
+            Failure::ScriptFailure(..) |
+            Failure::UnsupportedIsa(..) |
+            Failure::VmUnsupported(..) => ScriptError::from(failure).into(),
+
+            Failure::OperationAbsent(_) |
+            Failure::TransitionAbsent(_) |
+            Failure::BundleInvalid(_) |
+            Failure::ConsignmentTooManyNodes { .. } |
+            Failure::ConsignmentTooLarge { .. } |
+            Failure::ConsignmentTooDeep { .. } |
+            Failure::AttachmentMissing(..) |
+            Failure::BlindingReused(..) |
+            Failure::SealBlindingReused(..) |
+            Failure::FeaturesUnknown(..) |
+            Failure::NotAnchored(_) |
+            Failure::NotInAnchor(..) |
+            Failure::NoPrevState { .. } |
+            Failure::NoPrevOut(..) |
+            Failure::ConfidentialSeal(_) |
+            Failure::MpcInvalid(..) |
+            Failure::CheckpointMismatch(..) |
+            Failure::CheckpointUntrusted(..) |
+            Failure::ValencyNoParent { .. } |
+            Failure::NoPrevValency { .. } |
+            Failure::PluginSchemaAbsent(..) |
+            Failure::BulletproofsInvalid(..) => CommitmentError::from(failure).into(),
+
+            Failure::SealNoWitnessTx(_) |
+            Failure::WitnessHeightUnknown(_) |
+            Failure::WitnessTimeUnknown(_) |
+            Failure::WitnessMtpUnknown(_) |
+            Failure::SpvProofsAbsent |
+            Failure::SpvInvalid(_) |
+            Failure::WrongChain { .. } |
+            Failure::SealImmature { .. } |
+            Failure::SealInvalid(..) |
+            Failure::SealVoutUnderivable(..) |
+            Failure::AnchorInvalid(..) => Layer1Error::from(failure).into(),
+
+            Failure::Custom(msg) => ConsensusError::Custom(msg),
+        }
+    }
+}
+
+impl From<UntrustedError> for ConsensusError {
+    /// Classifies errors decoding untrusted data: data produced by an
+    /// unsupported protocol version are reported as schema errors, while
+    /// malformed data and data exceeding the decoding limits are reported as
+    /// commitment errors.
+    fn from(err: UntrustedError) -> Self {
+        match err {
+            UntrustedError::UnsupportedVersion { .. } => SchemaError(Cause::Decode(err)).into(),
+            UntrustedError::DataTooLarge(..) |
+            UntrustedError::CollectionTooLarge(..) |
+            UntrustedError::CollectionOverrun(..) |
+            UntrustedError::NestingTooDeep(_) |
+            UntrustedError::TrailingData(_) |
+            UntrustedError::Decode(_) => CommitmentError(Cause::Decode(err)).into(),
+        }
+    }
+}
+
+impl From<DecodeError> for ConsensusError {
+    fn from(err: DecodeError) -> Self { UntrustedError::from(err).into() }
+}
+
+#[cfg(test)]
+mod test {
+    use strict_encoding::{StrictDumb, StrictSerialize};
+
+    use super::*;
+    use crate::validation::DecodeUntrusted;
+    use crate::{CheckpointId, Ffv, OpFullType, Transition};
+
+    #[test]
+    fn failure_layers() {
+        let failure = Failure::CheckpointMismatch(CheckpointId::from([1u8; 32]));
+        let err = ConsensusError::from(failure.clone());
+        assert!(matches!(err, ConsensusError::Commitment(_)));
+        assert_eq!(err.as_failure(), Some(&failure));
+
+        let failure = Failure::SchemaOpEmptyInputs(OpFullType::StateTransition(1));
+        let ConsensusError::Schema(err) = ConsensusError::from(failure.clone()) else {
+            panic!("schema failure classified into a different layer");
+        };
+        assert_eq!(err.as_decode_error(), None);
+        assert_eq!(err.into_failure(), Some(failure));
+    }
+
+    #[test]
+    fn decode_layers() {
+        let mut transition = Transition::strict_dumb();
+        transition.ffv = Ffv::LATEST;
+        let mut data = transition
+            .to_strict_serialized::<{ u16::MAX as usize }>()
+            .unwrap();
+        data.push(0).unwrap();
+        let err = ConsensusError::from(Transition::decode_untrusted(&data).unwrap_err());
+        let ConsensusError::Commitment(ref layer) = err else {
+            panic!("malformed data classified into a different layer");
+        };
+        assert_eq!(layer.as_decode_error(), Some(&UntrustedError::TrailingData(1)));
+        assert_eq!(err.as_failure(), None);
+        let source = err.source().and_then(Error::source).unwrap();
+        assert_eq!(source.downcast_ref(), Some(&UntrustedError::TrailingData(1)));
+
+        data[0] = 0xFF;
+        let ConsensusError::Schema(layer) =
+            ConsensusError::from(Transition::decode_untrusted(&data).unwrap_err())
+        else {
+            panic!("unsupported version classified into a different layer");
+        };
+        assert!(matches!(
+            layer.as_decode_error(),
+            Some(UntrustedError::UnsupportedVersion { found: Ffv(0xFF), .. })
+        ));
+
+        let decode_err = DecodeError::DataIntegrityError(s!("invalid data"));
+        let ConsensusError::Commitment(layer) = ConsensusError::from(decode_err.clone()) else {
+            panic!("strict decoding error classified into a different layer");
+        };
+        assert_eq!(layer.as_decode_error(), Some(&UntrustedError::Decode(decode_err)));
+    }
+}
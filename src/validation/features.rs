@@ -0,0 +1,104 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Feature bits of the contract operations (see [`OpFeatures`]).
+
+use std::collections::BTreeSet;
+
+use super::{
+    ConsignmentApi, Failure, OfflineResolver, Status, ValidationProfile, Validator, Warning,
+};
+use crate::{OpFeatures, Operation};
+
+impl<'consignment, C: ConsignmentApi> Validator<'consignment, 'static, C, OfflineResolver> {
+    /// Checks that the consignment operations don't use feature bits unknown
+    /// to this library version (see [`OpFeatures::KNOWN`]), reporting them
+    /// according to the validation `profile`.
+    ///
+    /// Unknown features may change the meaning of the operations in future
+    /// protocol versions, thus the consensus-critical validators should use
+    /// [`ValidationProfile::Strict`]. The returned status can be combined
+    /// with the statuses of other validation phases with `+=`.
+    pub fn validate_features(consignment: &'consignment C, profile: ValidationProfile) -> Status {
+        let mut status = Status::default();
+        for opid in consignment.op_ids_except(&BTreeSet::new()) {
+            let Some(op) = consignment.operation(opid) else {
+                continue;
+            };
+            let unknown = op.features().unknown();
+            if unknown == OpFeatures::NONE {
+                continue;
+            }
+            match profile {
+                ValidationProfile::Lenient => {
+                    status.add_warning(Warning::FeaturesUnknown(opid, unknown));
+                }
+                ValidationProfile::Strict => {
+                    status.add_failure(Failure::FeaturesUnknown(opid, unknown));
+                }
+            }
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "testing")]
+mod test {
+    use bp::Chain;
+
+    use super::*;
+    use crate::testing::FakeConsignment;
+    use crate::validation::Validity;
+    use crate::{Ffv, Genesis, SubSchema};
+
+    #[test]
+    fn unknown_features() {
+        let schema = SubSchema::default();
+        let genesis = |features| Genesis {
+            ffv: Ffv::default(),
+            features,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: none!(),
+            globals: none!(),
+            assignments: none!(),
+            valencies: none!(),
+        };
+
+        let consignment = FakeConsignment::new(schema.clone(), genesis(OpFeatures::NONE));
+        let status = Validator::validate_features(&consignment, ValidationProfile::Strict);
+        assert_eq!(status, Status::default());
+
+        let unknown = OpFeatures::from_bits(0x0101);
+        let genesis = genesis(unknown);
+        let opid = genesis.id();
+        let consignment = FakeConsignment::new(schema, genesis);
+        let status = Validator::validate_features(&consignment, ValidationProfile::Lenient);
+        assert_eq!(status.validity(), Validity::Valid);
+        assert_eq!(status.warnings, vec![Warning::FeaturesUnknown(opid, unknown)]);
+
+        let status = Validator::validate_features(&consignment, ValidationProfile::Strict);
+        assert_eq!(status.failures, vec![Failure::FeaturesUnknown(opid, unknown)]);
+        assert_eq!(unknown.to_string(), "0x0101");
+    }
+}
@@ -0,0 +1,307 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Block header chains used for the SPV verification of the witness
+//! transactions.
+
+use std::collections::BTreeMap;
+
+use amplify::num::u256;
+use amplify::{Bytes32, Wrapper};
+use bp::Chain;
+use commit_verify::{DigestExt, Sha256};
+
+use super::SpvError;
+
+/// Bitcoin block header.
+///
+/// Hashes are kept in the internal byte order, i.e. reversed relative to
+/// their usual hexadecimal representation.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct BlockHeader {
+    pub version: i32,
+    pub prev_block_hash: Bytes32,
+    pub merkle_root: Bytes32,
+    pub time: u32,
+    /// Proof-of-work target in the compact form.
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    /// Computes block hash, which is a double SHA256 hash of the consensus
+    /// serialization of the header.
+    pub fn block_hash(&self) -> Bytes32 {
+        let mut data = Vec::with_capacity(80);
+        data.extend(self.version.to_le_bytes());
+        data.extend(self.prev_block_hash.as_slice());
+        data.extend(self.merkle_root.as_slice());
+        data.extend(self.time.to_le_bytes());
+        data.extend(self.bits.to_le_bytes());
+        data.extend(self.nonce.to_le_bytes());
+        Bytes32::from(sha256d(&data))
+    }
+
+    /// Expands the proof-of-work target from the compact form into a
+    /// big-endian 256-bit number. Returns `None` for negative, zero or
+    /// overflowing targets.
+    pub fn target(&self) -> Option<[u8; 32]> { expand_bits(self.bits) }
+
+    /// Checks that the block hash doesn't exceed the proof-of-work target.
+    pub fn has_valid_pow(&self) -> bool {
+        let Some(target) = self.target() else {
+            return false;
+        };
+        let mut hash = self.block_hash().into_inner();
+        hash.reverse();
+        hash <= target
+    }
+
+    /// Computes the expected number of hashes required to produce a block
+    /// with the header target. Zero for invalid targets.
+    pub fn work(&self) -> u256 {
+        let Some(target) = self.target() else {
+            return u256::ZERO;
+        };
+        // 2^256 / (target + 1) computed without overflowing 256 bits
+        let target = u256::from_be_bytes(target);
+        (!target / (target + u256::ONE)) + u256::ONE
+    }
+}
+
+/// Provider of a bitcoin block header chain, which allows to plug different
+/// header backends (like a compact block filter or a lightning node) into the
+/// SPV validation (see [`super::SpvResolver::verify`]).
+pub trait HeaderSource {
+    /// Height of the most recent header known to the source, or `None` if
+    /// the source has no headers.
+    fn tip_height(&self) -> Option<u32>;
+
+    /// Header of the block at the given height in the best chain known to the
+    /// source.
+    fn header_at(&self, height: u32) -> Option<BlockHeader>;
+
+    /// Height and header of the block with the given hash, if the block
+    /// belongs to the best chain known to the source.
+    fn header_by_hash(&self, hash: Bytes32) -> Option<(u32, BlockHeader)>;
+
+    /// Verifies links and proof of work of the headers starting from the
+    /// `from` height up to the tip, returning their cumulative work.
+    fn verify_chainwork(&self, from: u32, chain: Chain) -> Result<u256, SpvError> {
+        let tip = self.tip_height().ok_or(SpvError::NoHeaders)?;
+        let headers = (from..=tip)
+            .map(|height| self.header_at(height).ok_or(SpvError::UnknownHeight(height)))
+            .collect::<Result<Vec<_>, _>>()?;
+        verify_headers(from, &headers, chain)
+    }
+}
+
+/// In-memory chain of consecutive block headers.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct HeaderChain {
+    start_height: u32,
+    headers: Vec<BlockHeader>,
+    index: BTreeMap<Bytes32, u32>,
+}
+
+impl HeaderChain {
+    /// Constructs empty chain, which first header will have the given height.
+    pub fn new(start_height: u32) -> Self {
+        HeaderChain {
+            start_height,
+            headers: vec![],
+            index: empty!(),
+        }
+    }
+
+    /// Constructs chain from the consecutive headers starting at the given
+    /// height, checking that they are linked to each other.
+    pub fn with(
+        start_height: u32,
+        headers: impl IntoIterator<Item = BlockHeader>,
+    ) -> Result<Self, SpvError> {
+        let mut chain = HeaderChain::new(start_height);
+        for header in headers {
+            chain.push(header)?;
+        }
+        Ok(chain)
+    }
+
+    /// Appends header to the tip of the chain, returning its height. The
+    /// header must reference the current tip; its proof of work is not
+    /// checked (see [`HeaderSource::verify_chainwork`]).
+    pub fn push(&mut self, header: BlockHeader) -> Result<u32, SpvError> {
+        let height = self.start_height + self.headers.len() as u32;
+        if let Some(tip) = self.headers.last() {
+            if tip.block_hash() != header.prev_block_hash {
+                return Err(SpvError::Disconnected(height));
+            }
+        }
+        self.index.insert(header.block_hash(), height);
+        self.headers.push(header);
+        Ok(height)
+    }
+
+    /// Height of the first header of the chain.
+    pub fn start_height(&self) -> u32 { self.start_height }
+
+    /// Headers of the chain, starting from the first one.
+    pub fn headers(&self) -> &[BlockHeader] { &self.headers }
+}
+
+impl HeaderSource for HeaderChain {
+    fn tip_height(&self) -> Option<u32> {
+        (!self.headers.is_empty()).then(|| self.start_height + self.headers.len() as u32 - 1)
+    }
+
+    fn header_at(&self, height: u32) -> Option<BlockHeader> {
+        let index = height.checked_sub(self.start_height)?;
+        self.headers.get(index as usize).copied()
+    }
+
+    fn header_by_hash(&self, hash: Bytes32) -> Option<(u32, BlockHeader)> {
+        let height = *self.index.get(&hash)?;
+        Some((height, self.headers[(height - self.start_height) as usize]))
+    }
+}
+
+/// Verifies links and proof of work of the consecutive headers starting at
+/// the given height, returning their cumulative work.
+pub(super) fn verify_headers(
+    start_height: u32,
+    headers: &[BlockHeader],
+    chain: Chain,
+) -> Result<u256, SpvError> {
+    let limit = expand_bits(pow_limit(chain)).expect("valid chain limit");
+    let mut work = u256::ZERO;
+    let mut prev_hash = None;
+    for (height, header) in (start_height..).zip(headers) {
+        if prev_hash.map_or(false, |hash| hash != header.prev_block_hash) {
+            return Err(SpvError::Disconnected(height));
+        }
+        if !header.has_valid_pow() {
+            return Err(SpvError::PowInvalid(height));
+        }
+        if header.target().map_or(true, |target| target > limit) {
+            return Err(SpvError::TargetTooEasy(height));
+        }
+        work = work.saturating_add(header.work());
+        prev_hash = Some(header.block_hash());
+    }
+    Ok(work)
+}
+
+/// Compact proof-of-work limit of the chain.
+fn pow_limit(chain: Chain) -> u32 {
+    match chain {
+        Chain::Bitcoin | Chain::Testnet3 => 0x1d00ffff,
+        Chain::Signet => 0x1e0377ae,
+        Chain::Regtest => 0x207fffff,
+    }
+}
+
+fn expand_bits(bits: u32) -> Option<[u8; 32]> {
+    let exponent = (bits >> 24) as usize;
+    let mantissa = bits & 0x007f_ffff;
+    if bits & 0x0080_0000 != 0 || mantissa == 0 {
+        return None;
+    }
+    let mut target = [0u8; 32];
+    for (i, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+        // Position of the byte counting from the most significant one
+        match (32 + i).checked_sub(exponent) {
+            Some(pos) if pos < 32 => target[pos] = *byte,
+            // Shifted out to the right
+            Some(_) => {}
+            None if *byte == 0 => {}
+            None => return None,
+        }
+    }
+    Some(target).filter(|target| target.iter().any(|byte| *byte != 0))
+}
+
+pub(super) fn sha256d(data: &[u8]) -> [u8; 32] {
+    let mut engine = Sha256::default();
+    engine.input_raw(data);
+    let hash = engine.finish();
+    let mut engine = Sha256::default();
+    engine.input_raw(&hash);
+    engine.finish()
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use super::*;
+
+    pub fn mine(prev_block_hash: Bytes32, merkle_root: Bytes32, time: u32) -> BlockHeader {
+        let mut header = BlockHeader {
+            version: 4,
+            prev_block_hash,
+            merkle_root,
+            time,
+            bits: 0x207fffff,
+            nonce: 0,
+        };
+        while !header.has_valid_pow() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    #[test]
+    fn compact_target() {
+        let mut limit = [0u8; 32];
+        limit[4..6].copy_from_slice(&[0xFF, 0xFF]);
+        assert_eq!(expand_bits(0x1d00ffff), Some(limit));
+        assert_eq!(expand_bits(0x03123456).unwrap()[29..], [0x12, 0x34, 0x56]);
+        assert_eq!(expand_bits(0x01123456).unwrap()[31], 0x12);
+        assert_eq!(expand_bits(0x04923456), None);
+        assert_eq!(expand_bits(0x21010000), None);
+        assert_eq!(expand_bits(0x00123456), None);
+    }
+
+    #[test]
+    fn chainwork() {
+        let mut chain = HeaderChain::new(10);
+        assert_eq!(chain.verify_chainwork(10, Chain::Regtest), Err(SpvError::NoHeaders));
+
+        let mut prev = Bytes32::from([0; 32]);
+        for time in 0..3 {
+            let header = mine(prev, Bytes32::from([0xAA; 32]), time);
+            prev = header.block_hash();
+            chain.push(header).unwrap();
+        }
+        assert_eq!(chain.tip_height(), Some(12));
+        assert_eq!(chain.header_by_hash(prev), Some((12, chain.headers()[2])));
+        assert_eq!(chain.header_at(9), None);
+
+        // Regtest blocks require two hashes on average
+        let work = chain.verify_chainwork(10, Chain::Regtest).unwrap();
+        assert_eq!(work, u256::from(6u8));
+        assert_eq!(chain.verify_chainwork(12, Chain::Regtest).unwrap(), u256::from(2u8));
+        assert_eq!(chain.verify_chainwork(9, Chain::Regtest), Err(SpvError::UnknownHeight(9)));
+        assert_eq!(chain.verify_chainwork(10, Chain::Bitcoin), Err(SpvError::TargetTooEasy(10)));
+
+        let orphan = mine(Bytes32::from([1; 32]), Bytes32::from([0xAA; 32]), 4);
+        assert_eq!(chain.push(orphan), Err(SpvError::Disconnected(13)));
+    }
+}
@@ -0,0 +1,45 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compatibility layer for historical contracts which schemata use the legacy
+//! embedded virtual machine ([`Script::Embedded`]). The embedded virtual
+//! machine has no script code: operations of such contracts are validated
+//! with the schema rules built into the validator only.
+//!
+//! The layer is compiled with the `embedded` feature only. Without it,
+//! consignments of such contracts fail validation with
+//! [`Failure::VmUnsupported`], which allows minimal consensus builds
+//! supporting AluVM only.
+//!
+//! [`Script::Embedded`]: crate::Script::Embedded
+
+use super::{Failure, OpContext, VirtualMachine};
+
+/// Runtime of the legacy embedded virtual machine.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct EmbeddedVm;
+
+impl VirtualMachine for EmbeddedVm {
+    // All the rules of the embedded virtual machine are the schema rules,
+    // which are checked by the validator before running the virtual machine
+    fn validate(&self, _context: OpContext) -> Result<(), Failure> { Ok(()) }
+}
@@ -27,9 +27,42 @@ mod state;
 mod validator;
 mod consignment;
 mod status;
+mod error;
+mod untrusted;
+mod tapret;
+mod progress;
+mod bounded;
+mod cache;
+mod prefetch;
+mod privacy;
+mod report;
+mod attachment;
+mod spv;
+mod headers;
+mod features;
+mod typecheck;
+#[cfg(feature = "embedded")]
+mod legacy;
 
+pub use attachment::AttachmentResolver;
+pub use bounded::{BoundedError, BoundedValidator, ConsignmentItem, ConsignmentReader};
+pub use cache::{CachedResolver, PersistTx};
 pub use consignment::{AnchoredBundle, ConsignmentApi};
-pub(crate) use model::OpInfo;
-pub use script::VirtualMachine;
-pub use status::{Failure, Info, Status, Validity, Warning};
-pub use validator::{ResolveTx, TxResolverError, Validator};
+pub use error::{CommitmentError, ConsensusError, Layer1Error, SchemaError, ScriptError};
+pub use headers::{BlockHeader, HeaderChain, HeaderSource};
+#[cfg(feature = "embedded")]
+pub use legacy::EmbeddedVm;
+pub use model::OpContext;
+pub use prefetch::PrefetchHints;
+pub use privacy::{PrivacyReport, RevealStats};
+pub use progress::{CancelToken, Progress, ValidationPhase, ValidationProgress};
+pub use report::{NodeReport, ValidationReport};
+pub use script::{ScriptPolicy, VirtualMachine};
+pub use spv::{SpvError, SpvProofs, SpvResolver, TxProof};
+pub use status::{Failure, Info, Status, ValidationProfile, Validity, Warning};
+pub use tapret::{
+    taproot_tweak, tapret_merkle_root, tapret_tweak, verify_tapret, TapretTweak, TapretVerifyError,
+};
+pub use typecheck::MetaField;
+pub use untrusted::{DecodeLimits, DecodeUntrusted, FieldReader, UntrustedError, UntrustedReader};
+pub use validator::{OfflineResolver, ResolveTx, TxResolverError, Validator, ValidatorConfig};
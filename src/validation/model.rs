@@ -20,18 +20,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use amplify::confinement::{Confined, SmallBlob};
+use amplify::confinement::{Confined, SmallBlob, TinyOrdMap};
 use amplify::Wrapper;
+use bp::Txid;
+use commit_verify::{DigestExt, Sha256};
+use secp256k1_zkp::{schnorr, Message, XOnlyPublicKey, SECP256K1};
 use strict_types::SemId;
 
-use crate::schema::{AssignmentsSchema, GlobalSchema, ValencySchema};
-use crate::validation::{ConsignmentApi, VirtualMachine};
+use crate::schema::{
+    AssignmentsSchema, ConstantType, EmbeddedProcedure, GlobalSchema, SchemaConstant,
+    TransitionType, ValencySchema, ISSUER_IDENTITY_ID,
+};
+use crate::validation::{typecheck, ConsignmentApi, MetaField, VirtualMachine};
 use crate::{
-    validation, Assignments, AssignmentsRef, ExposedSeal, GlobalState, GlobalStateSchema,
-    GlobalValues, GraphSeal, Inputs, OpFullType, OpId, OpRef, Operation, Opout, Redeemed, Schema,
-    SchemaRoot, TypedAssigns, Valencies, BLANK_TRANSITION_ID,
+    validation, Amount, Assign, AssignmentCategory, AssignmentType, Assignments, AssignmentsRef,
+    ExposedSeal, Genesis, GlobalState, GlobalStateSchema, GlobalValues, GraphSeal, Inputs,
+    IssuerIdentity, OpFullType, OpId, OpRef, Operation, Opout, OtsProof, Redeemed, RevealedData,
+    Schema, SchemaId, SchemaMigration, SchemaRoot, StateSchema, Transition, TypedAssigns, Valencies,
+    WitnessPos, BLANK_TRANSITION_ID,
 };
 
 impl<Root: SchemaRoot> Schema<Root> {
@@ -39,6 +47,9 @@ impl<Root: SchemaRoot> Schema<Root> {
         &self,
         consignment: &C,
         op: OpRef,
+        witness_txid: Option<Txid>,
+        witness_pos: Option<WitnessPos>,
+        contract_global: Option<&GlobalState>,
         vm: &dyn VirtualMachine,
     ) -> validation::Status {
         let id = op.id();
@@ -53,6 +64,7 @@ impl<Root: SchemaRoot> Schema<Root> {
             redeem_schema,
             assign_schema,
             valency_schema,
+            timestamped,
         ) = match (op.transition_type(), op.extension_type()) {
             (None, None) => {
                 // Right now we do not have actions to implement; but later
@@ -71,6 +83,7 @@ impl<Root: SchemaRoot> Schema<Root> {
                     &empty_valency_schema,
                     &self.genesis.assignments,
                     &self.genesis.valencies,
+                    self.genesis.timestamped,
                 )
             }
             (Some(transition_type), None) => {
@@ -100,6 +113,7 @@ impl<Root: SchemaRoot> Schema<Root> {
                     &empty_valency_schema,
                     &transition_schema.assignments,
                     &transition_schema.valencies,
+                    false,
                 )
             }
             (None, Some(extension_type)) => {
@@ -128,6 +142,7 @@ impl<Root: SchemaRoot> Schema<Root> {
                     &extension_schema.redeems,
                     &extension_schema.assignments,
                     &extension_schema.redeems,
+                    extension_schema.timestamped,
                 )
             }
             _ => unreachable!("Node can't be extension and state transition at the same time"),
@@ -137,11 +152,30 @@ impl<Root: SchemaRoot> Schema<Root> {
 
         // Validate type system
         status += self.validate_type_system();
-        status += self.validate_metadata(id, *metadata_schema, op.metadata());
+        status += self.validate_metadata(id, *metadata_schema, timestamped, op.metadata());
         status += self.validate_global_state(id, op.globals(), global_schema);
+        if let OpRef::Genesis(genesis) = op {
+            status += self.validate_issuer_identity(id, genesis);
+        }
+        let mut migrated = BTreeSet::new();
         let prev_state = if let OpRef::Transition(ref transition) = op {
-            let prev_state = extract_prev_state(consignment, id, &transition.inputs, &mut status);
+            let prev_state = extract_prev_state(
+                consignment,
+                id,
+                &transition.inputs,
+                self.migration.as_ref(),
+                &mut migrated,
+                &mut status,
+            );
             status += self.validate_prev_state(id, &prev_state, owned_schema);
+            status += self.validate_thresholds(id, &transition.inputs);
+            status += self.validate_key_rights(
+                id,
+                op.signing_id(),
+                timestamped,
+                op.metadata(),
+                &prev_state,
+            );
             prev_state
         } else {
             Assignments::default()
@@ -154,7 +188,26 @@ impl<Root: SchemaRoot> Schema<Root> {
         } else {
             Valencies::default()
         };
-        status += match op.assignments() {
+        // Operations created under the previous schema version assign the
+        // state of the migrated types
+        let (migrated_genesis, migrated_graph);
+        let owned_state = match op.assignments() {
+            AssignmentsRef::Genesis(assignments) => match self.migrate_assignments(assignments) {
+                Some(assignments) => {
+                    migrated_genesis = assignments;
+                    AssignmentsRef::Genesis(&migrated_genesis)
+                }
+                None => AssignmentsRef::Genesis(assignments),
+            },
+            AssignmentsRef::Graph(assignments) => match self.migrate_assignments(assignments) {
+                Some(assignments) => {
+                    migrated_graph = assignments;
+                    AssignmentsRef::Graph(&migrated_graph)
+                }
+                None => AssignmentsRef::Graph(assignments),
+            },
+        };
+        status += match owned_state {
             AssignmentsRef::Genesis(assignments) => {
                 self.validate_owned_state(id, assignments, assign_schema)
             }
@@ -163,17 +216,97 @@ impl<Root: SchemaRoot> Schema<Root> {
             }
         };
 
+        status += self.validate_nft_tokens(id, op.transition_type(), &prev_state, owned_state);
+
+        if let (OpRef::Transition(transition), AssignmentsRef::Graph(assignments)) =
+            (&op, owned_state)
+        {
+            status += self.validate_counters(id, &prev_state, assignments);
+            if transition.transition_type == BLANK_TRANSITION_ID {
+                status += self.validate_blank_values(id, &prev_state, assignments);
+            } else {
+                status += self.validate_covenants(
+                    consignment,
+                    id,
+                    witness_txid,
+                    transition,
+                    assignments,
+                );
+            }
+        }
+
         status += self.validate_valencies(id, op.valencies(), valency_schema);
 
-        let op_info = OpInfo::with(id, self.subset_of.is_some(), &op, &prev_state, &redeemed);
+        // Expired global state is not a part of the contract state anymore
+        let unexpired;
+        let mut contract_global = contract_global;
+        if let Some(pos) = witness_pos {
+            status += self.validate_global_expiry(id, op.globals(), pos);
+            if let Some(global) = contract_global {
+                unexpired = self.unexpired_global(global, pos);
+                contract_global = Some(&unexpired);
+            }
+        }
+
+        status += self.validate_deprecation(id, op, witness_pos);
+
+        let metadata_fields = self.metadata_fields(*metadata_schema, timestamped, op.metadata());
+        let context = OpContext {
+            schema_id: consignment.genesis().schema_id,
+            subschema: self.subset_of.is_some(),
+            id,
+            signing_id: op.signing_id(),
+            ty: op.full_type(),
+            witness_txid,
+            witness_mtp: witness_pos.map(|pos| pos.mtp),
+            metadata: op.metadata(),
+            metadata_fields: &metadata_fields,
+            prev_state: &prev_state,
+            migrated: &migrated,
+            constants: &self.constants,
+            owned_state,
+            redeemed: &redeemed,
+            valencies: op.valencies(),
+            global: op.globals(),
+            contract_global,
+        };
 
         // We need to run scripts as the very last step, since before that
         // we need to make sure that the operation data match the schema, so
         // scripts are not required to validate the structure of the state
-        status += self.validate_state_evolution(op_info, vm);
+        status += self.validate_state_evolution(context, vm);
         status
     }
 
+    /// Replaces owned state types of the previous schema version with the
+    /// types of this schema. Returns `None` if the assignments don't contain
+    /// migrated types.
+    ///
+    /// Migrated types are kept in place if the assignments already contain
+    /// the type replacing them, so the operation fails the validation.
+    fn migrate_assignments<Seal: ExposedSeal>(
+        &self,
+        assignments: &Assignments<Seal>,
+    ) -> Option<Assignments<Seal>> {
+        let migration = self.migration.as_ref()?;
+        if !assignments
+            .keys()
+            .any(|ty| migration.owned_types.contains_key(ty))
+        {
+            return None;
+        }
+        let mut migrated = assignments.clone();
+        for (from, to) in &migration.owned_types {
+            if migrated.contains_key(to) {
+                continue;
+            }
+            if let Some(assigns) = migrated.remove(from).expect("no minimal size") {
+                migrated.insert(*to, assigns).expect("same size");
+            }
+        }
+        Some(migrated)
+    }
+
     fn validate_type_system(&self) -> validation::Status {
         validation::Status::new()
         // TODO: Validate type system
@@ -190,21 +323,47 @@ impl<Root: SchemaRoot> Schema<Root> {
         &self,
         opid: OpId,
         sem_id: SemId,
+        timestamped: bool,
         metadata: &SmallBlob,
     ) -> validation::Status {
         let mut status = validation::Status::new();
 
-        if self
-            .type_system
-            .strict_deserialize_type(sem_id, metadata.as_ref())
-            .is_err()
-        {
+        let mut metadata = metadata.as_slice();
+        if timestamped {
+            match OtsProof::extract(metadata) {
+                Ok((_, rest)) => metadata = rest,
+                Err(err) => {
+                    status.add_failure(validation::Failure::InvalidTimestampProof(opid, err));
+                    return status;
+                }
+            }
+        }
+
+        if !typecheck::conforms(&self.type_system, sem_id, metadata) {
             status.add_failure(validation::Failure::SchemaInvalidMetadata(opid, sem_id));
         };
 
         status
     }
 
+    /// Splits metadata into the fields accessible to the scripts, skipping
+    /// the timestamp proof.
+    fn metadata_fields<'op>(
+        &self,
+        sem_id: SemId,
+        timestamped: bool,
+        metadata: &'op SmallBlob,
+    ) -> Vec<MetaField<'op>> {
+        let mut metadata = metadata.as_slice();
+        if timestamped {
+            let Ok((_, rest)) = OtsProof::extract(metadata) else {
+                return vec![];
+            };
+            metadata = rest;
+        }
+        typecheck::meta_fields(&self.type_system, sem_id, metadata)
+    }
+
     fn validate_global_state(
         &self,
         opid: OpId,
@@ -231,7 +390,9 @@ impl<Root: SchemaRoot> Schema<Root> {
                 .map(Confined::unbox)
                 .unwrap_or_default();
 
-            let GlobalStateSchema { sem_id, max_items } = self.global_types.get(type_id).expect(
+            let GlobalStateSchema {
+                sem_id, max_items, ..
+            } = self.global_types.get(type_id).expect(
                 "if the field were absent, the schema would not be able to pass the internal \
                  validation and we would not reach this point",
             );
@@ -266,6 +427,140 @@ impl<Root: SchemaRoot> Schema<Root> {
         status
     }
 
+    fn validate_global_expiry(
+        &self,
+        opid: OpId,
+        global: &GlobalState,
+        pos: WitnessPos,
+    ) -> validation::Status {
+        let mut status = validation::Status::new();
+        for type_id in global.keys() {
+            let expired = self
+                .global_types
+                .get(type_id)
+                .map(|schema| schema.expiry.is_expired(pos))
+                .unwrap_or_default();
+            if expired {
+                status.add_failure(validation::Failure::GlobalStateExpired(opid, *type_id));
+            }
+        }
+        status
+    }
+
+    fn validate_deprecation(
+        &self,
+        opid: OpId,
+        op: OpRef,
+        pos: Option<WitnessPos>,
+    ) -> validation::Status {
+        let mut status = validation::Status::new();
+        let op_type = op.full_type();
+        let Some(sunset) = self.deprecations.sunset(op_type) else {
+            return status;
+        };
+        let ffv = match op {
+            OpRef::Genesis(genesis) => genesis.ffv,
+            OpRef::Transition(transition) => transition.ffv,
+            OpRef::Extension(extension) => extension.ffv,
+        };
+        if sunset.is_reached(ffv, pos) {
+            status.add_failure(validation::Failure::OperationSunset(opid, op_type));
+        } else {
+            status.add_warning(validation::Warning::OperationDeprecated(opid, op_type));
+        }
+        status
+    }
+
+    fn unexpired_global(&self, global: &GlobalState, pos: WitnessPos) -> GlobalState {
+        let unexpired = global
+            .iter()
+            .filter(|(type_id, _)| {
+                self.global_types
+                    .get(type_id)
+                    .map(|schema| !schema.expiry.is_expired(pos))
+                    .unwrap_or(true)
+            })
+            .map(|(type_id, values)| (*type_id, values.clone()));
+        GlobalState::from_inner(
+            Confined::try_from_iter(unexpired).expect("subset of a confined collection"),
+        )
+    }
+
+    fn validate_thresholds(&self, id: OpId, inputs: &Inputs) -> validation::Status {
+        let mut status = validation::Status::new();
+
+        for (state_type, threshold) in &self.thresholds {
+            let spent = inputs
+                .iter()
+                .filter(|input| self.migrated_type(input.prev_out.ty) == *state_type)
+                .count();
+            if spent > 0 && spent < *threshold as usize {
+                status.add_failure(validation::Failure::ThresholdNotMet {
+                    opid: id,
+                    state_type: *state_type,
+                    required: *threshold,
+                    spent: spent as u16,
+                });
+            }
+        }
+
+        status
+    }
+
+    /// Checks that the metadata of a transition spending key rights (following
+    /// the timestamp proof, if any) starts with BIP340 signatures of the
+    /// transition signing id, one per spent key right in the order of the
+    /// key right types and then of the inputs, made with the keys from the
+    /// spent state.
+    fn validate_key_rights(
+        &self,
+        id: OpId,
+        signing_id: OpId,
+        timestamped: bool,
+        metadata: &SmallBlob,
+        prev_state: &Assignments<GraphSeal>,
+    ) -> validation::Status {
+        let mut status = validation::Status::new();
+
+        let mut metadata = metadata.as_slice();
+        if timestamped {
+            // Invalid timestamp proofs are reported by the metadata validation
+            let Ok((_, rest)) = OtsProof::extract(metadata) else {
+                return status;
+            };
+            metadata = rest;
+        }
+        let mut engine = Sha256::default();
+        engine.input_raw(signing_id.as_slice());
+        let msg = Message::from_slice(&engine.finish()).expect("hash has 32 bytes");
+        let mut signatures = metadata.chunks(64);
+
+        for state_type in &self.key_rights {
+            let Some(assigns) = prev_state.get(state_type) else {
+                continue;
+            };
+            for index in 0..assigns.len_u16() {
+                let verified = assigns
+                    .as_structured_state_at(index)
+                    .ok()
+                    .flatten()
+                    .and_then(|data| XOnlyPublicKey::from_slice(data.as_inner()).ok())
+                    .zip(signatures.next())
+                    .and_then(|(key, sig)| Some((key, schnorr::Signature::from_slice(sig).ok()?)))
+                    .map(|(key, sig)| SECP256K1.verify_schnorr(&sig, &msg, &key).is_ok())
+                    .unwrap_or_default();
+                if !verified {
+                    status.add_failure(validation::Failure::KeyRightSignatureInvalid {
+                        opid: id,
+                        state_type: *state_type,
+                    });
+                }
+            }
+        }
+
+        status
+    }
+
     fn validate_prev_state<Seal: ExposedSeal>(
         &self,
         id: OpId,
@@ -381,6 +676,315 @@ impl<Root: SchemaRoot> Schema<Root> {
         status
     }
 
+    fn validate_counters(
+        &self,
+        id: OpId,
+        prev_state: &Assignments<GraphSeal>,
+        owned_state: &Assignments<GraphSeal>,
+    ) -> validation::Status {
+        let mut status = validation::Status::new();
+
+        for (state_type, schema) in &self.owned_types {
+            let StateSchema::Counter(counter) = schema else {
+                continue
+            };
+            let counters = |assigns: Option<&TypedAssigns<GraphSeal>>| {
+                assigns
+                    .map(TypedAssigns::as_structured)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(Assign::as_revealed_state)
+                    .filter_map(RevealedData::as_counter)
+                    .collect::<Vec<_>>()
+            };
+            // Counters assigned without spending a previous counter value are
+            // checked only against the schema bounds
+            let Some(prev) = counters(prev_state.get(state_type)).into_iter().max() else {
+                continue
+            };
+            for value in counters(owned_state.get(state_type)) {
+                if !counter.allows_step(prev, value) {
+                    status.add_failure(validation::Failure::CounterStep {
+                        opid: id,
+                        state_type: *state_type,
+                        prev,
+                        value,
+                    });
+                }
+            }
+        }
+
+        status
+    }
+
+    /// Checks that a blank transition moves the value-bearing state without
+    /// changing its total amount.
+    fn validate_blank_values(
+        &self,
+        id: OpId,
+        prev_state: &Assignments<GraphSeal>,
+        owned_state: &Assignments<GraphSeal>,
+    ) -> validation::Status {
+        let mut status = validation::Status::new();
+
+        for (state_type, category) in &self.categories {
+            if *category != AssignmentCategory::Value {
+                continue;
+            }
+            // Totals are unknown if some of the state is confidential
+            let total = |assigns: Option<&TypedAssigns<GraphSeal>>| -> Option<Option<Amount>> {
+                let values = assigns
+                    .map(TypedAssigns::as_fungible)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|assign| assign.as_revealed_state().map(|state| state.value))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Amount::sum(values))
+            };
+            let (Some(inputs), Some(outputs)) =
+                (total(prev_state.get(state_type)), total(owned_state.get(state_type)))
+            else {
+                status.add_info(validation::Info::UncheckableConfidentialState(id, *state_type));
+                continue;
+            };
+            let (Some(inputs), Some(outputs)) = (inputs, outputs) else {
+                status.add_failure(validation::Failure::SupplyOverflow(id, *state_type));
+                continue;
+            };
+            if inputs != outputs {
+                status.add_failure(validation::Failure::BlankValueMismatch {
+                    opid: id,
+                    state_type: *state_type,
+                    inputs,
+                    outputs,
+                });
+            }
+        }
+
+        status
+    }
+
+    /// Checks that a transition spending the state with covenants declares the
+    /// co-signature valencies and doesn't re-assign non-transferable state.
+    fn validate_covenants<C: ConsignmentApi>(
+        &self,
+        consignment: &C,
+        id: OpId,
+        witness_txid: Option<Txid>,
+        transition: &Transition,
+        owned_state: &Assignments<GraphSeal>,
+    ) -> validation::Status {
+        let mut status = validation::Status::new();
+
+        for (state_type, covenant) in &self.covenants {
+            let spent = transition
+                .inputs
+                .iter()
+                .map(|input| input.prev_out)
+                .filter(|prev_out| self.migrated_type(prev_out.ty) == *state_type)
+                .collect::<Vec<_>>();
+            if spent.is_empty() {
+                continue;
+            }
+            if let Some(cosign) = covenant.cosign {
+                let cosigned = witness_txid
+                    .map(|txid| {
+                        bundle_transitions(consignment, id, txid)
+                            .any(|sibling| self.spends(sibling, cosign))
+                    })
+                    .unwrap_or_default();
+                if !cosigned {
+                    status.add_failure(validation::Failure::CovenantCosignMissing {
+                        opid: id,
+                        state_type: *state_type,
+                        cosign,
+                    });
+                }
+            }
+            let locked = covenant.non_transferable ||
+                covenant.transferable_once &&
+                    spent
+                        .iter()
+                        .any(|prev_out| self.is_transferred(consignment, *prev_out));
+            if locked && owned_state.contains_key(state_type) {
+                status.add_failure(validation::Failure::CovenantNonTransferable {
+                    opid: id,
+                    state_type: *state_type,
+                });
+            }
+        }
+
+        status
+    }
+
+    /// Returns transitions committed into the same bundle as the provided one,
+    /// which co-sign its transfers of the state restricted by the covenants
+    /// (see [`StateCovenant::cosign`]). The co-signing transitions have to be
+    /// validated together with the transition.
+    pub(crate) fn cosigners<'c, C: ConsignmentApi>(
+        &self,
+        consignment: &'c C,
+        transition: &Transition,
+        witness_txid: Txid,
+    ) -> Vec<&'c Transition> {
+        if transition.transition_type == BLANK_TRANSITION_ID {
+            return vec![];
+        }
+        let id = transition.id();
+        let cosign = self
+            .covenants
+            .iter()
+            .filter(|(state_type, _)| self.spends(transition, **state_type))
+            .filter_map(|(_, covenant)| covenant.cosign)
+            .collect::<BTreeSet<_>>();
+        if cosign.is_empty() {
+            return vec![];
+        }
+        bundle_transitions(consignment, id, witness_txid)
+            .filter(|sibling| sibling.id() != id)
+            .filter(|sibling| cosign.iter().any(|ty| self.spends(sibling, *ty)))
+            .collect()
+    }
+
+    /// Checks whether the transition spends state of the given (migrated)
+    /// type.
+    fn spends(&self, transition: &Transition, state_type: AssignmentType) -> bool {
+        transition
+            .inputs
+            .iter()
+            .any(|input| self.migrated_type(input.prev_out.ty) == state_type)
+    }
+
+    /// Verifies the issuer identity proof of the genesis if the schema enables
+    /// [`EmbeddedProcedure::IssuerIdentity`] procedure.
+    fn validate_issuer_identity(&self, id: OpId, genesis: &Genesis) -> validation::Status {
+        let mut status = validation::Status::new();
+        if !self.procedures.contains(&EmbeddedProcedure::IssuerIdentity) {
+            return status;
+        }
+        let Some(values) = genesis.globals.get(&ISSUER_IDENTITY_ID) else {
+            return status;
+        };
+        for data in values.iter() {
+            let verified = IssuerIdentity::from_state(data)
+                .map(|identity| identity.verify(genesis).is_ok())
+                .unwrap_or_default();
+            if !verified {
+                status.add_failure(validation::Failure::IssuerIdentityInvalid(id));
+            }
+        }
+        status
+    }
+
+    /// Checks the tokens of the [`EmbeddedProcedure::NftIssue`] procedures:
+    /// the tokens assigned by an operation must have distinct ids, and state
+    /// transitions must re-assign exactly the tokens they spend, changing
+    /// their data only by engraving. Uniqueness of the token ids issued by
+    /// different operations is checked by the validator.
+    fn validate_nft_tokens(
+        &self,
+        id: OpId,
+        transition_type: Option<TransitionType>,
+        prev_state: &Assignments<GraphSeal>,
+        owned_state: AssignmentsRef,
+    ) -> validation::Status {
+        let mut status = validation::Status::new();
+
+        // Tokens are unknown if some of the state is confidential
+        let tokens = |assigns: Option<&TypedAssigns<GraphSeal>>| -> Option<Vec<RevealedData>> {
+            assigns
+                .map(TypedAssigns::as_structured)
+                .unwrap_or_default()
+                .iter()
+                .map(|assign| assign.as_revealed_state().cloned())
+                .collect()
+        };
+        for procedure in &self.procedures {
+            let EmbeddedProcedure::NftIssue {
+                token_type,
+                engraving,
+            } = procedure
+            else {
+                continue;
+            };
+            let Some(outputs) = tokens(owned_state.get(*token_type).as_ref()) else {
+                status.add_info(validation::Info::UncheckableConfidentialState(id, *token_type));
+                continue;
+            };
+            let mut assigned = BTreeMap::new();
+            for data in &outputs {
+                let Some(token_id) = data.token_id() else {
+                    status.add_failure(validation::Failure::NftTokenMalformed(id, *token_type));
+                    continue;
+                };
+                if assigned.insert(token_id, data).is_some() {
+                    status.add_failure(validation::Failure::NftTokenDuplicated {
+                        opid: id,
+                        token_id,
+                    });
+                }
+            }
+
+            let Some(transition_type) = transition_type else {
+                continue;
+            };
+            let Some(inputs) = tokens(prev_state.get(token_type)) else {
+                status.add_info(validation::Info::UncheckableConfidentialState(id, *token_type));
+                continue;
+            };
+            let spent = inputs
+                .iter()
+                .filter_map(|data| Some((data.token_id()?, data)))
+                .collect::<BTreeMap<_, _>>();
+            if !spent.keys().eq(assigned.keys()) {
+                status.add_failure(validation::Failure::NftTokensMismatch {
+                    opid: id,
+                    state_type: *token_type,
+                });
+            }
+            for (token_id, data) in assigned {
+                let Some(prev) = spent.get(&token_id) else {
+                    continue;
+                };
+                let engraved = *engraving == Some(transition_type) &&
+                    data.as_inner().starts_with(prev.as_inner());
+                if data != *prev && !engraved {
+                    status.add_failure(validation::Failure::NftTokenChanged {
+                        opid: id,
+                        token_id,
+                    });
+                }
+            }
+        }
+
+        status
+    }
+
+    /// Detects whether the state was already transferred, i.e. assigned by a
+    /// transition other than a blank one. Blank transitions keep the status of
+    /// the state they spend.
+    fn is_transferred<C: ConsignmentApi>(&self, consignment: &C, opout: Opout) -> bool {
+        match consignment.operation(opout.op) {
+            Some(OpRef::Transition(parent)) if parent.transition_type == BLANK_TRANSITION_ID => {
+                let state_type = self.migrated_type(opout.ty);
+                parent
+                    .inputs
+                    .iter()
+                    .map(|input| input.prev_out)
+                    .filter(|prev_out| self.migrated_type(prev_out.ty) == state_type)
+                    .any(|prev_out| self.is_transferred(consignment, prev_out))
+            }
+            Some(OpRef::Transition(_)) => true,
+            Some(_) => false,
+            // Checkpoint outputs assigned by transitions have witness
+            None => consignment
+                .checkpoint()
+                .and_then(|checkpoint| checkpoint.output(opout))
+                .map(|output| output.witness.is_some())
+                .unwrap_or_default(),
+        }
+    }
+
     fn validate_valencies(
         &self,
         id: OpId,
@@ -403,7 +1007,7 @@ impl<Root: SchemaRoot> Schema<Root> {
 
     fn validate_state_evolution(
         &self,
-        op_info: OpInfo,
+        context: OpContext,
         vm: &dyn VirtualMachine,
     ) -> validation::Status {
         let mut status = validation::Status::new();
@@ -411,7 +1015,7 @@ impl<Root: SchemaRoot> Schema<Root> {
         // We do not validate public rights, since they do not have an
         // associated state and there is nothing to validate beyond schema
 
-        if let Err(err) = vm.validate(op_info) {
+        if let Err(err) = vm.validate(context) {
             status.add_failure(err);
         }
 
@@ -419,64 +1023,113 @@ impl<Root: SchemaRoot> Schema<Root> {
     }
 }
 
-pub struct OpInfo<'op> {
+/// Validation context of a contract operation.
+///
+/// The context is constructed by the validator once per operation and is
+/// provided to the validation procedures, either embedded (via
+/// [`VirtualMachine`] implementations) or run by AluVM, where it is accessed
+/// with the RGB ISA instructions.
+pub struct OpContext<'op> {
+    /// Schema id, as declared by the contract genesis.
+    pub schema_id: SchemaId,
+    /// Whether the operation is validated against a subschema.
     pub subschema: bool,
+    /// Operation id.
     pub id: OpId,
+    /// Operation id computed with empty metadata (see
+    /// [`Operation::signing_id`]).
+    pub signing_id: OpId,
+    /// Operation type.
     pub ty: OpFullType,
+    /// Id of the witness transaction for the state transitions. `None` for
+    /// genesis and state extensions.
+    pub witness_txid: Option<Txid>,
+    /// Median time past of the block mining the witness transaction (see
+    /// [`WitnessPos::mtp`]). `None` for genesis, state extensions and if the
+    /// witness transactions are not validated.
+    pub witness_mtp: Option<i64>,
+    /// Operation metadata.
     pub metadata: &'op SmallBlob,
+    /// Fields of the operation metadata, split according to the metadata
+    /// semantic type declared by the schema.
+    pub metadata_fields: &'op [MetaField<'op>],
+    /// State assigned by the parent operations and spent by this operation.
     pub prev_state: &'op Assignments<GraphSeal>,
+    /// Types of the spent state which was assigned under the previous schema
+    /// version (see [`crate::SchemaMigration`]).
+    pub migrated: &'op BTreeSet<AssignmentType>,
+    /// State assigned by this operation.
     pub owned_state: AssignmentsRef<'op>,
+    /// Valencies of the parent operations redeemed by this operation.
     pub redeemed: &'op Valencies,
+    /// Valencies defined by this operation.
     pub valencies: &'op Valencies,
+    /// Global state defined by this operation.
     pub global: &'op GlobalState,
+    /// Contract global state defined by the operations preceding this one in
+    /// the contract history, with the values of each type ordered starting
+    /// from the most recent one; `None` if the state is not available.
+    pub contract_global: Option<&'op GlobalState>,
+    /// Constants declared by the schema.
+    pub constants: &'op TinyOrdMap<ConstantType, SchemaConstant>,
 }
 
-impl<'op> OpInfo<'op> {
-    pub fn with(
-        id: OpId,
-        subschema: bool,
-        op: &'op OpRef<'op>,
-        prev_state: &'op Assignments<GraphSeal>,
-        redeemed: &'op Valencies,
-    ) -> Self {
-        OpInfo {
-            id,
-            subschema,
-            ty: op.full_type(),
-            metadata: op.metadata(),
-            prev_state,
-            owned_state: op.assignments(),
-            redeemed,
-            valencies: op.valencies(),
-            global: op.globals(),
-        }
-    }
+/// Iterates over the revealed transitions of the bundles anchored to the given
+/// witness transaction which contain the transition `id`, including the
+/// transition itself.
+fn bundle_transitions<C: ConsignmentApi>(
+    consignment: &C,
+    id: OpId,
+    witness_txid: Txid,
+) -> impl Iterator<Item = &Transition> {
+    consignment
+        .anchored_bundles()
+        .filter(move |anchored| {
+            anchored.anchor.txid == witness_txid && anchored.bundle.contains_key(&id)
+        })
+        .flat_map(|anchored| anchored.bundle.values())
+        .filter_map(|item| item.transition.as_ref())
 }
 
 fn extract_prev_state<C: ConsignmentApi>(
     consignment: &C,
     opid: OpId,
     inputs: &Inputs,
+    migration: Option<&SchemaMigration>,
+    migrated: &mut BTreeSet<AssignmentType>,
     status: &mut validation::Status,
 ) -> Assignments<GraphSeal> {
     let mut assignments = bmap! {};
     for input in inputs {
         let Opout { op, ty, no } = input.prev_out;
-
-        let prev_op = match consignment.operation(op) {
-            None => {
-                status.add_failure(validation::Failure::OperationAbsent(op));
-                continue;
+        // State assigned under the previous schema version is spent as the
+        // state of the type replacing it
+        let state_type = match migration.map(|migration| migration.migrate(ty)) {
+            Some(state_type) if state_type != ty => {
+                migrated.insert(state_type);
+                state_type
             }
-            Some(op) => op,
+            _ => ty,
         };
 
-        let no = no as usize;
-        match prev_op.assignments_by_type(ty) {
+        let checkpoint = consignment.checkpoint();
+        let (prev_assigns, no) = match consignment.operation(op) {
+            Some(prev_op) => (prev_op.assignments_by_type(ty), no as usize),
+            // State assigned before the checkpoint is kept as a single assignment
+            None => match checkpoint.and_then(|checkpoint| checkpoint.output(input.prev_out)) {
+                Some(output) => (Some(output.assign.clone()), 0),
+                None => {
+                    status.add_failure(validation::Failure::OperationAbsent(op));
+                    continue;
+                }
+            },
+        };
+
+        match prev_assigns {
             Some(TypedAssigns::Declarative(prev_assignments)) => {
                 if let Some(prev_assign) = prev_assignments.get(no) {
                     if let Some(typed_assigns) = assignments
-                        .entry(ty)
+                        .entry(state_type)
                         .or_insert_with(|| TypedAssigns::Declarative(Default::default()))
                         .as_declarative_mut()
                     {
@@ -489,7 +1142,7 @@ fn extract_prev_state<C: ConsignmentApi>(
             Some(TypedAssigns::Fungible(prev_assignments)) => {
                 if let Some(prev_assign) = prev_assignments.get(no) {
                     if let Some(typed_assigns) = assignments
-                        .entry(ty)
+                        .entry(state_type)
                         .or_insert_with(|| TypedAssigns::Fungible(Default::default()))
                         .as_fungible_mut()
                     {
@@ -502,7 +1155,7 @@ fn extract_prev_state<C: ConsignmentApi>(
             Some(TypedAssigns::Structured(prev_assignments)) => {
                 if let Some(prev_assign) = prev_assignments.get(no) {
                     if let Some(typed_assigns) = assignments
-                        .entry(ty)
+                        .entry(state_type)
                         .or_insert_with(|| TypedAssigns::Structured(Default::default()))
                         .as_structured_mut()
                     {
@@ -515,7 +1168,7 @@ fn extract_prev_state<C: ConsignmentApi>(
             Some(TypedAssigns::Attachment(prev_assignments)) => {
                 if let Some(prev_assign) = prev_assignments.get(no) {
                     if let Some(typed_assigns) = assignments
-                        .entry(ty)
+                        .entry(state_type)
                         .or_insert_with(|| TypedAssigns::Attachment(Default::default()))
                         .as_attachment_mut()
                     {
@@ -544,7 +1197,7 @@ fn extract_redeemed_valencies<C: ConsignmentApi>(
 ) -> Valencies {
     let mut public_rights = Valencies::default();
     for (valency, id) in redeemed.iter() {
-        if consignment.has_operation(*id) {
+        if !consignment.has_operation(*id) {
             status.add_failure(validation::Failure::OperationAbsent(*id));
         } else {
             public_rights.push(*valency).expect("same size");
@@ -552,3 +1205,560 @@ fn extract_redeemed_valencies<C: ConsignmentApi>(
     }
     public_rights
 }
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::{SmallVec, TinyOrdSet};
+    use bp::secp256k1::rand::thread_rng;
+    use bp::seals::txout::CloseMethod;
+    use secp256k1_zkp::KeyPair;
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::{FungibleState, FungibleType, Input, RevealedValue, SubSchema};
+
+    #[test]
+    fn thresholds() {
+        let schema = SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Declarative },
+            thresholds: tiny_bmap! { 1 => 2 },
+            ..default!()
+        };
+        assert!(!schema
+            .verify()
+            .failures
+            .contains(&validation::Failure::SchemaThresholdInvalid(1)));
+
+        let (parent, other) = (OpId::from([1u8; 32]), OpId::from([2u8; 32]));
+        let inputs = |prev_outs: &[(OpId, u16)]| {
+            Inputs::from(
+                TinyOrdSet::try_from_iter(
+                    prev_outs
+                        .iter()
+                        .map(|(op, no)| Input::with(Opout::new(*op, 1, *no))),
+                )
+                .unwrap(),
+            )
+        };
+        let id = OpId::from([3u8; 32]);
+
+        assert!(schema
+            .validate_thresholds(id, &inputs(&[(parent, 0), (parent, 2)]))
+            .failures
+            .is_empty());
+        assert_eq!(schema.validate_thresholds(id, &inputs(&[(parent, 0)])).failures, vec![
+            validation::Failure::ThresholdNotMet {
+                opid: id,
+                state_type: 1,
+                required: 2,
+                spent: 1,
+            }
+        ]);
+        assert!(schema
+            .validate_thresholds(id, &inputs(&[(parent, 0), (other, 1)]))
+            .failures
+            .is_empty());
+    }
+
+    #[test]
+    fn key_rights() {
+        let schema = SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Structured(SemId::strict_dumb()) },
+            key_rights: tiny_bset![1],
+            ..default!()
+        };
+        assert!(!schema
+            .verify()
+            .failures
+            .contains(&validation::Failure::SchemaKeyRightInvalid(1)));
+
+        let (key, other) = (
+            KeyPair::from_seckey_slice(SECP256K1, &[1u8; 32]).unwrap(),
+            KeyPair::from_seckey_slice(SECP256K1, &[2u8; 32]).unwrap(),
+        );
+        let rights = |keys: &[&KeyPair]| {
+            let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 1);
+            let assigns = keys
+                .iter()
+                .map(|key| key.x_only_public_key().0.serialize().to_vec())
+                .map(|key| RevealedData::from(SmallVec::try_from(key).unwrap()))
+                .map(|state| Assign::revealed(seal, state))
+                .collect::<Vec<_>>();
+            let assigns = TypedAssigns::Structured(Confined::try_from(assigns).unwrap());
+            Assignments::from(tiny_bmap! { 1 => assigns })
+        };
+        let (id, signing_id) = (OpId::from([1u8; 32]), OpId::from([2u8; 32]));
+        let sign = |keys: &[&KeyPair]| {
+            let mut engine = Sha256::default();
+            engine.input_raw(signing_id.as_slice());
+            let msg = Message::from_slice(&engine.finish()).unwrap();
+            let sigs = keys
+                .iter()
+                .flat_map(|key| *SECP256K1.sign_schnorr_no_aux_rand(&msg, key).as_ref())
+                .collect::<Vec<_>>();
+            SmallBlob::try_from(sigs).unwrap()
+        };
+        let failure = validation::Failure::KeyRightSignatureInvalid {
+            opid: id,
+            state_type: 1,
+        };
+
+        assert!(schema
+            .validate_key_rights(id, signing_id, false, &sign(&[&key]), &rights(&[&key]))
+            .failures
+            .is_empty());
+        assert!(schema
+            .validate_key_rights(
+                id,
+                signing_id,
+                false,
+                &sign(&[&key, &other]),
+                &rights(&[&key, &other])
+            )
+            .failures
+            .is_empty());
+        assert_eq!(
+            schema
+                .validate_key_rights(id, signing_id, false, &sign(&[&other]), &rights(&[&key]))
+                .failures,
+            vec![failure.clone()]
+        );
+        assert_eq!(
+            schema
+                .validate_key_rights(id, signing_id, false, &none!(), &rights(&[&key]))
+                .failures,
+            vec![failure.clone()]
+        );
+        assert_eq!(
+            schema
+                .validate_key_rights(id, signing_id, false, &sign(&[&key]), &rights(&[&key, &key]))
+                .failures,
+            vec![failure]
+        );
+        assert!(schema
+            .validate_key_rights(id, signing_id, false, &none!(), &Assignments::default())
+            .failures
+            .is_empty());
+    }
+
+    #[test]
+    fn blank_values() {
+        let schema = SubSchema {
+            owned_types: tiny_bmap! {
+                1 => StateSchema::Fungible(FungibleType::Unsigned64Bit),
+                2 => StateSchema::Declarative,
+            },
+            categories: tiny_bmap! {
+                1 => AssignmentCategory::Value,
+                2 => AssignmentCategory::Value,
+            },
+            ..default!()
+        };
+        let failures = schema.verify().failures;
+        assert!(failures.contains(&validation::Failure::SchemaCategoryInvalid(
+            2,
+            AssignmentCategory::Value
+        )));
+        assert!(!failures.contains(&validation::Failure::SchemaCategoryInvalid(
+            1,
+            AssignmentCategory::Value
+        )));
+
+        let values = |amounts: &[u128]| {
+            let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 1);
+            let assigns = amounts
+                .iter()
+                .map(|amount| FungibleState::Bits128(*amount))
+                .map(|value| RevealedValue::new(value, &mut thread_rng()))
+                .map(|state| Assign::revealed(seal, state))
+                .collect::<Vec<_>>();
+            let assigns = TypedAssigns::Fungible(Confined::try_from(assigns).unwrap());
+            Assignments::from(tiny_bmap! { 1 => assigns })
+        };
+        let id = OpId::from([1u8; 32]);
+
+        assert!(schema
+            .validate_blank_values(id, &values(&[10, 20]), &values(&[30]))
+            .failures
+            .is_empty());
+        assert_eq!(
+            schema
+                .validate_blank_values(id, &values(&[10, 20]), &values(&[10]))
+                .failures,
+            vec![validation::Failure::BlankValueMismatch {
+                opid: id,
+                state_type: 1,
+                inputs: Amount::from(30u64),
+                outputs: Amount::from(10u64),
+            }]
+        );
+        assert_eq!(
+            schema
+                .validate_blank_values(id, &values(&[10]), &Assignments::default())
+                .failures,
+            vec![validation::Failure::BlankValueMismatch {
+                opid: id,
+                state_type: 1,
+                inputs: Amount::from(10u64),
+                outputs: Amount::ZERO,
+            }]
+        );
+        assert_eq!(
+            schema
+                .validate_blank_values(id, &values(&[u128::MAX, 1]), &values(&[u128::MAX]))
+                .failures,
+            vec![validation::Failure::SupplyOverflow(id, 1)]
+        );
+    }
+
+    #[test]
+    fn nft_tokens() {
+        let procedure = EmbeddedProcedure::NftIssue {
+            token_type: 1,
+            engraving: Some(2),
+        };
+        let schema = SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Structured(SemId::strict_dumb()) },
+            transitions: tiny_bmap! { 1 => default!(), 2 => default!() },
+            procedures: tiny_bset![procedure],
+            ..default!()
+        };
+        assert!(!schema
+            .verify()
+            .failures
+            .contains(&validation::Failure::SchemaProcedureInvalid(procedure)));
+
+        let tokens = |data: &[&[u8]]| {
+            let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 1);
+            let assigns = data
+                .iter()
+                .map(|data| RevealedData::from(SmallVec::try_from(data.to_vec()).unwrap()))
+                .map(|state| Assign::revealed(seal, state))
+                .collect::<Vec<_>>();
+            let assigns = TypedAssigns::Structured(Confined::try_from(assigns).unwrap());
+            Assignments::from(tiny_bmap! { 1 => assigns })
+        };
+        let id = OpId::from([1u8; 32]);
+        let check = |ty: Option<TransitionType>, inputs: &[&[u8]], outputs: &[&[u8]]| {
+            let outputs = tokens(outputs);
+            schema
+                .validate_nft_tokens(id, ty, &tokens(inputs), AssignmentsRef::Graph(&outputs))
+                .failures
+        };
+        let (token, engraved) = (&[1u8, 0, 0, 0, 0xAA][..], &[1u8, 0, 0, 0, 0xAA, 0xBB][..]);
+
+        assert!(check(None, &[], &[token, &[2, 0, 0, 0]]).is_empty());
+        assert_eq!(check(None, &[], &[token, token]), vec![
+            validation::Failure::NftTokenDuplicated {
+                opid: id,
+                token_id: 1
+            }
+        ]);
+        assert_eq!(check(None, &[], &[&[1, 0]]), vec![
+            validation::Failure::NftTokenMalformed(id, 1)
+        ]);
+        assert!(check(Some(1), &[token], &[token]).is_empty());
+        assert!(check(Some(2), &[token], &[engraved]).is_empty());
+        assert_eq!(check(Some(1), &[token], &[engraved]), vec![
+            validation::Failure::NftTokenChanged {
+                opid: id,
+                token_id: 1
+            }
+        ]);
+        assert_eq!(check(Some(1), &[token], &[&[2, 0, 0, 0]]), vec![
+            validation::Failure::NftTokensMismatch {
+                opid: id,
+                state_type: 1
+            }
+        ]);
+    }
+
+    #[test]
+    fn issuer_identity() {
+        use amplify::confinement::SmallBlob;
+        use bp::Chain;
+        use secp256k1_zkp::{KeyPair, SECP256K1};
+
+        use crate::{Ffv, GenesisSchema, Occurrences, OpFeatures, TransitionSchema};
+
+        let schema = SubSchema {
+            global_types: tiny_bmap! {
+                ISSUER_IDENTITY_ID => GlobalStateSchema::once(SemId::strict_dumb())
+            },
+            genesis: GenesisSchema {
+                globals: tiny_bmap! { ISSUER_IDENTITY_ID => Occurrences::Once },
+                ..default!()
+            },
+            procedures: tiny_bset![EmbeddedProcedure::IssuerIdentity],
+            ..default!()
+        };
+        let failure =
+            validation::Failure::SchemaProcedureInvalid(EmbeddedProcedure::IssuerIdentity);
+        assert!(!schema.verify().failures.contains(&failure));
+        let unrestricted = SubSchema {
+            transitions: tiny_bmap! { 1 => TransitionSchema {
+                globals: tiny_bmap! { ISSUER_IDENTITY_ID => Occurrences::Once },
+                ..default!()
+            }},
+            ..schema.clone()
+        };
+        assert!(unrestricted.verify().failures.contains(&failure));
+
+        let issuer = KeyPair::new(SECP256K1, &mut thread_rng());
+        let mut genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: Assignments::default(),
+            valencies: Valencies::default(),
+        };
+        let claim = SmallVec::try_from(b"issuer.example".to_vec()).unwrap();
+        let identity = IssuerIdentity::sign(&genesis, claim, &issuer);
+        genesis
+            .globals
+            .add_state(ISSUER_IDENTITY_ID, identity.to_state().unwrap())
+            .unwrap();
+        let id = genesis.id();
+        assert_eq!(IssuerIdentity::from_state(&identity.to_state().unwrap()), Some(identity));
+        assert!(schema.validate_issuer_identity(id, &genesis).failures.is_empty());
+
+        genesis.chain = Chain::Testnet3;
+        assert_eq!(schema.validate_issuer_identity(id, &genesis).failures, vec![
+            validation::Failure::IssuerIdentityInvalid(id)
+        ]);
+    }
+
+    #[test]
+    fn deprecations() {
+        use crate::{Ffv, OpFeatures, RootSchema, SchemaDeprecations, Sunset};
+
+        let deprecations = SchemaDeprecations {
+            transitions: tiny_bmap! { 1 => Sunset::Ffv(Ffv::LATEST), 2 => Sunset::Time(1000) },
+            extensions: tiny_bmap! { 1 => Sunset::Time(1000) },
+        };
+        let root = RootSchema {
+            transitions: tiny_bmap! { 1 => default!(), 2 => default!() },
+            deprecations: deprecations.clone(),
+            ..default!()
+        };
+        let schema = SubSchema {
+            subset_of: Some(root.clone()),
+            transitions: tiny_bmap! { 1 => default!() },
+            deprecations: SchemaDeprecations {
+                transitions: tiny_bmap! { 1 => Sunset::Time(1000) },
+                ..default!()
+            },
+            ..default!()
+        };
+        let status = schema.verify();
+        let op_type = OpFullType::StateTransition(1);
+        assert!(status
+            .failures
+            .contains(&validation::Failure::SubschemaDeprecationMismatch(op_type)));
+        assert_eq!(status.warnings, vec![validation::Warning::SubschemaOpDeprecated(op_type)]);
+        assert!(!status
+            .failures
+            .contains(&validation::Failure::SubschemaDeprecationMismatch(
+                OpFullType::StateTransition(2)
+            )));
+        let schema = SubSchema {
+            transitions: root.transitions.clone(),
+            deprecations,
+            ..default!()
+        };
+        let failures = schema.verify().failures;
+        assert!(failures.contains(&validation::Failure::SchemaDeprecationTypeUnknown(
+            OpFullType::StateExtension(1)
+        )));
+        assert!(!failures.contains(&validation::Failure::SchemaDeprecationTypeUnknown(
+            OpFullType::StateTransition(2)
+        )));
+
+        let transition = |ffv: Ffv, transition_type: TransitionType| Transition {
+            ffv,
+            features: OpFeatures::NONE,
+            contract_id: strict_dumb!(),
+            transition_type,
+            metadata: default!(),
+            globals: default!(),
+            inputs: default!(),
+            assignments: default!(),
+            valencies: default!(),
+        };
+        let check = |transition: &Transition, pos: Option<WitnessPos>| {
+            let status = schema.validate_deprecation(transition.id(), transition.into(), pos);
+            (status.failures.len(), status.warnings.len())
+        };
+        assert_eq!(check(&transition(Ffv::default(), 1), None), (0, 1));
+        assert_eq!(check(&transition(Ffv::LATEST, 1), None), (1, 0));
+        assert_eq!(check(&transition(Ffv::LATEST, 2), None), (0, 1));
+        assert_eq!(check(&transition(Ffv::LATEST, 2), Some(WitnessPos::new(1, 999))), (0, 1));
+        assert_eq!(check(&transition(Ffv::LATEST, 2), Some(WitnessPos::new(1, 1000))), (1, 0));
+        assert_eq!(check(&transition(Ffv::LATEST, 3), None), (0, 0));
+    }
+
+    #[test]
+    #[cfg(feature = "testing")]
+    fn covenants() {
+        use amplify::confinement::SmallBlob;
+        use bp::{Chain, Outpoint};
+
+        use crate::testing::{bundle_of, FakeChain, FakeConsignment};
+        use crate::{Ffv, Genesis, GenesisSeal, OpFeatures, StateCovenant};
+
+        let schema = SubSchema {
+            owned_types: tiny_bmap! {
+                1 => StateSchema::Declarative,
+                2 => StateSchema::Declarative,
+            },
+            valency_types: tiny_bset![5],
+            covenants: tiny_bmap! {
+                1 => StateCovenant {
+                    non_transferable: false,
+                    transferable_once: true,
+                    cosign: Some(2),
+                },
+            },
+            ..default!()
+        };
+        assert!(!schema
+            .verify()
+            .failures
+            .contains(&validation::Failure::SchemaCovenantInvalid(1)));
+        let mut invalid = schema.clone();
+        invalid.covenants = tiny_bmap! { 1 => StateCovenant { cosign: Some(1), ..default!() } };
+        assert!(invalid
+            .verify()
+            .failures
+            .contains(&validation::Failure::SchemaCovenantInvalid(1)));
+
+        let mut chain = FakeChain::new();
+        let issue = chain.next_txid();
+        let right = |vout: u32| {
+            let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue, vout, 1);
+            TypedAssigns::Declarative(small_vec![Assign::right(seal)])
+        };
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: Assignments::from(tiny_bmap! { 1 => right(0), 2 => right(1) }),
+            valencies: Valencies::default(),
+        };
+        let contract_id = genesis.contract_id();
+        let transition = |ty: u16, parent: OpId, valencies: Valencies| Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id,
+            transition_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            inputs: Inputs::from(tiny_bset![Input::with(Opout::new(parent, ty, 0))]),
+            assignments: Assignments::from(tiny_bmap! {
+                ty => TypedAssigns::Declarative(small_vec![Assign::right(
+                    GraphSeal::with_vout(CloseMethod::OpretFirst, 0, 2)
+                )]),
+            }),
+            valencies,
+        };
+        // The spender only declares the valency, without co-signature
+        let declared = transition(1, genesis.id(), Valencies::from(tiny_bset![5]));
+        let cosigned = transition(1, genesis.id(), Valencies::default());
+        let cosigner = transition(2, genesis.id(), Valencies::default());
+        let second = transition(1, cosigned.id(), Valencies::default());
+        let second_cosigner = transition(2, cosigner.id(), Valencies::default());
+        let mut consignment = FakeConsignment::new(schema.clone(), genesis);
+        let spent = [Outpoint::new(issue, 0), Outpoint::new(issue, 1)];
+        let declared_bundle = chain.anchor(contract_id, bundle_of([declared.clone()]), spent, 1);
+        let cosigned_bundle =
+            chain.anchor(contract_id, bundle_of([cosigned.clone(), cosigner.clone()]), spent, 1);
+        let second_bundle = chain.anchor(
+            contract_id,
+            bundle_of([second.clone(), second_cosigner.clone()]),
+            [],
+            1,
+        );
+        let witnesses = [
+            (declared.id(), declared_bundle.anchor.txid),
+            (cosigned.id(), cosigned_bundle.anchor.txid),
+            (second.id(), second_bundle.anchor.txid),
+        ];
+        consignment.add_terminal(declared_bundle);
+        consignment.add_terminal(cosigned_bundle);
+        consignment.add_terminal(second_bundle);
+
+        let witness = |id: OpId| witnesses.iter().find(|(op, _)| *op == id).map(|(_, txid)| *txid);
+        let covenants = |transition: &Transition| {
+            schema
+                .validate_covenants(
+                    &consignment,
+                    transition.id(),
+                    witness(transition.id()),
+                    transition,
+                    &transition.assignments,
+                )
+                .failures
+        };
+        assert_eq!(covenants(&declared), vec![validation::Failure::CovenantCosignMissing {
+            opid: declared.id(),
+            state_type: 1,
+            cosign: 2,
+        }]);
+        assert!(covenants(&cosigned).is_empty());
+        assert_eq!(covenants(&second), vec![validation::Failure::CovenantNonTransferable {
+            opid: second.id(),
+            state_type: 1,
+        }]);
+        assert_eq!(
+            schema
+                .validate_covenants(
+                    &consignment,
+                    cosigned.id(),
+                    None,
+                    &cosigned,
+                    &cosigned.assignments
+                )
+                .failures,
+            vec![validation::Failure::CovenantCosignMissing {
+                opid: cosigned.id(),
+                state_type: 1,
+                cosign: 2,
+            }]
+        );
+
+        let cosigners = |transition: &Transition| {
+            schema
+                .cosigners(&consignment, transition, witness(transition.id()).unwrap())
+                .into_iter()
+                .map(Transition::id)
+                .collect::<Vec<_>>()
+        };
+        assert!(cosigners(&declared).is_empty());
+        assert_eq!(cosigners(&cosigned), vec![cosigner.id()]);
+        assert_eq!(cosigners(&second), vec![second_cosigner.id()]);
+    }
+
+    #[test]
+    #[cfg(feature = "testing")]
+    fn redeemed_valencies() {
+        use crate::testing::FakeConsignment;
+        use crate::Genesis;
+
+        let genesis = Genesis::strict_dumb();
+        let present = genesis.id();
+        let absent = OpId::from([0xAB; 32]);
+        let consignment = FakeConsignment::new(SubSchema::default(), genesis);
+
+        let mut status = validation::Status::new();
+        let redeemed = Redeemed::from(tiny_bmap! { 1 => present, 2 => absent });
+        let valencies = extract_redeemed_valencies(&consignment, &redeemed, &mut status);
+        assert_eq!(valencies, Valencies::from(tiny_bset![1]));
+        assert_eq!(status.failures, vec![validation::Failure::OperationAbsent(absent)]);
+    }
+}
@@ -0,0 +1,233 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bp::seals::txout::TxPtr;
+use bp::Txid;
+
+use super::ConsignmentApi;
+use crate::{AttachId, GlobalExpiry, OpId, Operation, Sunset, TypedAssigns, VOUT_DERIVED};
+
+/// Data which will be requested from the resolver during the validation of a
+/// consignment, and media referenced by its state.
+///
+/// Hints are collected from the consignment structure only, without any
+/// validation, such that the callers can prefetch chain data and attachments
+/// in parallel with [`super::Validator::validate_structure`]. For invalid
+/// consignments the hints may be incomplete or excessive.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PrefetchHints {
+    /// Witness transactions of the anchored bundles.
+    pub witness_txids: BTreeSet<Txid>,
+    /// Transactions defining seals with outputs derived by the schema (see
+    /// [`crate::Schema::vout_derivation`]).
+    pub seal_txids: BTreeSet<Txid>,
+    /// Transactions which block heights or times are required to check seal
+    /// maturity, global state expiry, sunset of deprecated operation types or
+    /// to select among replaced witnesses.
+    pub height_txids: BTreeSet<Txid>,
+    /// Attachments of the revealed state.
+    pub attachments: BTreeSet<AttachId>,
+}
+
+impl PrefetchHints {
+    /// Scans the consignment for the data which the validator will request.
+    pub fn with(consignment: &impl ConsignmentApi) -> Self {
+        let schema = consignment.schema();
+        let mut hints = PrefetchHints::default();
+
+        let mut witnesses = BTreeMap::<OpId, BTreeSet<Txid>>::new();
+        for anchored_bundle in consignment.anchored_bundles() {
+            let txid = anchored_bundle.anchor.txid;
+            hints.witness_txids.insert(txid);
+            for transition in anchored_bundle
+                .bundle
+                .values()
+                .filter_map(|item| item.transition.as_ref())
+            {
+                witnesses.entry(transition.id()).or_default().insert(txid);
+            }
+        }
+        // Only one of the witnesses replacing each other is selected, depending
+        // on whether it is mined
+        for txids in witnesses.values().filter(|txids| txids.len() > 1) {
+            hints.height_txids.extend(txids);
+        }
+        let expiring = schema
+            .global_types
+            .values()
+            .any(|ty| ty.expiry != GlobalExpiry::Never);
+        let sunsetting = schema
+            .deprecations
+            .iter()
+            .any(|(_, sunset)| matches!(sunset, Sunset::Time(_)));
+        if expiring || sunsetting {
+            hints.height_txids.extend(&hints.witness_txids);
+        }
+
+        for opid in consignment.op_ids_except(&BTreeSet::new()) {
+            let Some(op) = consignment.operation(opid) else {
+                continue;
+            };
+            let assignments = op.assignments();
+            for ty in assignments.types() {
+                if let Some(TypedAssigns::Attachment(assigns)) = assignments.get(ty) {
+                    hints
+                        .attachments
+                        .extend(assigns.iter().filter_map(|a| a.as_revealed_state()).map(|s| s.id));
+                }
+            }
+
+            let Some(transition) = op.as_transition() else {
+                continue;
+            };
+            let own_witnesses = witnesses.get(&opid).cloned().unwrap_or_default();
+            for input in &transition.inputs {
+                let prev_out = input.prev_out;
+                let ty = schema.migrated_type(prev_out.ty);
+                if schema.key_rights.contains(&ty) {
+                    continue;
+                }
+                let (seal, prev_witnesses) = match consignment.operation(prev_out.op) {
+                    Some(prev_op) => {
+                        let seal = prev_op
+                            .assignments_by_type(prev_out.ty)
+                            .and_then(|assigns| assigns.revealed_seal_at(prev_out.no).ok())
+                            .flatten();
+                        let prev_witnesses =
+                            witnesses.get(&prev_out.op).cloned().unwrap_or_default();
+                        (seal, prev_witnesses)
+                    }
+                    None => match consignment
+                        .checkpoint()
+                        .and_then(|checkpoint| checkpoint.output(prev_out))
+                    {
+                        Some(output) => {
+                            let seal = output.assign.revealed_seal_at(0).ok().flatten();
+                            (seal, output.witness.into_iter().collect())
+                        }
+                        None => continue,
+                    },
+                };
+                let Some(seal) = seal else {
+                    continue;
+                };
+                let funding = match seal.txid {
+                    TxPtr::Txid(txid) => bset![txid],
+                    TxPtr::WitnessTx => prev_witnesses,
+                };
+                if seal.vout.into_u32() == VOUT_DERIVED {
+                    hints.seal_txids.extend(&funding);
+                }
+                if schema.maturity.contains_key(&ty) {
+                    hints.height_txids.extend(funding);
+                    hints.height_txids.extend(&own_witnesses);
+                }
+            }
+        }
+
+        hints
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "testing")]
+mod test {
+    use amplify::confinement::SmallBlob;
+    use bp::seals::txout::CloseMethod;
+    use bp::{Chain, Outpoint};
+
+    use super::*;
+    use crate::schema::StateSchema;
+    use crate::testing::{bundle_of, FakeChain, FakeConsignment};
+    use crate::{
+        Assign, Assignments, Ffv, Genesis, GenesisSeal, GlobalState, GraphSeal, Input, Inputs,
+        MediaType, OpFeatures, Opout, RevealedAttach, SubSchema, Transition, Valencies,
+    };
+
+    fn fake_consignment(maturity: bool, vout: u32) -> (FakeConsignment, Txid, Txid) {
+        let mut chain = FakeChain::new();
+        let issue = chain.next_txid();
+        let mut schema = SubSchema {
+            owned_types: tiny_bmap! {
+                1 => StateSchema::Declarative,
+                2 => StateSchema::Attachment(MediaType::Any),
+            },
+            ..default!()
+        };
+        if maturity {
+            schema.maturity.insert(1, 6).unwrap();
+        }
+        let attach = RevealedAttach::new(AttachId::from([0xAA; 32]), MediaType::Any);
+        let seal = GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue, vout, 1);
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Declarative(small_vec![Assign::right(seal)]),
+                2 => TypedAssigns::Attachment(small_vec![Assign::revealed(seal, attach)]),
+            }),
+            valencies: Valencies::default(),
+        };
+        let seal = GraphSeal::with_vout(CloseMethod::OpretFirst, 1, 2);
+        let transition = Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            transition_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            inputs: Inputs::from(tiny_bset![Input::with(Opout::new(genesis.id(), 1, 0))]),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Declarative(small_vec![Assign::right(seal)]),
+            }),
+            valencies: Valencies::default(),
+        };
+        let contract_id = genesis.contract_id();
+        let mut consignment = FakeConsignment::new(schema, genesis);
+        let spent = [Outpoint::new(issue, vout)];
+        let anchored_bundle = chain.anchor(contract_id, bundle_of([transition]), spent, 1);
+        let witness = anchored_bundle.anchor.txid;
+        consignment.add_terminal(anchored_bundle);
+        (consignment, issue, witness)
+    }
+
+    #[test]
+    fn hints() {
+        let (consignment, _, witness) = fake_consignment(false, 0);
+        let hints = PrefetchHints::with(&consignment);
+        assert_eq!(hints.witness_txids, bset![witness]);
+        assert!(hints.seal_txids.is_empty());
+        assert!(hints.height_txids.is_empty());
+        assert_eq!(hints.attachments, bset![AttachId::from([0xAA; 32])]);
+
+        let (consignment, issue, witness) = fake_consignment(true, VOUT_DERIVED);
+        let hints = PrefetchHints::with(&consignment);
+        assert_eq!(hints.seal_txids, bset![issue]);
+        assert_eq!(hints.height_txids, bset![issue, witness]);
+    }
+}
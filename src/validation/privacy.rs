@@ -0,0 +1,404 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Statistics of the contract data revealed by a consignment.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bp::seals::txout::TxPtr;
+use bp::{Outpoint, Txid};
+
+use super::{
+    ConsignmentApi, Failure, OfflineResolver, Status, ValidationProfile, Validator, Warning,
+};
+use crate::{BlindingFactor, GraphSeal, OpId, Operation, Opout, SecretSeal, TypedAssigns};
+
+/// Number of the consignment items of some kind and how many of them are
+/// revealed.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct RevealStats {
+    pub total: usize,
+    pub revealed: usize,
+}
+
+impl RevealStats {
+    /// Number of the confidential items.
+    pub fn confidential(&self) -> usize { self.total - self.revealed }
+
+    fn count(&mut self, revealed: bool) {
+        self.total += 1;
+        if revealed {
+            self.revealed += 1;
+        }
+    }
+}
+
+/// Report on the owned state data revealed by a consignment, which wallets may
+/// use to warn users before sharing a consignment revealing more than needed.
+///
+/// The report is collected from the consignment structure only, without any
+/// validation.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PrivacyReport {
+    /// Seals of the owned state assignments.
+    pub seals: RevealStats,
+    /// Owned state assignments, except the declarative ones which have no
+    /// state data.
+    pub state: RevealStats,
+    /// Fungible owned state assignments.
+    pub amounts: RevealStats,
+    /// Blinding factors used by more than one revealed amount, which makes
+    /// the commitments to these amounts linkable.
+    pub reused_blindings: BTreeSet<BlindingFactor>,
+    /// Transaction outputs to which revealed seals of more than one operation
+    /// point, similarly to a bitcoin address reuse.
+    pub reused_outpoints: BTreeSet<Outpoint>,
+}
+
+impl PrivacyReport {
+    /// Scans the consignment for the revealed data.
+    pub fn with(consignment: &impl ConsignmentApi) -> Self {
+        let mut report = PrivacyReport::default();
+
+        // Seals pointing to the witness transaction are resolved with the
+        // first of the operation witnesses
+        let mut witnesses = BTreeMap::<OpId, Txid>::new();
+        for anchored_bundle in consignment.anchored_bundles() {
+            for transition in anchored_bundle
+                .bundle
+                .values()
+                .filter_map(|item| item.transition.as_ref())
+            {
+                witnesses
+                    .entry(transition.id())
+                    .or_insert(anchored_bundle.anchor.txid);
+            }
+        }
+
+        let mut blindings = BTreeMap::<BlindingFactor, usize>::new();
+        let mut outpoints = BTreeMap::<Outpoint, BTreeSet<OpId>>::new();
+        for opid in consignment.op_ids_except(&BTreeSet::new()) {
+            let Some(op) = consignment.operation(opid) else {
+                continue;
+            };
+            let assignments = op.assignments();
+            for ty in assignments.types() {
+                let Some(assigns) = assignments.get(ty) else {
+                    continue;
+                };
+                for no in 0..assigns.len_u16() {
+                    let seal = assigns.revealed_seal_at(no).ok().flatten();
+                    report.seals.count(seal.is_some());
+                    let txid = match seal.map(|seal| seal.txid) {
+                        Some(TxPtr::Txid(txid)) => Some(txid),
+                        Some(TxPtr::WitnessTx) => witnesses.get(&opid).copied(),
+                        None => None,
+                    };
+                    if let (Some(seal), Some(txid)) = (seal, txid) {
+                        let outpoint = Outpoint::new(txid, seal.vout);
+                        outpoints.entry(outpoint).or_default().insert(opid);
+                    }
+                }
+                match &assigns {
+                    TypedAssigns::Declarative(_) => {}
+                    TypedAssigns::Fungible(assigns) => {
+                        for assign in assigns {
+                            let state = assign.as_revealed_state();
+                            report.state.count(state.is_some());
+                            report.amounts.count(state.is_some());
+                            if let Some(state) = state {
+                                *blindings.entry(state.blinding).or_default() += 1;
+                            }
+                        }
+                    }
+                    TypedAssigns::Structured(assigns) => {
+                        for assign in assigns {
+                            report.state.count(assign.as_revealed_state().is_some());
+                        }
+                    }
+                    TypedAssigns::Attachment(assigns) => {
+                        for assign in assigns {
+                            report.state.count(assign.as_revealed_state().is_some());
+                        }
+                    }
+                }
+            }
+        }
+
+        report.reused_blindings = blindings
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(blinding, _)| blinding)
+            .collect();
+        report.reused_outpoints = outpoints
+            .into_iter()
+            .filter(|(_, ops)| ops.len() > 1)
+            .map(|(outpoint, _)| outpoint)
+            .collect();
+        report
+    }
+
+    /// Checks whether the consignment reuses blinding factors or outpoints.
+    pub fn has_reuse(&self) -> bool {
+        !self.reused_blindings.is_empty() || !self.reused_outpoints.is_empty()
+    }
+}
+
+impl<'consignment, C: ConsignmentApi> Validator<'consignment, 'static, C, OfflineResolver> {
+    /// Detects blindings reused by distinct assignments of the consignment,
+    /// which break the confidentiality of the concealed data and may indicate
+    /// a faulty random number generator of the wallet, reporting them
+    /// according to the validation `profile`.
+    ///
+    /// The following blindings are checked:
+    /// - blinding factors of the Pedersen commitments to the revealed amounts;
+    /// - blindings of the revealed seals with different definitions;
+    /// - concealed seals (revealed or not) repeated by distinct operations.
+    ///
+    /// The check doesn't validate the consignment itself and is independent
+    /// of other validation phases; the returned status can be combined with
+    /// their statuses with `+=`.
+    pub fn validate_blindings(consignment: &'consignment C, profile: ValidationProfile) -> Status {
+        let mut value_blindings = BTreeMap::<BlindingFactor, Opout>::new();
+        let mut seal_blindings = BTreeMap::<u64, (GraphSeal, Opout)>::new();
+        let mut secret_seals = BTreeMap::<SecretSeal, (Opout, BTreeSet<OpId>)>::new();
+        let mut value_reuses = vec![];
+        let mut seal_reuses = vec![];
+        for opid in consignment.op_ids_except(&BTreeSet::new()) {
+            let Some(op) = consignment.operation(opid) else {
+                continue;
+            };
+            let assignments = op.assignments();
+            for ty in assignments.types() {
+                let Some(assigns) = assignments.get(ty) else {
+                    continue;
+                };
+                let secret = assigns.to_confidential_seals();
+                for no in 0..assigns.len_u16() {
+                    let opout = Opout::new(opid, ty, no);
+                    // The same seal may be assigned with several state types
+                    // by a single operation, so the reuse is reported once per
+                    // operation
+                    let (first, ops) = secret_seals
+                        .entry(secret[no as usize])
+                        .or_insert_with(|| (opout, bset![opid]));
+                    if ops.insert(opid) {
+                        seal_reuses.push((*first, opout));
+                    }
+                    let Some(seal) = assigns.revealed_seal_at(no).ok().flatten() else {
+                        continue;
+                    };
+                    match seal_blindings.get(&seal.blinding) {
+                        Some((first_seal, first)) if *first_seal != seal => {
+                            seal_reuses.push((*first, opout))
+                        }
+                        Some(_) => {}
+                        None => {
+                            seal_blindings.insert(seal.blinding, (seal, opout));
+                        }
+                    }
+                }
+                let TypedAssigns::Fungible(assigns) = &assigns else {
+                    continue;
+                };
+                for (no, assign) in assigns.iter().enumerate() {
+                    let Some(state) = assign.as_revealed_state() else {
+                        continue;
+                    };
+                    let opout = Opout::new(opid, ty, no as u16);
+                    match value_blindings.get(&state.blinding) {
+                        Some(first) => value_reuses.push((*first, opout)),
+                        None => {
+                            value_blindings.insert(state.blinding, opout);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut status = Status::default();
+        for (first, second) in value_reuses {
+            match profile {
+                ValidationProfile::Lenient => {
+                    status.add_warning(Warning::BlindingReused(first, second));
+                }
+                ValidationProfile::Strict => {
+                    status.add_failure(Failure::BlindingReused(first, second));
+                }
+            }
+        }
+        for (first, second) in seal_reuses {
+            match profile {
+                ValidationProfile::Lenient => {
+                    status.add_warning(Warning::SealBlindingReused(first, second));
+                }
+                ValidationProfile::Strict => {
+                    status.add_failure(Failure::SealBlindingReused(first, second));
+                }
+            }
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "testing")]
+mod test {
+    use amplify::confinement::SmallBlob;
+    use bp::seals::txout::CloseMethod;
+    use bp::Chain;
+    use commit_verify::Conceal;
+
+    use super::*;
+    use crate::testing::FakeConsignment;
+    use crate::validation::Validity;
+    use crate::{
+        Assign, Assignments, Extension, Ffv, Genesis, GenesisSeal, GlobalState, OpFeatures,
+        Redeemed, RevealedData, RevealedValue, SubSchema, Valencies,
+    };
+
+    #[test]
+    fn report() {
+        let schema = SubSchema::default();
+        let txid = Txid::from([1; 32]);
+        let seal = |vout| GenesisSeal::with_blinding(CloseMethod::OpretFirst, txid, vout, 1);
+        let blinding = BlindingFactor::try_from([1u8; 32]).unwrap();
+        let value = |amount: u64| RevealedValue::with(amount, blinding);
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Fungible(small_vec![
+                    Assign::revealed(seal(0), value(10)),
+                    Assign::ConfidentialSeal {
+                        seal: seal(1).conceal(),
+                        state: value(20),
+                    },
+                ]),
+                2 => TypedAssigns::Declarative(small_vec![Assign::right(seal(0))]),
+            }),
+            valencies: Valencies::default(),
+        };
+        let extension = Extension {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            extension_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Fungible(small_vec![Assign::revealed(seal(2), value(30))]),
+                2 => TypedAssigns::Declarative(small_vec![Assign::right(seal(0))]),
+                3 => TypedAssigns::Structured(small_vec![Assign::Confidential {
+                    seal: seal(3).conceal(),
+                    state: RevealedData::with_counter(1).conceal(),
+                }]),
+            }),
+            redeemed: Redeemed::default(),
+            valencies: Valencies::default(),
+        };
+        let mut consignment = FakeConsignment::new(schema, genesis);
+        consignment.extensions.push(extension);
+
+        let report = PrivacyReport::with(&consignment);
+        assert_eq!(report.seals, RevealStats {
+            total: 6,
+            revealed: 4
+        });
+        assert_eq!(report.amounts, RevealStats {
+            total: 3,
+            revealed: 3
+        });
+        assert_eq!(report.state, RevealStats {
+            total: 4,
+            revealed: 3
+        });
+        assert_eq!(report.state.confidential(), 1);
+        assert_eq!(report.reused_blindings, bset![blinding]);
+        assert_eq!(report.reused_outpoints, bset![Outpoint::new(txid, 0)]);
+        assert!(report.has_reuse());
+    }
+
+    #[test]
+    fn blindings() {
+        let schema = SubSchema::default();
+        let txid = Txid::from([1; 32]);
+        let seal = |vout, blinding| {
+            GenesisSeal::with_blinding(CloseMethod::OpretFirst, txid, vout, blinding)
+        };
+        let value = |amount: u64, blinding: u8| {
+            RevealedValue::with(amount, BlindingFactor::try_from([blinding; 32]).unwrap())
+        };
+        let genesis = |blindings: [u8; 2], seal_blindings: [u64; 2]| Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Fungible(small_vec![
+                    Assign::revealed(seal(0, seal_blindings[0]), value(10, blindings[0])),
+                    Assign::revealed(seal(1, seal_blindings[1]), value(20, blindings[1])),
+                ]),
+                2 => TypedAssigns::Declarative(small_vec![Assign::right(seal(0, 1))]),
+            }),
+            valencies: Valencies::default(),
+        };
+
+        let mut consignment = FakeConsignment::new(schema.clone(), genesis([1, 2], [1, 2]));
+        let status = Validator::validate_blindings(&consignment, ValidationProfile::Strict);
+        assert_eq!(status, Status::default());
+
+        let extension = Extension {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: consignment.genesis.contract_id(),
+            extension_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: Assignments::from(tiny_bmap! {
+                2 => TypedAssigns::Declarative(small_vec![Assign::right(seal(0, 1))]),
+            }),
+            redeemed: Redeemed::default(),
+            valencies: Valencies::default(),
+        };
+        consignment.extensions.push(extension);
+        let status = Validator::validate_blindings(&consignment, ValidationProfile::Lenient);
+        assert_eq!(status.validity(), Validity::Valid);
+        assert!(matches!(status.warnings[..], [Warning::SealBlindingReused(..)]));
+
+        let genesis = genesis([1, 1], [1, 1]);
+        let opid = genesis.id();
+        let consignment = FakeConsignment::new(schema, genesis);
+        let (first, second) = (Opout::new(opid, 1, 0), Opout::new(opid, 1, 1));
+        let status = Validator::validate_blindings(&consignment, ValidationProfile::Strict);
+        assert_eq!(status.failures, vec![
+            Failure::BlindingReused(first, second),
+            Failure::SealBlindingReused(first, second),
+        ]);
+    }
+}
@@ -0,0 +1,110 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bp::Txid;
+
+/// Phase of the consignment validation.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum ValidationPhase {
+    /// Verification of the schema and its scripts.
+    Schema,
+
+    /// Validation of the contract genesis.
+    Genesis,
+
+    /// Validation of the contract operations, traversing the operation graph
+    /// from the terminals towards the genesis.
+    Operations,
+
+    /// Validation is completed.
+    Completed,
+}
+
+/// Progress of the consignment validation, reported to
+/// [`ValidationProgress`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Progress {
+    /// Current validation phase.
+    pub phase: ValidationPhase,
+
+    /// Number of the operations which were already processed.
+    pub processed: usize,
+
+    /// Total number of the operations in the consignment. Operations which
+    /// are not a part of the history of any of the terminals are never
+    /// processed, so the final value of `processed` may be smaller.
+    pub total: usize,
+
+    /// Witness transaction of the operation which is being processed, if any.
+    pub witness: Option<Txid>,
+}
+
+/// Receiver of the progress reports from long-running validations, which can
+/// be used, for instance, to display a progress bar.
+pub trait ValidationProgress {
+    /// Called by the validator each time a validation phase starts and after
+    /// each processed operation.
+    fn report(&mut self, progress: Progress);
+}
+
+impl<F: FnMut(Progress)> ValidationProgress for F {
+    fn report(&mut self, progress: Progress) { self(progress) }
+}
+
+/// Token allowing to cancel a long-running validation from another thread.
+/// The validator checks the token between validation phases and processed
+/// operations; once cancelled, the validation stops and returns
+/// [`crate::validation::Status`] marked as partial.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self { Self::default() }
+
+    /// Requests cancellation of the validations using this token.
+    pub fn cancel(&self) { self.0.store(true, Ordering::Relaxed) }
+
+    pub fn is_cancelled(&self) -> bool { self.0.load(Ordering::Relaxed) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::validation::{Status, Validity};
+
+    #[test]
+    fn cancel() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+        clone.cancel();
+        assert!(token.is_cancelled());
+
+        let mut status = Status::new();
+        status.partial = true;
+        assert_eq!(status.validity(), Validity::Partial);
+    }
+}
@@ -0,0 +1,169 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use crate::vm::ScriptMeter;
+use crate::{OpFullType, OpId};
+
+/// Resources spent on the validation of a single operation.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NodeReport {
+    pub opid: OpId,
+    pub ty: OpFullType,
+    /// Time spent validating the operation against the schema, including its
+    /// scripts.
+    pub duration: Duration,
+    /// Resources consumed by the scripts validating the operation.
+    pub script: ScriptMeter,
+}
+
+/// Report on the resources spent on a consignment validation, produced by
+/// [`super::Validator::validate_instrumented`].
+///
+/// Integrators may use the report to profile the validation of contracts
+/// and schemata and to set policy limits.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ValidationReport {
+    /// Reports on the validated operations, in the order of their validation.
+    pub nodes: Vec<NodeReport>,
+    /// Total duration of the validation.
+    pub duration: Duration,
+}
+
+impl ValidationReport {
+    /// Total number of the executed script instructions.
+    pub fn script_steps(&self) -> u64 {
+        self.nodes
+            .iter()
+            .fold(0u64, |steps, node| steps.saturating_add(node.script.steps))
+    }
+
+    /// Peak size of the data exposed to the scripts.
+    pub fn peak_memory(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| node.script.memory)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns the operation which took the longest time to validate.
+    pub fn slowest(&self) -> Option<&NodeReport> {
+        self.nodes.iter().max_by_key(|node| node.duration)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "testing")]
+mod test {
+    use amplify::confinement::{MediumOrdMap, SmallBlob};
+    use bp::seals::txout::CloseMethod;
+    use bp::{Chain, Outpoint};
+    use strict_types::{Ty, TypeSystem};
+
+    use crate::schema::{GenesisSchema, Occurrences, StateSchema, TransitionSchema};
+    use crate::testing::{bundle_of, FakeChain, FakeConsignment};
+    use crate::validation::Validator;
+    use crate::vm::stdlib::StdRoutine;
+    use crate::vm::{AluScript, EntryPoint};
+    use crate::{
+        Assign, Assignments, Ffv, Genesis, GenesisSeal, GlobalState, GraphSeal, Input, Inputs,
+        OpFeatures, Opout, Operation, Script, SubSchema, Transition, TypedAssigns, Valencies,
+        VoidState,
+    };
+
+    fn rights<Seal: crate::ExposedSeal>(seal: Seal) -> Assignments<Seal> {
+        let assign = Assign::revealed(seal, VoidState::default());
+        Assignments::from(tiny_bmap! { 1 => TypedAssigns::Declarative(small_vec![assign]) })
+    }
+
+    #[test]
+    fn instrumented() {
+        let mut script = AluScript::default();
+        script
+            .link_std(EntryPoint::ValidateTransition(1), StdRoutine::NftUniqueness(1))
+            .unwrap();
+        let mut schema = SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Declarative },
+            genesis: GenesisSchema {
+                assignments: tiny_bmap! { 1 => Occurrences::Once },
+                ..default!()
+            },
+            transitions: tiny_bmap! {
+                1 => TransitionSchema {
+                    inputs: tiny_bmap! { 1 => Occurrences::Once },
+                    assignments: tiny_bmap! { 1 => Occurrences::Once },
+                    ..default!()
+                },
+            },
+            script: Script::AluVM(script),
+            ..default!()
+        };
+        let types = bmap! { schema.genesis.metadata => Ty::UNIT };
+        schema.type_system = TypeSystem::from(MediumOrdMap::try_from(types).unwrap());
+
+        let mut chain = FakeChain::new();
+        let issue = chain.next_txid();
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            assignments: rights(GenesisSeal::with_blinding(CloseMethod::OpretFirst, issue, 0, 1)),
+            valencies: Valencies::default(),
+        };
+        let transition = Transition {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            transition_type: 1,
+            metadata: SmallBlob::default(),
+            globals: GlobalState::default(),
+            inputs: Inputs::from(tiny_bset![Input::with(Opout::new(genesis.id(), 1, 0))]),
+            assignments: rights(GraphSeal::with_vout(CloseMethod::OpretFirst, 1, 2)),
+            valencies: Valencies::default(),
+        };
+        let (genesis_id, opid) = (genesis.id(), transition.id());
+        let contract_id = genesis.contract_id();
+        let mut consignment = FakeConsignment::new(schema, genesis);
+        let spent = [Outpoint::new(issue, 0)];
+        consignment.add_terminal(chain.anchor(contract_id, bundle_of([transition]), spent, 1));
+
+        let (status, report) =
+            Validator::validate_instrumented(&consignment, &chain, Chain::Regtest);
+        assert_eq!(status, Validator::validate(&consignment, &chain, Chain::Regtest));
+        let nodes = report
+            .nodes
+            .iter()
+            .map(|node| (node.opid, node.script.steps))
+            .collect::<Vec<_>>();
+        assert_eq!(nodes[0], (genesis_id, 0));
+        assert_eq!(nodes[1].0, opid);
+        assert!(nodes[1].1 > 0);
+        assert_eq!(report.script_steps(), nodes[1].1);
+        assert!(report.peak_memory() > 0);
+        assert!(report.slowest().is_some());
+    }
+}
@@ -20,9 +20,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeSet;
+
 use crate::validation::Status;
 use crate::{
-    validation, OpFullType, OpSchema, Schema, StateSchema, SubSchema, BLANK_TRANSITION_ID,
+    validation, vm, AssignmentCategory, EmbeddedProcedure, Occurrences, OpFullType, OpSchema,
+    Schema, Script, StateSchema, SubSchema, BLANK_TRANSITION_ID, ISSUER_IDENTITY_ID,
 };
 
 impl SubSchema {
@@ -54,6 +57,19 @@ impl SubSchema {
             status.add_failure(validation::Failure::SchemaBlankTransitionRedefined);
         }
 
+        for (type_id, constant) in &self.constants {
+            if self
+                .type_system
+                .strict_deserialize_type(constant.sem_id, constant.value.as_ref())
+                .is_err()
+            {
+                status.add_failure(validation::Failure::SchemaConstantInvalid(
+                    *type_id,
+                    constant.sem_id,
+                ));
+            }
+        }
+
         for (type_id, schema) in &self.global_types {
             if !self.type_system.contains_key(&schema.sem_id) {
                 status.add_failure(validation::Failure::SchemaGlobalSemIdUnknown(
@@ -64,12 +80,144 @@ impl SubSchema {
         }
 
         for (type_id, schema) in &self.owned_types {
-            if let StateSchema::Structured(sem_id) = schema {
-                if !self.type_system.contains_key(sem_id) {
+            match schema {
+                StateSchema::Structured(sem_id) if !self.type_system.contains_key(sem_id) => {
                     status.add_failure(validation::Failure::SchemaOwnedSemIdUnknown(
                         *type_id, *sem_id,
                     ));
                 }
+                StateSchema::Counter(counter) if counter.min > counter.max => {
+                    status.add_failure(validation::Failure::SchemaCounterInvalid(*type_id));
+                }
+                _ => {}
+            }
+        }
+
+        for type_id in self.maturity.keys() {
+            if !self.owned_types.contains_key(type_id) {
+                status.add_failure(validation::Failure::SchemaMaturityTypeUnknown(*type_id));
+            }
+        }
+
+        for type_id in self.vout_derivation.keys() {
+            if !self.owned_types.contains_key(type_id) {
+                status.add_failure(validation::Failure::SchemaVoutDerivationTypeUnknown(*type_id));
+            }
+        }
+
+        for type_id in &self.key_rights {
+            if !matches!(self.owned_types.get(type_id), Some(StateSchema::Structured(_))) {
+                status.add_failure(validation::Failure::SchemaKeyRightInvalid(*type_id));
+            }
+        }
+
+        for (type_id, threshold) in &self.thresholds {
+            if *threshold == 0 ||
+                !matches!(self.owned_types.get(type_id), Some(StateSchema::Declarative))
+            {
+                status.add_failure(validation::Failure::SchemaThresholdInvalid(*type_id));
+            }
+        }
+
+        for (type_id, category) in &self.categories {
+            let valid = match (category, self.owned_types.get(type_id)) {
+                (AssignmentCategory::Value, Some(StateSchema::Fungible(_))) => true,
+                (AssignmentCategory::Right, Some(StateSchema::Declarative)) => true,
+                (AssignmentCategory::Right, Some(StateSchema::Structured(_))) => {
+                    self.key_rights.contains(type_id)
+                }
+                (
+                    AssignmentCategory::Data,
+                    Some(
+                        StateSchema::Structured(_) |
+                        StateSchema::Counter(_) |
+                        StateSchema::Attachment(_),
+                    ),
+                ) => true,
+                _ => false,
+            };
+            if !valid {
+                status.add_failure(validation::Failure::SchemaCategoryInvalid(*type_id, *category));
+            }
+        }
+
+        for (type_id, covenant) in &self.covenants {
+            let cosign_known = covenant
+                .cosign
+                .map(|ty| ty != *type_id && self.owned_types.contains_key(&ty))
+                .unwrap_or(true);
+            if !self.owned_types.contains_key(type_id) || !cosign_known {
+                status.add_failure(validation::Failure::SchemaCovenantInvalid(*type_id));
+            }
+        }
+
+        let mut token_types = BTreeSet::new();
+        for procedure in &self.procedures {
+            let valid = match procedure {
+                EmbeddedProcedure::NftIssue {
+                    token_type,
+                    engraving,
+                } => {
+                    matches!(self.owned_types.get(token_type), Some(StateSchema::Structured(_))) &&
+                        engraving
+                            .map(|ty| self.transitions.contains_key(&ty))
+                            .unwrap_or(true)
+                }
+                EmbeddedProcedure::IssuerIdentity => {
+                    self.global_types.contains_key(&ISSUER_IDENTITY_ID) &&
+                        self.genesis.globals.get(&ISSUER_IDENTITY_ID) ==
+                            Some(&Occurrences::Once) &&
+                        self.transitions
+                            .values()
+                            .all(|schema| !schema.globals.contains_key(&ISSUER_IDENTITY_ID)) &&
+                        self.extensions
+                            .values()
+                            .all(|schema| !schema.globals.contains_key(&ISSUER_IDENTITY_ID))
+                }
+            };
+            let reused = procedure
+                .token_type()
+                .map_or(false, |ty| !token_types.insert(ty));
+            if !valid || reused {
+                status.add_failure(validation::Failure::SchemaProcedureInvalid(*procedure));
+            }
+        }
+
+        for (op_type, _) in self.deprecations.iter() {
+            if !self.defines(op_type) {
+                status.add_failure(validation::Failure::SchemaDeprecationTypeUnknown(op_type));
+            }
+        }
+
+        if let Some(migration) = &self.migration {
+            for (from, to) in &migration.owned_types {
+                if self.owned_types.contains_key(from) || !self.owned_types.contains_key(to) {
+                    status.add_failure(validation::Failure::SchemaMigrationInvalid(*from, *to));
+                }
+            }
+        }
+
+        for (transition_type, global_type) in &self.provisional {
+            let once = self
+                .transitions
+                .get(transition_type)
+                .and_then(|schema| schema.globals.get(global_type)) ==
+                Some(&Occurrences::Once);
+            if !once {
+                status.add_failure(validation::Failure::SchemaProvisionalInvalid(*transition_type));
+            }
+        }
+
+        for valency in self.plugins.keys() {
+            if !self.valency_types.contains(valency) {
+                status.add_failure(validation::Failure::SchemaPluginInvalid(*valency));
+            }
+        }
+
+        // Reject scripts which may validate differently across nodes
+        if let Script::AluVM(script) = &self.script {
+            for issue in vm::analyze(script) {
+                status.add_failure(validation::Failure::SchemaScriptNondeterministic(issue));
             }
         }
 
@@ -144,6 +292,87 @@ impl SubSchema {
             };
         }
 
+        for (assignments_type, depth) in &root.maturity {
+            if self
+                .maturity
+                .get(assignments_type)
+                .copied()
+                .unwrap_or_default() <
+                *depth
+            {
+                status
+                    .add_failure(validation::Failure::SubschemaMaturityMismatch(*assignments_type));
+            }
+        }
+
+        for (assignments_type, derivation) in &self.vout_derivation {
+            if root.vout_derivation.get(assignments_type) != Some(derivation) {
+                status.add_failure(validation::Failure::SubschemaVoutDerivationMismatch(
+                    *assignments_type,
+                ));
+            }
+        }
+
+        for assignments_type in self.key_rights.symmetric_difference(&root.key_rights) {
+            status.add_failure(validation::Failure::SubschemaKeyRightsMismatch(*assignments_type));
+        }
+
+        for assignments_type in self.thresholds.keys().chain(root.thresholds.keys()) {
+            if self.thresholds.get(assignments_type) != root.thresholds.get(assignments_type) {
+                status.add_failure(validation::Failure::SubschemaThresholdMismatch(
+                    *assignments_type,
+                ));
+            }
+        }
+
+        for assignments_type in self.categories.keys().chain(root.categories.keys()) {
+            if self.categories.get(assignments_type) != root.categories.get(assignments_type) {
+                status.add_failure(validation::Failure::SubschemaCategoryMismatch(
+                    *assignments_type,
+                ));
+            }
+        }
+
+        for assignments_type in self.covenants.keys().chain(root.covenants.keys()) {
+            if self.covenants.get(assignments_type) != root.covenants.get(assignments_type) {
+                status.add_failure(validation::Failure::SubschemaCovenantMismatch(
+                    *assignments_type,
+                ));
+            }
+        }
+
+        for procedure in self.procedures.symmetric_difference(&root.procedures) {
+            status.add_failure(validation::Failure::SubschemaProcedureMismatch(*procedure));
+        }
+
+        for valency in self.plugins.keys().chain(root.plugins.keys()) {
+            if self.plugins.get(valency) != root.plugins.get(valency) {
+                status.add_failure(validation::Failure::SubschemaPluginMismatch(*valency));
+            }
+        }
+
+        // Subschemata may omit deprecated operation types, but must keep the
+        // deprecations of the types they define
+        let deprecated = root
+            .deprecations
+            .iter()
+            .map(|(op_type, _)| op_type)
+            .filter(|op_type| self.defines(*op_type))
+            .collect::<BTreeSet<_>>();
+        for op_type in &deprecated {
+            status.add_warning(validation::Warning::SubschemaOpDeprecated(*op_type));
+        }
+        let declared = self.deprecations.iter().map(|(op_type, _)| op_type);
+        for op_type in declared.chain(deprecated).collect::<BTreeSet<_>>() {
+            if self.deprecations.sunset(op_type) != root.deprecations.sunset(op_type) {
+                status.add_failure(validation::Failure::SubschemaDeprecationMismatch(op_type));
+            }
+        }
+
+        if !self.history_limits.is_within(&root.history_limits) {
+            status.add_failure(validation::Failure::SubschemaHistoryLimitsMismatch);
+        }
+
         for valencies_type in &self.valency_types {
             match root.valency_types.contains(valencies_type) {
                 false => status.add_failure(validation::Failure::SubschemaValencyTypeMismatch(
@@ -201,6 +430,9 @@ where T: OpSchema
                 actual: self.metadata(),
             });
         }
+        if self.timestamped() != root.timestamped() {
+            status.add_failure(validation::Failure::SubschemaOpTimestampMismatch(op_type));
+        }
 
         for (type_id, occ) in self.globals() {
             match root.globals().get(type_id) {
@@ -20,29 +20,78 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::validation::OpInfo;
-use crate::vm::AluRuntime;
-use crate::{validation, Script};
+#[cfg(feature = "embedded")]
+use super::legacy::EmbeddedVm;
+use crate::validation::OpContext;
+use crate::vm::{AluRuntime, ScriptMeter};
+use crate::{validation, Script, VmType};
+
+/// Policy restricting virtual machines which may be used by schema scripts
+/// (see [`crate::validation::Validator::validate_with_policy`]).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub enum ScriptPolicy {
+    /// Any virtual machine supported by the validator, including the legacy
+    /// embedded one used by historical contracts.
+    #[default]
+    Any,
+
+    /// AluVM only, which must be used by newly issued contracts.
+    AluVmOnly,
+}
+
+impl ScriptPolicy {
+    /// Checks whether schema scripts for the virtual machine are allowed by
+    /// the policy.
+    pub fn allows(self, vm_type: VmType) -> bool {
+        match self {
+            ScriptPolicy::Any => true,
+            ScriptPolicy::AluVmOnly => vm_type == VmType::AluVM,
+        }
+    }
+}
 
 /// Trait for concrete types wrapping virtual machines to be used from inside
 /// RGB schema validation routines.
 pub trait VirtualMachine {
     /// Validates state change in a contract operation.
-    fn validate(&self, info: OpInfo) -> Result<(), validation::Failure>;
+    fn validate(&self, context: OpContext) -> Result<(), validation::Failure>;
+
+    /// Returns resources consumed by the validations since the previous call,
+    /// if the virtual machine is metered.
+    fn take_meter(&self) -> Option<ScriptMeter> { None }
 }
 
 impl VirtualMachine for Script {
-    fn validate(&self, info: OpInfo) -> Result<(), validation::Failure> {
+    fn validate(&self, context: OpContext) -> Result<(), validation::Failure> {
         match self {
-            Script::AluVM(script) => AluRuntime::new(script).validate(info),
+            Script::AluVM(script) => AluRuntime::new(script).validate(context),
+            #[cfg(feature = "embedded")]
+            Script::Embedded => EmbeddedVm.validate(context),
+            #[cfg(not(feature = "embedded"))]
+            Script::Embedded => Err(validation::Failure::VmUnsupported(VmType::Embedded)),
         }
     }
 }
 
 impl<'script> VirtualMachine for AluRuntime<'script> {
-    fn validate(&self, info: OpInfo) -> Result<(), validation::Failure> {
-        let id = info.id;
-        self.run_validations(&info)
+    fn validate(&self, context: OpContext) -> Result<(), validation::Failure> {
+        let id = context.id;
+        self.run_validations(&context)
             .map_err(|msg| validation::Failure::ScriptFailure(id, msg))
     }
+
+    fn take_meter(&self) -> Option<ScriptMeter> { AluRuntime::take_meter(self) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn policy() {
+        assert!(ScriptPolicy::default().allows(VmType::AluVM));
+        assert!(ScriptPolicy::default().allows(VmType::Embedded));
+        assert!(ScriptPolicy::AluVmOnly.allows(VmType::AluVM));
+        assert!(!ScriptPolicy::AluVmOnly.allows(VmType::Embedded));
+    }
 }
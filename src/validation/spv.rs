@@ -0,0 +1,463 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the witness transactions with SPV proofs, allowing light
+//! clients to validate consignments without a trusted transaction resolver.
+
+use std::collections::BTreeMap;
+
+use amplify::{Bytes32, RawArray};
+use bp::{Chain, Tx, Txid};
+use strict_encoding::{StrictEncode, StrictWriter};
+
+use super::headers::{sha256d, verify_headers};
+use super::{
+    BlockHeader, ConsignmentApi, Failure, HeaderSource, OfflineResolver, ResolveTx, Status,
+    TxResolverError, Validator,
+};
+
+/// Number of the blocks used to compute the median time past.
+const MTP_BLOCKS: usize = 11;
+
+/// Proof of a transaction inclusion into a block.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TxProof {
+    pub tx: Tx,
+    /// Height of the block mining the transaction.
+    pub height: u32,
+    /// Index of the transaction in the block.
+    pub index: u32,
+    /// Hashes of the sibling nodes of the merkle tree, starting from the
+    /// leaves.
+    pub merkle_branch: Vec<Bytes32>,
+}
+
+/// SPV proofs of the witness transactions, provided alongside a consignment
+/// (see [`ConsignmentApi::spv_proofs`]).
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct SpvProofs {
+    /// Height of the first header.
+    pub start_height: u32,
+    /// Chain of consecutive block headers containing all blocks mining the
+    /// witness transactions, plus at least ten blocks before them if the
+    /// schema scripts use the median time past.
+    pub headers: Vec<BlockHeader>,
+    /// Inclusion proofs of the witness transactions.
+    pub txs: Vec<TxProof>,
+}
+
+/// Errors of the SPV proof verification.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[display(doc_comments)]
+pub enum SpvError {
+    /// SPV proofs contain no block headers.
+    NoHeaders,
+
+    /// none of the trusted block headers is a part of the SPV header chain.
+    Untrusted,
+
+    /// block at height {0} doesn't match the trusted block header.
+    CheckpointMismatch(u32),
+
+    /// header chain has no block header at height {0}.
+    UnknownHeight(u32),
+
+    /// block header at height {0} doesn't reference the previous header.
+    Disconnected(u32),
+
+    /// block header at height {0} has invalid proof of work.
+    PowInvalid(u32),
+
+    /// block header at height {0} has proof-of-work target exceeding the
+    /// limit of the chain.
+    TargetTooEasy(u32),
+
+    /// transaction {0} is proved to be mined in block {1}, which is not a part
+    /// of the SPV header chain.
+    UnknownBlock(Txid, u32),
+
+    /// merkle proof for transaction {0} doesn't match the block merkle root.
+    NotIncluded(Txid),
+
+    /// transaction {0} has a size of 64 bytes, which makes its merkle proof
+    /// ambiguous.
+    AmbiguousTx(Txid),
+}
+
+/// Transaction resolver backed by the verified SPV proofs.
+///
+/// Provides transactions, heights, block times and median time past only for
+/// the transactions which inclusion into the header chain was proved.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SpvResolver {
+    start_height: u32,
+    headers: Vec<BlockHeader>,
+    txs: BTreeMap<Txid, (Tx, u32)>,
+}
+
+impl SpvResolver {
+    /// Verifies the proofs, including proof of work of all headers.
+    ///
+    /// Proof of work doesn't prove that the headers belong to the most-work
+    /// chain, thus the caller must provide a source of the headers it trusts
+    /// (for instance, its own header chain). Headers of the proofs must match
+    /// the trusted ones at all heights known to the source, and at least one
+    /// of the heights must be known.
+    pub fn verify(
+        proofs: &SpvProofs,
+        chain: Chain,
+        trusted: &impl HeaderSource,
+    ) -> Result<Self, SpvError> {
+        if proofs.headers.is_empty() {
+            return Err(SpvError::NoHeaders);
+        }
+        verify_headers(proofs.start_height, &proofs.headers, chain)?;
+        let mut anchored = false;
+        for (height, header) in (proofs.start_height..).zip(&proofs.headers) {
+            match trusted.header_at(height) {
+                Some(trusted) if trusted.block_hash() != header.block_hash() => {
+                    return Err(SpvError::CheckpointMismatch(height));
+                }
+                Some(_) => anchored = true,
+                None => {}
+            }
+        }
+        if !anchored {
+            return Err(SpvError::Untrusted);
+        }
+
+        let mut txs = BTreeMap::new();
+        for proof in &proofs.txs {
+            let (txid, size) = txid(&proof.tx);
+            if size == 64 {
+                return Err(SpvError::AmbiguousTx(txid));
+            }
+            let Some(header) = proof
+                .height
+                .checked_sub(proofs.start_height)
+                .and_then(|index| proofs.headers.get(index as usize))
+            else {
+                return Err(SpvError::UnknownBlock(txid, proof.height));
+            };
+            let mut node = txid.to_raw_array();
+            let mut index = proof.index;
+            for sibling in &proof.merkle_branch {
+                let mut data = Vec::with_capacity(64);
+                if index & 1 == 0 {
+                    data.extend(node);
+                    data.extend(sibling.as_slice());
+                } else {
+                    data.extend(sibling.as_slice());
+                    data.extend(node);
+                }
+                node = sha256d(&data);
+                index >>= 1;
+            }
+            if index != 0 || Bytes32::from(node) != header.merkle_root {
+                return Err(SpvError::NotIncluded(txid));
+            }
+            txs.insert(txid, (proof.tx.clone(), proof.height));
+        }
+
+        Ok(SpvResolver {
+            start_height: proofs.start_height,
+            headers: proofs.headers.clone(),
+            txs,
+        })
+    }
+
+    fn header_index(&self, txid: Txid) -> Result<usize, TxResolverError> {
+        let (_, height) = self.txs.get(&txid).ok_or(TxResolverError::Unknown(txid))?;
+        Ok((height - self.start_height) as usize)
+    }
+}
+
+impl ResolveTx for SpvResolver {
+    fn resolve_tx(&self, txid: Txid) -> Result<Tx, TxResolverError> {
+        self.txs
+            .get(&txid)
+            .map(|(tx, _)| tx.clone())
+            .ok_or(TxResolverError::Unknown(txid))
+    }
+
+    fn resolve_height(&self, txid: Txid) -> Result<u32, TxResolverError> {
+        self.txs
+            .get(&txid)
+            .map(|(_, height)| *height)
+            .ok_or(TxResolverError::Unknown(txid))
+    }
+
+    fn resolve_time(&self, txid: Txid) -> Result<i64, TxResolverError> {
+        let index = self.header_index(txid)?;
+        Ok(self.headers[index].time as i64)
+    }
+
+    fn resolve_mtp(&self, txid: Txid) -> Result<i64, TxResolverError> {
+        let index = self.header_index(txid)?;
+        if index + 1 < MTP_BLOCKS {
+            return Err(TxResolverError::Unknown(txid));
+        }
+        let mut times = self.headers[index + 1 - MTP_BLOCKS..=index]
+            .iter()
+            .map(|header| header.time)
+            .collect::<Vec<_>>();
+        times.sort_unstable();
+        Ok(times[MTP_BLOCKS / 2] as i64)
+    }
+}
+
+impl HeaderSource for SpvResolver {
+    fn tip_height(&self) -> Option<u32> {
+        (!self.headers.is_empty()).then(|| self.start_height + self.headers.len() as u32 - 1)
+    }
+
+    fn header_at(&self, height: u32) -> Option<BlockHeader> {
+        let index = height.checked_sub(self.start_height)?;
+        self.headers.get(index as usize).copied()
+    }
+
+    fn header_by_hash(&self, hash: Bytes32) -> Option<(u32, BlockHeader)> {
+        (self.start_height..)
+            .zip(&self.headers)
+            .find(|(_, header)| header.block_hash() == hash)
+            .map(|(height, header)| (height, *header))
+    }
+}
+
+impl<'consignment, C: ConsignmentApi> Validator<'consignment, 'static, C, OfflineResolver> {
+    /// Validates the consignment like [`Validator::validate`], resolving the
+    /// witness transactions from the SPV proofs provided by the consignment
+    /// instead of a trusted resolver. Proofs are verified against the block
+    /// headers `trusted` by the caller (see [`SpvResolver::verify`]).
+    pub fn validate_spv(
+        consignment: &'consignment C,
+        chain: Chain,
+        trusted: &impl HeaderSource,
+    ) -> Status {
+        let mut status = Status::default();
+        let Some(proofs) = consignment.spv_proofs() else {
+            status.add_failure(Failure::SpvProofsAbsent);
+            return status;
+        };
+        match SpvResolver::verify(proofs, chain, trusted) {
+            Ok(resolver) => Validator::validate(consignment, &resolver, chain),
+            Err(err) => {
+                status.add_failure(Failure::SpvInvalid(err));
+                status
+            }
+        }
+    }
+}
+
+/// Computes transaction id and size of the transaction serialized without the
+/// witness data.
+fn txid(tx: &Tx) -> (Txid, usize) {
+    fn var_int(data: &mut Vec<u8>, len: usize) {
+        match len {
+            0..=0xFC => data.push(len as u8),
+            0xFD..=0xFFFF => {
+                data.push(0xFD);
+                data.extend((len as u16).to_le_bytes());
+            }
+            0x10000..=0xFFFF_FFFF => {
+                data.push(0xFE);
+                data.extend((len as u32).to_le_bytes());
+            }
+            _ => {
+                data.push(0xFF);
+                data.extend((len as u64).to_le_bytes());
+            }
+        }
+    }
+    // Fixed-size transaction fields have the same strict and consensus
+    // encodings
+    fn fixed(data: &mut Vec<u8>, value: &impl StrictEncode) {
+        value
+            .strict_encode(StrictWriter::with(usize::MAX, &mut *data))
+            .expect("in-memory encoding");
+    }
+
+    let mut data = vec![];
+    data.extend(tx.version.to_consensus_u32().to_le_bytes());
+    var_int(&mut data, tx.inputs.len());
+    for input in &tx.inputs {
+        data.extend(input.prev_output.txid.to_raw_array());
+        data.extend(input.prev_output.vout.into_u32().to_le_bytes());
+        var_int(&mut data, input.sig_script.len());
+        data.extend(&input.sig_script[..]);
+        data.extend(input.sequence.to_consensus_u32().to_le_bytes());
+    }
+    var_int(&mut data, tx.outputs.len());
+    for output in &tx.outputs {
+        fixed(&mut data, &output.value);
+        var_int(&mut data, output.script_pubkey.len());
+        data.extend(&output.script_pubkey[..]);
+    }
+    fixed(&mut data, &tx.lock_time);
+    (Txid::from(sha256d(&data)), data.len())
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::Confined;
+    use amplify::hex::FromHex;
+    use bp::{LockTime, Outpoint, SeqNo, TxIn, TxOut, TxVer, Witness};
+
+    use super::*;
+    use crate::validation::headers::test::mine;
+    use crate::validation::HeaderChain;
+
+    fn tx(seed: u8) -> Tx {
+        Tx {
+            version: TxVer::V2,
+            inputs: Confined::try_from(vec![TxIn {
+                prev_output: Outpoint::new(Txid::from([seed; 32]), 0),
+                sig_script: none!(),
+                sequence: SeqNo::from_consensus_u32(0xFFFF_FFFF),
+                witness: Witness::from_consensus_stack(Vec::<Vec<u8>>::new()),
+            }])
+            .unwrap(),
+            outputs: Confined::try_from(vec![TxOut {
+                value: 1000.into(),
+                script_pubkey: vec![0x51].into(),
+            }])
+            .unwrap(),
+            lock_time: LockTime::from_consensus_u32(0),
+        }
+    }
+
+    #[test]
+    fn txid_computation() {
+        // Transaction 4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b
+        // from the bitcoin genesis block
+        let coinbase = "04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63\
+                        656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f\
+                        722062616e6b73";
+        let script_pubkey = "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61\
+                             deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf1\
+                             1d5fac";
+        let genesis_tx = Tx {
+            version: TxVer::V1,
+            inputs: Confined::try_from(vec![TxIn {
+                prev_output: Outpoint::new(Txid::from([0; 32]), 0xFFFF_FFFF),
+                sig_script: Vec::<u8>::from_hex(coinbase).unwrap().into(),
+                sequence: SeqNo::from_consensus_u32(0xFFFF_FFFF),
+                witness: Witness::from_consensus_stack(Vec::<Vec<u8>>::new()),
+            }])
+            .unwrap(),
+            outputs: Confined::try_from(vec![TxOut {
+                value: 50_0000_0000.into(),
+                script_pubkey: Vec::<u8>::from_hex(script_pubkey).unwrap().into(),
+            }])
+            .unwrap(),
+            lock_time: LockTime::from_consensus_u32(0),
+        };
+        let txid: Txid = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+            .parse()
+            .unwrap();
+        assert_eq!(super::txid(&genesis_tx).0, txid);
+    }
+
+    #[test]
+    fn verification() {
+        let txs = [tx(1), tx(2)];
+        let txids = txs.clone().map(|tx| txid(&tx).0.to_raw_array());
+        let mut data = txids[0].to_vec();
+        data.extend(txids[1]);
+        let merkle_root = Bytes32::from(sha256d(&data));
+
+        let mut headers = vec![];
+        let mut prev = Bytes32::from([0; 32]);
+        for height in 0..12 {
+            let root = if height == 11 {
+                merkle_root
+            } else {
+                Bytes32::from([height as u8; 32])
+            };
+            let header = mine(prev, root, 1000 + height * 600);
+            prev = header.block_hash();
+            headers.push(header);
+        }
+        let proofs = SpvProofs {
+            start_height: 100,
+            headers,
+            txs: vec![TxProof {
+                tx: txs[1].clone(),
+                height: 111,
+                index: 1,
+                merkle_branch: vec![Bytes32::from(txids[0])],
+            }],
+        };
+        let trusted = HeaderChain::with(100, [proofs.headers[0]]).unwrap();
+
+        let resolver = SpvResolver::verify(&proofs, Chain::Regtest, &trusted).unwrap();
+        assert_eq!(resolver.tip_height(), Some(111));
+        assert_eq!(resolver.header_by_hash(prev), Some((111, proofs.headers[11])));
+        assert_eq!(resolver.verify_chainwork(100, Chain::Regtest).unwrap(), 24u8.into());
+        let txid = Txid::from(txids[1]);
+        assert_eq!(resolver.resolve_tx(txid).unwrap(), txs[1]);
+        assert_eq!(resolver.resolve_height(txid).unwrap(), 111);
+        assert_eq!(resolver.resolve_time(txid).unwrap(), 1000 + 11 * 600);
+        assert_eq!(resolver.resolve_mtp(txid).unwrap(), 1000 + 6 * 600);
+        assert!(resolver.resolve_tx(Txid::from(txids[0])).is_err());
+
+        assert_eq!(
+            SpvResolver::verify(&proofs, Chain::Bitcoin, &trusted),
+            Err(SpvError::TargetTooEasy(100))
+        );
+        let other = mine(proofs.headers[0].block_hash(), Bytes32::from([0xFF; 32]), 0);
+        assert_eq!(
+            SpvResolver::verify(&proofs, Chain::Regtest, &HeaderChain::with(101, [other]).unwrap()),
+            Err(SpvError::CheckpointMismatch(101))
+        );
+        assert_eq!(
+            SpvResolver::verify(&proofs, Chain::Regtest, &HeaderChain::new(0)),
+            Err(SpvError::Untrusted)
+        );
+
+        let mut invalid = proofs.clone();
+        invalid.txs[0].index = 0;
+        assert_eq!(
+            SpvResolver::verify(&invalid, Chain::Regtest, &trusted),
+            Err(SpvError::NotIncluded(txid))
+        );
+
+        let mut invalid = proofs.clone();
+        invalid.headers[5].time += 1;
+        assert!(matches!(
+            SpvResolver::verify(&invalid, Chain::Regtest, &trusted),
+            Err(SpvError::Disconnected(106) | SpvError::PowInvalid(105))
+        ));
+
+        let mut invalid = proofs;
+        invalid.txs[0].height = 112;
+        assert_eq!(
+            SpvResolver::verify(&invalid, Chain::Regtest, &trusted),
+            Err(SpvError::UnknownBlock(txid, 112))
+        );
+    }
+}
@@ -51,7 +51,10 @@ impl StateSchema {
                             ));
                         }
                     }
-                    (StateSchema::Structured(_), StateCommitment::Structured(_)) => {
+                    (
+                        StateSchema::Structured(_) | StateSchema::Counter(_),
+                        StateCommitment::Structured(_),
+                    ) => {
                         status.add_info(validation::Info::UncheckableConfidentialState(
                             *opid, state_type,
                         ));
@@ -106,6 +109,21 @@ impl StateSchema {
                             ));
                         };
                     }
+                    (StateSchema::Counter(counter), StateData::Structured(data)) => {
+                        match data.as_counter() {
+                            None => {
+                                status.add_failure(validation::Failure::CounterMalformed(
+                                    *opid, state_type,
+                                ));
+                            }
+                            Some(value) if !counter.contains(value) => {
+                                status.add_failure(validation::Failure::CounterOutOfBounds(
+                                    *opid, state_type, value,
+                                ));
+                            }
+                            Some(_) => {}
+                        }
+                    }
                     // all other options are mismatches
                     (state_schema, found) => {
                         status.add_failure(validation::Failure::StateTypeMismatch {
@@ -121,3 +139,44 @@ impl StateSchema {
         status
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bp::seals::txout::CloseMethod;
+
+    use super::*;
+    use crate::schema::CounterSchema;
+    use crate::{GraphSeal, RevealedData};
+
+    #[test]
+    fn counter() {
+        let schema = StateSchema::Counter(CounterSchema {
+            min: 1,
+            max: 10,
+            step: 2,
+        });
+        let opid = OpId::from([0u8; 32]);
+        let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 0);
+        let validate = |data: RevealedData| {
+            let assign = Assign::revealed(seal, data);
+            schema.validate(&TypeSystem::default(), &opid, 1, &assign)
+        };
+
+        assert_eq!(RevealedData::with_counter(7).as_counter(), Some(7));
+        assert!(validate(RevealedData::with_counter(10)).failures.is_empty());
+        assert_eq!(validate(RevealedData::with_counter(11)).failures, vec![
+            validation::Failure::CounterOutOfBounds(opid, 1, 11)
+        ]);
+        assert_eq!(validate(RevealedData::from(small_vec![1u8])).failures, vec![
+            validation::Failure::CounterMalformed(opid, 1)
+        ]);
+
+        let StateSchema::Counter(counter) = schema else {
+            unreachable!()
+        };
+        assert!(counter.allows_step(3, 3));
+        assert!(counter.allows_step(3, 5));
+        assert!(!counter.allows_step(3, 6));
+        assert!(!counter.allows_step(3, 2));
+    }
+}
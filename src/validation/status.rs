@@ -22,16 +22,20 @@
 
 use core::iter::FromIterator;
 use core::ops::AddAssign;
+use std::collections::BTreeSet;
 use std::fmt::{self, Display, Formatter};
 
 use bp::dbc::anchor;
-use bp::{seals, Txid};
+use bp::{seals, Chain, Txid};
 use strict_types::SemId;
 
+use super::{ConsensusError, SpvError};
 use crate::contract::Opout;
-use crate::schema::{self, SchemaId};
+use crate::schema::{self, SchemaId, SchemaIdVersion};
+use crate::vm::ScriptIssue;
 use crate::{
-    AssignmentType, BundleId, OccurrencesMismatch, OpFullType, OpId, SecretSeal, StateType,
+    Amount, AssignmentType, AttachId, BundleId, CheckpointId, OccurrencesMismatch, OpFeatures,
+    OpFullType, OpId, OtsError, SecretSeal, StateType, VmType,
 };
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Display)]
@@ -40,16 +44,36 @@ pub enum Validity {
     #[display("is valid")]
     Valid,
 
+    #[display("is valid off-chain, pending channel close")]
+    OffchainWitnesses,
+
     #[display("has non-mined terminal(s)")]
     UnminedTerminals,
 
-    #[display("contains unknown witness transactions")]
-    UnresolvedTransactions,
+    #[display("validation was cancelled before completion")]
+    Partial,
+
+    #[display("can't be validated until its witness transactions are resolved")]
+    UnresolvedWitnesses,
 
     #[display("is NOT valid")]
     Invalid,
 }
 
+/// Profile of the optional validation checks, which detect issues not making
+/// the consignment invalid under the consensus rules, like missing attachments,
+/// reused blinding factors or operation feature bits unknown to the validator.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum ValidationProfile {
+    /// Detected issues are reported as warnings.
+    #[default]
+    Lenient,
+
+    /// Detected issues are reported as failures, making the consignment
+    /// invalid.
+    Strict,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
 #[cfg_attr(
     feature = "serde",
@@ -59,15 +83,23 @@ pub enum Validity {
 pub struct Status {
     pub unresolved_txids: Vec<Txid>,
     pub unmined_terminals: Vec<Txid>,
+    pub offchain_witnesses: Vec<Txid>,
     pub failures: Vec<Failure>,
     pub warnings: Vec<Warning>,
     pub info: Vec<Info>,
+    /// Validation was cancelled and the status doesn't cover the whole
+    /// consignment.
+    pub partial: bool,
 }
 
 impl Display for Status {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         writeln!(f, "Consignment {}", self.validity())?;
 
+        if self.partial {
+            f.write_str("Validation was cancelled; the report is incomplete\n")?;
+        }
+
         if !self.unresolved_txids.is_empty() {
             f.write_str("Unknown witness transactions:\n")?;
             for txid in &self.unresolved_txids {
@@ -82,6 +114,13 @@ impl Display for Status {
             }
         }
 
+        if !self.offchain_witnesses.is_empty() {
+            f.write_str("Off-chain witnesses:\n")?;
+            for txid in &self.offchain_witnesses {
+                writeln!(f, "- {txid}")?;
+            }
+        }
+
         if !self.failures.is_empty() {
             f.write_str("Validation failures:\n")?;
             for fail in &self.failures {
@@ -111,9 +150,11 @@ impl AddAssign for Status {
     fn add_assign(&mut self, rhs: Self) {
         self.unresolved_txids.extend(rhs.unresolved_txids);
         self.unmined_terminals.extend(rhs.unmined_terminals);
+        self.offchain_witnesses.extend(rhs.offchain_witnesses);
         self.failures.extend(rhs.failures);
         self.warnings.extend(rhs.warnings);
         self.info.extend(rhs.info);
+        self.partial = self.partial || rhs.partial;
     }
 }
 
@@ -122,9 +163,11 @@ impl Status {
         Status {
             unresolved_txids: vec![],
             unmined_terminals: vec![],
+            offchain_witnesses: vec![],
             failures: vec![v],
             warnings: vec![],
             info: vec![],
+            partial: false,
         }
     }
 }
@@ -165,17 +208,31 @@ impl Status {
 
     pub fn validity(&self) -> Validity {
         if self.failures.is_empty() {
-            if self.unmined_terminals.is_empty() {
-                Validity::Valid
-            } else {
+            if !self.unmined_terminals.is_empty() {
                 Validity::UnminedTerminals
+            } else if self.partial {
+                Validity::Partial
+            } else if !self.offchain_witnesses.is_empty() {
+                Validity::OffchainWitnesses
+            } else {
+                Validity::Valid
             }
-        } else if self.unresolved_txids.is_empty() {
-            Validity::Invalid
+        } else if self.failures.iter().all(Failure::is_unresolved) {
+            Validity::UnresolvedWitnesses
         } else {
-            Validity::UnresolvedTransactions
+            Validity::Invalid
         }
     }
+
+    /// Iterates over all failures classified into the consensus error
+    /// hierarchy.
+    pub fn errors(&self) -> impl Iterator<Item = ConsensusError> + '_ {
+        self.failures.iter().cloned().map(ConsensusError::from)
+    }
+
+    /// Returns the first detected failure classified into the consensus error
+    /// hierarchy, if any.
+    pub fn first_error(&self) -> Option<ConsensusError> { self.errors().next() }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Display, From)]
@@ -201,6 +258,8 @@ pub enum Failure {
     /// schema global state #{0} uses semantic data type absent in type library
     /// ({1}).
     SchemaGlobalSemIdUnknown(schema::GlobalStateType, SemId),
+    /// schema constant #{0} value doesn't match its semantic data type ({1}).
+    SchemaConstantInvalid(schema::ConstantType, SemId),
     /// schema owned state #{0} uses semantic data type absent in type library
     /// ({1}).
     SchemaOwnedSemIdUnknown(schema::AssignmentType, SemId),
@@ -216,6 +275,49 @@ pub enum Failure {
     SchemaOpAssignmentTypeUnknown(OpFullType, schema::AssignmentType),
     /// schema for {0} references undeclared valency type {1}.
     SchemaOpValencyTypeUnknown(OpFullType, schema::ValencyType),
+    /// schema defines maturity for undeclared owned state type {0}.
+    SchemaMaturityTypeUnknown(schema::AssignmentType),
+    /// schema defines seal output derivation for undeclared owned state type
+    /// {0}.
+    SchemaVoutDerivationTypeUnknown(schema::AssignmentType),
+    /// schema defines key right for owned state type {0}, which is either
+    /// undeclared or not a structured state.
+    SchemaKeyRightInvalid(schema::AssignmentType),
+    /// schema defines threshold for owned state type {0}, which is either
+    /// undeclared, not a declarative state or has a zero threshold.
+    SchemaThresholdInvalid(schema::AssignmentType),
+    /// schema declares category {1} for owned state type {0}, which is either
+    /// undeclared or has a state incompatible with the category.
+    SchemaCategoryInvalid(schema::AssignmentType, schema::AssignmentCategory),
+    /// schema defines covenant for owned state type {0}, which is either
+    /// undeclared, or the covenant requires co-signature right of the same or
+    /// undeclared type.
+    SchemaCovenantInvalid(schema::AssignmentType),
+    /// schema enables embedded procedure {0}, which references either not a
+    /// structured owned state type or undeclared transition type, or uses the
+    /// state type of another procedure, or doesn't restrict the issuer
+    /// identity to a single genesis global state.
+    SchemaProcedureInvalid(schema::EmbeddedProcedure),
+    /// schema deprecates {0}, which is not defined by the schema.
+    SchemaDeprecationTypeUnknown(OpFullType),
+    /// schema migrates owned state type {0} into {1}, while the migrated type
+    /// must not be defined by the schema and the target type must be.
+    SchemaMigrationInvalid(schema::AssignmentType, schema::AssignmentType),
+    /// schema script is not deterministic: {0}
+    SchemaScriptNondeterministic(ScriptIssue),
+    /// schema counter state type {0} has minimal value exceeding maximal one.
+    SchemaCounterInvalid(schema::AssignmentType),
+    /// schema provisional transition type {0} must have its revocation number
+    /// global state defined exactly once.
+    SchemaProvisionalInvalid(schema::TransitionType),
+    /// schema defines plug-in point for undeclared valency type {0}.
+    SchemaPluginInvalid(schema::ValencyType),
+    /// schema script requires ISA extensions {0:?} which are not supported by
+    /// the runtime.
+    UnsupportedIsa(BTreeSet<String>),
+    /// schema script requires {0} virtual machine, which is not supported by
+    /// the validator or not allowed by the validation policy.
+    VmUnsupported(VmType),
 
     /// invalid schema - no match with root schema requirements for global state
     /// type #{0}.
@@ -226,6 +328,35 @@ pub enum Failure {
     /// invalid schema - no match with root schema requirements for valency
     /// type #{0}.
     SubschemaValencyTypeMismatch(schema::ValencyType),
+    /// invalid schema - maturity for assignment type #{0} is lower than
+    /// required by the root schema.
+    SubschemaMaturityMismatch(schema::AssignmentType),
+    /// invalid schema - seal output derivation for assignment type #{0} is not
+    /// defined by the root schema.
+    SubschemaVoutDerivationMismatch(schema::AssignmentType),
+    /// invalid schema - assignment type #{0} is a key right in only one of
+    /// the subschema and the root schema.
+    SubschemaKeyRightsMismatch(schema::AssignmentType),
+    /// invalid schema - threshold for assignment type #{0} differs from the
+    /// root schema.
+    SubschemaThresholdMismatch(schema::AssignmentType),
+    /// invalid schema - category of assignment type #{0} differs from the root
+    /// schema.
+    SubschemaCategoryMismatch(schema::AssignmentType),
+    /// invalid schema - covenant of assignment type #{0} differs from the root
+    /// schema.
+    SubschemaCovenantMismatch(schema::AssignmentType),
+    /// invalid schema - embedded procedure {0} is enabled only by one of the
+    /// subschema and the root schema.
+    SubschemaProcedureMismatch(schema::EmbeddedProcedure),
+    /// invalid schema - deprecation of {0} differs from the root schema.
+    SubschemaDeprecationMismatch(OpFullType),
+    /// invalid schema - history limits are weaker than required by the root
+    /// schema.
+    SubschemaHistoryLimitsMismatch,
+    /// invalid schema - plug-in point for valency type #{0} differs from the
+    /// root schema.
+    SubschemaPluginMismatch(schema::ValencyType),
     /// invalid schema - no match with root schema requirements for transition
     /// type #{0}.
     SubschemaTransitionTypeMismatch(schema::TransitionType),
@@ -240,6 +371,9 @@ pub enum Failure {
         expected: SemId,
         actual: SemId,
     },
+    /// invalid schema - no match with root schema requirements for timestamping
+    /// of {0}.
+    SubschemaOpTimestampMismatch(OpFullType),
     /// invalid schema - no match with root schema requirements for global state
     /// type #{1} used in {0}.
     SubschemaOpGlobalStateMismatch(OpFullType, schema::GlobalStateType),
@@ -273,8 +407,13 @@ pub enum Failure {
     /// number of global state entries of type {1} in operation {0} exceeds
     /// schema-defined maximum for that global state type ({2} vs {3}).
     SchemaGlobalStateLimit(OpId, schema::GlobalStateType, u16, u16),
+    /// operation {0} defines global state of type {1}, which has already
+    /// expired at the position of its witness transaction.
+    GlobalStateExpired(OpId, schema::GlobalStateType),
     /// invalid metadata in operation {0} not matching semantic type id {1}.
     SchemaInvalidMetadata(OpId, SemId),
+    /// operation {0} metadata has invalid timestamp proof. Details: {1}
+    InvalidTimestampProof(OpId, OtsError),
     /// invalid global state value in operation {0}, state type #{1} which does
     /// not match semantic type id {2}.
     SchemaInvalidGlobalValue(OpId, schema::GlobalStateType, SemId),
@@ -285,6 +424,102 @@ pub enum Failure {
     SchemaInputOccurrences(OpId, schema::AssignmentType, OccurrencesMismatch),
     /// invalid number of assignment entries of type {1} in operation {0} - {2}
     SchemaAssignmentOccurrences(OpId, schema::AssignmentType, OccurrencesMismatch),
+    /// counter state of type {1} in operation {0} is not a 64-bit integer.
+    CounterMalformed(OpId, schema::AssignmentType),
+    /// counter state of type {1} in operation {0} has value {2} outside of the
+    /// bounds defined by the schema.
+    CounterOutOfBounds(OpId, schema::AssignmentType, u64),
+    /// transition {opid} spends {spent} of threshold rights of type
+    /// {state_type}, while at least {required} are required.
+    ThresholdNotMet {
+        opid: OpId,
+        state_type: schema::AssignmentType,
+        required: u8,
+        spent: u16,
+    },
+    /// transition {opid} spends key right of type {state_type} without a
+    /// valid signature made with the key.
+    KeyRightSignatureInvalid {
+        opid: OpId,
+        state_type: schema::AssignmentType,
+    },
+    /// blank transition {opid} spends {inputs} and assigns {outputs} units of
+    /// the value-bearing state of type {state_type}, while blank transitions
+    /// must move such state without changes.
+    BlankValueMismatch {
+        opid: OpId,
+        state_type: schema::AssignmentType,
+        inputs: Amount,
+        outputs: Amount,
+    },
+    /// operation {0} spends or assigns the fungible state of type {1}, which
+    /// total amount overflows.
+    SupplyOverflow(OpId, schema::AssignmentType),
+    /// transition {opid} re-assigns state of type {state_type}, which is not
+    /// transferable anymore.
+    CovenantNonTransferable {
+        opid: OpId,
+        state_type: schema::AssignmentType,
+    },
+    /// transition {opid} spends state of type {state_type} without a transition
+    /// in the same bundle spending co-signature right of type {cosign}.
+    CovenantCosignMissing {
+        opid: OpId,
+        state_type: schema::AssignmentType,
+        cosign: schema::AssignmentType,
+    },
+    /// operation {0} assigns token of type {1} with the data lacking token
+    /// id.
+    NftTokenMalformed(OpId, schema::AssignmentType),
+    /// operation {opid} assigns token {token_id}, which is already issued.
+    NftTokenDuplicated { opid: OpId, token_id: u32 },
+    /// transition {opid} assigns tokens of type {state_type} different from
+    /// the tokens it spends.
+    NftTokensMismatch {
+        opid: OpId,
+        state_type: schema::AssignmentType,
+    },
+    /// transition {opid} changes data of token {token_id} other than by
+    /// engraving.
+    NftTokenChanged { opid: OpId, token_id: u32 },
+    /// genesis {0} has issuer identity proof which is malformed or has an
+    /// invalid signature.
+    IssuerIdentityInvalid(OpId),
+    /// operation {0} has type {1}, which is deprecated by the schema and
+    /// is past its sunset.
+    OperationSunset(OpId, OpFullType),
+    /// counter state of type {state_type} in operation {opid} changes from
+    /// {prev} to {value}, which is not an allowed step.
+    CounterStep {
+        opid: OpId,
+        state_type: schema::AssignmentType,
+        prev: u64,
+        value: u64,
+    },
+    /// consignment starts from checkpoint {0}, while the schema doesn't allow
+    /// checkpoints.
+    CheckpointNotAllowed(CheckpointId),
+    /// checkpoint {0} belongs to a different contract or schema.
+    CheckpointMismatch(CheckpointId),
+    /// checkpoint {0} is not trusted, so the contract history compacted by it
+    /// can't be verified.
+    CheckpointUntrusted(CheckpointId),
+    /// checkpoint {0} contains global state of type {1} which is not defined
+    /// by the schema or exceeds its limits.
+    CheckpointGlobalType(CheckpointId, schema::GlobalStateType),
+    /// checkpoint {0} contains output {1} which doesn't match the schema.
+    CheckpointOutput(CheckpointId, Opout),
+    /// provisional transition {0} has malformed revocation number.
+    ProvisionalRevocationMalformed(OpId),
+    /// provisional transitions {0} and {1} spend the same state and have the
+    /// same revocation number.
+    ProvisionalConflict(OpId, OpId),
+    /// operation {opid} has depth {depth} in the contract history, exceeding
+    /// the maximum of {max} allowed by the schema.
+    HistoryTooDeep { opid: OpId, depth: u32, max: u32 },
+    /// contract history has {width} operations of depth {depth}, exceeding the
+    /// maximum of {max} allowed by the schema.
+    HistoryTooWide { depth: u32, width: u32, max: u32 },
 
     // Consignment consistency errors
     /// operation {0} is absent from the consignment.
@@ -293,6 +528,27 @@ pub enum Failure {
     TransitionAbsent(OpId),
     /// bundle with id {0} is invalid.
     BundleInvalid(BundleId),
+    /// consignment contains {count} operations, exceeding the validator limit
+    /// of {max}.
+    ConsignmentTooManyNodes { count: usize, max: usize },
+    /// consignment data take {size} bytes, exceeding the validator limit of
+    /// {max} bytes.
+    ConsignmentTooLarge { size: usize, max: usize },
+    /// consignment history has depth {depth}, exceeding the validator limit of
+    /// {max}.
+    ConsignmentTooDeep { depth: u32, max: u32 },
+    /// operation {0} references attachment {1}, which is unknown to the
+    /// attachment resolver.
+    AttachmentMissing(OpId, AttachId),
+    /// assignments {0} and {1} use the same blinding factor for the Pedersen
+    /// commitments to their amounts, breaking their confidentiality.
+    BlindingReused(Opout, Opout),
+    /// assignments {0} and {1} have seals with the same blinding, making their
+    /// concealed seals linkable.
+    SealBlindingReused(Opout, Opout),
+    /// operation {0} has feature bits {1} unknown to this version of the
+    /// validator.
+    FeaturesUnknown(OpId, OpFeatures),
 
     // Errors checking seal closing
     /// transition {0} is not anchored.
@@ -316,9 +572,39 @@ pub enum Failure {
     MpcInvalid(OpId, Txid),
     /// witness transaction {0} is not known to the transaction resolver.
     SealNoWitnessTx(Txid),
+    /// mining height of transaction {0} is not known to the transaction
+    /// resolver, so the seal maturity can't be checked.
+    WitnessHeightUnknown(Txid),
+    /// mining time of transaction {0} is not known to the transaction
+    /// resolver, so the global state expiry can't be checked.
+    WitnessTimeUnknown(Txid),
+    /// median time past of the block mining transaction {0} is not known to
+    /// the transaction resolver, so the timechain instructions of the schema
+    /// scripts can't be executed.
+    WitnessMtpUnknown(Txid),
+    /// consignment doesn't provide SPV proofs of the witness transactions.
+    SpvProofsAbsent,
+    /// SPV proofs of the witness transactions are invalid: {0}
+    SpvInvalid(SpvError),
+    /// contract genesis is issued on {actual} chain, while the validation is
+    /// performed for {expected}.
+    WrongChain { expected: Chain, actual: Chain },
+    /// transition {opid} spends output {prev_out} created by transaction
+    /// {funding_txid} which has only {depth} confirmations out of required
+    /// {required}.
+    SealImmature {
+        opid: OpId,
+        prev_out: Opout,
+        funding_txid: Txid,
+        depth: u32,
+        required: u16,
+    },
     /// transition {0} doesn't close seal with the witness transaction {1}.
     /// Details: {2}
     SealInvalid(OpId, Txid, seals::txout::VerifyError),
+    /// transition {0} spends output {1} defined by a seal referencing a whole
+    /// transaction, but the seal output can't be derived from the transaction.
+    SealVoutUnderivable(OpId, Opout),
     /// transition {0} is not properly anchored to the witness transaction {1}.
     /// Details: {2}
     AnchorInvalid(OpId, Txid, anchor::VerifyError),
@@ -338,6 +624,11 @@ pub enum Failure {
         prev_id: OpId,
         valency: schema::ValencyType,
     },
+    /// state extension {0} redeems valencies governed by different schemata.
+    PluginValenciesMixed(OpId),
+    /// state extension {0} is governed by schema {1}, which is not provided by
+    /// the consignment.
+    PluginSchemaAbsent(OpId, SchemaId),
 
     // Data check errors
     /// state in {opid}/{state_type} is of {found} type, while schema requires
@@ -374,6 +665,31 @@ pub enum Failure {
     Custom(String),
 }
 
+impl Failure {
+    /// Detects failures caused by the chain data missing from the transaction
+    /// resolver, which may disappear once the data become available.
+    pub fn is_unresolved(&self) -> bool {
+        matches!(
+            self,
+            Failure::SealNoWitnessTx(_) |
+                Failure::WitnessHeightUnknown(_) |
+                Failure::WitnessTimeUnknown(_) |
+                Failure::WitnessMtpUnknown(_)
+        )
+    }
+}
+
+impl std::error::Error for Failure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Failure::InvalidTimestampProof(_, err) => Some(err),
+            Failure::SealInvalid(_, _, err) => Some(err),
+            Failure::AnchorInvalid(_, _, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Display, From)]
 #[cfg_attr(
     feature = "serde",
@@ -390,6 +706,38 @@ pub enum Warning {
     ExcessiveOperation(OpId),
     /// terminal witness transaction {0} is not yet mined.
     TerminalWitnessNotMined(Txid),
+    /// witness transaction {0} contains more than one valid deterministic
+    /// bitcoin commitment to the same anchor.
+    RedundantDbcProofs(Txid),
+    /// witness transaction {0} is an off-chain transaction (like a lightning
+    /// channel commitment), which is not published until the channel is
+    /// closed.
+    OffchainWitness(Txid),
+    /// provisional transition {0} is replaced by transition {1} with a higher
+    /// revocation number.
+    ProvisionalSuperseded(OpId, OpId),
+    /// witness transaction {1} of operation {0} is replaced (e.g. with RBF) by
+    /// witness transaction {2}, which is used for the validation.
+    ReplacedWitness(OpId, Txid, Txid),
+    /// contract references schema by id {0} produced with legacy {1} schema id
+    /// commitment tag.
+    SchemaIdLegacy(SchemaId, SchemaIdVersion),
+    /// schema defines {0}, which is deprecated by the root schema.
+    SubschemaOpDeprecated(OpFullType),
+    /// operation {0} has type {1}, which is deprecated by the schema.
+    OperationDeprecated(OpId, OpFullType),
+    /// operation {0} references attachment {1}, which is unknown to the
+    /// attachment resolver.
+    AttachmentMissing(OpId, AttachId),
+    /// assignments {0} and {1} use the same blinding factor for the Pedersen
+    /// commitments to their amounts, breaking their confidentiality.
+    BlindingReused(Opout, Opout),
+    /// assignments {0} and {1} have seals with the same blinding, making their
+    /// concealed seals linkable.
+    SealBlindingReused(Opout, Opout),
+    /// operation {0} has feature bits {1} unknown to this version of the
+    /// validator.
+    FeaturesUnknown(OpId, OpFeatures),
 
     /// Custom warning by external services on top of RGB Core.
     #[display(inner)]
@@ -408,7 +756,31 @@ pub enum Info {
     /// thus was not validated.
     UncheckableConfidentialState(OpId, AssignmentType),
 
+    /// contract was issued with schema {0} and is validated with its upgraded
+    /// version {1}.
+    SchemaMigrated(SchemaId, SchemaId),
+
     /// Custom info by external services on top of RGB Core.
     #[display(inner)]
     Custom(String),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unresolved() {
+        let txid = Txid::from([1u8; 32]);
+        let mut status = Status::with_failure(Failure::SealNoWitnessTx(txid));
+        status.unresolved_txids.push(txid);
+        assert_eq!(status.validity(), Validity::UnresolvedWitnesses);
+        status.add_failure(Failure::WitnessHeightUnknown(txid));
+        assert_eq!(status.validity(), Validity::UnresolvedWitnesses);
+        status.add_failure(Failure::SpvProofsAbsent);
+        assert_eq!(status.validity(), Validity::Invalid);
+
+        let status = Status::with_failure(Failure::WitnessMtpUnknown(txid));
+        assert_eq!(status.validity(), Validity::UnresolvedWitnesses);
+    }
+}
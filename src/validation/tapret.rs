@@ -0,0 +1,205 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for placing tapret commitments into taproot outputs which also
+//! have script spending paths, and for verifying such commitments.
+
+use bp::dbc::tapret::{
+    TapretCommitment, TapretKeyError, TapretNodePartner, TapretPathProof, TapretProof,
+};
+use bp::secp256k1::{Scalar, XOnlyPublicKey};
+use bp::{InternalPk, TapBranchHash, TapLeafHash, TapNodeHash, TapScript, MIDSTATE_TAPTWEAK};
+use commit_verify::{mpc, CommitVerify, ConvolveCommit, ConvolveCommitProof, DigestExt, Sha256};
+
+/// Errors verifying tapret commitment.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum TapretVerifyError {
+    /// tapret proof commits to a script tree with root {found:?} instead of
+    /// the expected root {expected:?}.
+    OriginalRootMismatch {
+        expected: Option<TapNodeHash>,
+        found: Option<TapNodeHash>,
+    },
+
+    /// tapret proof doesn't match output key {0}.
+    OutputKeyMismatch(XOnlyPublicKey),
+
+    /// invalid tapret proof. Details: {0}
+    #[from]
+    Proof(TapretKeyError),
+}
+
+/// Tweak of a taproot internal key containing tapret commitment.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TapretTweak {
+    /// Merkle root of the script tree containing the commitment.
+    pub merkle_root: TapNodeHash,
+
+    /// Tweak which must be added to the internal key.
+    pub tweak: Scalar,
+
+    /// Resulting taproot output key.
+    pub output_key: XOnlyPublicKey,
+
+    /// Proof of the commitment, which must be kept client-side.
+    pub proof: TapretProof,
+}
+
+/// Computes merkle root of the script tree after placing tapret commitment
+/// with the given `nonce` into it. The `partner` node describes scripts which
+/// were present in the tree before the commitment; if there were none, the
+/// tree consists only of the commitment leaf.
+pub fn tapret_merkle_root(
+    commitment: mpc::Commitment,
+    nonce: u8,
+    partner: Option<&TapretNodePartner>,
+) -> Result<TapNodeHash, TapretKeyError> {
+    let script = TapScript::commit(&TapretCommitment::with(commitment, nonce));
+    let leaf = TapLeafHash::with_tap_script(&script);
+    let Some(partner) = partner else {
+        return Ok(leaf.into())
+    };
+    if !partner.check_no_commitment() {
+        return Err(TapretKeyError::AlternativeCommitment(partner.clone()));
+    }
+    if !partner.check_ordering(leaf.into()) {
+        return Err(TapretKeyError::IncorrectOrdering(partner.clone(), leaf));
+    }
+    Ok(TapBranchHash::with_nodes(leaf.into(), partner.tap_node_hash()).into())
+}
+
+/// Computes BIP-341 tweak of the internal key for the script tree with the
+/// given merkle root.
+pub fn taproot_tweak(internal_pk: InternalPk, merkle_root: TapNodeHash) -> Scalar {
+    let mut engine = Sha256::from_tag(MIDSTATE_TAPTWEAK);
+    engine.input_raw(&internal_pk.serialize());
+    engine.input_raw(merkle_root.as_slice());
+    Scalar::from_be_bytes(engine.finish()).expect("hash value greater than curve order")
+}
+
+/// Computes taproot tweak placing tapret commitment into an output with the
+/// given internal key, preserving existing script spending paths described by
+/// the `partner` node.
+pub fn tapret_tweak(
+    internal_pk: InternalPk,
+    commitment: mpc::Commitment,
+    nonce: u8,
+    partner: Option<TapretNodePartner>,
+) -> Result<TapretTweak, TapretKeyError> {
+    let merkle_root = tapret_merkle_root(commitment, nonce, partner.as_ref())?;
+    let path_proof = match partner {
+        None => TapretPathProof::root(nonce),
+        Some(partner) => TapretPathProof::with(partner, nonce)
+            .expect("absence of alternative commitment is already checked"),
+    };
+    let (output_key, proof) = internal_pk.convolve_commit(&path_proof, &commitment)?;
+    Ok(TapretTweak {
+        merkle_root,
+        tweak: taproot_tweak(internal_pk, merkle_root),
+        output_key,
+        proof,
+    })
+}
+
+/// Verifies that the tapret proof commits to the `commitment` in the output
+/// with `output_key`, and that the script tree before the commitment had the
+/// merkle root `original_root` (`None` for outputs without script spending
+/// paths).
+pub fn verify_tapret(
+    proof: &TapretProof,
+    commitment: mpc::Commitment,
+    output_key: XOnlyPublicKey,
+    original_root: Option<TapNodeHash>,
+) -> Result<(), TapretVerifyError> {
+    let found = proof.path_proof.original_merkle_root();
+    if found != original_root {
+        return Err(TapretVerifyError::OriginalRootMismatch {
+            expected: original_root,
+            found,
+        });
+    }
+    if !ConvolveCommitProof::<_, InternalPk, _>::verify(proof, &commitment, &output_key)? {
+        return Err(TapretVerifyError::OutputKeyMismatch(output_key));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bp::{LeafScript, TapCode};
+
+    use super::*;
+
+    #[test]
+    fn tweak_with_scripts() {
+        let internal_pk = InternalPk::from_str(
+            "c5f93479093e2b8f724a79844cc10928dd44e9a390b539843fb83fbf842723f3",
+        )
+        .unwrap();
+        let commitment = mpc::Commitment::from([8u8; 32]);
+        let mut script = TapScript::new();
+        script.push_opcode(TapCode::Reserved);
+        let leaf = LeafScript::from_tap_script(script);
+        let original_root = TapNodeHash::from(leaf.tap_leaf_hash());
+
+        // Script leaf is placed to the right of the commitment, which requires
+        // finding the proper nonce
+        let tweak = (0..=u8::MAX)
+            .find_map(|nonce| {
+                tapret_tweak(internal_pk, commitment, nonce, Some(leaf.clone().into())).ok()
+            })
+            .unwrap();
+        assert_eq!(internal_pk.to_output_key(Some(tweak.merkle_root)), tweak.output_key);
+        let (key, _) = internal_pk
+            .add_tweak(bp::secp256k1::SECP256K1, &tweak.tweak)
+            .unwrap();
+        assert_eq!(key, tweak.output_key);
+
+        assert_eq!(
+            verify_tapret(&tweak.proof, commitment, tweak.output_key, Some(original_root)),
+            Ok(())
+        );
+        assert!(matches!(
+            verify_tapret(&tweak.proof, commitment, tweak.output_key, None),
+            Err(TapretVerifyError::OriginalRootMismatch { .. })
+        ));
+        let other = mpc::Commitment::from([9u8; 32]);
+        assert_eq!(
+            verify_tapret(&tweak.proof, other, tweak.output_key, Some(original_root)),
+            Err(TapretVerifyError::OutputKeyMismatch(tweak.output_key))
+        );
+    }
+
+    #[test]
+    fn tweak_key_only() {
+        let internal_pk = InternalPk::from_str(
+            "c5f93479093e2b8f724a79844cc10928dd44e9a390b539843fb83fbf842723f3",
+        )
+        .unwrap();
+        let commitment = mpc::Commitment::from([8u8; 32]);
+        let tweak = tapret_tweak(internal_pk, commitment, 0, None).unwrap();
+        assert_eq!(verify_tapret(&tweak.proof, commitment, tweak.output_key, None), Ok(()));
+    }
+}
@@ -0,0 +1,316 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the strict-encoded data against the semantic types of the
+//! schema type system.
+//!
+//! Strict types decoder reads collections using the length prefix matching the
+//! maximal size of the type, but doesn't check the actual bounds of the size,
+//! and panics on types containing standalone unicode characters. The checks
+//! here close these gaps, such that the data are accepted only if they
+//! exactly match their semantic type.
+
+use std::collections::BTreeSet;
+
+use strict_encoding::{NumCls, Sizing};
+use strict_types::{SemId, StrictVal, Ty, TypeRef, TypeSystem};
+
+/// Field of the operation metadata, accessible to the validation scripts with
+/// `ldmi` and `ldms` instructions (see [`crate::vm::ContractOp::LdMI`]).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MetaField<'op> {
+    /// Unsigned integer, in little-endian encoding.
+    Uint(&'op [u8]),
+    /// Signed integer, in little-endian encoding.
+    Int(&'op [u8]),
+    /// Byte string (array or list of byte-sized items) without the length
+    /// prefix.
+    Bytes(&'op [u8]),
+    /// Field of any other type, which can't be loaded by the scripts.
+    Other,
+}
+
+impl<'op> MetaField<'op> {
+    /// Coerces the field into an unsigned 64-bit integer. Fails for
+    /// non-integer fields, negative numbers and numbers exceeding 64 bits.
+    pub fn to_u64(&self) -> Option<u64> {
+        let (bytes, signed) = match *self {
+            MetaField::Uint(bytes) => (bytes, false),
+            MetaField::Int(bytes) => (bytes, true),
+            MetaField::Bytes(_) | MetaField::Other => return None,
+        };
+        if signed && bytes.last().map_or(false, |byte| byte & 0x80 != 0) {
+            return None;
+        }
+        let (low, high) = bytes.split_at(bytes.len().min(8));
+        if high.iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        let mut buf = [0u8; 8];
+        buf[..low.len()].copy_from_slice(low);
+        Some(u64::from_le_bytes(buf))
+    }
+
+    /// Coerces the field into a byte string; integers are represented by their
+    /// little-endian encoding. Fails for fields of other types.
+    pub fn as_bytes(&self) -> Option<&'op [u8]> {
+        match *self {
+            MetaField::Uint(bytes) | MetaField::Int(bytes) | MetaField::Bytes(bytes) => Some(bytes),
+            MetaField::Other => None,
+        }
+    }
+}
+
+/// Splits the metadata conforming to the semantic type `sem_id` into fields:
+/// fields of a structure or tuple, or the metadata itself for other types.
+///
+/// Fields are returned up to the first field which has a type other than a
+/// number, enumeration or byte string, since the position of the fields
+/// following it is not computed.
+pub(super) fn meta_fields<'op>(
+    type_system: &TypeSystem,
+    sem_id: SemId,
+    data: &'op [u8],
+) -> Vec<MetaField<'op>> {
+    let field_types: Vec<SemId> = match type_system.get(sem_id) {
+        Some(Ty::Struct(fields)) => fields.iter().map(|field| field.ty).collect(),
+        Some(Ty::Tuple(fields)) => fields.iter().copied().collect(),
+        _ => vec![sem_id],
+    };
+    split_fields(type_system, field_types, data)
+}
+
+fn split_fields<'op>(
+    type_system: &TypeSystem,
+    field_types: Vec<SemId>,
+    mut data: &'op [u8],
+) -> Vec<MetaField<'op>> {
+    let mut fields = Vec::with_capacity(field_types.len());
+    for sem_id in field_types {
+        let Some((field, rest)) = split_field(type_system, sem_id, data) else {
+            break;
+        };
+        fields.push(field);
+        data = rest;
+    }
+    fields
+}
+
+fn split_field<'op>(
+    type_system: &TypeSystem,
+    sem_id: SemId,
+    data: &'op [u8],
+) -> Option<(MetaField<'op>, &'op [u8])> {
+    let split = |data: &'op [u8], len: usize| (data.len() >= len).then(|| data.split_at(len));
+    let is_byte = |id: &SemId| match type_system.get(*id) {
+        Some(Ty::Primitive(primitive)) => primitive.byte_size() == 1,
+        Some(Ty::Enum(_)) => true,
+        _ => false,
+    };
+    match type_system.get(sem_id)? {
+        Ty::Primitive(primitive) => {
+            let (value, rest) = split(data, primitive.byte_size() as usize)?;
+            let field = match primitive.info().ty {
+                NumCls::Unsigned | NumCls::NonZero => MetaField::Uint(value),
+                NumCls::Signed => MetaField::Int(value),
+                NumCls::Float => MetaField::Other,
+            };
+            Some((field, rest))
+        }
+        Ty::Enum(_) => split(data, 1).map(|(_, rest)| (MetaField::Other, rest)),
+        Ty::Array(id, len) if is_byte(id) => {
+            let (value, rest) = split(data, *len as usize)?;
+            Some((MetaField::Bytes(value), rest))
+        }
+        Ty::List(id, sizing) if is_byte(id) => {
+            let (len, rest) = split(data, len_prefix(sizing))?;
+            let mut buf = [0u8; 8];
+            buf[..len.len()].copy_from_slice(len);
+            let (value, rest) = split(rest, usize::try_from(u64::from_le_bytes(buf)).ok()?)?;
+            Some((MetaField::Bytes(value), rest))
+        }
+        _ => None,
+    }
+}
+
+/// Size of the length prefix of a collection, which depends on its maximal
+/// size.
+fn len_prefix(sizing: &Sizing) -> usize {
+    match sizing.max {
+        max if max <= u8::MAX as u64 => 1,
+        max if max <= u16::MAX as u64 => 2,
+        max if max <= 0xFF_FFFF => 3,
+        max if max <= u32::MAX as u64 => 4,
+        _ => 8,
+    }
+}
+
+/// Checks that the data deserialize into a value of the semantic type `sem_id`
+/// and that all collections inside the value are within the size bounds of
+/// their types.
+pub(super) fn conforms(type_system: &TypeSystem, sem_id: SemId, data: &[u8]) -> bool {
+    if !is_decodable(type_system, sem_id, &mut BTreeSet::new()) {
+        return false;
+    }
+    let Ok(value) = type_system.strict_deserialize_type(sem_id, data) else {
+        return false;
+    };
+    is_bounded(type_system, sem_id, value.as_val())
+}
+
+/// Checks that the type is present in the type system and can be decoded
+/// without reaching a standalone unicode character, which the strict types
+/// decoder doesn't support.
+fn is_decodable(type_system: &TypeSystem, sem_id: SemId, visited: &mut BTreeSet<SemId>) -> bool {
+    if !visited.insert(sem_id) {
+        return true;
+    }
+    let Some(ty) = type_system.get(sem_id) else {
+        return false;
+    };
+    let mut check = |id: &SemId| is_decodable(type_system, *id, visited);
+    match ty {
+        Ty::Primitive(_) | Ty::Enum(_) => true,
+        Ty::UnicodeChar => false,
+        Ty::List(id, _) if id.is_unicode_char() => true,
+        Ty::Union(variants) => variants.values().all(check),
+        Ty::Tuple(fields) => fields.iter().all(check),
+        Ty::Struct(fields) => fields.iter().all(|field| check(&field.ty)),
+        Ty::Array(id, _) | Ty::List(id, _) | Ty::Set(id, _) | Ty::Map(_, id, _) => check(id),
+    }
+}
+
+/// Checks that the sizes of all collections inside the decoded value are
+/// within the bounds of their types.
+fn is_bounded(type_system: &TypeSystem, sem_id: SemId, value: &StrictVal) -> bool {
+    let Some(ty) = type_system.get(sem_id) else {
+        return false;
+    };
+    let check = |id: &SemId, value: &StrictVal| is_bounded(type_system, *id, value);
+    match (ty, value) {
+        (Ty::Primitive(_) | Ty::Enum(_), _) => true,
+        (Ty::Union(variants), StrictVal::Union(tag, value)) => variants
+            .ty_by_tag(tag.unwrap_ord())
+            .map_or(false, |id| check(id, value)),
+        (Ty::Tuple(fields), StrictVal::Tuple(values)) => fields
+            .iter()
+            .zip(values)
+            .all(|(id, value)| check(id, value)),
+        (Ty::Struct(fields), StrictVal::Struct(values)) => fields
+            .iter()
+            .zip(values.values())
+            .all(|(field, value)| check(&field.ty, value)),
+        (Ty::Array(_, _), StrictVal::Bytes(_)) => true,
+        (Ty::Array(id, _), StrictVal::List(values)) => values.iter().all(|value| check(id, value)),
+        (Ty::List(_, sizing), StrictVal::Bytes(bytes)) => fits(sizing, bytes.len()),
+        (Ty::List(_, sizing), StrictVal::String(string)) => fits(sizing, string.chars().count()),
+        (Ty::List(id, sizing), StrictVal::List(values)) |
+        (Ty::Set(id, sizing), StrictVal::Set(values)) => {
+            fits(sizing, values.len()) && values.iter().all(|value| check(id, value))
+        }
+        (Ty::Map(_, id, sizing), StrictVal::Map(values)) => {
+            fits(sizing, values.len()) && values.iter().all(|(_, value)| check(id, value))
+        }
+        _ => false,
+    }
+}
+
+fn fits(sizing: &Sizing, len: usize) -> bool { (sizing.min..=sizing.max).contains(&(len as u64)) }
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::MediumOrdMap;
+
+    use super::*;
+
+    #[test]
+    fn bounds() {
+        let byte = Ty::<SemId>::U8.id(None);
+        let word = Ty::<SemId>::U16.id(None);
+        let char = Ty::<SemId>::UNICODE.id(None);
+        let blob = Ty::<SemId>::list(byte, Sizing::new(1, 4));
+        let words = Ty::<SemId>::list(word, Sizing::new(0, 2));
+        let chars = Ty::<SemId>::Array(char, 2);
+        let (blob_id, words_id, chars_id) = (blob.id(None), words.id(None), chars.id(None));
+        let types = bmap! {
+            byte => Ty::U8,
+            word => Ty::U16,
+            char => Ty::UNICODE,
+            blob_id => blob,
+            words_id => words,
+            chars_id => chars,
+        };
+        let type_system = TypeSystem::from(MediumOrdMap::try_from(types).unwrap());
+
+        assert!(conforms(&type_system, blob_id, &[2, 0xAA, 0xBB]));
+        assert!(!conforms(&type_system, blob_id, &[0]));
+        assert!(!conforms(&type_system, blob_id, &[5, 1, 2, 3, 4, 5]));
+        assert!(!conforms(&type_system, blob_id, &[1, 0xAA, 0xBB]));
+
+        assert!(conforms(&type_system, words_id, &[0]));
+        assert!(conforms(&type_system, words_id, &[2, 1, 0, 2, 0]));
+        assert!(!conforms(&type_system, words_id, &[3, 1, 0, 2, 0, 3, 0]));
+
+        assert!(!conforms(&type_system, chars_id, &[0x61, 0x62]));
+        assert!(!conforms(&type_system, Ty::<SemId>::U32.id(None), &[0; 4]));
+    }
+
+    #[test]
+    fn fields() {
+        let byte = Ty::<SemId>::U8.id(None);
+        let word = Ty::<SemId>::U16.id(None);
+        let signed = Ty::<SemId>::I16.id(None);
+        let blob = Ty::<SemId>::list(byte, Sizing::new(0, 0x1FF));
+        let words = Ty::<SemId>::list(word, Sizing::new(0, 2));
+        let blob_id = blob.id(None);
+        let words_id = words.id(None);
+        let types = bmap! {
+            byte => Ty::U8,
+            word => Ty::U16,
+            signed => Ty::I16,
+            blob_id => blob,
+            words_id => words,
+        };
+        let type_system = TypeSystem::from(MediumOrdMap::try_from(types).unwrap());
+
+        let field_types = vec![word, blob_id, signed, words_id, word];
+        let data = [0x34, 0x12, 2, 0, 0xAA, 0xBB, 0xFF, 0xFF, 0, 1, 0];
+        let fields = split_fields(&type_system, field_types.clone(), &data);
+        assert_eq!(fields, vec![
+            MetaField::Uint(&[0x34, 0x12]),
+            MetaField::Bytes(&[0xAA, 0xBB]),
+            MetaField::Int(&[0xFF, 0xFF]),
+        ]);
+        assert_eq!(fields[0].to_u64(), Some(0x1234));
+        assert_eq!(fields[0].as_bytes(), Some(&[0x34, 0x12][..]));
+        assert_eq!(fields[1].to_u64(), None);
+        assert_eq!(fields[2].to_u64(), None);
+        assert_eq!(MetaField::Int(&[1, 0]).to_u64(), Some(1));
+        assert_eq!(MetaField::Uint(&[1; 9]).to_u64(), None);
+        assert_eq!(MetaField::Uint(&[1, 0, 0, 0, 0, 0, 0, 0, 0]).to_u64(), Some(1));
+
+        assert_eq!(meta_fields(&type_system, word, &[1, 0]), vec![MetaField::Uint(&[1, 0])]);
+        assert_eq!(split_fields(&type_system, field_types, &[1, 0, 3, 0, 0]), vec![
+            MetaField::Uint(&[1, 0])
+        ]);
+    }
+}
@@ -0,0 +1,414 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decoding of consignment-level data coming from untrusted sources.
+//!
+//! Strict decoding of confined collections pre-allocates memory for the number
+//! of elements claimed by the encoded length prefix. A crafted consignment may
+//! claim millions of elements in a collection while providing just a few bytes
+//! of data, exhausting the memory of the validating node. The decoder from
+//! this module checks all collection lengths and the depth of the type nesting
+//! against hard limits *before* any allocation happens.
+//!
+//! The decoder also reads each fast-forward version code ([`Ffv`]) before
+//! decoding the data which follow it, reporting data produced by a newer RGB
+//! version with [`UntrustedError::UnsupportedVersion`] instead of a generic
+//! decoding error.
+
+use std::io;
+
+use amplify::num::u24;
+use strict_encoding::{
+    DecodeError, FieldName, ReadStruct, ReadTuple, ReadUnion, StrictDecode, StrictEnum,
+    StrictStruct, StrictSum, StrictTuple, StrictType, StrictUnion, TypedRead, VariantName,
+};
+
+use crate::validation::AnchoredBundle;
+use crate::{
+    Extension, Ffv, Genesis, Schema, SchemaRoot, Transition, TransitionBundle, LIB_NAME_RGB,
+};
+
+/// Hard limits applied during decoding of untrusted data.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DecodeLimits {
+    /// Maximal size of the encoded data, in bytes.
+    pub max_data_len: usize,
+    /// Maximal number of elements in a single collection (including byte
+    /// strings).
+    pub max_collection_len: usize,
+    /// Maximal depth of nested structures, tuples and unions.
+    pub max_depth: usize,
+}
+
+impl DecodeLimits {
+    /// Default limits, which are sufficient for any consensus-valid data.
+    pub const DEFAULT: DecodeLimits = DecodeLimits {
+        max_data_len: 1 << 24,
+        max_collection_len: u16::MAX as usize,
+        max_depth: 64,
+    };
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self { DecodeLimits::DEFAULT }
+}
+
+/// Errors decoding untrusted data.
+#[derive(Clone, PartialEq, Eq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum UntrustedError {
+    /// data size of {0} bytes exceeds the limit of {1} bytes.
+    DataTooLarge(usize, usize),
+
+    /// collection claims {0} elements, while the limit is {1} elements.
+    CollectionTooLarge(usize, usize),
+
+    /// collection claims {0} elements, while only {1} bytes of data remain.
+    CollectionOverrun(usize, usize),
+
+    /// type nesting exceeds the maximal depth of {0}.
+    NestingTooDeep(usize),
+
+    /// {0} bytes of data remain after the end of the decoded value.
+    TrailingData(usize),
+
+    /// data were produced by a newer RGB version {found}, while this software
+    /// supports versions up to {supported}. Please update your software.
+    UnsupportedVersion {
+        /// Fast-forward version found in the data.
+        found: Ffv,
+        /// Latest fast-forward version supported by this software.
+        supported: Ffv,
+    },
+
+    /// malformed data. Details: {0}
+    #[from]
+    Decode(DecodeError),
+}
+
+/// Decoder of strict-encoded data enforcing [`DecodeLimits`].
+#[derive(Debug)]
+pub struct UntrustedReader<'data> {
+    data: &'data [u8],
+    pos: usize,
+    depth: usize,
+    limits: DecodeLimits,
+    violation: Option<UntrustedError>,
+}
+
+impl<'data> UntrustedReader<'data> {
+    /// Constructs reader over the provided data, failing if the data exceed
+    /// [`DecodeLimits::max_data_len`].
+    pub fn with(limits: DecodeLimits, data: &'data [u8]) -> Result<Self, UntrustedError> {
+        if data.len() > limits.max_data_len {
+            return Err(UntrustedError::DataTooLarge(data.len(), limits.max_data_len));
+        }
+        Ok(UntrustedReader {
+            data,
+            pos: 0,
+            depth: 0,
+            limits,
+            violation: None,
+        })
+    }
+
+    /// Number of bytes which were not consumed by the decoder yet.
+    pub fn remaining(&self) -> usize { self.data.len() - self.pos }
+
+    /// Completes decoding, failing if a limit was violated or if some data
+    /// were left unconsumed.
+    pub fn complete<T>(self, res: Result<T, DecodeError>) -> Result<T, UntrustedError> {
+        if let Some(violation) = self.violation {
+            return Err(violation);
+        }
+        let value = res?;
+        match self.remaining() {
+            0 => Ok(value),
+            rest => Err(UntrustedError::TrailingData(rest)),
+        }
+    }
+
+    fn violate(&mut self, err: UntrustedError) -> DecodeError {
+        let msg = err.to_string();
+        self.violation.get_or_insert(err);
+        DecodeError::DataIntegrityError(msg)
+    }
+
+    fn check_ffv(&mut self) -> Result<(), DecodeError> {
+        let Some(bytes) = self.data.get(self.pos..self.pos + 2) else {
+            return Ok(());
+        };
+        let found = Ffv(u16::from_le_bytes([bytes[0], bytes[1]]));
+        if found > Ffv::LATEST {
+            return Err(self.violate(UntrustedError::UnsupportedVersion {
+                found,
+                supported: Ffv::LATEST,
+            }));
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self) -> Result<(), DecodeError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(self.violate(UntrustedError::NestingTooDeep(self.limits.max_depth)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn nested<T>(
+        &mut self,
+        inner: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError> {
+        self.enter()?;
+        let res = inner(self);
+        self.depth -= 1;
+        res
+    }
+
+    fn field_reader(&mut self) -> Result<FieldReader<'_, 'data>, DecodeError> {
+        self.enter()?;
+        Ok(FieldReader { parent: self })
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'data [u8]> {
+        if len > self.remaining() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let data = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(data)
+    }
+}
+
+impl<'data> TypedRead for UntrustedReader<'data> {
+    type TupleReader<'parent> = FieldReader<'parent, 'data> where Self: 'parent;
+    type StructReader<'parent> = FieldReader<'parent, 'data> where Self: 'parent;
+    type UnionReader = Self;
+
+    fn read_union<T: StrictUnion>(
+        &mut self,
+        inner: impl FnOnce(VariantName, &mut Self::UnionReader) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError> {
+        self.nested(|reader| {
+            let tag = u8::strict_decode(reader)?;
+            let name = T::strict_name().map(|n| n.to_string()).unwrap_or_default();
+            let variant_name =
+                T::variant_name_by_tag(tag).ok_or(DecodeError::UnionTagNotKnown(name, tag))?;
+            inner(variant_name, reader)
+        })
+    }
+
+    fn read_enum<T: StrictEnum>(&mut self) -> Result<T, DecodeError>
+    where u8: From<T> {
+        let tag = u8::strict_decode(self)?;
+        let name = T::strict_name().map(|n| n.to_string()).unwrap_or_default();
+        T::try_from(tag).map_err(|_| DecodeError::EnumTagNotKnown(name, tag))
+    }
+
+    fn read_tuple<'parent, 'me, T: StrictTuple>(
+        &'me mut self,
+        inner: impl FnOnce(&mut Self::TupleReader<'parent>) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError>
+    where
+        Self: 'parent,
+        'me: 'parent,
+    {
+        if T::STRICT_LIB_NAME == LIB_NAME_RGB && T::strict_name() == Ffv::strict_name() {
+            self.check_ffv()?;
+        }
+        inner(&mut self.field_reader()?)
+    }
+
+    fn read_struct<'parent, 'me, T: StrictStruct>(
+        &'me mut self,
+        inner: impl FnOnce(&mut Self::StructReader<'parent>) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError>
+    where
+        Self: 'parent,
+        'me: 'parent,
+    {
+        inner(&mut self.field_reader()?)
+    }
+
+    unsafe fn _read_raw<const MAX_LEN: usize>(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        self.take(len).map(<[u8]>::to_vec)
+    }
+
+    unsafe fn _read_raw_array<const LEN: usize>(&mut self) -> io::Result<[u8; LEN]> {
+        let mut buf = [0u8; LEN];
+        buf.copy_from_slice(self.take(LEN)?);
+        Ok(buf)
+    }
+
+    unsafe fn _read_raw_len<const MAX_LEN: usize>(&mut self) -> Result<usize, DecodeError> {
+        let len = match MAX_LEN {
+            tiny if tiny <= u8::MAX as usize => u8::strict_decode(self)? as usize,
+            small if small <= u16::MAX as usize => u16::strict_decode(self)? as usize,
+            medium if medium <= u24::MAX.into_usize() => u24::strict_decode(self)?.into_usize(),
+            large if large <= u32::MAX as usize => u32::strict_decode(self)? as usize,
+            _ => u64::strict_decode(self)? as usize,
+        };
+        if len > self.limits.max_collection_len {
+            let max = self.limits.max_collection_len;
+            return Err(self.violate(UntrustedError::CollectionTooLarge(len, max)));
+        }
+        // Each element of a collection takes at least a single byte
+        if len > self.remaining() {
+            let remaining = self.remaining();
+            return Err(self.violate(UntrustedError::CollectionOverrun(len, remaining)));
+        }
+        Ok(len)
+    }
+}
+
+impl<'data> ReadUnion for UntrustedReader<'data> {
+    type TupleReader<'parent> = FieldReader<'parent, 'data> where Self: 'parent;
+    type StructReader<'parent> = FieldReader<'parent, 'data> where Self: 'parent;
+
+    fn read_tuple<'parent, 'me, T: StrictSum>(
+        &'me mut self,
+        inner: impl FnOnce(&mut Self::TupleReader<'parent>) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError>
+    where
+        Self: 'parent,
+        'me: 'parent,
+    {
+        inner(&mut self.field_reader()?)
+    }
+
+    fn read_struct<'parent, 'me, T: StrictSum>(
+        &'me mut self,
+        inner: impl FnOnce(&mut Self::StructReader<'parent>) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError>
+    where
+        Self: 'parent,
+        'me: 'parent,
+    {
+        inner(&mut self.field_reader()?)
+    }
+}
+
+/// Reader of tuple and structure fields for [`UntrustedReader`].
+#[derive(Debug)]
+pub struct FieldReader<'parent, 'data> {
+    parent: &'parent mut UntrustedReader<'data>,
+}
+
+impl<'parent, 'data> Drop for FieldReader<'parent, 'data> {
+    fn drop(&mut self) { self.parent.depth -= 1; }
+}
+
+impl<'parent, 'data> ReadTuple for FieldReader<'parent, 'data> {
+    fn read_field<T: StrictDecode>(&mut self) -> Result<T, DecodeError> {
+        T::strict_decode(self.parent)
+    }
+}
+
+impl<'parent, 'data> ReadStruct for FieldReader<'parent, 'data> {
+    fn read_field<T: StrictDecode>(&mut self, _field: FieldName) -> Result<T, DecodeError> {
+        T::strict_decode(self.parent)
+    }
+}
+
+/// Consignment-level data types which may be received from untrusted sources
+/// and must be decoded with [`DecodeLimits`] applied.
+pub trait DecodeUntrusted: StrictDecode {
+    /// Decodes the value using [`DecodeLimits::DEFAULT`], requiring all of the
+    /// provided data to be consumed.
+    fn decode_untrusted(data: &[u8]) -> Result<Self, UntrustedError> {
+        Self::decode_untrusted_with(DecodeLimits::DEFAULT, data)
+    }
+
+    /// Decodes the value using custom limits, requiring all of the provided
+    /// data to be consumed.
+    fn decode_untrusted_with(limits: DecodeLimits, data: &[u8]) -> Result<Self, UntrustedError> {
+        let mut reader = UntrustedReader::with(limits, data)?;
+        let res = Self::strict_decode(&mut reader);
+        reader.complete(res)
+    }
+}
+
+impl<Root: SchemaRoot> DecodeUntrusted for Schema<Root> {}
+impl DecodeUntrusted for Genesis {}
+impl DecodeUntrusted for Transition {}
+impl DecodeUntrusted for Extension {}
+impl DecodeUntrusted for TransitionBundle {}
+impl DecodeUntrusted for AnchoredBundle {}
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::MediumVec;
+    use strict_encoding::{StrictDumb, StrictSerialize};
+
+    use super::*;
+    use crate::RootSchema;
+
+    #[test]
+    fn schema_roundtrip() {
+        let schema = RootSchema::default();
+        let mut data = schema
+            .to_strict_serialized::<{ u16::MAX as usize }>()
+            .unwrap();
+        assert_eq!(RootSchema::decode_untrusted(&data), Ok(schema));
+        data.push(0).unwrap();
+        assert_eq!(RootSchema::decode_untrusted(&data), Err(UntrustedError::TrailingData(1)));
+    }
+
+    #[test]
+    fn oversized_collection() {
+        let data = [0xFF, 0xFF, 0xFF, 0x00];
+        let mut reader = UntrustedReader::with(DecodeLimits::DEFAULT, &data).unwrap();
+        let res = MediumVec::<u64>::strict_decode(&mut reader);
+        assert_eq!(
+            reader.complete(res),
+            Err(UntrustedError::CollectionTooLarge(0xFFFFFF, u16::MAX as usize))
+        );
+
+        let limits = DecodeLimits {
+            max_collection_len: usize::MAX,
+            ..default!()
+        };
+        let mut reader = UntrustedReader::with(limits, &data).unwrap();
+        let res = MediumVec::<u64>::strict_decode(&mut reader);
+        assert_eq!(reader.complete(res), Err(UntrustedError::CollectionOverrun(0xFFFFFF, 1)));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut transition = Transition::strict_dumb();
+        transition.ffv = Ffv::LATEST;
+        let mut data = transition
+            .to_strict_serialized::<{ u16::MAX as usize }>()
+            .unwrap();
+        assert_eq!(Transition::decode_untrusted(&data), Ok(transition));
+
+        data[0] = 0xFF;
+        assert_eq!(
+            Transition::decode_untrusted(&data),
+            Err(UntrustedError::UnsupportedVersion {
+                found: Ffv(0xFF),
+                supported: Ffv::LATEST,
+            })
+        );
+    }
+}
@@ -20,22 +20,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp::{Ordering, Reverse};
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::Instant;
 
 use bp::dbc::Anchor;
+use bp::seals::txout::blind::BlindSeal;
 use bp::seals::txout::{TxPtr, Witness};
-use bp::{Tx, Txid};
+use bp::{Chain, Tx, Txid};
 use commit_verify::mpc;
 use single_use_seals::SealWitness;
 
-use super::status::{Failure, Warning};
-use super::{ConsignmentApi, Status, Validity, VirtualMachine};
+use super::bounded::encoded_len;
+use super::progress::{CancelToken, Progress, ValidationPhase, ValidationProgress};
+use super::report::{NodeReport, ValidationReport};
+use super::status::{Failure, Info, Warning};
+use super::{ConsignmentApi, ScriptPolicy, Status, Validity, VirtualMachine};
 use crate::contract::Opout;
 use crate::validation::AnchoredBundle;
 use crate::vm::AluRuntime;
 use crate::{
-    BundleId, ContractId, OpId, OpRef, Operation, Schema, SchemaId, SchemaRoot, Script, SubSchema,
-    Transition, TransitionBundle, TypedAssigns,
+    Assign, BundleId, CheckpointId, ContractId, Extension, GlobalExpiry, GlobalState,
+    HistoryLimits, OpId, OpRef, Operation, RevealedData, Schema, SchemaId, SchemaRoot, Script,
+    SubSchema, Sunset, Transition, TransitionBundle, TypedAssigns, WitnessPos, VOUT_DERIVED,
 };
 
 #[derive(Clone, Debug, Display, Error, From)]
@@ -49,6 +56,86 @@ pub enum TxResolverError {
 
 pub trait ResolveTx {
     fn resolve_tx(&self, txid: Txid) -> Result<Tx, TxResolverError>;
+
+    /// Returns the height of the block in which the transaction was mined.
+    /// Used to check maturity of the seals for assignment types which
+    /// require it; resolvers not tracking heights may keep the default
+    /// implementation, which fails validation of such seals.
+    fn resolve_height(&self, txid: Txid) -> Result<u32, TxResolverError> {
+        Err(TxResolverError::Unknown(txid))
+    }
+
+    /// Returns the timestamp of the block in which the transaction was mined.
+    /// Used to check expiry of the global state for state types which expire
+    /// at some time; resolvers not tracking block times may keep the default
+    /// implementation, which fails validation of such contracts.
+    fn resolve_time(&self, txid: Txid) -> Result<i64, TxResolverError> {
+        Err(TxResolverError::Unknown(txid))
+    }
+
+    /// Returns the median time past (see BIP-113) of the block in which the
+    /// transaction was mined. Used by the schema scripts with timechain
+    /// instructions; resolvers not tracking block times may keep the default
+    /// implementation, which fails validation of such contracts.
+    fn resolve_mtp(&self, txid: Txid) -> Result<i64, TxResolverError> {
+        Err(TxResolverError::Unknown(txid))
+    }
+
+    /// Resolves transaction which is not published on-chain, but is known to
+    /// the resolver as a part of an off-chain protocol, like a commitment
+    /// transaction of a lightning channel spending the channel funding
+    /// output. Such transactions become on-chain witnesses only once the
+    /// channel is closed. Resolvers not participating in off-chain protocols
+    /// may keep the default implementation.
+    fn resolve_offchain_tx(&self, txid: Txid) -> Result<Tx, TxResolverError> {
+        Err(TxResolverError::Unknown(txid))
+    }
+}
+
+/// Resolver which doesn't know any transactions, used for the validation of
+/// the consignment structure, which doesn't require access to the bitcoin
+/// network (see [`Validator::validate_structure`]).
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct OfflineResolver;
+
+impl ResolveTx for OfflineResolver {
+    fn resolve_tx(&self, txid: Txid) -> Result<Tx, TxResolverError> {
+        Err(TxResolverError::Unknown(txid))
+    }
+}
+
+/// Configuration of [`Validator::validate_with_config`]: limits on the
+/// resources used for a single consignment, allowing public-facing services to
+/// reject oversized consignments before validating them, and the checkpoints
+/// trusted by the caller.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ValidatorConfig {
+    /// Maximal number of operations in the consignment, including genesis.
+    pub max_nodes: usize,
+    /// Maximal total size of the consignment data (schema, genesis, state
+    /// extensions and anchored bundles), in bytes.
+    pub max_total_bytes: usize,
+    /// Maximal depth of the contract history, counted in operations from the
+    /// genesis.
+    pub max_depth: u32,
+    /// Ids of the checkpoints which the consignment history may start from
+    /// (see [`ConsignmentApi::checkpoint`]). Consignments starting from other
+    /// checkpoints are invalid.
+    pub trusted_checkpoints: BTreeSet<CheckpointId>,
+}
+
+impl ValidatorConfig {
+    /// Default limits, sufficient for the most of the contracts.
+    pub const DEFAULT: ValidatorConfig = ValidatorConfig {
+        max_nodes: 1 << 16,
+        max_total_bytes: 1 << 24,
+        max_depth: 1 << 14,
+        trusted_checkpoints: BTreeSet::new(),
+    };
+}
+
+impl Default for ValidatorConfig {
+    fn default() -> Self { ValidatorConfig::DEFAULT }
 }
 
 pub struct Validator<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx> {
@@ -60,18 +147,83 @@ pub struct Validator<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx> {
     genesis_id: OpId,
     contract_id: ContractId,
     anchor_index: BTreeMap<OpId, &'consignment Anchor<mpc::MerkleProof>>,
+    alt_dbc_index: BTreeMap<OpId, &'consignment AnchoredBundle>,
+    // Operations anchored into several witness transactions, one of which has
+    // replaced the others
+    replaced_index: BTreeMap<OpId, Vec<&'consignment AnchoredBundle>>,
     end_transitions: Vec<(&'consignment Transition, BundleId)>,
     validation_index: BTreeSet<OpId>,
     anchor_validation_index: BTreeSet<OpId>,
 
     vm: Box<dyn VirtualMachine + 'consignment>,
+    // Whether the schema scripts use timechain instructions, which require
+    // median time past of the witness transactions
+    timechain: bool,
     resolver: &'resolver R,
+
+    // Validation phases which are run
+    structure: bool,
+    witnesses: bool,
+
+    progress: Option<Box<dyn ValidationProgress + 'consignment>>,
+    op_count: usize,
+    cancel: Option<CancelToken>,
+    // Reports on the validated operations, collected only by the instrumented
+    // validation
+    nodes: Option<Vec<NodeReport>>,
+    trusted_checkpoints: BTreeSet<CheckpointId>,
+}
+
+impl<'consignment, C: ConsignmentApi> Validator<'consignment, 'static, C, OfflineResolver> {
+    /// Validates the consignment structure: schema, scripts, operations against
+    /// the schema and commitments of the operations into the anchors. This
+    /// phase doesn't access bitcoin network and thus can be run before the
+    /// resolver is available.
+    ///
+    /// Witness transactions are verified separately with
+    /// [`Validator::validate_witnesses`]; statuses returned by both phases can
+    /// be combined with `+=`.
+    pub fn validate_structure(consignment: &'consignment C, chain: Chain) -> Status {
+        Validator::run(consignment, &OfflineResolver, chain, true, false, None, None)
+    }
+
+    /// Validates schema, genesis and all state extensions of the consignment.
+    /// State extensions don't have witness transactions, thus this validation
+    /// never accesses the resolver and skips anchors, bundles and state
+    /// transitions - including the transitions defining the valencies redeemed
+    /// by the extensions, which are validated by [`Validator::validate`].
+    ///
+    /// Allows to validate extension-heavy contracts quickly, for instance
+    /// before the resolver is available.
+    pub fn validate_extensions(consignment: &'consignment C, chain: Chain) -> Status {
+        // Failures of the bundles and terminals detected during initialization
+        // are related to the state transitions, thus we skip them
+        let mut validator = Validator::init(consignment, &OfflineResolver, false, false);
+        validator.structure = true;
+        let schema = consignment.schema();
+        validator.validate_chain(chain);
+        validator.validate_schema(schema);
+        if validator.status.validity() == Validity::Invalid || !validator.validate_genesis(schema) {
+            return validator.status;
+        }
+        for opid in consignment.op_ids_except(&BTreeSet::new()) {
+            if let Some(extension) = consignment.extension(opid) {
+                validator.validate_extension_node(schema, extension);
+            }
+        }
+        validator.status
+    }
 }
 
 impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
     Validator<'consignment, 'resolver, C, R>
 {
-    fn init(consignment: &'consignment C, resolver: &'resolver R) -> Self {
+    fn init(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        structure: bool,
+        witnesses: bool,
+    ) -> Self {
         // We use validation status object to store all detected failures and
         // warnings
         let mut status = Status::default();
@@ -83,19 +235,36 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
 
         // Create indexes
         let mut anchor_index = BTreeMap::<OpId, &Anchor<mpc::MerkleProof>>::new();
-        for AnchoredBundle {
-            ref anchor,
-            ref bundle,
-        } in consignment.anchored_bundles()
-        {
+        let mut alt_dbc_index = BTreeMap::<OpId, &AnchoredBundle>::new();
+        let mut witness_index = BTreeMap::<OpId, Vec<&AnchoredBundle>>::new();
+        for anchored_bundle in consignment.anchored_bundles() {
+            let AnchoredBundle {
+                ref anchor,
+                ref bundle,
+                ref alt_dbc_proofs,
+            } = anchored_bundle;
             if !TransitionBundle::validate(bundle) {
                 status.add_failure(Failure::BundleInvalid(bundle.bundle_id()));
             }
             for transition in bundle.values().filter_map(|item| item.transition.as_ref()) {
                 let opid = transition.id();
                 anchor_index.insert(opid, anchor);
+                if !alt_dbc_proofs.is_empty() {
+                    alt_dbc_index.insert(opid, anchored_bundle);
+                }
+                let witnesses = witness_index.entry(opid).or_default();
+                if witnesses.iter().all(|ab| ab.anchor.txid != anchor.txid) {
+                    witnesses.push(anchored_bundle);
+                }
             }
         }
+        // Operation anchored into several transactions (for instance, when the
+        // witness was replaced with RBF) has its witness selected during the
+        // witness validation phase, which has access to the bitcoin network
+        let replaced_index = witness_index
+            .into_iter()
+            .filter(|(_, witnesses)| witnesses.len() > 1)
+            .collect();
 
         // Collect all endpoint transitions.
         // This is pretty simple operation; it takes a lot of code because we would like
@@ -134,9 +303,19 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
         // Index used to avoid repeated validations of the same anchor+transition pairs
         let anchor_validation_index = BTreeSet::<OpId>::new();
 
-        let vm = match &consignment.schema().script {
-            Script::AluVM(lib) => {
-                Box::new(AluRuntime::new(lib)) as Box<dyn VirtualMachine + 'consignment>
+        // Failures in the consignment structure are reported by the structure
+        // validation phase only
+        if !structure {
+            status = Status::default();
+        }
+
+        let (vm, timechain) = match &consignment.schema().script {
+            Script::AluVM(lib) => (
+                Box::new(AluRuntime::new(lib)) as Box<dyn VirtualMachine + 'consignment>,
+                lib.uses_timechain(),
+            ),
+            script @ Script::Embedded => {
+                (Box::new(script.clone()) as Box<dyn VirtualMachine + 'consignment>, false)
             }
         };
 
@@ -147,11 +326,21 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
             genesis_id,
             contract_id,
             anchor_index,
+            alt_dbc_index,
+            replaced_index,
             end_transitions,
             validation_index,
             anchor_validation_index,
             vm,
+            timechain,
             resolver,
+            structure,
+            witnesses,
+            progress: None,
+            op_count: 0,
+            cancel: None,
+            nodes: None,
+            trusted_checkpoints: empty!(),
         }
     }
 
@@ -164,58 +353,406 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
     /// the status object, but the validation continues for the rest of the
     /// consignment data. This can help it debugging and detecting all problems
     /// with the consignment.
-    pub fn validate(consignment: &'consignment C, resolver: &'resolver R) -> Status {
-        let mut validator = Validator::init(consignment, resolver);
+    ///
+    /// Consignments of contracts issued on a chain other than `chain` are
+    /// invalid, such that testnet contracts can't be accepted by mainnet
+    /// wallets and vice versa.
+    pub fn validate(consignment: &'consignment C, resolver: &'resolver R, chain: Chain) -> Status {
+        Validator::run(consignment, resolver, chain, true, true, None, None)
+    }
 
-        validator.validate_schema(consignment.schema());
-        // We must return here, since if the schema is not valid there is no reason to
-        // validate contract nodes against it: it will produce a plenty of errors
-        if validator.status.validity() == Validity::Invalid {
-            return validator.status;
+    /// Performs the same validation as [`Validator::validate`], failing
+    /// consignments which schema script uses a virtual machine not allowed by
+    /// the `policy` before any other validation is done.
+    pub fn validate_with_policy(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+        policy: ScriptPolicy,
+    ) -> Status {
+        let vm_type = consignment.schema().script.vm_type();
+        if !policy.allows(vm_type) {
+            return Status::with_failure(Failure::VmUnsupported(vm_type));
         }
+        Validator::validate(consignment, resolver, chain)
+    }
 
-        validator.validate_contract(consignment.schema());
+    /// Performs the same validation as [`Validator::validate`], reporting its
+    /// progress to the provided receiver.
+    pub fn validate_with_progress(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+        progress: impl ValidationProgress + 'consignment,
+    ) -> Status {
+        Validator::run(consignment, resolver, chain, true, true, Some(Box::new(progress)), None)
+    }
+
+    /// Performs the same validation as [`Validator::validate_with_progress`],
+    /// which can be aborted with the `cancel` token. Validation of a cancelled
+    /// consignment returns status with [`Status::partial`] set, which
+    /// [`Validity`] is never [`Validity::Valid`].
+    pub fn validate_cancellable(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+        progress: impl ValidationProgress + 'consignment,
+        cancel: CancelToken,
+    ) -> Status {
+        let progress = Some(Box::new(progress) as Box<dyn ValidationProgress>);
+        Validator::run(consignment, resolver, chain, true, true, progress, Some(cancel))
+    }
+
+    /// Performs the same validation as [`Validator::validate`], measuring the
+    /// time and the script resources spent on each of the validated
+    /// operations. Scripts of the plug-in schemata are not metered.
+    pub fn validate_instrumented(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+    ) -> (Status, ValidationReport) {
+        let start = Instant::now();
+        let mut validator = Validator::init(consignment, resolver, true, true);
+        // Embedded virtual machine runs no scripts which may be metered
+        if let Script::AluVM(lib) = &consignment.schema().script {
+            validator.vm = Box::new(AluRuntime::metered(lib));
+        }
+        validator.nodes = Some(vec![]);
+        validator.execute(chain);
+        let report = ValidationReport {
+            nodes: validator.nodes.take().unwrap_or_default(),
+            duration: start.elapsed(),
+        };
+        (validator.status, report)
+    }
+
+    /// Performs the same validation as [`Validator::validate`] if the
+    /// consignment fits into the resource limits of the `config`; otherwise
+    /// returns status with the failures describing the exceeded limits.
+    pub fn validate_with_config(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+        config: ValidatorConfig,
+    ) -> Status {
+        // Limits are checked before indexing the consignment, such that the
+        // oversized consignments don't consume the resources
+        let status = Self::validate_resources(consignment, &config);
+        if status.validity() == Validity::Invalid {
+            return status;
+        }
+        let mut validator = Validator::init(consignment, resolver, true, true);
+        validator.trusted_checkpoints = config.trusted_checkpoints;
+        validator.execute(chain);
+        validator.status
+    }
+
+    /// Validates witness transactions of the consignment: their presence in
+    /// the bitcoin network, closing of the single-use seals, seal maturity and
+    /// deterministic bitcoin commitments. Consignment structure is not
+    /// verified by this phase (see [`Validator::validate_structure`]).
+    pub fn validate_witnesses(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+    ) -> Status {
+        Validator::run(consignment, resolver, chain, false, true, None, None)
+    }
+
+    /// Updates validation result after a change of the witness transactions
+    /// known to the resolver, like replacement of an unconfirmed witness with
+    /// RBF or its confirmation. Only witnesses are re-validated; `structure`
+    /// must be the status returned by [`Validator::validate_structure`] for
+    /// the same consignment.
+    pub fn update_witnesses(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+        structure: &Status,
+    ) -> Status {
+        let mut status = structure.clone();
+        status += Validator::validate_witnesses(consignment, resolver, chain);
+        status
+    }
+
+    fn run(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+        structure: bool,
+        witnesses: bool,
+        progress: Option<Box<dyn ValidationProgress + 'consignment>>,
+        cancel: Option<CancelToken>,
+    ) -> Status {
+        let mut validator = Validator::init(consignment, resolver, structure, witnesses);
+        if progress.is_some() {
+            validator.op_count = consignment.op_ids_except(&BTreeSet::new()).len();
+            validator.progress = progress;
+        }
+        validator.cancel = cancel;
+        validator.execute(chain);
 
         // Done. Returning status report with all possible failures, issues, warnings
         // and notifications about transactions we were unable to obtain.
         validator.status
     }
 
-    fn validate_schema(&mut self, schema: &SubSchema) { self.status += schema.verify(); }
+    fn execute(&mut self, chain: Chain) {
+        let schema = self.consignment.schema();
+        self.validate_chain(chain);
 
-    fn validate_contract<Root: SchemaRoot>(&mut self, schema: &Schema<Root>) {
+        self.report(ValidationPhase::Schema, None);
+        if self.structure {
+            self.validate_schema(schema);
+            // We must return here, since if the schema is not valid there is no reason to
+            // validate contract nodes against it: it will produce a plenty of errors
+            if self.status.validity() == Validity::Invalid {
+                return;
+            }
+        }
+        if self.is_cancelled() {
+            return;
+        }
+
+        self.validate_contract(schema);
+        self.report(ValidationPhase::Completed, None);
+    }
+
+    /// Checks that the consignment fits into the resource limits, returning
+    /// status with the failure describing the first exceeded limit. Limits are
+    /// checked from the cheapest to compute, such that oversized consignments
+    /// are rejected early.
+    fn validate_resources(consignment: &C, config: &ValidatorConfig) -> Status {
+        let mut status = Status::new();
+
+        let opids = consignment.op_ids_except(&BTreeSet::new());
+        if opids.len() > config.max_nodes {
+            status.add_failure(Failure::ConsignmentTooManyNodes {
+                count: opids.len(),
+                max: config.max_nodes,
+            });
+            return status;
+        }
+
+        let extensions = opids
+            .iter()
+            .filter_map(|opid| consignment.extension(*opid))
+            .map(encoded_len);
+        let bundles = consignment.anchored_bundles().map(encoded_len);
+        let size = encoded_len(consignment.schema()) +
+            encoded_len(consignment.genesis()) +
+            extensions.chain(bundles).sum::<usize>();
+        if size > config.max_total_bytes {
+            status.add_failure(Failure::ConsignmentTooLarge {
+                size,
+                max: config.max_total_bytes,
+            });
+            return status;
+        }
+
+        let depth = history_depths(consignment, &opids)
+            .into_values()
+            .max()
+            .unwrap_or_default();
+        if depth > config.max_depth {
+            status.add_failure(Failure::ConsignmentTooDeep {
+                depth,
+                max: config.max_depth,
+            });
+        }
+        status
+    }
+
+    fn validate_chain(&mut self, chain: Chain) {
+        let actual = self.consignment.genesis().chain;
+        if actual != chain {
+            self.status.add_failure(Failure::WrongChain {
+                expected: chain,
+                actual,
+            });
+        }
+    }
+
+    fn validate_schema(&mut self, schema: &SubSchema) {
+        // [VALIDATION]: Negotiating ISA extensions, such that a script which can't be
+        //               executed by this runtime fails before any of its code is run
+        match &schema.script {
+            Script::AluVM(script) => {
+                let unsupported = script.unsupported_isa_ids();
+                if !unsupported.is_empty() {
+                    self.status
+                        .add_failure(Failure::UnsupportedIsa(unsupported));
+                    return;
+                }
+            }
+            #[cfg(feature = "embedded")]
+            Script::Embedded => {}
+            #[cfg(not(feature = "embedded"))]
+            Script::Embedded => {
+                self.status
+                    .add_failure(Failure::VmUnsupported(crate::VmType::Embedded));
+                return;
+            }
+        }
+
+        self.status += schema.verify();
+    }
+
+    /// Validates schema and genesis of the consignment streamed by
+    /// [`super::BoundedValidator`].
+    pub(super) fn validate_head(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        chain: Chain,
+    ) -> Status {
+        let mut validator = Validator::init(consignment, resolver, true, true);
+        validator.validate_chain(chain);
+        validator.validate_schema(consignment.schema());
+        if validator.status.validity() != Validity::Invalid {
+            validator.validate_genesis(consignment.schema());
+        }
+        validator.status
+    }
+
+    /// Validates the last item of the consignment streamed by
+    /// [`super::BoundedValidator`], which is either the `extension` or the only
+    /// anchored bundle of the `consignment`.
+    pub(super) fn validate_streamed(
+        consignment: &'consignment C,
+        resolver: &'resolver R,
+        extension: Option<&Extension>,
+    ) -> Status {
+        let mut validator = Validator::init(consignment, resolver, true, true);
+        let schema = consignment.schema();
+        if let Some(extension) = extension {
+            validator.status += validator.validate_extension(schema, extension);
+        }
+        for anchored_bundle in consignment.anchored_bundles() {
+            let AnchoredBundle {
+                ref anchor,
+                ref bundle,
+                ..
+            } = anchored_bundle;
+            let bundle_id = bundle.bundle_id();
+            for transition in bundle.values().filter_map(|item| item.transition.as_ref()) {
+                let op = OpRef::Transition(transition);
+                let witness_txid = Some(anchor.txid);
+                let witness_pos = validator.witness_pos(schema, anchor.txid);
+                let global = contract_global(consignment, op);
+                validator.status += schema.validate(
+                    consignment,
+                    op,
+                    witness_txid,
+                    witness_pos,
+                    Some(&global),
+                    validator.vm.as_ref(),
+                );
+                if anchor
+                    .convolve(validator.contract_id, bundle_id.into())
+                    .is_err()
+                {
+                    validator
+                        .status
+                        .add_failure(Failure::NotInAnchor(transition.id(), anchor.txid));
+                }
+                validator.validate_transition(transition, bundle_id, anchor);
+            }
+        }
+        validator.status
+    }
+
+    /// Validates schema id, checkpoint and genesis. Returns `false` if the
+    /// validation of the contract can't proceed.
+    fn validate_genesis<Root: SchemaRoot>(&mut self, schema: &Schema<Root>) -> bool {
         // [VALIDATION]: Making sure that we were supplied with the schema
         //               that corresponds to the schema of the contract genesis
-        if schema.schema_id() != self.schema_id {
-            self.status.add_failure(Failure::SchemaMismatch {
-                expected: self.schema_id,
-                actual: schema.schema_id(),
-            });
-            // Unlike other failures, here we return immediatelly, since there is no point
-            // to validate all consignment data against an invalid schema: it will result in
-            // a plenty of meaningless errors
-            return;
+        //               using any of the accepted schema id versions
+        let migrated_from = schema.migration.as_ref().map(|migration| migration.from);
+        match schema.schema_id_version(self.schema_id) {
+            None if migrated_from == Some(self.schema_id) => {
+                if self.structure {
+                    self.status
+                        .add_info(Info::SchemaMigrated(self.schema_id, schema.schema_id()));
+                }
+            }
+            None if !self.structure => return false,
+            None => {
+                self.status.add_failure(Failure::SchemaMismatch {
+                    expected: self.schema_id,
+                    actual: schema.schema_id(),
+                });
+                // Unlike other failures, here we return immediatelly, since there is no point
+                // to validate all consignment data against an invalid schema: it will result
+                // in a plenty of meaningless errors
+                return false;
+            }
+            Some(version) if version.is_legacy() && self.structure => {
+                self.status
+                    .add_warning(Warning::SchemaIdLegacy(self.schema_id, version));
+            }
+            Some(_) => {}
+        }
+
+        // [VALIDATION]: Validate checkpoint against the schema rules, if the history
+        //               starts from a checkpoint
+        if let Some(checkpoint) = self.consignment.checkpoint().filter(|_| self.structure) {
+            let id = checkpoint.checkpoint_id();
+            if !schema.checkpoints {
+                self.status.add_failure(Failure::CheckpointNotAllowed(id));
+            }
+            if !self.trusted_checkpoints.contains(&id) {
+                self.status.add_failure(Failure::CheckpointUntrusted(id));
+            }
+            let ids = (checkpoint.contract_id, checkpoint.schema_id);
+            if ids != (self.contract_id, self.schema_id) {
+                self.status.add_failure(Failure::CheckpointMismatch(id));
+            }
+            self.status += checkpoint.verify(schema);
         }
 
         // [VALIDATION]: Validate genesis
-        self.status += schema.validate(
-            self.consignment,
-            OpRef::Genesis(self.consignment.genesis()),
-            self.vm.as_ref(),
-        );
+        self.report(ValidationPhase::Genesis, None);
+        if self.is_cancelled() {
+            return false;
+        }
+        if self.structure {
+            let genesis = OpRef::Genesis(self.consignment.genesis());
+            let status = self.measure(genesis, |validator| {
+                let global = contract_global(validator.consignment, genesis);
+                let vm = validator.vm.as_ref();
+                schema.validate(validator.consignment, genesis, None, None, Some(&global), vm)
+            });
+            self.status += status;
+        }
         self.validation_index.insert(self.genesis_id);
+        true
+    }
+
+    fn validate_contract<Root: SchemaRoot>(&mut self, schema: &Schema<Root>) {
+        if !self.validate_genesis(schema) {
+            return;
+        }
 
         // [VALIDATION]: Iterating over each endpoint, reconstructing operation
         //               graph up to genesis for each one of them.
         // NB: We are not aiming to validate the consignment as a whole, but instead
         // treat it as a superposition of subgraphs, one for each endpoint; and validate
         // them independently.
+        self.report(ValidationPhase::Operations, None);
+        if self.witnesses {
+            self.select_witnesses();
+        }
+        self.select_provisional(schema);
         for (operation, bundle_id) in self.end_transitions.clone() {
             self.validate_branch(schema, operation, bundle_id);
         }
+        // Remaining checks require the whole consignment to be validated
+        if self.status.partial {
+            return;
+        }
         // Replace missed (not yet mined) endpoint witness transaction failures
         // with a dedicated type
-        for (operation, _) in &self.end_transitions {
+        for (operation, _) in self.end_transitions.iter().filter(|_| self.witnesses) {
             if let Some(anchor) = self.anchor_index.get(&operation.id()) {
                 if let Some(pos) = self
                     .status
@@ -238,8 +775,77 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
         // Generate warning if some of the transitions within the consignment were
         // excessive (i.e. not part of validation_index). Nothing critical, but still
         // good to report the user that the consignment is not perfect
-        for opid in self.consignment.op_ids_except(&self.validation_index) {
-            self.status.add_warning(Warning::ExcessiveOperation(opid));
+        if self.structure {
+            for opid in self.consignment.op_ids_except(&self.validation_index) {
+                self.status.add_warning(Warning::ExcessiveOperation(opid));
+            }
+            self.validate_history_limits(schema.history_limits);
+            self.validate_nft_issue(schema);
+        }
+    }
+
+    /// Checks that each non-fungible token is issued only once across the
+    /// validated genesis and state extensions (see
+    /// [`crate::EmbeddedProcedure::NftIssue`]). Duplicates within a single
+    /// operation are reported by the operation validation.
+    fn validate_nft_issue<Root: SchemaRoot>(&mut self, schema: &Schema<Root>) {
+        for procedure in &schema.procedures {
+            let Some(token_type) = procedure.token_type() else {
+                continue;
+            };
+            let mut issued = BTreeSet::new();
+            for opid in &self.validation_index {
+                let Some(op) = self.consignment.operation(*opid) else {
+                    continue;
+                };
+                if op.transition_type().is_some() {
+                    continue;
+                }
+                let Some(assigns) = op.assignments_by_type(token_type) else {
+                    continue;
+                };
+                let token_ids = assigns
+                    .as_structured()
+                    .iter()
+                    .filter_map(Assign::as_revealed_state)
+                    .filter_map(RevealedData::token_id)
+                    .collect::<BTreeSet<_>>();
+                for token_id in token_ids {
+                    if !issued.insert(token_id) {
+                        self.status.add_failure(Failure::NftTokenDuplicated {
+                            opid: *opid,
+                            token_id,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks that the validated operations don't exceed the history limits
+    /// declared by the schema.
+    fn validate_history_limits(&mut self, limits: HistoryLimits) {
+        if limits == HistoryLimits::default() {
+            return;
+        }
+        let depths = history_depths(self.consignment, &self.validation_index);
+
+        let mut widths = BTreeMap::<u32, u32>::new();
+        for (opid, depth) in depths {
+            match limits.max_depth {
+                Some(max) if depth > max => {
+                    self.status
+                        .add_failure(Failure::HistoryTooDeep { opid, depth, max });
+                }
+                _ => {}
+            }
+            *widths.entry(depth).or_default() += 1;
+        }
+        if let Some(max) = limits.max_width {
+            for (depth, width) in widths.into_iter().filter(|(_, width)| *width > max) {
+                self.status
+                    .add_failure(Failure::HistoryTooWide { depth, width, max });
+            }
         }
     }
 
@@ -262,13 +868,41 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
         // checking in the code below:
         queue.push_back(OpRef::Transition(transition));
         while let Some(operation) = queue.pop_front() {
+            if self.is_cancelled() {
+                return;
+            }
             let opid = operation.id();
 
+            // State extensions have no witnesses, thus they take a dedicated path which
+            // doesn't involve anchors and the resolver
+            if let OpRef::Extension(extension) = operation {
+                let parents = self.validate_extension_node(schema, extension);
+                queue.extend(parents);
+                continue;
+            }
+
             // [VALIDATION]: Verify operation against the schema. Here we check only a single
             //               operation, not state evolution (it will be checked lately)
             if !self.validation_index.contains(&opid) {
-                self.status += schema.validate(self.consignment, operation, self.vm.as_ref());
+                let witness_txid = self.anchor_index.get(&opid).map(|anchor| anchor.txid);
+                if self.structure {
+                    let status = self.measure(operation, |validator| {
+                        let witness_pos =
+                            witness_txid.and_then(|txid| validator.witness_pos(schema, txid));
+                        let global = contract_global(validator.consignment, operation);
+                        schema.validate(
+                            validator.consignment,
+                            operation,
+                            witness_txid,
+                            witness_pos,
+                            Some(&global),
+                            validator.vm.as_ref(),
+                        )
+                    });
+                    self.status += status;
+                }
                 self.validation_index.insert(opid);
+                self.report(ValidationPhase::Operations, witness_txid);
             }
 
             match operation {
@@ -287,55 +921,275 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
                             // [VALIDATION]: Check that transition is committed into the anchor.
                             //               This must be done with deterministic bitcoin
                             // commitments &               LNPBP-4.
-                            if anchor.convolve(self.contract_id, bundle_id.into()).is_err() {
+                            if self.structure &&
+                                anchor.convolve(self.contract_id, bundle_id.into()).is_err()
+                            {
                                 self.status
                                     .add_failure(Failure::NotInAnchor(opid, anchor.txid));
                             }
 
-                            self.validate_transition(transition, bundle_id, anchor);
+                            if self.witnesses {
+                                self.validate_transition(transition, bundle_id, anchor);
+                            }
                             self.anchor_validation_index.insert(opid);
                         }
-                    } else {
+                    } else if self.structure {
                         // If we've got here there is something broken with the consignment
                         // provider.
                         self.status.add_failure(Failure::NotAnchored(opid));
                     }
 
                     // Now, we must collect all parent nodes and add them to the verification queue
+                    // Ancestors compacted into the checkpoint are not present in the consignment
+                    let checkpoint = self.consignment.checkpoint();
                     let parent_nodes = transition.inputs.iter().filter_map(|input| {
                         self.consignment.operation(input.prev_out.op).or_else(|| {
+                            if checkpoint.and_then(|c| c.output(input.prev_out)).is_some() {
+                                return None;
+                            }
                             // This will not actually happen since we already checked that each
                             // ancestor reference has a corresponding operation in the code above.
                             // But lets double-check :)
-                            self.status
-                                .add_failure(Failure::TransitionAbsent(input.prev_out.op));
+                            if self.structure {
+                                self.status
+                                    .add_failure(Failure::TransitionAbsent(input.prev_out.op));
+                            }
                             None
                         })
                     });
 
                     queue.extend(parent_nodes);
+
+                    // Transitions co-signing the transfer must be valid as well
+                    if let Some(anchor) = self.anchor_index.get(&opid) {
+                        let cosigners = schema
+                            .cosigners(self.consignment, transition, anchor.txid)
+                            .into_iter()
+                            .filter(|cosigner| !self.validation_index.contains(&cosigner.id()));
+                        queue.extend(cosigners.map(OpRef::Transition));
+                    }
                 }
-                OpRef::Extension(ref extension) => {
-                    for (valency, prev_id) in &extension.redeemed {
-                        let Some(prev_op) = self.consignment.operation(*prev_id) else {
-                                self.status.add_failure(Failure::ValencyNoParent { opid, prev_id: *prev_id, valency: *valency });
-                                continue;
-                            };
-
-                        if !prev_op.valencies().contains(valency) {
-                            self.status.add_failure(Failure::NoPrevValency {
-                                opid,
-                                prev_id: *prev_id,
-                                valency: *valency,
-                            });
-                            continue;
-                        }
+                OpRef::Extension(_) => unreachable!("extensions are validated separately"),
+            }
+        }
+    }
+
+    /// Validates state extension, which doesn't have a witness transaction,
+    /// such that neither anchors nor the resolver are involved. Returns the
+    /// operations defining the valencies redeemed by the extension.
+    fn validate_extension_node<Root: SchemaRoot>(
+        &mut self,
+        schema: &Schema<Root>,
+        extension: &'consignment Extension,
+    ) -> Vec<OpRef<'consignment>> {
+        let opid = extension.id();
+        if !self.validation_index.contains(&opid) {
+            if self.structure {
+                let op = OpRef::Extension(extension);
+                let status =
+                    self.measure(op, |validator| validator.validate_extension(schema, extension));
+                self.status += status;
+            }
+            self.validation_index.insert(opid);
+            self.report(ValidationPhase::Operations, None);
+        }
+
+        let mut parents = vec![];
+        for (valency, prev_id) in &extension.redeemed {
+            let Some(prev_op) = self.consignment.operation(*prev_id) else {
+                if self.structure {
+                    self.status.add_failure(Failure::ValencyNoParent {
+                        opid,
+                        prev_id: *prev_id,
+                        valency: *valency,
+                    });
+                }
+                continue;
+            };
+
+            if !prev_op.valencies().contains(valency) {
+                if self.structure {
+                    self.status.add_failure(Failure::NoPrevValency {
+                        opid,
+                        prev_id: *prev_id,
+                        valency: *valency,
+                    });
+                }
+                continue;
+            }
+
+            parents.push(prev_op);
+        }
+        parents
+    }
+
+    /// Validates state extension against the schema governing it, which is a
+    /// foreign schema for the extensions redeeming plug-in valencies (see
+    /// [`Schema::plugins`]).
+    fn validate_extension<Root: SchemaRoot>(
+        &self,
+        schema: &Schema<Root>,
+        extension: &Extension,
+    ) -> Status {
+        let opid = extension.id();
+        let op = OpRef::Extension(extension);
+        let global = contract_global(self.consignment, op);
+        let mut governors = extension
+            .redeemed
+            .keys()
+            .map(|valency| schema.plugins.get(valency).copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter();
+        let plugin_id = match (governors.next(), governors.next()) {
+            (None, _) | (Some(None), None) => {
+                let vm = self.vm.as_ref();
+                return schema.validate(self.consignment, op, None, None, Some(&global), vm);
+            }
+            (Some(Some(plugin_id)), None) => plugin_id,
+            _ => return Status::with_failure(Failure::PluginValenciesMixed(opid)),
+        };
+
+        let Some(plugin) = self.consignment.plugin_schema(plugin_id) else {
+            return Status::with_failure(Failure::PluginSchemaAbsent(opid, plugin_id));
+        };
+        if plugin.schema_id() != plugin_id {
+            return Status::with_failure(Failure::SchemaMismatch {
+                expected: plugin_id,
+                actual: plugin.schema_id(),
+            });
+        }
+        // [VALIDATION]: Foreign extension must be valid against the rules of its own
+        //               schema, including the schema script
+        let mut status = plugin.verify();
+        if status.validity() == Validity::Invalid {
+            return status;
+        }
+        status += plugin.validate(self.consignment, op, None, None, Some(&global), &plugin.script);
+        status
+    }
+
+    /// Runs validation of the operation against the schema, recording the
+    /// resources spent if the validation is instrumented.
+    fn measure(&mut self, op: OpRef, validate: impl FnOnce(&mut Self) -> Status) -> Status {
+        if self.nodes.is_none() {
+            return validate(self);
+        }
+        let start = Instant::now();
+        let status = validate(self);
+        let node = NodeReport {
+            opid: op.id(),
+            ty: op.full_type(),
+            duration: start.elapsed(),
+            script: self.vm.take_meter().unwrap_or_default(),
+        };
+        if let Some(nodes) = &mut self.nodes {
+            nodes.push(node);
+        }
+        status
+    }
+
+    /// Checks the cancel token, marking the status as partial if the
+    /// validation was cancelled.
+    fn is_cancelled(&mut self) -> bool {
+        if self
+            .cancel
+            .as_ref()
+            .map(CancelToken::is_cancelled)
+            .unwrap_or_default()
+        {
+            self.status.partial = true;
+        }
+        self.status.partial
+    }
+
+    fn report(&mut self, phase: ValidationPhase, witness: Option<Txid>) {
+        let Some(progress) = &mut self.progress else {
+            return;
+        };
+        progress.report(Progress {
+            phase,
+            processed: self.validation_index.len(),
+            total: self.op_count,
+            witness,
+        });
+    }
+
+    /// Leaves only the latest version of each provisional transition among the
+    /// endpoints, such that versions replaced by a transition with a higher
+    /// revocation number are not validated.
+    fn select_provisional<Root: SchemaRoot>(&mut self, schema: &Schema<Root>) {
+        // Failures and warnings are reported by the structure validation phase only
+        let mut status = Status::default();
+        let mut versions = Vec::<(&Transition, u64)>::new();
+        for (transition, _) in &self.end_transitions {
+            if !schema.provisional.contains_key(&transition.transition_type) {
+                continue;
+            }
+            match schema.revocation_number(transition) {
+                Some(revocation) => versions.push((*transition, revocation)),
+                None => {
+                    status.add_failure(Failure::ProvisionalRevocationMalformed(transition.id()));
+                }
+            }
+        }
 
-                        queue.push_back(prev_op);
+        let mut superseded = BTreeSet::new();
+        for (pos, (a, a_revocation)) in versions.iter().enumerate() {
+            for (b, b_revocation) in &versions[pos + 1..] {
+                let conflicting = a.inputs.iter().any(|input| {
+                    b.inputs
+                        .iter()
+                        .any(|other| other.prev_out == input.prev_out)
+                });
+                if !conflicting {
+                    continue;
+                }
+                let (old, new) = match a_revocation.cmp(b_revocation) {
+                    Ordering::Less => (a.id(), b.id()),
+                    Ordering::Greater => (b.id(), a.id()),
+                    Ordering::Equal => {
+                        status.add_failure(Failure::ProvisionalConflict(a.id(), b.id()));
+                        continue;
                     }
+                };
+                if superseded.insert(old) {
+                    status.add_warning(Warning::ProvisionalSuperseded(old, new));
                 }
             }
         }
+        self.end_transitions
+            .retain(|(transition, _)| !superseded.contains(&transition.id()));
+        if self.structure {
+            self.status += status;
+        }
+    }
+
+    /// Selects witness of the operations anchored into several transactions
+    /// closing the same seals, of which only one can be mined: prefers the
+    /// confirmed transaction, then the latest known one.
+    fn select_witnesses(&mut self) {
+        for (opid, witnesses) in &self.replaced_index {
+            let txids = witnesses
+                .iter()
+                .map(|ab| ab.anchor.txid)
+                .collect::<Vec<_>>();
+            let selected = preferred_witness(
+                &txids,
+                |txid| self.resolver.resolve_height(txid).is_ok(),
+                |txid| self.resolver.resolve_tx(txid).is_ok(),
+            );
+            let anchored_bundle = witnesses[selected];
+            self.anchor_index.insert(*opid, &anchored_bundle.anchor);
+            if anchored_bundle.alt_dbc_proofs.is_empty() {
+                self.alt_dbc_index.remove(opid);
+            } else {
+                self.alt_dbc_index.insert(*opid, anchored_bundle);
+            }
+            for txid in txids.iter().filter(|txid| **txid != txids[selected]) {
+                self.status
+                    .add_warning(Warning::ReplacedWitness(*opid, *txid, txids[selected]));
+            }
+        }
     }
 
     fn validate_transition(
@@ -348,7 +1202,15 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
 
         // Check that the anchor is committed into a transaction spending all of the
         // transition inputs.
-        match self.resolver.resolve_tx(txid) {
+        // Assignments inside lightning channels are updated with off-chain
+        // transactions, which are not mined until the channel is closed
+        let resolved = self.resolver.resolve_tx(txid).or_else(|err| {
+            let tx = self.resolver.resolve_offchain_tx(txid).map_err(|_| err)?;
+            self.status.offchain_witnesses.push(txid);
+            self.status.add_warning(Warning::OffchainWitness(txid));
+            Ok::<_, TxResolverError>(tx)
+        });
+        match resolved {
             Err(_) => {
                 // We wre unable to retrieve corresponding transaction, so can't check.
                 // Reporting this incident and continuing further. Why this happens? No
@@ -364,18 +1226,47 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
                 self.status.add_failure(Failure::SealNoWitnessTx(txid));
             }
             Ok(witness_tx) => {
+                let anchor = self.select_anchor(transition.id(), bundle_id, anchor, &witness_tx);
                 let witness = Witness::with(witness_tx, anchor.clone());
-                self.validate_witness(transition, witness, bundle_id, anchor)
+                self.validate_witness(transition, witness, bundle_id, &anchor)
             }
         }
     }
 
+    /// Selects the anchor variant with a valid deterministic bitcoin commitment
+    /// in the witness transaction, if the anchor has alternative DBC proofs.
+    /// Falls back to the main anchor if none of the variants is valid, which
+    /// is reported later during the witness validation.
+    fn select_anchor(
+        &mut self,
+        opid: OpId,
+        bundle_id: BundleId,
+        anchor: &'consignment Anchor<mpc::MerkleProof>,
+        witness_tx: &Tx,
+    ) -> Anchor<mpc::MerkleProof> {
+        let Some(anchored_bundle) = self.alt_dbc_index.get(&opid) else {
+            return anchor.clone();
+        };
+        let message = mpc::Message::from(bundle_id);
+        let mut valid = anchored_bundle.anchors().filter(|anchor| {
+            matches!(anchor.verify(self.contract_id, message, witness_tx), Ok(true))
+        });
+        let Some(selected) = valid.next() else {
+            return anchor.clone();
+        };
+        if valid.next().is_some() {
+            self.status
+                .add_warning(Warning::RedundantDbcProofs(anchor.txid));
+        }
+        selected
+    }
+
     fn validate_witness(
         &mut self,
         transition: &'consignment Transition,
         witness: Witness,
         bundle_id: BundleId,
-        anchor: &'consignment Anchor<mpc::MerkleProof>,
+        anchor: &Anchor<mpc::MerkleProof>,
     ) {
         let opid = transition.id();
         let txid = witness.txid;
@@ -386,17 +1277,32 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
         for input in &transition.inputs {
             let Opout { op, ty, no } = input.prev_out;
 
-            let Some(prev_op) = self.consignment.operation(op) else {
-                // Node, referenced as the ancestor, was not found in the consignment. 
-                // Usually this means that the consignment data are broken
-                self.status
-                    .add_failure(Failure::OperationAbsent(op));
-                continue
-            };
+            // Key rights are not bound to seals and are authorized by the schema script
+            let schema = self.consignment.schema();
+            if schema.key_rights.contains(&schema.migrated_type(ty)) {
+                continue;
+            }
 
-            let Some(variant) = prev_op.assignments_by_type(ty) else {
-                self.status.add_failure(Failure::NoPrevState { opid, prev_id: op, state_type: ty });
-                continue
+            let checkpoint = self.consignment.checkpoint();
+            let (variant, no, prev_witness) = match self.consignment.operation(op) {
+                Some(prev_op) => {
+                    let Some(variant) = prev_op.assignments_by_type(ty) else {
+                        self.status.add_failure(Failure::NoPrevState { opid, prev_id: op, state_type: ty });
+                        continue
+                    };
+                    (variant, no, None)
+                }
+                // State assigned before the checkpoint is kept as a single assignment
+                None => match checkpoint.and_then(|checkpoint| checkpoint.output(input.prev_out)) {
+                    Some(output) => (output.assign.clone(), 0, Some(output.witness)),
+                    None => {
+                        // Node, referenced as the ancestor, was not found in the consignment.
+                        // Usually this means that the consignment data are broken
+                        self.status
+                            .add_failure(Failure::OperationAbsent(op));
+                        continue
+                    }
+                },
             };
 
             let Ok(seal) = variant.revealed_seal_at(no) else {
@@ -411,16 +1317,37 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
                 continue
             };
 
-            let seal = match (seal.txid, self.anchor_index.get(&op)) {
-                (TxPtr::WitnessTx, Some(anchor)) => {
+            let seal = match (seal.txid, self.anchor_index.get(&op), prev_witness) {
+                (TxPtr::WitnessTx, _, Some(Some(prev_witness_txid))) => {
+                    seal.resolve(prev_witness_txid)
+                }
+                (TxPtr::WitnessTx, _, Some(None)) => {
+                    // Checkpoint output with a seal lacking its witness transaction
+                    self.status
+                        .add_failure(Failure::NoPrevOut(opid, input.prev_out));
+                    continue
+                }
+                (TxPtr::WitnessTx, Some(anchor), None) => {
                     let prev_witness_txid = anchor.txid;
                     seal.resolve(prev_witness_txid)
                 }
-                (TxPtr::WitnessTx, None) => {
+                (TxPtr::WitnessTx, None, None) => {
                     panic!("anchor for the operation {op} was not indexed by the validator");
                 }
-                (TxPtr::Txid(txid), _) => seal.resolve(txid),
+                (TxPtr::Txid(txid), ..) => seal.resolve(txid),
+            };
+            let seal = if seal.vout.into_u32() == VOUT_DERIVED {
+                let Some(seal) = self.derive_seal_vout(opid, input.prev_out, seal) else {
+                    continue;
+                };
+                seal
+            } else {
+                seal
             };
+            let schema = self.consignment.schema();
+            if let Some(required) = schema.maturity.get(&schema.migrated_type(ty)) {
+                self.validate_maturity(opid, input.prev_out, seal.txid, txid, *required);
+            }
             seals.push(seal)
         }
 
@@ -449,4 +1376,463 @@ impl<'consignment, 'resolver, C: ConsignmentApi, R: ResolveTx>
                 .add_failure(Failure::AnchorInvalid(opid, txid, err));
         }
     }
+
+    /// Resolves output of a seal referencing a whole transaction, using the
+    /// output derivation declared by the schema for the assignment type.
+    fn derive_seal_vout(
+        &mut self,
+        opid: OpId,
+        prev_out: Opout,
+        mut seal: BlindSeal<Txid>,
+    ) -> Option<BlindSeal<Txid>> {
+        let schema = self.consignment.schema();
+        let ty = schema.migrated_type(prev_out.ty);
+        let Some(derivation) = schema.vout_derivation.get(&ty).copied() else {
+            self.status
+                .add_failure(Failure::SealVoutUnderivable(opid, prev_out));
+            return None;
+        };
+        let tx = match self.resolver.resolve_tx(seal.txid) {
+            Ok(tx) => tx,
+            Err(_) => {
+                self.status.unresolved_txids.push(seal.txid);
+                self.status.add_failure(Failure::SealNoWitnessTx(seal.txid));
+                return None;
+            }
+        };
+        let Some(vout) = derivation.derive_vout(&tx, seal.method) else {
+            self.status
+                .add_failure(Failure::SealVoutUnderivable(opid, prev_out));
+            return None;
+        };
+        seal.vout = vout;
+        Some(seal)
+    }
+
+    /// Resolves position of the witness transaction, which is required only
+    /// if the schema has global state types which may expire, operation types
+    /// deprecated until a timestamp or scripts using timechain instructions.
+    fn witness_pos<Root: SchemaRoot>(
+        &mut self,
+        schema: &Schema<Root>,
+        witness_txid: Txid,
+    ) -> Option<WitnessPos> {
+        if !self.witnesses {
+            return None;
+        }
+        let expiries = schema.global_types.values().map(|ty| ty.expiry);
+        let (mut by_height, mut by_time) = (false, false);
+        for expiry in expiries {
+            match expiry {
+                GlobalExpiry::Never => {}
+                GlobalExpiry::Height(_) => by_height = true,
+                GlobalExpiry::Time(_) => by_time = true,
+            }
+        }
+        by_time |= schema
+            .deprecations
+            .iter()
+            .any(|(_, sunset)| matches!(sunset, Sunset::Time(_)));
+        if !by_height && !by_time && !self.timechain {
+            return None;
+        }
+        // Positions which are not used by the schema are left zero
+        let mut pos = WitnessPos::new(0, 0);
+        if by_height {
+            let Ok(height) = self.resolver.resolve_height(witness_txid) else {
+                self.status
+                    .add_failure(Failure::WitnessHeightUnknown(witness_txid));
+                return None;
+            };
+            pos.height = height;
+        }
+        if by_time {
+            let Ok(timestamp) = self.resolver.resolve_time(witness_txid) else {
+                self.status
+                    .add_failure(Failure::WitnessTimeUnknown(witness_txid));
+                return None;
+            };
+            pos.timestamp = timestamp;
+        }
+        if self.timechain {
+            let Ok(mtp) = self.resolver.resolve_mtp(witness_txid) else {
+                self.status
+                    .add_failure(Failure::WitnessMtpUnknown(witness_txid));
+                return None;
+            };
+            pos.mtp = mtp;
+        }
+        Some(pos)
+    }
+
+    fn validate_maturity(
+        &mut self,
+        opid: OpId,
+        prev_out: Opout,
+        funding_txid: Txid,
+        witness_txid: Txid,
+        required: u16,
+    ) {
+        let height = |txid| self.resolver.resolve_height(txid).map_err(|_| txid);
+        let (funding_height, witness_height) = match (height(funding_txid), height(witness_txid)) {
+            (Ok(funding), Ok(witness)) => (funding, witness),
+            (Err(txid), _) | (_, Err(txid)) => {
+                self.status.add_failure(Failure::WitnessHeightUnknown(txid));
+                return;
+            }
+        };
+        let depth = witness_height.saturating_sub(funding_height);
+        if depth < required as u32 {
+            self.status.add_failure(Failure::SealImmature {
+                opid,
+                prev_out,
+                funding_txid,
+                depth,
+                required,
+            });
+        }
+    }
+}
+
+/// Computes depth of each of the operations (see [`HistoryLimits`]). Ancestors
+/// which are not present in the consignment are compacted into its checkpoint
+/// and are not counted.
+/// Returns ids of the operations present in the consignment which state is
+/// spent or which valencies are redeemed by the operation.
+fn parent_ids(consignment: &impl ConsignmentApi, op: OpRef) -> Vec<OpId> {
+    let redeemed = op
+        .as_extension()
+        .map(|extension| extension.redeemed.values().copied().collect::<Vec<_>>())
+        .unwrap_or_default();
+    op.inputs()
+        .iter()
+        .map(|input| input.prev_out.op)
+        .chain(redeemed)
+        .filter(|id| consignment.operation(*id).is_some())
+        .collect()
+}
+
+/// Builds the contract global state available to the validation scripts of
+/// the operation from the global state defined by its ancestors, such that
+/// the consignment can't provide the scripts with the state which is not a
+/// part of the validated contract history.
+///
+/// Values of each global state type are ordered starting from the most recent
+/// one, i.e. from the operation with the largest history depth (operations of
+/// the same depth are ordered by their ids). The oldest values exceeding the
+/// global state size limits are omitted.
+fn contract_global(consignment: &impl ConsignmentApi, op: OpRef) -> GlobalState {
+    let mut ancestors = BTreeSet::new();
+    let mut stack = parent_ids(consignment, op);
+    while let Some(opid) = stack.pop() {
+        if ancestors.insert(opid) {
+            if let Some(parent) = consignment.operation(opid) {
+                stack.extend(parent_ids(consignment, parent));
+            }
+        }
+    }
+    let depths = history_depths(consignment, &ancestors);
+    let mut ancestors = ancestors.into_iter().collect::<Vec<_>>();
+    ancestors.sort_by_key(|opid| (Reverse(depths[opid]), *opid));
+
+    let mut global = GlobalState::default();
+    for opid in ancestors {
+        let Some(ancestor) = consignment.operation(opid) else {
+            continue;
+        };
+        for (ty, values) in ancestor.globals() {
+            for value in values.iter() {
+                if global.add_state(*ty, value.clone()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    global
+}
+
+fn history_depths(
+    consignment: &impl ConsignmentApi,
+    opids: &BTreeSet<OpId>,
+) -> BTreeMap<OpId, u32> {
+    let parents = |opid: OpId| -> Vec<OpId> {
+        match consignment.operation(opid) {
+            Some(op) => parent_ids(consignment, op),
+            None => vec![],
+        }
+    };
+
+    // Depth-first traversal uses explicit stack, since recursion may overflow on
+    // the long histories
+    let mut depths = BTreeMap::<OpId, u32>::new();
+    for opid in opids {
+        let mut stack = vec![*opid];
+        while let Some(opid) = stack.last().copied() {
+            if depths.contains_key(&opid) {
+                stack.pop();
+                continue;
+            }
+            let parents = parents(opid);
+            let pending = parents
+                .iter()
+                .filter(|id| !depths.contains_key(*id))
+                .copied()
+                .collect::<Vec<_>>();
+            if pending.is_empty() {
+                let depth = parents.iter().map(|id| depths[id] + 1).max();
+                depths.insert(opid, depth.unwrap_or_default());
+                stack.pop();
+            } else {
+                stack.extend(pending);
+            }
+        }
+    }
+    depths
+}
+
+/// Returns index of the preferred witness among the transactions replacing
+/// each other: the first confirmed one or, if none is confirmed, the latest
+/// one known to the resolver, falling back to the latest one.
+fn preferred_witness(
+    txids: &[Txid],
+    confirmed: impl Fn(Txid) -> bool,
+    known: impl Fn(Txid) -> bool,
+) -> usize {
+    txids
+        .iter()
+        .position(|txid| confirmed(*txid))
+        .or_else(|| txids.iter().rposition(|txid| known(*txid)))
+        .unwrap_or(txids.len() - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn witness_preference() {
+        let txids = [1u8, 2, 3].map(|no| Txid::from([no; 32]));
+        let none = |_| false;
+        let is = |no: u8| move |txid| txid == Txid::from([no; 32]);
+
+        assert_eq!(preferred_witness(&txids, none, none), 2);
+        assert_eq!(preferred_witness(&txids, none, is(2)), 1);
+        assert_eq!(preferred_witness(&txids, is(1), is(2)), 0);
+        assert_eq!(preferred_witness(&txids, is(3), none), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "testing")]
+    fn extensions() {
+        use amplify::confinement::MediumOrdMap;
+        use bp::seals::txout::CloseMethod;
+        use strict_types::{Ty, TypeSystem};
+
+        use crate::schema::{ExtensionSchema, GenesisSchema, Occurrences, StateSchema};
+        use crate::testing::FakeConsignment;
+        use crate::{Assignments, Ffv, Genesis, GenesisSeal, OpFeatures, Redeemed, Valencies};
+
+        let mut schema = SubSchema {
+            owned_types: tiny_bmap! { 1 => StateSchema::Declarative },
+            valency_types: tiny_bset! { 1 },
+            genesis: GenesisSchema {
+                valencies: tiny_bset! { 1 },
+                ..default!()
+            },
+            extensions: tiny_bmap! {
+                1 => ExtensionSchema {
+                    redeems: tiny_bset! { 1 },
+                    assignments: tiny_bmap! { 1 => Occurrences::Once },
+                    ..default!()
+                },
+            },
+            ..default!()
+        };
+        let types = bmap! { schema.genesis.metadata => Ty::UNIT };
+        schema.type_system = TypeSystem::from(MediumOrdMap::try_from(types).unwrap());
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: none!(),
+            globals: none!(),
+            assignments: none!(),
+            valencies: Valencies::from(tiny_bset! { 1 }),
+        };
+        let extension = |valency| Extension {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            extension_type: 1,
+            metadata: none!(),
+            globals: none!(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Declarative(small_vec![Assign::right(
+                    GenesisSeal::with_blinding(CloseMethod::OpretFirst, Txid::from([1; 32]), 0, 1)
+                )]),
+            }),
+            redeemed: Redeemed::from(tiny_bmap! { valency => genesis.id() }),
+            valencies: none!(),
+        };
+        let mut consignment = FakeConsignment::new(schema, genesis.clone());
+        consignment.extensions.push(extension(1));
+        let status = Validator::validate_extensions(&consignment, Chain::Regtest);
+        assert_eq!(status.failures, vec![]);
+
+        let invalid = extension(2);
+        let opid = invalid.id();
+        consignment.extensions.push(invalid);
+        let status = Validator::validate_extensions(&consignment, Chain::Regtest);
+        assert!(status.failures.contains(&Failure::NoPrevValency {
+            opid,
+            prev_id: genesis.id(),
+            valency: 2,
+        }));
+    }
+
+    #[test]
+    #[cfg(feature = "testing")]
+    fn contract_global_state() {
+        use crate::testing::FakeConsignment;
+        use crate::{Genesis, Redeemed, Valencies};
+
+        let globals = |value: Option<u64>| {
+            let mut global = GlobalState::default();
+            if let Some(value) = value {
+                global.add_state(1, RevealedData::with_counter(value)).unwrap();
+            }
+            global
+        };
+        let genesis = Genesis {
+            globals: globals(Some(1)),
+            valencies: Valencies::from(tiny_bset! { 1, 2 }),
+            ..strict_encoding::StrictDumb::strict_dumb()
+        };
+        let extension = |prev_id, valency, global, valencies| Extension {
+            globals: globals(global),
+            redeemed: Redeemed::from(tiny_bmap! { valency => prev_id }),
+            valencies: Valencies::from(valencies),
+            ..strict_encoding::StrictDumb::strict_dumb()
+        };
+        let parent = extension(genesis.id(), 1, Some(2), tiny_bset! { 3 });
+        // Operation which is not an ancestor of the validated one can't
+        // provide it with the contract global state
+        let unrelated = extension(genesis.id(), 2, Some(10), none!());
+        let child = extension(parent.id(), 3, None, none!());
+
+        let mut consignment = FakeConsignment::new(SubSchema::default(), genesis.clone());
+        consignment.extensions.extend([parent.clone(), unrelated, child.clone()]);
+
+        assert_eq!(contract_global(&consignment, OpRef::Genesis(&genesis)), globals(None));
+        assert_eq!(contract_global(&consignment, OpRef::Extension(&parent)), globals(Some(1)));
+        let mut expected = globals(Some(2));
+        expected.add_state(1, RevealedData::with_counter(1)).unwrap();
+        assert_eq!(contract_global(&consignment, OpRef::Extension(&child)), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "testing")]
+    fn resource_limits() {
+        use bp::seals::txout::CloseMethod;
+        use strict_encoding::StrictDumb;
+
+        use crate::schema::{ExtensionSchema, GenesisSchema};
+        use crate::testing::FakeConsignment;
+        use crate::{
+            Assignments, Ffv, Genesis, GenesisSeal, OpFeatures, Redeemed, SecretSeal, Valencies,
+        };
+
+        let schema = SubSchema {
+            valency_types: tiny_bset! { 1 },
+            genesis: GenesisSchema {
+                valencies: tiny_bset! { 1 },
+                ..default!()
+            },
+            extensions: tiny_bmap! {
+                1 => ExtensionSchema {
+                    redeems: tiny_bset! { 1 },
+                    ..default!()
+                },
+            },
+            ..default!()
+        };
+        let genesis = Genesis {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            schema_id: schema.schema_id(),
+            chain: Chain::Regtest,
+            metadata: none!(),
+            globals: none!(),
+            assignments: none!(),
+            valencies: Valencies::from(tiny_bset! { 1 }),
+        };
+        let mut consignment = FakeConsignment::new(schema, genesis.clone());
+        consignment.extensions.push(Extension {
+            ffv: Ffv::default(),
+            features: OpFeatures::NONE,
+            contract_id: genesis.contract_id(),
+            extension_type: 1,
+            metadata: none!(),
+            globals: none!(),
+            assignments: Assignments::from(tiny_bmap! {
+                1 => TypedAssigns::Declarative(small_vec![Assign::right(
+                    GenesisSeal::with_blinding(CloseMethod::OpretFirst, Txid::from([1; 32]), 0, 1)
+                )]),
+            }),
+            redeemed: Redeemed::from(tiny_bmap! { 1 => genesis.id() }),
+            valencies: none!(),
+        });
+
+        let failures = |config| {
+            Validator::validate_with_config(&consignment, &OfflineResolver, Chain::Regtest, config)
+                .failures
+        };
+        let default = ValidatorConfig::default();
+        assert!(failures(default.clone()).iter().all(|failure| !matches!(
+            failure,
+            Failure::ConsignmentTooManyNodes { .. } |
+                Failure::ConsignmentTooLarge { .. } |
+                Failure::ConsignmentTooDeep { .. }
+        )));
+        assert_eq!(
+            failures(ValidatorConfig {
+                max_nodes: 1,
+                ..default.clone()
+            }),
+            vec![Failure::ConsignmentTooManyNodes { count: 2, max: 1 }]
+        );
+        assert!(matches!(
+            failures(ValidatorConfig {
+                max_total_bytes: 100,
+                ..default.clone()
+            })[..],
+            [Failure::ConsignmentTooLarge { max: 100, .. }]
+        ));
+        assert_eq!(
+            failures(ValidatorConfig {
+                max_depth: 0,
+                ..default.clone()
+            }),
+            vec![Failure::ConsignmentTooDeep { depth: 1, max: 0 }]
+        );
+
+        // Consignment exceeding the limits is rejected before it gets indexed
+        let bundle_id = BundleId::from([0xAB; 32]);
+        consignment
+            .terminals
+            .insert((bundle_id, SecretSeal::strict_dumb()));
+        let failures = |config| {
+            Validator::validate_with_config(&consignment, &OfflineResolver, Chain::Regtest, config)
+                .failures
+        };
+        assert!(failures(default.clone()).contains(&Failure::BundleInvalid(bundle_id)));
+        assert_eq!(
+            failures(ValidatorConfig {
+                max_nodes: 1,
+                ..default
+            }),
+            vec![Failure::ConsignmentTooManyNodes { count: 2, max: 1 }]
+        );
+    }
 }
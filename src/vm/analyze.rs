@@ -0,0 +1,188 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Static analysis of AluVM scripts, detecting code which may validate
+//! non-deterministically across nodes.
+//!
+//! The deterministic subset consists of the instructions from the ISA
+//! extensions supported by the RGB runtime, which are explicitly declared by
+//! the library containing them (the RGB extension itself is always implied).
+//! Floating-point arithmetic is not a part of the subset: no RGB runtime
+//! declares support for it. The analysis is run as a part of the schema
+//! verification; declared extensions which are not supported by the runtime
+//! are detected by the validator even before that (see
+//! [`AluScript::unsupported_isa_ids`]).
+
+use aluvm::isa::{ArithmeticOp, Bytecode, CmpOp, Instr, MoveOp, PutOp};
+use aluvm::library::constants::{ISA_ID_ALU, ISA_ID_BPDIGEST, ISA_ID_ED25519, ISA_ID_SECP256K};
+use aluvm::library::{Lib, LibId, LibSite};
+use aluvm::reg::RegAF;
+
+use crate::vm::{AluScript, RgbIsa, ISA_ID_RGB};
+
+/// Issues detected by the static analysis of AluVM scripts.
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "camelCase")
+)]
+#[display(doc_comments)]
+pub enum ScriptIssue {
+    /// library {0} has truncated or undecodable code.
+    Undecodable(LibId),
+
+    /// instruction at {0} requires ISA extension {1} which is not declared by
+    /// the library.
+    UndeclaredIsa(LibSite, String),
+
+    /// instruction at {0} performs floating-point operation, which is not a
+    /// part of the deterministic subset.
+    FloatOp(LibSite),
+}
+
+/// Analyzes all libraries of the script, returning the list of detected
+/// issues. Empty list means that the script uses only the deterministic subset
+/// of the instructions.
+pub fn analyze(script: &AluScript) -> Vec<ScriptIssue> {
+    script.libs.values().flat_map(analyze_lib).collect()
+}
+
+/// Analyzes a single AluVM library, returning the list of detected issues.
+pub fn analyze_lib(lib: &Lib) -> Vec<ScriptIssue> {
+    let id = lib.id();
+    let mut issues = vec![];
+
+    let Ok(code) = lib.disassemble::<Instr<RgbIsa>>() else {
+        issues.push(ScriptIssue::Undecodable(id));
+        return issues;
+    };
+    let mut pos = 0u16;
+    for instr in code {
+        let site = LibSite::with(pos, id);
+        let isa = isa_id(&instr);
+        // RGB extension is always available to the RGB scripts and thus may
+        // be omitted from the list of declared extensions
+        if isa != ISA_ID_RGB && !lib.isae.iter().any(|declared| declared == isa) {
+            issues.push(ScriptIssue::UndeclaredIsa(site, isa.to_owned()));
+        }
+        if is_float(&instr) {
+            issues.push(ScriptIssue::FloatOp(site));
+        }
+        pos = pos.saturating_add(instr.byte_count());
+    }
+    issues
+}
+
+/// Detects ISA extension of the instruction from its opcode.
+fn isa_id(instr: &Instr<RgbIsa>) -> &'static str {
+    match instr.instr_byte() {
+        0b01_000_000..=0b01_000_111 => ISA_ID_BPDIGEST,
+        0b01_001_000..=0b01_001_011 => ISA_ID_SECP256K,
+        0b01_001_100..=0b01_001_111 => ISA_ID_ED25519,
+        byte if RgbIsa::instr_range().contains(&byte) => ISA_ID_RGB,
+        _ => ISA_ID_ALU,
+    }
+}
+
+fn is_float(instr: &Instr<RgbIsa>) -> bool {
+    matches!(
+        instr,
+        Instr::Put(PutOp::ClrF(..) | PutOp::PutF(..)) |
+            Instr::Move(
+                MoveOp::MovF(..) |
+                    MoveOp::DupF(..) |
+                    MoveOp::SwpF(..) |
+                    MoveOp::CnvF(..) |
+                    MoveOp::CnvAF(..) |
+                    MoveOp::CnvFA(..)
+            ) |
+            Instr::Cmp(CmpOp::GtF(..) | CmpOp::LtF(..) | CmpOp::EqF(..)) |
+            Instr::Arithmetic(
+                ArithmeticOp::AddF(..) |
+                    ArithmeticOp::SubF(..) |
+                    ArithmeticOp::MulF(..) |
+                    ArithmeticOp::DivF(..) |
+                    ArithmeticOp::Neg(RegAF::F(_), _) |
+                    ArithmeticOp::Abs(RegAF::F(_), _)
+            )
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use aluvm::isa::{ControlFlowOp, IntFlags, RoundingFlag};
+    use aluvm::library::IsaSeg;
+    use aluvm::reg::{Reg32, RegA, RegF};
+
+    use super::*;
+
+    #[test]
+    fn float_detection() {
+        let code = [
+            Instr::Arithmetic(ArithmeticOp::AddA(
+                IntFlags::default(),
+                RegA::A8,
+                Reg32::Reg0,
+                Reg32::Reg1,
+            )),
+            Instr::Arithmetic(ArithmeticOp::AddF(
+                RoundingFlag::TowardsNearest,
+                RegF::F32,
+                Reg32::Reg0,
+                Reg32::Reg1,
+            )),
+            Instr::ControlFlow(ControlFlowOp::Ret),
+        ];
+        let lib = Lib::assemble::<Instr<RgbIsa>>(&code).unwrap();
+        let issues = analyze_lib(&lib);
+        assert_eq!(issues, vec![ScriptIssue::FloatOp(LibSite::with(3, lib.id()))]);
+    }
+
+    #[test]
+    fn undeclared_isa() {
+        let code = [Instr::ControlFlow(ControlFlowOp::Ret)];
+        let mut lib = Lib::assemble::<Instr<RgbIsa>>(&code).unwrap();
+        lib.isae = IsaSeg::from_iter([ISA_ID_RGB]).unwrap();
+        let issues = analyze_lib(&lib);
+        let site = LibSite::with(0, lib.id());
+        assert_eq!(issues, vec![ScriptIssue::UndeclaredIsa(site, ISA_ID_ALU.to_owned())]);
+    }
+
+    #[test]
+    fn isa_negotiation() {
+        let code = [Instr::ControlFlow(ControlFlowOp::Ret)];
+        let mut lib = Lib::assemble::<Instr<RgbIsa>>(&code).unwrap();
+        let script = AluScript {
+            libs: confined_bmap! { lib.id() => lib.clone() },
+            entry_points: none!(),
+        };
+        assert!(script.unsupported_isa_ids().is_empty());
+
+        lib.isae = IsaSeg::from_iter([ISA_ID_ALU, ISA_ID_RGB, "FLOAT"]).unwrap();
+        let script = AluScript {
+            libs: confined_bmap! { lib.id() => lib },
+            entry_points: none!(),
+        };
+        assert_eq!(script.unsupported_isa_ids(), bset! { s!("FLOAT") });
+    }
+}
@@ -30,7 +30,7 @@ use aluvm::reg::CoreRegs;
 
 use super::opcodes::{INSTR_ISAE_FROM, INSTR_ISAE_TO};
 use super::{ContractOp, TimechainOp};
-use crate::validation::OpInfo;
+use crate::validation::OpContext;
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
 #[display(inner)]
@@ -45,17 +45,29 @@ pub enum RgbIsa {
     Fail(u8),
 }
 
+/// ISA extension id of the RGB-specific instructions.
+pub const ISA_ID_RGB: &str = "RGB";
+
+/// Registry of the ISA extensions supported by the RGB runtime: AluVM core
+/// instructions, bitcoin-specific digest and cryptographic operations
+/// compiled into the runtime, and RGB contract and timechain operations.
+pub fn supported_isa_ids() -> BTreeSet<&'static str> {
+    let mut ids = isa::Instr::<RgbIsa>::isa_ids();
+    ids.extend(RgbIsa::isa_ids());
+    ids
+}
+
 impl InstructionSet for RgbIsa {
-    type Context<'ctx> = OpInfo<'ctx>;
+    type Context<'ctx> = OpContext<'ctx>;
 
     fn isa_ids() -> BTreeSet<&'static str> {
-        bset! {"RGB"}
+        bset! {ISA_ID_RGB}
     }
 
     fn exec(&self, regs: &mut CoreRegs, site: LibSite, context: &Self::Context<'_>) -> ExecStep {
         match self {
             RgbIsa::Contract(op) => op.exec(regs, site, context),
-            RgbIsa::Timechain(op) => op.exec(regs, site, &()),
+            RgbIsa::Timechain(op) => op.exec(regs, site, context),
             RgbIsa::Fail(_) => {
                 isa::ControlFlowOp::Fail.exec(regs, site, &());
                 ExecStep::Stop
@@ -30,9 +30,12 @@ mod op_contract;
 mod op_timechain;
 mod script;
 mod runtime;
+pub mod stdlib;
+mod analyze;
 
-pub use isa::RgbIsa;
+pub use analyze::{analyze, analyze_lib, ScriptIssue};
+pub use isa::{supported_isa_ids, RgbIsa, ISA_ID_RGB};
 pub use op_contract::ContractOp;
 pub use op_timechain::TimechainOp;
-pub use runtime::AluRuntime;
+pub use runtime::{AluRuntime, ScriptMeter};
 pub use script::{AluScript, EntryPoint, LIBS_MAX_TOTAL};
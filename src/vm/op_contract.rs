@@ -25,17 +25,20 @@
 use std::collections::BTreeSet;
 use std::ops::RangeInclusive;
 
+use aluvm::data::ByteStr;
 use aluvm::isa;
 use aluvm::isa::{Bytecode, BytecodeError, ExecStep, InstructionSet};
 use aluvm::library::{CodeEofError, LibSite, Read, Write};
 use aluvm::reg::{CoreRegs, Reg16, RegA, RegS};
 use amplify::num::u4;
 use amplify::Wrapper;
+use commit_verify::{DigestExt, Sha256};
+use secp256k1_zkp::{schnorr, Message, XOnlyPublicKey, SECP256K1};
 use strict_encoding::StrictSerialize;
 
 use super::opcodes::*;
-use crate::validation::OpInfo;
-use crate::{Assign, TypedAssigns};
+use crate::validation::{MetaField, OpContext};
+use crate::{Assign, RevealedData, TypedAssigns};
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
 pub enum ContractOp {
@@ -49,16 +52,34 @@ pub enum ContractOp {
     #[display("cns      {0},a16{1}")]
     CnS(u16, Reg16),
 
-    /// Counts number of inputs (previous state entries) of the provided type
-    /// and assigns the number to the destination `a8` register.
-    #[display("cng      {0},a8{1}")]
+    /// Counts number of global state entries of the provided type in the
+    /// current operation and assigns the number to the destination `a16`
+    /// register.
+    ///
+    /// If the operation has no global state of the type sets destination to
+    /// `None`.
+    #[display("cng      {0},a16{1}")]
     CnG(u16, Reg16),
 
-    /// Counts number of inputs (previous state entries) of the provided type
+    /// Counts number of contract global state entries of the provided type
     /// and assigns the number to the destination `a16` register.
+    ///
+    /// The contract global state is defined by the operations preceding the
+    /// validated one in the contract history.
+    ///
+    /// If the contract has no global state of the type, or the contract global
+    /// state is not available to the validation, sets destination to `None`.
     #[display("cnc      {0},a16{1}")]
     CnC(u16, Reg16),
 
+    /// Counts number of all inputs (previous state entries of any type) and
+    /// assigns the number to the destination `a16` register.
+    ///
+    /// Together with `cnp` allows to check that the operation doesn't spend
+    /// state of types other than the expected ones.
+    #[display("cni      a16{0}")]
+    CnI(Reg16),
+
     /// Loads input (previous) state with type id from the first argument and
     /// index from the second argument into a register provided in the third
     /// argument.
@@ -70,6 +91,20 @@ pub enum ContractOp {
     #[display("ldp      {0},{1},{2}")]
     LdP(u16, u16, RegS),
 
+    /// Loads raw data of input (previous) structured state with type id from
+    /// the first argument and index from the second argument into a register
+    /// provided in the third argument.
+    ///
+    /// Unlike `ldp`, the data are loaded without their length prefix, which
+    /// allows to use them directly as keys or hashes.
+    ///
+    /// If the state is absent or is not a structured state sets `st0` to
+    /// `false` and terminates the program.
+    ///
+    /// If the state at the index is concealed, sets destination to `None`.
+    #[display("ldpr     {0},{1},{2}")]
+    LdPR(u16, u16, RegS),
+
     /// Loads owned structured state with type id from the first argument and
     /// index from the second argument into a register provided in the third
     /// argument.
@@ -88,10 +123,23 @@ pub enum ContractOp {
     /// If the state is absent or is not a fungible state sets `st0` to
     /// `false` and terminates the program.
     ///
-    /// If the state at the index is concealed, sets destination to `None`.
+    /// If the state at the index is concealed, sets destination to `None`. If
+    /// the state value does not fit into 64 bits, fails the verification.
     #[display("ldf      {0},{1},a64{2}")]
     LdF(u16, u16, Reg16),
 
+    /// Loads input (previous) fungible state with type id from the first
+    /// argument and index from the second argument into `a64` register
+    /// provided in the third argument.
+    ///
+    /// If the state is absent or is not a fungible state sets `st0` to
+    /// `false` and terminates the program.
+    ///
+    /// If the state at the index is concealed, sets destination to `None`. If
+    /// the state value does not fit into 64 bits, fails the verification.
+    #[display("ldpf     {0},{1},a64{2}")]
+    LdPF(u16, u16, Reg16),
+
     /// Loads global state from the current operation with type id from the
     /// first argument and index from the second argument into a register
     /// provided in the third argument.
@@ -102,7 +150,8 @@ pub enum ContractOp {
 
     /// Loads part of the contract global state with type id from the first
     /// argument at the depth from the second argument into a register
-    /// provided in the third argument.
+    /// provided in the third argument. Depth zero corresponds to the most
+    /// recent state entry.
     ///
     /// If the state is absent or concealed sets destination to `None`.
     /// Does not modify content of `st0` register.
@@ -116,17 +165,88 @@ pub enum ContractOp {
     #[display("ldm      {0}")]
     LdM(RegS),
 
+    /// Loads metadata field with the number from the first argument into `a64`
+    /// register provided in the second argument. Metadata fields are the
+    /// fields of the metadata structure or tuple; metadata of other types
+    /// has a single field.
+    ///
+    /// Unsigned and signed integer fields are coerced into 64-bit unsigned
+    /// integer. If the field is absent, has other type, or its value is
+    /// negative or does not fit into 64 bits, sets `st0` to `false` and
+    /// terminates the program.
+    #[display("ldmi     {0},a64{1}")]
+    LdMI(u8, Reg16),
+
+    /// Loads metadata field with the number from the first argument into a
+    /// string register provided in the second argument (see `ldmi` for the
+    /// definition of metadata fields).
+    ///
+    /// Byte strings are loaded without their length prefix; integer fields are
+    /// coerced into their little-endian representation. If the field is
+    /// absent or has other type, sets `st0` to `false` and terminates the
+    /// program.
+    #[display("ldms     {0},{1}")]
+    LdMS(u8, RegS),
+
+    /// Loads value of the schema constant with type id from the first argument
+    /// into a register provided in the second argument.
+    ///
+    /// If the constant is absent sets `st0` to `false` and terminates the
+    /// program.
+    #[display("ldk      {0},{1}")]
+    LdK(u16, RegS),
+
     /// Verify sum of pedersen commitments from inputs and outputs.
     ///
     /// The only argument specifies owned state type for the sum operation. If
     /// this state does not exists, either inputs or outputs does not have
-    /// any data for the state, or the state is not
-    /// of `FungibleState::Bits64` fails the verification.
+    /// any data for the state, or the state is not fungible fails the
+    /// verification.
     ///
     /// If verification succeeds, doesn't changes `st0` value; otherwise sets it
     /// to `false`.
     #[display("pcvs     {0}")]
     PcVs(u16),
+
+    /// Verifies BIP340 Schnorr signature.
+    ///
+    /// The first argument is a register containing 32-byte x-only public key,
+    /// the second - a register with 64-byte signature. The signed message is
+    /// a SHA256 hash of the data from the register in the third argument
+    /// (which may be empty), prefixed with the signing id of the current
+    /// operation if the fourth argument is `true`. The signing id is the
+    /// operation id computed with empty metadata, such that the signature can
+    /// be placed into the operation metadata.
+    ///
+    /// If the key or signature registers are empty or contain invalid data, or
+    /// if the signature is not valid, sets `st0` to `false` and terminates the
+    /// program.
+    #[display("vfsig    {0},{1},{2},{3}")]
+    VfSig(RegS, RegS, RegS, bool),
+
+    /// Computes SHA256 hash of the data from the string register in the first
+    /// argument and puts the 32-byte hash value into the string register
+    /// provided in the second argument.
+    ///
+    /// Unlike AluVM `sha2` instruction, which puts the result into `r256`
+    /// register, this allows further processing of the hash value with string
+    /// operations, like concatenation and comparison with the state data.
+    ///
+    /// If the source register is empty sets `st0` to `false` and terminates
+    /// the program.
+    #[display("sha      {0},{1}")]
+    Sha(RegS, RegS),
+
+    /// Computes BIP340 tagged hash (`SHA256(SHA256(tag) || SHA256(tag) ||
+    /// data)`) using the tag from the string register in the first argument
+    /// and the data from the string register in the second argument, and
+    /// puts the 32-byte hash value into the string register provided in the
+    /// third argument.
+    ///
+    /// If either the tag or the source register is empty sets `st0` to
+    /// `false` and terminates the program.
+    #[display("tsha     {0},{1},{2}")]
+    TSha(RegS, RegS, RegS),
     /*
     /// Verifies corrected sum of pedersen commitments adding a value taken from `RegR` to the list
     /// of inputs (negatives).
@@ -138,7 +258,7 @@ pub enum ContractOp {
 }
 
 impl InstructionSet for ContractOp {
-    type Context<'ctx> = OpInfo<'ctx>;
+    type Context<'ctx> = OpContext<'ctx>;
 
     fn isa_ids() -> BTreeSet<&'static str> { none!() }
 
@@ -164,9 +284,26 @@ impl InstructionSet for ContractOp {
             ContractOp::CnG(state_type, reg) => {
                 regs.set(RegA::A16, *reg, context.global.get(state_type).map(|a| a.len_u16()));
             }
-            ContractOp::CnC(_state_type, _reg) => {
-                // TODO: implement global contract state
-                fail!()
+            ContractOp::CnC(state_type, reg) => {
+                regs.set(
+                    RegA::A16,
+                    *reg,
+                    context
+                        .contract_global
+                        .and_then(|global| global.get(state_type))
+                        .map(|a| a.len_u16()),
+                );
+            }
+            ContractOp::CnI(reg) => {
+                let count = context
+                    .prev_state
+                    .values()
+                    .map(|a| a.len_u16() as usize)
+                    .sum::<usize>();
+                let Ok(count) = u16::try_from(count) else {
+                    fail!()
+                };
+                regs.set(RegA::A16, *reg, count);
             }
             ContractOp::LdP(state_type, index, reg) => {
                 let Some(Ok(state)) = context
@@ -181,6 +318,15 @@ impl InstructionSet for ContractOp {
                 });
                 regs.set_s(*reg, state);
             }
+            ContractOp::LdPR(state_type, index, reg) => {
+                let Some(Ok(state)) = context
+                    .prev_state
+                    .get(state_type)
+                    .map(|a| a.as_structured_state_at(*index)) else {
+                    fail!()
+                };
+                regs.set_s(*reg, state.map(RevealedData::as_inner));
+            }
             ContractOp::LdS(state_type, index, reg) => {
                 let Some(Ok(state)) = context
                     .owned_state
@@ -201,7 +347,22 @@ impl InstructionSet for ContractOp {
                     .map(|a| a.into_fungible_state_at(*index)) else {
                     fail!()
                 };
-                regs.set(RegA::A64, *reg, state.map(|s| s.value.as_u64()));
+                let Ok(state) = state.map(|s| u64::try_from(s.value)).transpose() else {
+                    fail!()
+                };
+                regs.set(RegA::A64, *reg, state);
+            }
+            ContractOp::LdPF(state_type, index, reg) => {
+                let Some(Ok(state)) = context
+                    .prev_state
+                    .get(state_type)
+                    .map(|a| a.as_fungible_state_at(*index)) else {
+                    fail!()
+                };
+                let Ok(state) = state.map(|s| u64::try_from(s.value)).transpose() else {
+                    fail!()
+                };
+                regs.set(RegA::A64, *reg, state);
             }
             ContractOp::LdG(state_type, index, reg) => {
                 let Some(state) = context
@@ -212,13 +373,40 @@ impl InstructionSet for ContractOp {
                 };
                 regs.set_s(*reg, Some(state.as_inner()));
             }
-            ContractOp::LdC(_state_type, _index, _reg) => {
-                // TODO: implement global contract state
-                fail!()
+            ContractOp::LdC(state_type, depth, reg) => {
+                let state = context
+                    .contract_global
+                    .and_then(|global| global.get(state_type))
+                    .and_then(|a| a.get(*depth as usize));
+                regs.set_s(*reg, state.map(RevealedData::as_inner));
             }
             ContractOp::LdM(reg) => {
                 regs.set_s(*reg, Some(context.metadata));
             }
+            ContractOp::LdMI(field, reg) => {
+                let Some(value) = context
+                    .metadata_fields
+                    .get(*field as usize)
+                    .and_then(MetaField::to_u64) else {
+                    fail!()
+                };
+                regs.set(RegA::A64, *reg, value);
+            }
+            ContractOp::LdMS(field, reg) => {
+                let Some(value) = context
+                    .metadata_fields
+                    .get(*field as usize)
+                    .and_then(MetaField::as_bytes) else {
+                    fail!()
+                };
+                regs.set_s(*reg, Some(ByteStr::with(value)));
+            }
+            ContractOp::LdK(ty, reg) => {
+                let Some(constant) = context.constants.get(ty) else {
+                    fail!()
+                };
+                regs.set_s(*reg, Some(&constant.value));
+            }
 
             ContractOp::PcVs(state_type) => {
                 if !context.prev_state.contains_key(state_type) &&
@@ -260,6 +448,47 @@ impl InstructionSet for ContractOp {
                 }
             }
 
+            ContractOp::VfSig(key_reg, sig_reg, msg_reg, with_opid) => {
+                let Some(Ok(key)) = regs
+                    .get_s(*key_reg)
+                    .map(|s| XOnlyPublicKey::from_slice(s.as_ref())) else {
+                    fail!()
+                };
+                let Some(Ok(sig)) = regs
+                    .get_s(*sig_reg)
+                    .map(|s| schnorr::Signature::from_slice(s.as_ref())) else {
+                    fail!()
+                };
+                let mut engine = Sha256::default();
+                if *with_opid {
+                    engine.input_raw(context.signing_id.as_slice());
+                }
+                if let Some(data) = regs.get_s(*msg_reg) {
+                    engine.input_raw(data.as_ref());
+                }
+                let msg = Message::from_slice(&engine.finish()).expect("hash has 32 bytes");
+                if SECP256K1.verify_schnorr(&sig, &msg, &key).is_err() {
+                    fail!()
+                }
+            }
+
+            ContractOp::Sha(src_reg, dst_reg) => {
+                let Some(data) = regs.get_s(*src_reg) else {
+                    fail!()
+                };
+                let mut engine = Sha256::default();
+                engine.input_raw(data.as_ref());
+                regs.set_s(*dst_reg, Some(ByteStr::with(engine.finish())));
+            }
+            ContractOp::TSha(tag_reg, src_reg, dst_reg) => {
+                let (Some(tag), Some(data)) = (regs.get_s(*tag_reg), regs.get_s(*src_reg)) else {
+                    fail!()
+                };
+                let mut engine = Sha256::from_tag(tag.as_ref());
+                engine.input_raw(data.as_ref());
+                regs.set_s(*dst_reg, Some(ByteStr::with(engine.finish())));
+            }
+
             // All other future unsupported operations, which must set `st0` to `false`.
             _ => fail!(),
         }
@@ -274,21 +503,29 @@ impl Bytecode for ContractOp {
             ContractOp::CnS(_, _) |
             ContractOp::CnG(_, _) |
             ContractOp::CnC(_, _) => 3,
+            ContractOp::CnI(_) => 1,
 
             ContractOp::LdP(_, _, _) |
+            ContractOp::LdPR(_, _, _) |
             ContractOp::LdS(_, _, _) |
             ContractOp::LdF(_, _, _) |
+            ContractOp::LdPF(_, _, _) |
             ContractOp::LdC(_, _, _) => 5,
             ContractOp::LdG(_, _, _) => 4,
             ContractOp::LdM(_) => 1,
+            ContractOp::LdMI(_, _) | ContractOp::LdMS(_, _) => 2,
+            ContractOp::LdK(_, _) => 3,
 
             ContractOp::PcVs(_) => 2,
+            ContractOp::VfSig(_, _, _, _) => 2,
+            ContractOp::Sha(_, _) => 1,
+            ContractOp::TSha(_, _, _) => 2,
 
             ContractOp::Fail(_) => 0,
         }
     }
 
-    fn instr_range() -> RangeInclusive<u8> { INSTR_CNP..=0b11_001_111 }
+    fn instr_range() -> RangeInclusive<u8> { INSTR_CNP..=0b11_011_111 }
 
     fn instr_byte(&self) -> u8 {
         match self {
@@ -296,15 +533,24 @@ impl Bytecode for ContractOp {
             ContractOp::CnS(_, _) => INSTR_CNS,
             ContractOp::CnG(_, _) => INSTR_CNG,
             ContractOp::CnC(_, _) => INSTR_CNC,
+            ContractOp::CnI(_) => INSTR_CNI,
 
             ContractOp::LdP(_, _, _) => INSTR_LDP,
+            ContractOp::LdPR(_, _, _) => INSTR_LDPR,
             ContractOp::LdS(_, _, _) => INSTR_LDS,
             ContractOp::LdF(_, _, _) => INSTR_LDF,
+            ContractOp::LdPF(_, _, _) => INSTR_LDPF,
             ContractOp::LdG(_, _, _) => INSTR_LDG,
             ContractOp::LdC(_, _, _) => INSTR_LDC,
             ContractOp::LdM(_) => INSTR_LDM,
+            ContractOp::LdMI(_, _) => INSTR_LDMI,
+            ContractOp::LdMS(_, _) => INSTR_LDMS,
+            ContractOp::LdK(_, _) => INSTR_LDK,
 
             ContractOp::PcVs(_) => INSTR_PCVS,
+            ContractOp::VfSig(_, _, _, _) => INSTR_VFSIG,
+            ContractOp::Sha(_, _) => INSTR_SHA,
+            ContractOp::TSha(_, _, _) => INSTR_TSHA,
 
             ContractOp::Fail(other) => *other,
         }
@@ -333,12 +579,22 @@ impl Bytecode for ContractOp {
                 writer.write_u4(reg)?;
                 writer.write_u4(u4::ZERO)?;
             }
+            ContractOp::CnI(reg) => {
+                writer.write_u4(reg)?;
+                writer.write_u4(u4::ZERO)?;
+            }
             ContractOp::LdP(state_type, index, reg) => {
                 writer.write_u16(*state_type)?;
                 writer.write_u16(*index)?;
                 writer.write_u4(reg)?;
                 writer.write_u4(u4::ZERO)?;
             }
+            ContractOp::LdPR(state_type, index, reg) => {
+                writer.write_u16(*state_type)?;
+                writer.write_u16(*index)?;
+                writer.write_u4(reg)?;
+                writer.write_u4(u4::ZERO)?;
+            }
             ContractOp::LdS(state_type, index, reg) => {
                 writer.write_u16(*state_type)?;
                 writer.write_u16(*index)?;
@@ -351,6 +607,12 @@ impl Bytecode for ContractOp {
                 writer.write_u4(reg)?;
                 writer.write_u4(u4::ZERO)?;
             }
+            ContractOp::LdPF(state_type, index, reg) => {
+                writer.write_u16(*state_type)?;
+                writer.write_u16(*index)?;
+                writer.write_u4(reg)?;
+                writer.write_u4(u4::ZERO)?;
+            }
             ContractOp::LdG(state_type, index, reg) => {
                 writer.write_u16(*state_type)?;
                 writer.write_u8(*index)?;
@@ -367,8 +629,39 @@ impl Bytecode for ContractOp {
                 writer.write_u4(reg)?;
                 writer.write_u4(u4::ZERO)?;
             }
+            ContractOp::LdMI(field, reg) => {
+                writer.write_u8(*field)?;
+                writer.write_u4(reg)?;
+                writer.write_u4(u4::ZERO)?;
+            }
+            ContractOp::LdMS(field, reg) => {
+                writer.write_u8(*field)?;
+                writer.write_u4(reg)?;
+                writer.write_u4(u4::ZERO)?;
+            }
+            ContractOp::LdK(ty, reg) => {
+                writer.write_u16(*ty)?;
+                writer.write_u4(reg)?;
+                writer.write_u4(u4::ZERO)?;
+            }
 
             ContractOp::PcVs(state_type) => writer.write_u16(*state_type)?,
+            ContractOp::VfSig(key_reg, sig_reg, msg_reg, with_opid) => {
+                writer.write_u4(key_reg)?;
+                writer.write_u4(sig_reg)?;
+                writer.write_u4(msg_reg)?;
+                writer.write_u4(if *with_opid { u4::ONE } else { u4::ZERO })?;
+            }
+            ContractOp::Sha(src_reg, dst_reg) => {
+                writer.write_u4(src_reg)?;
+                writer.write_u4(dst_reg)?;
+            }
+            ContractOp::TSha(tag_reg, src_reg, dst_reg) => {
+                writer.write_u4(tag_reg)?;
+                writer.write_u4(src_reg)?;
+                writer.write_u4(dst_reg)?;
+                writer.write_u4(u4::ZERO)?;
+            }
 
             ContractOp::Fail(_) => {}
         }
@@ -401,6 +694,11 @@ impl Bytecode for ContractOp {
                 reader.read_u4()?; // Discard garbage bits
                 i
             }
+            INSTR_CNI => {
+                let i = Self::CnI(reader.read_u4()?.into());
+                reader.read_u4()?; // Discard garbage bits
+                i
+            }
 
             INSTR_LDP => {
                 let i = Self::LdP(reader.read_u16()?, reader.read_u16()?, reader.read_u4()?.into());
@@ -417,6 +715,18 @@ impl Bytecode for ContractOp {
                 reader.read_u4()?; // Discard garbage bits
                 i
             }
+            INSTR_LDPR => {
+                let i =
+                    Self::LdPR(reader.read_u16()?, reader.read_u16()?, reader.read_u4()?.into());
+                reader.read_u4()?; // Discard garbage bits
+                i
+            }
+            INSTR_LDPF => {
+                let i =
+                    Self::LdPF(reader.read_u16()?, reader.read_u16()?, reader.read_u4()?.into());
+                reader.read_u4()?; // Discard garbage bits
+                i
+            }
             INSTR_LDG => {
                 let i = Self::LdG(reader.read_u16()?, reader.read_u8()?, reader.read_u4()?.into());
                 reader.read_u4()?; // Discard garbage bits
@@ -432,10 +742,195 @@ impl Bytecode for ContractOp {
                 reader.read_u4()?; // Discard garbage bits
                 i
             }
+            INSTR_LDMI => {
+                let i = Self::LdMI(reader.read_u8()?, reader.read_u4()?.into());
+                reader.read_u4()?; // Discard garbage bits
+                i
+            }
+            INSTR_LDMS => {
+                let i = Self::LdMS(reader.read_u8()?, reader.read_u4()?.into());
+                reader.read_u4()?; // Discard garbage bits
+                i
+            }
+            INSTR_LDK => {
+                let i = Self::LdK(reader.read_u16()?, reader.read_u4()?.into());
+                reader.read_u4()?; // Discard garbage bits
+                i
+            }
 
             INSTR_PCVS => Self::PcVs(reader.read_u16()?),
+            INSTR_VFSIG => Self::VfSig(
+                reader.read_u4()?.into(),
+                reader.read_u4()?.into(),
+                reader.read_u4()?.into(),
+                reader.read_u4()? != u4::ZERO,
+            ),
+            INSTR_SHA => Self::Sha(reader.read_u4()?.into(), reader.read_u4()?.into()),
+            INSTR_TSHA => {
+                let i = Self::TSha(
+                    reader.read_u4()?.into(),
+                    reader.read_u4()?.into(),
+                    reader.read_u4()?.into(),
+                );
+                reader.read_u4()?; // Discard garbage bits
+                i
+            }
 
             x => Self::Fail(x),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use aluvm::isa::Instr;
+    use aluvm::library::{Lib, LibId};
+    use aluvm::reg::RegS;
+    use amplify::confinement::SmallBlob;
+    use bp::seals::txout::CloseMethod;
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::schema::SchemaId;
+    use crate::vm::RgbIsa;
+    use crate::{
+        Assignments, BlindingFactor, GlobalState, GraphSeal, OpFullType, OpId, RevealedValue,
+        Valencies, VoidState,
+    };
+
+    #[test]
+    fn bytecode_roundtrip() {
+        let code = [
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::PcVs(0x1234))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::VfSig(
+                RegS::from(1u8),
+                RegS::from(2u8),
+                RegS::from(3u8),
+                true,
+            ))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::VfSig(
+                RegS::from(4u8),
+                RegS::from(5u8),
+                RegS::from(6u8),
+                false,
+            ))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::Sha(
+                RegS::from(7u8),
+                RegS::from(8u8),
+            ))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::TSha(
+                RegS::from(9u8),
+                RegS::from(10u8),
+                RegS::from(11u8),
+            ))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::LdK(0x0102, RegS::from(12u8)))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::CnI(Reg16::Reg3))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::LdPF(0x0304, 5, Reg16::Reg4))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::LdMI(6, Reg16::Reg5))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::LdMS(7, RegS::from(13u8)))),
+            Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::LdPR(0x0506, 8, RegS::from(14u8)))),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        assert_eq!(lib.disassemble::<Instr<RgbIsa>>().unwrap(), code);
+    }
+
+    #[test]
+    fn parent_state() {
+        let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 0);
+        let blinding = BlindingFactor::try_from([1u8; 32]).unwrap();
+        let value = |amount| Assign::revealed(seal, RevealedValue::with(amount, blinding));
+        let prev_state = Assignments::from(tiny_bmap! {
+            1 => TypedAssigns::Fungible(small_vec![value(10), value(20)]),
+            2 => TypedAssigns::Declarative(small_vec![Assign::revealed(seal, VoidState::default())])
+        });
+        let (metadata, migrated, constants) = (SmallBlob::default(), none!(), none!());
+        let (owned_state, valencies, global) =
+            (Assignments::<GraphSeal>::default(), Valencies::default(), GlobalState::default());
+        let context = OpContext {
+            schema_id: SchemaId::strict_dumb(),
+            subschema: false,
+            id: OpId::strict_dumb(),
+            signing_id: OpId::strict_dumb(),
+            ty: OpFullType::StateTransition(1),
+            witness_txid: None,
+            witness_mtp: None,
+            metadata: &metadata,
+            metadata_fields: &[],
+            prev_state: &prev_state,
+            migrated: &migrated,
+            owned_state: (&owned_state).into(),
+            redeemed: &valencies,
+            valencies: &valencies,
+            global: &global,
+            contract_global: None,
+            constants: &constants,
+        };
+        let exec = |op: ContractOp, reg: RegA| {
+            let mut regs = CoreRegs::default();
+            let step = op.exec(&mut regs, LibSite::with(0, LibId::strict_dumb()), &context);
+            let value = regs.get(reg, Reg16::Reg0).map(u64::from);
+            (step == ExecStep::Next, value)
+        };
+
+        assert_eq!(exec(ContractOp::CnI(Reg16::Reg0), RegA::A16), (true, Some(3)));
+        assert_eq!(exec(ContractOp::CnP(1, Reg16::Reg0), RegA::A16), (true, Some(2)));
+        assert_eq!(exec(ContractOp::LdPF(1, 1, Reg16::Reg0), RegA::A64), (true, Some(20)));
+        assert_eq!(exec(ContractOp::LdPF(1, 2, Reg16::Reg0), RegA::A64), (false, None));
+        assert_eq!(exec(ContractOp::LdPF(2, 0, Reg16::Reg0), RegA::A64), (false, None));
+        assert_eq!(exec(ContractOp::LdPF(3, 0, Reg16::Reg0), RegA::A64), (false, None));
+    }
+
+    #[test]
+    fn metadata_fields() {
+        let (metadata, prev_state, migrated, constants) =
+            (SmallBlob::default(), Assignments::default(), none!(), none!());
+        let (owned_state, valencies, global) =
+            (Assignments::<GraphSeal>::default(), Valencies::default(), GlobalState::default());
+        let metadata_fields = [
+            MetaField::Uint(&[0x34, 0x12]),
+            MetaField::Int(&[0xFF]),
+            MetaField::Bytes(b"ticker"),
+            MetaField::Other,
+        ];
+        let context = OpContext {
+            schema_id: SchemaId::strict_dumb(),
+            subschema: false,
+            id: OpId::strict_dumb(),
+            signing_id: OpId::strict_dumb(),
+            ty: OpFullType::StateTransition(1),
+            witness_txid: None,
+            witness_mtp: None,
+            metadata: &metadata,
+            metadata_fields: &metadata_fields,
+            prev_state: &prev_state,
+            migrated: &migrated,
+            owned_state: (&owned_state).into(),
+            redeemed: &valencies,
+            valencies: &valencies,
+            global: &global,
+            contract_global: None,
+            constants: &constants,
+        };
+        let exec = |op: ContractOp| {
+            let mut regs = CoreRegs::default();
+            let step = op.exec(&mut regs, LibSite::with(0, LibId::strict_dumb()), &context);
+            let value = regs.get(RegA::A64, Reg16::Reg0).map(u64::from);
+            let data = regs.get_s(RegS::from(0u8)).map(|s| s.as_ref().to_vec());
+            (step == ExecStep::Next, value, data)
+        };
+
+        assert_eq!(exec(ContractOp::LdMI(0, Reg16::Reg0)), (true, Some(0x1234), None));
+        assert_eq!(exec(ContractOp::LdMI(1, Reg16::Reg0)), (false, None, None));
+        assert_eq!(exec(ContractOp::LdMI(2, Reg16::Reg0)), (false, None, None));
+        assert_eq!(exec(ContractOp::LdMI(4, Reg16::Reg0)), (false, None, None));
+        assert_eq!(
+            exec(ContractOp::LdMS(0, RegS::from(0u8))),
+            (true, None, Some(vec![0x34, 0x12]))
+        );
+        assert_eq!(
+            exec(ContractOp::LdMS(2, RegS::from(0u8))),
+            (true, None, Some(b"ticker".to_vec()))
+        );
+        assert_eq!(exec(ContractOp::LdMS(3, RegS::from(0u8))), (false, None, None));
+    }
+}
@@ -20,49 +20,196 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#![allow(clippy::unusual_byte_groupings)]
+
 use std::collections::BTreeSet;
 use std::ops::RangeInclusive;
 
+use aluvm::isa;
 use aluvm::isa::{Bytecode, BytecodeError, ExecStep, InstructionSet};
 use aluvm::library::{CodeEofError, LibSite, Read, Write};
 use aluvm::reg::CoreRegs;
 
-use super::opcodes::{INSTR_ISAE_FROM, INSTR_ISAE_TO};
+use super::opcodes::{INSTR_MTPGE, INSTR_MTPLT};
+use crate::validation::OpContext;
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
-#[display(inner)]
 #[non_exhaustive]
 pub enum TimechainOp {
-    Fail,
+    /// Compares median time past of the block mining the witness transaction
+    /// with the UNIX timestamp from the schema constant with type id from the
+    /// argument, setting `st0` to `true` if the median time past is greater
+    /// or equal to the timestamp and to `false` otherwise.
+    ///
+    /// The constant must be a strict-encoded 32-bit unsigned integer, like the
+    /// timestamps in bitcoin block headers. If the constant is absent or has a
+    /// different size, or if the median time past is not known (for genesis,
+    /// state extensions or if the witness transactions are not validated),
+    /// sets `st0` to `false` and terminates the program.
+    #[display("mtpge    {0}")]
+    MtpGe(u16),
+
+    /// Compares median time past of the block mining the witness transaction
+    /// with the UNIX timestamp from the schema constant with type id from the
+    /// argument, setting `st0` to `true` if the median time past is less than
+    /// the timestamp and to `false` otherwise.
+    ///
+    /// Fails under the same conditions as [`TimechainOp::MtpGe`].
+    #[display("mtplt    {0}")]
+    MtpLt(u16),
+
+    /// All other future unsupported operations, which must set `st0` to
+    /// `false`.
+    #[display("fail     {0}")]
+    Fail(u8),
 }
 
 impl InstructionSet for TimechainOp {
-    type Context<'ctx> = ();
+    type Context<'ctx> = OpContext<'ctx>;
 
     fn isa_ids() -> BTreeSet<&'static str> { none!() }
 
-    fn exec(&self, _regs: &mut CoreRegs, _site: LibSite, _context: &Self::Context<'_>) -> ExecStep {
-        unreachable!()
+    fn exec(&self, regs: &mut CoreRegs, site: LibSite, context: &Self::Context<'_>) -> ExecStep {
+        macro_rules! fail {
+            () => {{
+                isa::ControlFlowOp::Fail.exec(regs, site, &());
+                return ExecStep::Stop;
+            }};
+        }
+
+        let (TimechainOp::MtpGe(const_type) | TimechainOp::MtpLt(const_type)) = self else {
+            fail!()
+        };
+        let Some(mtp) = context.witness_mtp else {
+            fail!()
+        };
+        let Some(Ok(bytes)) = context
+            .constants
+            .get(const_type)
+            .map(|constant| <[u8; 4]>::try_from(constant.value.as_slice()))
+        else {
+            fail!()
+        };
+        let timestamp = u32::from_le_bytes(bytes) as i64;
+        let flag = match self {
+            TimechainOp::MtpGe(_) => mtp >= timestamp,
+            _ => mtp < timestamp,
+        };
+        // AluVM doesn't expose `st0` for writing, so we invert it when needed
+        if regs.status() != flag {
+            isa::CmpOp::StInv.exec(regs, site, &());
+        }
+        ExecStep::Next
     }
 }
 
 impl Bytecode for TimechainOp {
-    fn byte_count(&self) -> u16 { 0 }
+    fn byte_count(&self) -> u16 {
+        match self {
+            TimechainOp::MtpGe(_) | TimechainOp::MtpLt(_) => 2,
+            TimechainOp::Fail(_) => 0,
+        }
+    }
 
-    fn instr_range() -> RangeInclusive<u8> { INSTR_ISAE_FROM..=INSTR_ISAE_TO }
+    fn instr_range() -> RangeInclusive<u8> { INSTR_MTPGE..=0b11_100_111 }
 
-    fn instr_byte(&self) -> u8 { unreachable!() }
+    fn instr_byte(&self) -> u8 {
+        match self {
+            TimechainOp::MtpGe(_) => INSTR_MTPGE,
+            TimechainOp::MtpLt(_) => INSTR_MTPLT,
+            TimechainOp::Fail(other) => *other,
+        }
+    }
 
-    fn encode_args<W>(&self, _writer: &mut W) -> Result<(), BytecodeError>
+    fn encode_args<W>(&self, writer: &mut W) -> Result<(), BytecodeError>
     where W: Write {
-        unreachable!()
+        match self {
+            TimechainOp::MtpGe(const_type) | TimechainOp::MtpLt(const_type) => {
+                writer.write_u16(*const_type)?
+            }
+            TimechainOp::Fail(_) => {}
+        }
+        Ok(())
     }
 
-    fn decode<R>(_reader: &mut R) -> Result<Self, CodeEofError>
+    fn decode<R>(reader: &mut R) -> Result<Self, CodeEofError>
     where
         Self: Sized,
         R: Read,
     {
-        unreachable!()
+        Ok(match reader.read_u8()? {
+            INSTR_MTPGE => Self::MtpGe(reader.read_u16()?),
+            INSTR_MTPLT => Self::MtpLt(reader.read_u16()?),
+            x => Self::Fail(x),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aluvm::library::{Lib, LibId};
+    use amplify::confinement::{SmallBlob, TinyOrdMap};
+    use strict_encoding::StrictDumb;
+    use strict_types::SemId;
+
+    use super::*;
+    use crate::schema::SchemaId;
+    use crate::vm::RgbIsa;
+    use crate::{Assignments, GlobalState, GraphSeal, OpFullType, OpId, SchemaConstant, Valencies};
+
+    #[test]
+    fn bytecode_roundtrip() {
+        let code = [
+            isa::Instr::ExtensionCodes(RgbIsa::Timechain(TimechainOp::MtpGe(0x1234))),
+            isa::Instr::ExtensionCodes(RgbIsa::Timechain(TimechainOp::MtpLt(0x0102))),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        assert_eq!(lib.disassemble::<isa::Instr<RgbIsa>>().unwrap(), code);
+    }
+
+    #[test]
+    fn mtp_comparison() {
+        let constants = TinyOrdMap::try_from(bmap! {
+            1 => SchemaConstant {
+                sem_id: SemId::strict_dumb(),
+                value: SmallBlob::try_from(1_000u32.to_le_bytes().to_vec()).unwrap(),
+            },
+        })
+        .unwrap();
+        let (metadata, prev_state, migrated) =
+            (SmallBlob::default(), Assignments::default(), none!());
+        let (owned_state, valencies, global) =
+            (Assignments::<GraphSeal>::default(), Valencies::default(), GlobalState::default());
+        let exec = |op: TimechainOp, witness_mtp: Option<i64>| {
+            let context = OpContext {
+                schema_id: SchemaId::strict_dumb(),
+                subschema: false,
+                id: OpId::strict_dumb(),
+                signing_id: OpId::strict_dumb(),
+                ty: OpFullType::StateTransition(1),
+                witness_txid: None,
+                witness_mtp,
+                metadata: &metadata,
+                metadata_fields: &[],
+                prev_state: &prev_state,
+                migrated: &migrated,
+                owned_state: (&owned_state).into(),
+                redeemed: &valencies,
+                valencies: &valencies,
+                global: &global,
+                contract_global: None,
+                constants: &constants,
+            };
+            let mut regs = CoreRegs::default();
+            let step = op.exec(&mut regs, LibSite::with(0, LibId::strict_dumb()), &context);
+            (step == ExecStep::Next, regs.status())
+        };
+
+        assert_eq!(exec(TimechainOp::MtpGe(1), Some(1_000)), (true, true));
+        assert_eq!(exec(TimechainOp::MtpGe(1), Some(999)), (true, false));
+        assert_eq!(exec(TimechainOp::MtpLt(1), Some(999)), (true, true));
+        assert_eq!(exec(TimechainOp::MtpLt(1), Some(1_000)), (true, false));
+        assert_eq!(exec(TimechainOp::MtpGe(1), None), (false, false));
+        assert_eq!(exec(TimechainOp::MtpGe(2), Some(1_000)), (false, false));
     }
 }
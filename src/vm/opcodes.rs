@@ -31,24 +31,36 @@ pub const INSTR_CNC: u8 = 0b11_000_011;
 pub const INSTR_LDP: u8 = 0b11_000_100;
 pub const INSTR_LDS: u8 = 0b11_000_101;
 pub const INSTR_LDF: u8 = 0b11_000_110;
-// Reserved 0b11_000_111
+pub const INSTR_LDPF: u8 = 0b11_000_111;
 
 pub const INSTR_LDG: u8 = 0b11_001_000;
 pub const INSTR_LDC: u8 = 0b11_001_001;
 pub const INSTR_LDM: u8 = 0b11_001_010;
-// Reserved 0b11_001_111
+pub const INSTR_LDK: u8 = 0b11_001_011;
+pub const INSTR_CNI: u8 = 0b11_001_100;
+pub const INSTR_LDMI: u8 = 0b11_001_101;
+pub const INSTR_LDMS: u8 = 0b11_001_110;
+pub const INSTR_LDPR: u8 = 0b11_001_111;
 
 pub const INSTR_PCVS: u8 = 0b11_010_000;
 pub const INSTR_PCCS: u8 = 0b11_010_001;
-// Reserved 0b11_010_010
+pub const INSTR_VFSIG: u8 = 0b11_010_010;
 // Reserved 0b11_010_011
 
+pub const INSTR_SHA: u8 = 0b11_011_000;
+pub const INSTR_TSHA: u8 = 0b11_011_001;
+// Reserved 0b11_011_010
+// Reserved 0b11_011_011
+
 // Reserved 0b11_011_100
 // Reserved 0b11_011_101
 // Reserved 0b11_011_110
 // Reserved 0b11_011_111
 
 // TIMECHAIN:
+pub const INSTR_MTPGE: u8 = 0b11_100_000;
+pub const INSTR_MTPLT: u8 = 0b11_100_001;
+// Reserved 0b11_100_010..=0b11_100_111
 
 // DATA:
 
@@ -20,65 +20,229 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cell::Cell;
 use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
 
 use aluvm::data::{ByteStr, Number};
-use aluvm::reg::{Reg32, RegA, RegAFR, RegS};
-use aluvm::Vm;
+use aluvm::isa::{Bytecode, BytecodeError, ExecStep, Instr, InstructionSet};
+use aluvm::library::{CodeEofError, Lib, LibId, LibSite, Read, Write};
+use aluvm::reg::{CoreRegs, Reg32, RegA, RegAFR, RegS};
+use aluvm::{Program, Vm};
+use strict_encoding::{StrictEncode, StrictWriter};
 
-use crate::validation::OpInfo;
-use crate::vm::{AluScript, EntryPoint};
-use crate::OpFullType;
+use crate::validation::OpContext;
+use crate::vm::{AluScript, EntryPoint, RgbIsa};
+use crate::{AssignmentsRef, OpFullType};
+
+/// Resources consumed by the validation scripts, collected by a metered
+/// runtime (see [`AluRuntime::metered`]).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct ScriptMeter {
+    /// Number of the executed script instructions.
+    pub steps: u64,
+    /// Peak size (in bytes) of the operation data and register values exposed
+    /// to a script.
+    pub memory: usize,
+}
 
 pub struct AluRuntime<'script> {
     script: &'script AluScript,
+    meter: Option<Cell<ScriptMeter>>,
 }
 
 impl<'script> AluRuntime<'script> {
-    pub fn new(script: &'script AluScript) -> Self { AluRuntime { script } }
+    pub fn new(script: &'script AluScript) -> Self {
+        AluRuntime {
+            script,
+            meter: None,
+        }
+    }
+
+    /// Creates runtime collecting the resources consumed by the scripts. The
+    /// collected values are returned by [`AluRuntime::take_meter`].
+    pub fn metered(script: &'script AluScript) -> Self {
+        AluRuntime {
+            script,
+            meter: Some(Cell::default()),
+        }
+    }
+
+    /// Returns resources consumed by the scripts since the previous call,
+    /// resetting the meter. Returns `None` if the runtime is not metered.
+    pub fn take_meter(&self) -> Option<ScriptMeter> { self.meter.as_ref().map(Cell::take) }
 
-    pub fn run_validations(&self, info: &OpInfo) -> Result<(), String> {
+    pub fn run_validations(&self, context: &OpContext) -> Result<(), String> {
         let mut regs = RegSetup::default();
 
-        match info.ty {
+        match context.ty {
             OpFullType::Genesis => {
                 regs.nums
-                    .insert((RegAFR::A(RegA::A16), Reg32::Reg1), (info.subschema as u8).into());
-                self.run(EntryPoint::ValidateGenesis, &regs, info)?;
+                    .insert((RegAFR::A(RegA::A16), Reg32::Reg1), (context.subschema as u8).into());
+                self.run(EntryPoint::ValidateGenesis, &regs, context)?;
             }
             OpFullType::StateTransition(ty) => {
-                self.run(EntryPoint::ValidateTransition(ty), &regs, info)?;
+                self.run(EntryPoint::ValidateTransition(ty), &regs, context)?;
             }
             OpFullType::StateExtension(ty) => {
-                self.run(EntryPoint::ValidateExtension(ty), &regs, info)?;
+                self.run(EntryPoint::ValidateExtension(ty), &regs, context)?;
             }
         }
 
-        for ty in info.global.keys() {
-            self.run(EntryPoint::ValidateGlobalState(*ty), &regs, info)?;
+        for ty in context.global.keys() {
+            self.run(EntryPoint::ValidateGlobalState(*ty), &regs, context)?;
         }
 
-        let used_state = info
+        let used_state = context
             .owned_state
             .types()
             .iter()
-            .chain(info.prev_state.keys())
+            .chain(context.prev_state.keys())
             .copied()
             .collect::<BTreeSet<_>>();
         for ty in used_state {
-            self.run(EntryPoint::ValidateGlobalState(ty), &regs, info)?;
+            self.run(EntryPoint::ValidateGlobalState(ty), &regs, context)?;
+        }
+
+        for ty in context.migrated {
+            self.run(EntryPoint::MigrateOwnedState(*ty), &regs, context)?;
         }
 
         Ok(())
     }
 
-    fn run(&self, entry: EntryPoint, regs: &RegSetup, info: &OpInfo) -> Result<(), String> {
+    fn run(&self, entry: EntryPoint, regs: &RegSetup, context: &OpContext) -> Result<(), String> {
+        let Some(site) = self.script.entry_points.get(&entry) else {
+            return Ok(());
+        };
+        let (success, registers) = match &self.meter {
+            None => {
+                let mut vm = regs.vm::<Instr<RgbIsa>>();
+                (vm.call(self.script, *site, context), vm.registers)
+            }
+            Some(meter) => {
+                let mut vm = regs.vm::<MeteredInstr>();
+                let context = MeteredContext {
+                    context,
+                    steps: Cell::default(),
+                };
+                let success = vm.call(&MeteredScript(self.script), *site, &context);
+                let data = regs.data.values().map(Vec::len).sum::<usize>();
+                let mut value = meter.get();
+                value.steps = value.steps.saturating_add(context.steps.get());
+                value.memory = value.memory.max(data + context_size(context.context));
+                meter.set(value);
+                (success, vm.registers)
+            }
+        };
+        match success {
+            true => Ok(()),
+            false => Err(registers
+                .get_s(0)
+                .and_then(|bs| String::from_utf8(bs.to_vec()).ok())
+                .unwrap_or_else(|| s!("unspecified error"))),
+        }
+    }
+}
+
+/// Instruction of the RGB instruction set counting its executions, which
+/// allows the metered runtime to measure the script steps without a separate
+/// execution pass.
+#[derive(Clone, Debug, Display)]
+#[display(inner)]
+struct MeteredInstr(Instr<RgbIsa>);
+
+/// Execution context of [`MeteredInstr`].
+struct MeteredContext<'op> {
+    context: &'op OpContext<'op>,
+    steps: Cell<u64>,
+}
+
+impl InstructionSet for MeteredInstr {
+    type Context<'ctx> = MeteredContext<'ctx>;
+
+    fn isa_ids() -> BTreeSet<&'static str> { Instr::<RgbIsa>::isa_ids() }
+
+    fn complexity(&self) -> u64 { self.0.complexity() }
+
+    fn exec(&self, regs: &mut CoreRegs, site: LibSite, context: &Self::Context<'_>) -> ExecStep {
+        context.steps.set(context.steps.get().saturating_add(1));
+        self.0.exec(regs, site, context.context)
+    }
+}
+
+impl Bytecode for MeteredInstr {
+    fn byte_count(&self) -> u16 { self.0.byte_count() }
+
+    fn instr_range() -> RangeInclusive<u8> { Instr::<RgbIsa>::instr_range() }
+
+    fn instr_byte(&self) -> u8 { self.0.instr_byte() }
+
+    fn call_site(&self) -> Option<LibSite> { self.0.call_site() }
+
+    fn encode_args<W>(&self, writer: &mut W) -> Result<(), BytecodeError>
+    where W: Write {
+        self.0.encode_args(writer)
+    }
+
+    fn decode<R>(reader: &mut R) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: Read,
+    {
+        Instr::decode(reader).map(Self)
+    }
+}
+
+/// Script executed with [`MeteredInstr`] instructions.
+struct MeteredScript<'script>(&'script AluScript);
+
+impl Program for MeteredScript<'_> {
+    type Isa = MeteredInstr;
+    type Iter<'a> = <AluScript as Program>::Iter<'a> where Self: 'a;
+
+    fn lib_count(&self) -> u16 { self.0.lib_count() }
+
+    fn libs(&self) -> Self::Iter<'_> { self.0.libs() }
+
+    fn lib(&self, id: LibId) -> Option<&Lib> { self.0.lib(id) }
+
+    fn entrypoint(&self) -> LibSite { self.0.entrypoint() }
+}
+
+/// Size of the strict-encoded operation data exposed to the scripts.
+fn context_size(context: &OpContext) -> usize {
+    fn encoded_len(data: &impl StrictEncode) -> usize {
+        data.strict_encode(StrictWriter::in_memory(usize::MAX))
+            .map(|writer| writer.unbox().len())
+            .unwrap_or_default()
+    }
+    let owned_state = match context.owned_state {
+        AssignmentsRef::Genesis(assignments) => encoded_len(assignments),
+        AssignmentsRef::Graph(assignments) => encoded_len(assignments),
+    };
+    context.metadata.len() +
+        encoded_len(context.global) +
+        encoded_len(context.prev_state) +
+        owned_state
+}
+
+#[derive(Debug, Default)]
+struct RegSetup {
+    pub nums: BTreeMap<(RegAFR, Reg32), Number>,
+    pub data: BTreeMap<RegS, Vec<u8>>,
+}
+
+impl RegSetup {
+    /// Constructs virtual machine with the registers initialized from the
+    /// setup.
+    fn vm<Isa: InstructionSet>(&self) -> Vm<Isa> {
         let mut vm = Vm::new();
 
-        for ((reg, idx), val) in &regs.nums {
+        for ((reg, idx), val) in &self.nums {
             vm.registers.set(*reg, *idx, *val);
         }
-        for (reg, val) in &regs.data {
+        for (reg, val) in &self.data {
             vm.registers.set_s(
                 *reg,
                 Some(
@@ -87,22 +251,73 @@ impl<'script> AluRuntime<'script> {
             );
         }
 
-        match self.script.entry_points.get(&entry) {
-            Some(site) => match vm.call(self.script, *site, info) {
-                true => Ok(()),
-                false => Err(vm
-                    .registers
-                    .get_s(0)
-                    .and_then(|bs| String::from_utf8(bs.to_vec()).ok())
-                    .unwrap_or_else(|| s!("unspecified error"))),
-            },
-            None => Ok(()),
-        }
+        vm
     }
 }
 
-#[derive(Debug, Default)]
-struct RegSetup {
-    pub nums: BTreeMap<(RegAFR, Reg32), Number>,
-    pub data: BTreeMap<RegS, Vec<u8>>,
+#[cfg(test)]
+mod test {
+    use aluvm::isa::{ControlFlowOp, PutOp};
+    use amplify::confinement::{Confined, SmallBlob};
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::{Assignments, GlobalState, GraphSeal, OpId, SchemaId, Valencies};
+
+    /// Runs script calling the library with the provided code from the
+    /// transition validation entry point, returning the validation result and
+    /// the number of the executed instructions.
+    fn run_metered(callee: &[Instr<RgbIsa>]) -> (Result<(), String>, u64) {
+        let callee = Lib::assemble(callee).unwrap();
+        let caller = Lib::assemble(&[Instr::<RgbIsa>::ControlFlow(ControlFlowOp::Call(
+            LibSite::with(0, callee.id()),
+        ))])
+        .unwrap();
+        let entry = LibSite::with(0, caller.id());
+        let script = AluScript {
+            libs: Confined::try_from(bmap! { caller.id() => caller, callee.id() => callee })
+                .unwrap(),
+            entry_points: small_bmap! { EntryPoint::ValidateTransition(1) => entry },
+        };
+
+        let (metadata, migrated, constants) = (SmallBlob::default(), none!(), none!());
+        let (prev_state, owned_state) = (Assignments::default(), Assignments::<GraphSeal>::default());
+        let (valencies, global) = (Valencies::default(), GlobalState::default());
+        let context = OpContext {
+            schema_id: SchemaId::strict_dumb(),
+            subschema: false,
+            id: OpId::strict_dumb(),
+            signing_id: OpId::strict_dumb(),
+            ty: OpFullType::StateTransition(1),
+            witness_txid: None,
+            witness_mtp: None,
+            metadata: &metadata,
+            metadata_fields: &[],
+            prev_state: &prev_state,
+            migrated: &migrated,
+            owned_state: (&owned_state).into(),
+            redeemed: &valencies,
+            valencies: &valencies,
+            global: &global,
+            contract_global: None,
+            constants: &constants,
+        };
+
+        let runtime = AluRuntime::metered(&script);
+        let result = runtime.run_validations(&context);
+        assert_eq!(AluRuntime::new(&script).run_validations(&context), result);
+        (result, runtime.take_meter().unwrap().steps)
+    }
+
+    #[test]
+    fn metered_call() {
+        let ret = [
+            Instr::Put(PutOp::ClrA(RegA::A8, Reg32::Reg0)),
+            Instr::ControlFlow(ControlFlowOp::Ret),
+        ];
+        assert_eq!(run_metered(&ret), (Ok(()), 3));
+        let (result, steps) = run_metered(&[Instr::ControlFlow(ControlFlowOp::Fail)]);
+        assert!(result.is_err());
+        assert_eq!(steps, 2);
+    }
 }
@@ -20,10 +20,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{btree_map, BTreeMap};
+use std::collections::{btree_map, BTreeMap, BTreeSet};
 use std::io;
 
 use aluvm::data::encoding::{Decode, Encode};
+use aluvm::isa::Instr;
 use aluvm::library::{Lib, LibId, LibSite};
 use aluvm::Program;
 use amplify::confinement::{Confined, SmallBlob, SmallOrdMap, TinyOrdMap};
@@ -32,7 +33,7 @@ use strict_encoding::{
     StrictType, TypedRead, TypedWrite, WriteStruct,
 };
 
-use crate::vm::RgbIsa;
+use crate::vm::{supported_isa_ids, RgbIsa};
 use crate::{AssignmentType, ExtensionType, GlobalStateType, TransitionType, LIB_NAME_RGB};
 
 /// Maximum total number of libraries which may be used by a single program;
@@ -54,6 +55,9 @@ pub enum EntryPoint {
     ValidateExtension(ExtensionType),
     ValidateGlobalState(GlobalStateType),
     ValidateOwnedState(AssignmentType),
+    /// Run for each of the owned state types receiving the state migrated
+    /// from the previous schema version (see [`crate::SchemaMigration`]).
+    MigrateOwnedState(AssignmentType),
 }
 
 impl StrictType for EntryPoint {
@@ -72,6 +76,7 @@ impl StrictEncode for EntryPoint {
             EntryPoint::ValidateExtension(ty) => (2, *ty),
             EntryPoint::ValidateGlobalState(ty) => (3, *ty),
             EntryPoint::ValidateOwnedState(ty) => (4, *ty),
+            EntryPoint::MigrateOwnedState(ty) => (5, *ty),
         };
         val[0] = ty;
         val[1..].copy_from_slice(&subty.to_le_bytes());
@@ -90,6 +95,7 @@ impl StrictDecode for EntryPoint {
             2 => EntryPoint::ValidateExtension(ty),
             3 => EntryPoint::ValidateGlobalState(ty),
             4 => EntryPoint::ValidateOwnedState(ty),
+            5 => EntryPoint::MigrateOwnedState(ty),
             x => return Err(DecodeError::EnumTagNotKnown(s!("EntryPoint"), x)),
         })
     }
@@ -164,7 +170,7 @@ impl StrictDecode for AluScript {
 }
 
 impl Program for AluScript {
-    type Isa = RgbIsa;
+    type Isa = Instr<RgbIsa>;
     type Iter<'a> = btree_map::Values<'a, LibId, Lib> where Self: 'a;
 
     fn lib_count(&self) -> u16 { self.libs.len() as u16 }
@@ -175,3 +181,36 @@ impl Program for AluScript {
 
     fn entrypoint(&self) -> LibSite { panic!("AluScript doesn't have a single entry point") }
 }
+
+impl AluScript {
+    /// Returns ISA extensions declared by all libraries of the script in their
+    /// ISAE segments.
+    pub fn isa_ids(&self) -> BTreeSet<String> {
+        self.libs
+            .values()
+            .flat_map(|lib| lib.isae.iter().cloned())
+            .collect()
+    }
+
+    /// Checks whether any of the script libraries contains timechain
+    /// instructions (see [`crate::vm::TimechainOp`]). Libraries with
+    /// undecodable code are ignored.
+    pub fn uses_timechain(&self) -> bool {
+        self.libs.values().any(|lib| {
+            lib.disassemble::<Instr<RgbIsa>>().map_or(false, |code| {
+                code.iter()
+                    .any(|instr| matches!(instr, Instr::ExtensionCodes(RgbIsa::Timechain(_))))
+            })
+        })
+    }
+
+    /// Returns ISA extensions declared by the script which are not present in
+    /// the registry of the [`supported_isa_ids`].
+    pub fn unsupported_isa_ids(&self) -> BTreeSet<String> {
+        let supported = supported_isa_ids();
+        self.isa_ids()
+            .into_iter()
+            .filter(|id| !supported.contains(id.as_str()))
+            .collect()
+    }
+}
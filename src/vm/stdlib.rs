@@ -0,0 +1,284 @@
+// RGB Core Library: consensus layer for RGB smart contracts.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Written in 2019-2023 by
+//     Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2023 LNP/BP Standards Association. All rights reserved.
+// Copyright (C) 2019-2023 Dr Maxim Orlovsky. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Standard library of reusable AluVM routines for the schema validation
+//! scripts.
+//!
+//! Each routine is assembled into a separate AluVM library, which is fully
+//! defined by the routine and its parameters; thus the library id is
+//! deterministic and the library needs to be audited only once for all
+//! schemata using it.
+
+use aluvm::data::MaybeNumber;
+use aluvm::isa::{CmpOp, ControlFlowOp, Instr, NoneEqFlag, PutOp};
+use aluvm::library::{Lib, LibId, LibSite};
+use aluvm::reg::{Reg16, Reg32, RegA, RegS};
+use amplify::confinement;
+
+use crate::vm::{AluScript, ContractOp, EntryPoint, RgbIsa, TimechainOp};
+use crate::{AssignmentType, ConstantType};
+
+/// Routines from the standard library.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+pub enum StdRoutine {
+    /// Checks that the sum of the fungible state of the provided type in the
+    /// operation inputs equals to the sum of the state in the operation
+    /// outputs, using pedersen commitments.
+    #[display("fungible_conservation({0})")]
+    FungibleConservation(AssignmentType),
+
+    /// Checks that the number of the owned state entries of the provided type
+    /// in the operation outputs equals to the number of the entries in the
+    /// operation inputs, preventing non-fungible tokens from being created or
+    /// destroyed by state transitions. Must not be used for genesis
+    /// validation, since genesis has no inputs.
+    #[display("nft_uniqueness({0})")]
+    NftUniqueness(AssignmentType),
+
+    /// Checks that the operation spends exactly one key right of the provided
+    /// type (see [`crate::Schema::key_rights`]) and that the operation
+    /// metadata is a BIP340 signature of the operation signing id with the key
+    /// from the spent state. Must be used only for the transitions which have
+    /// no other metadata.
+    #[display("key_right({0})")]
+    KeyRight(AssignmentType),
+
+    /// Checks that the median time past of the block mining the operation
+    /// witness transaction is not less than the UNIX timestamp from the schema
+    /// constant of the provided type (see [`TimechainOp::MtpGe`]). Must not be
+    /// used for genesis and state extension validation, since they have no
+    /// witness transaction.
+    #[display("timelock({0})")]
+    Timelock(ConstantType),
+}
+
+impl StdRoutine {
+    /// Returns code of the routine.
+    pub fn code(&self) -> Vec<Instr<RgbIsa>> {
+        match self {
+            StdRoutine::FungibleConservation(ty) => vec![
+                Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::PcVs(*ty))),
+                Instr::ControlFlow(ControlFlowOp::Ret),
+            ],
+            StdRoutine::NftUniqueness(ty) => vec![
+                Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::CnP(*ty, Reg16::Reg0))),
+                Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::CnS(*ty, Reg16::Reg1))),
+                Instr::Cmp(CmpOp::EqA(NoneEqFlag::Equal, RegA::A16, Reg32::Reg0, Reg32::Reg1)),
+                Instr::ControlFlow(ControlFlowOp::Ret),
+            ],
+            StdRoutine::KeyRight(ty) => vec![
+                Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::CnP(*ty, Reg16::Reg0))),
+                Instr::Put(PutOp::PutA(RegA::A16, Reg32::Reg1, Box::new(MaybeNumber::from(1u16)))),
+                Instr::Cmp(CmpOp::EqA(NoneEqFlag::Equal, RegA::A16, Reg32::Reg0, Reg32::Reg1)),
+                Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::LdPR(*ty, 0, RegS::from(0u8)))),
+                Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::LdM(RegS::from(1u8)))),
+                Instr::ExtensionCodes(RgbIsa::Contract(ContractOp::VfSig(
+                    RegS::from(0u8),
+                    RegS::from(1u8),
+                    RegS::from(2u8),
+                    true,
+                ))),
+                Instr::ControlFlow(ControlFlowOp::Ret),
+            ],
+            StdRoutine::Timelock(ty) => vec![
+                Instr::ExtensionCodes(RgbIsa::Timechain(TimechainOp::MtpGe(*ty))),
+                Instr::ControlFlow(ControlFlowOp::Ret),
+            ],
+        }
+    }
+
+    /// Assembles the routine into an AluVM library.
+    pub fn lib(&self) -> Lib {
+        Lib::assemble(&self.code()).expect("standard library routines must be valid")
+    }
+
+    /// Returns id of the AluVM library containing the routine.
+    pub fn lib_id(&self) -> LibId { self.lib().id() }
+
+    /// Returns site of the routine entry.
+    pub fn lib_site(&self) -> LibSite { LibSite::with(0, self.lib_id()) }
+}
+
+impl AluScript {
+    /// Adds library with the standard routine to the script and sets it as a
+    /// validation procedure for the provided entry point.
+    pub fn link_std(
+        &mut self,
+        entry_point: EntryPoint,
+        routine: StdRoutine,
+    ) -> Result<(), confinement::Error> {
+        let lib = routine.lib();
+        let site = LibSite::with(0, lib.id());
+        self.libs.insert(lib.id(), lib)?;
+        self.entry_points.insert(entry_point, site)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amplify::confinement::{SmallBlob, TinyOrdMap};
+    use bp::seals::txout::CloseMethod;
+    use commit_verify::{DigestExt, Sha256};
+    use secp256k1_zkp::{KeyPair, Message, SECP256K1};
+    use strict_encoding::StrictDumb;
+    use strict_types::SemId;
+
+    use super::*;
+    use crate::validation::OpContext;
+    use crate::vm::AluRuntime;
+    use crate::{
+        Assign, Assignments, GlobalState, GraphSeal, OpFullType, OpId, RevealedData, SchemaConstant,
+        SchemaId, TypedAssigns, Valencies, VoidState,
+    };
+
+    fn rights(count: usize) -> Assignments<GraphSeal> {
+        let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 0);
+        let assign = Assign::revealed(seal, VoidState::default());
+        let assigns = confinement::SmallVec::try_from(vec![assign; count]).unwrap();
+        Assignments::from(tiny_bmap! { 1 => TypedAssigns::Declarative(assigns) })
+    }
+
+    fn key_rights(keys: &[&KeyPair]) -> Assignments<GraphSeal> {
+        let seal = GraphSeal::with_vout(CloseMethod::TapretFirst, 0, 0);
+        let assigns = keys
+            .iter()
+            .map(|key| {
+                let (key, _) = key.x_only_public_key();
+                let data = confinement::SmallVec::try_from(key.serialize().to_vec()).unwrap();
+                Assign::revealed(seal, RevealedData::from(data))
+            })
+            .collect::<Vec<_>>();
+        let assigns = confinement::SmallVec::try_from(assigns).unwrap();
+        Assignments::from(tiny_bmap! { 1 => TypedAssigns::Structured(assigns) })
+    }
+
+    fn sign(key: &KeyPair) -> Vec<u8> {
+        let mut engine = Sha256::default();
+        engine.input_raw(OpId::strict_dumb().as_slice());
+        let msg = Message::from_slice(&engine.finish()).unwrap();
+        SECP256K1.sign_schnorr_no_aux_rand(&msg, key).as_ref().to_vec()
+    }
+
+    /// Runs the routine as a validation procedure of a state transition.
+    fn run(
+        routine: StdRoutine,
+        metadata: &[u8],
+        prev_state: &Assignments<GraphSeal>,
+        owned_state: &Assignments<GraphSeal>,
+    ) -> Result<(), String> {
+        run_mined(routine, None, &none!(), metadata, prev_state, owned_state)
+    }
+
+    /// Runs the routine as a validation procedure of a state transition mined
+    /// in a block with the provided median time past.
+    fn run_mined(
+        routine: StdRoutine,
+        witness_mtp: Option<i64>,
+        constants: &TinyOrdMap<ConstantType, SchemaConstant>,
+        metadata: &[u8],
+        prev_state: &Assignments<GraphSeal>,
+        owned_state: &Assignments<GraphSeal>,
+    ) -> Result<(), String> {
+        let mut script = AluScript::default();
+        script.link_std(EntryPoint::ValidateTransition(1), routine).unwrap();
+        let metadata = SmallBlob::try_from(metadata.to_vec()).unwrap();
+        let migrated = none!();
+        let (valencies, global) = (Valencies::default(), GlobalState::default());
+        let context = OpContext {
+            schema_id: SchemaId::strict_dumb(),
+            subschema: false,
+            id: OpId::strict_dumb(),
+            signing_id: OpId::strict_dumb(),
+            ty: OpFullType::StateTransition(1),
+            witness_txid: None,
+            witness_mtp,
+            metadata: &metadata,
+            metadata_fields: &[],
+            prev_state,
+            migrated: &migrated,
+            owned_state: owned_state.into(),
+            redeemed: &valencies,
+            valencies: &valencies,
+            global: &global,
+            contract_global: None,
+            constants,
+        };
+        AluRuntime::new(&script).run_validations(&context)
+    }
+
+    #[test]
+    fn deterministic_ids() {
+        let routine = StdRoutine::FungibleConservation(1);
+        assert_eq!(routine.lib_id(), StdRoutine::FungibleConservation(1).lib_id());
+        assert_ne!(routine.lib_id(), StdRoutine::FungibleConservation(2).lib_id());
+        assert_ne!(routine.lib_id(), StdRoutine::NftUniqueness(1).lib_id());
+        assert_eq!(routine.lib().disassemble::<Instr<RgbIsa>>().unwrap(), routine.code());
+    }
+
+    #[test]
+    fn key_right() {
+        let routine = StdRoutine::KeyRight(1);
+        assert_eq!(routine.lib().disassemble::<Instr<RgbIsa>>().unwrap(), routine.code());
+        assert_eq!(routine.to_string(), "key_right(1)");
+
+        let key = KeyPair::from_seckey_slice(SECP256K1, &[1u8; 32]).unwrap();
+        let other = KeyPair::from_seckey_slice(SECP256K1, &[2u8; 32]).unwrap();
+        let none = Assignments::default();
+        assert_eq!(run(routine, &sign(&key), &key_rights(&[&key]), &none), Ok(()));
+        assert!(run(routine, &sign(&other), &key_rights(&[&key]), &none).is_err());
+        assert!(run(routine, &sign(&key), &key_rights(&[&other]), &none).is_err());
+        assert!(run(routine, &[], &key_rights(&[&key]), &none).is_err());
+        assert!(run(routine, &sign(&key), &key_rights(&[&key, &key]), &none).is_err());
+    }
+
+    #[test]
+    fn nft_uniqueness() {
+        let routine = StdRoutine::NftUniqueness(1);
+        assert_eq!(run(routine, &[], &rights(2), &rights(2)), Ok(()));
+        assert!(run(routine, &[], &rights(2), &rights(3)).is_err());
+        assert!(run(routine, &[], &rights(2), &rights(1)).is_err());
+    }
+
+    #[test]
+    fn timelock() {
+        let routine = StdRoutine::Timelock(1);
+        assert_eq!(routine.lib().disassemble::<Instr<RgbIsa>>().unwrap(), routine.code());
+        assert_eq!(routine.to_string(), "timelock(1)");
+
+        let constants = TinyOrdMap::try_from(bmap! {
+            1 => SchemaConstant {
+                sem_id: SemId::strict_dumb(),
+                value: SmallBlob::try_from(1_000u32.to_le_bytes().to_vec()).unwrap(),
+            },
+        })
+        .unwrap();
+        let none = Assignments::default();
+        let run = |mtp| run_mined(routine, mtp, &constants, &[], &none, &none);
+        assert_eq!(run(Some(1_000)), Ok(()));
+        assert_eq!(run(Some(1_001)), Ok(()));
+        assert!(run(Some(999)).is_err());
+        assert!(run(None).is_err());
+        assert!(run_mined(StdRoutine::Timelock(2), Some(1_000), &constants, &[], &none, &none)
+            .is_err());
+    }
+}